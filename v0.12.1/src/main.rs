@@ -2,10 +2,7 @@
 
 use bevy::{
     core_pipeline::core_2d::Transparent2d,
-    ecs::{
-        query::QueryItem,
-        system::{lifetimeless::*, SystemParamItem},
-    },
+    ecs::system::{lifetimeless::*, SystemParamItem},
     prelude::*,
     render::{
         batching::NoAutomaticBatching,
@@ -22,8 +19,8 @@ use bevy::{
         Render, RenderApp, RenderSet,
     },
     sprite::{
-        MaterialMesh2dBundle, Mesh2d, Mesh2dHandle, Mesh2dPipeline, Mesh2dPipelineKey,
-        RenderMesh2dInstances, SetMesh2dBindGroup, SetMesh2dViewBindGroup,
+        Mesh2dHandle, Mesh2dPipeline, Mesh2dPipelineKey, RenderMesh2dInstances, SetMesh2dBindGroup,
+        SetMesh2dViewBindGroup,
     },
     utils::FloatOrd,
 };
@@ -124,7 +121,8 @@ fn prepare_buffer(
     for (mut instanced_material, children) in &mut instanced_materials {
         let children = children
             .iter()
-            .map(|entity| instanced_material_children.get(*entity).unwrap());
+            // skips children that aren't instances, like gizmos or labels
+            .filter_map(|entity| instanced_material_children.get(*entity).ok());
 
         instanced_material.buffer.clear();
 
@@ -189,7 +187,7 @@ fn queue_custom(
 
             transparent_phase.add(Transparent2d {
                 sort_key: FloatOrd(mesh_z),
-                entity: entity,
+                entity,
                 pipeline,
                 draw_function: draw_custom,
                 batch_range: 0..1,
@@ -333,3 +331,37 @@ impl<P: PhaseItem> RenderCommand<P> for DrawMeshInstanced {
         RenderCommandResult::Success
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unrelated_child_is_skipped() {
+        let mut app = App::new();
+        app.add_systems(Update, prepare_buffer);
+        let child = app
+            .world
+            .spawn((
+                InstancedMaterialChild {
+                    color: [1.0; 4],
+                    scale: 2.0,
+                },
+                Transform::from_xyz(1.0, 2.0, 3.0),
+            ))
+            .id();
+        // like a gizmo or a label
+        let label = app.world.spawn(TransformBundle::default()).id();
+        let host = app
+            .world
+            .spawn(InstancedMaterialHost::default())
+            .push_children(&[label, child])
+            .id();
+        app.update();
+
+        let buffer = &app.world.get::<InstancedMaterialHost>(host).unwrap().buffer;
+        assert_eq!(buffer.len(), 1);
+        assert_eq!(buffer[0].position, Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(buffer[0].scale, 2.0);
+    }
+}
@@ -0,0 +1,32 @@
+// Fragment shader of the `custom_params` example, drawing a progress ring from the `custom`
+// parameters of `CustomInstanceData`, passed on by the built-in vertex shader.
+
+// The inputs read from the vertex output of the built-in shader, at its locations.
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    // 0..1 across the quad
+    @location(3) mesh_uv: vec2<f32>,
+    // x: progress in 0..1, y: thickness of the ring in 0..1, z: brightness of the empty part
+    @location(12) @interpolate(flat) custom: vec4<f32>,
+};
+
+const TAU: f32 = 6.28318530718;
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let offset = (in.mesh_uv - 0.5) * vec2<f32>(2.0, -2.0);
+    let radius = length(offset);
+    // signed distance to the ring, smoothed over a pixel
+    let distance = abs(radius - 1.0 + 0.5 * in.custom.y) - 0.5 * in.custom.y;
+    let coverage = clamp(0.5 - distance / fwidth(distance), 0.0, 1.0);
+    if coverage <= 0.0 {
+        discard;
+    }
+
+    // clockwise from the top
+    let turn = fract(atan2(offset.x, offset.y) / TAU + 1.0);
+    let filled = turn <= in.custom.x;
+    let color = select(in.color.rgb * in.custom.z, in.color.rgb, filled);
+    return vec4<f32>(color, in.color.a * coverage);
+}
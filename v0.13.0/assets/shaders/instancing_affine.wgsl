@@ -0,0 +1,89 @@
+#import bevy_sprite::{mesh2d_functions as mesh_functions}
+
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    @location(0) position: vec3<f32>,
+#ifdef VERTEX_NORMALS
+    @location(1) normal: vec3<f32>,
+#endif
+#ifdef VERTEX_UVS
+    @location(2) uv: vec2<f32>,
+#endif
+
+#ifndef INSTANCE_STORAGE
+    @location(#{INSTANCE_LOCATION_3}) i_linear: vec4<f32>,
+    @location(#{INSTANCE_LOCATION_4}) i_translation: vec3<f32>,
+    @location(#{INSTANCE_LOCATION_5}) i_color: vec4<f32>,
+#endif
+};
+
+struct Instance {
+    linear: mat2x2<f32>,
+    translation: vec3<f32>,
+    color: vec4<f32>,
+};
+
+#ifdef INSTANCE_STORAGE
+// Mirrors the memory layout of `AffineInstanceData`. Arrays are used instead of vectors because
+// `vec3`/`vec4` members would be 16 byte aligned.
+struct InstanceData {
+    linear: array<f32, 4>,
+    translation: array<f32, 3>,
+    color: array<f32, 4>,
+};
+
+@group(2) @binding(0) var<storage, read> instances: array<InstanceData>;
+#endif
+
+#ifdef INSTANCE_SRGB_COLOR
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let lower = color / 12.92;
+    let higher = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(higher, lower, color <= vec3<f32>(0.04045));
+}
+#endif
+
+fn get_instance(vertex: Vertex) -> Instance {
+    var instance: Instance;
+#ifdef INSTANCE_STORAGE
+    let data = instances[vertex.instance_index];
+    instance.linear = mat2x2<f32>(data.linear[0], data.linear[1], data.linear[2], data.linear[3]);
+    instance.translation = vec3<f32>(data.translation[0], data.translation[1], data.translation[2]);
+    instance.color = vec4<f32>(data.color[0], data.color[1], data.color[2], data.color[3]);
+#else
+    instance.linear = mat2x2<f32>(vertex.i_linear.xy, vertex.i_linear.zw);
+    instance.translation = vertex.i_translation;
+    instance.color = vertex.i_color;
+#endif
+#ifdef INSTANCE_SRGB_COLOR
+    instance.color = vec4<f32>(srgb_to_linear(instance.color.rgb), instance.color.a);
+#endif
+    return instance;
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let instance = get_instance(vertex);
+
+    let position = vec3<f32>(instance.linear * vertex.position.xy, vertex.position.z)
+        + instance.translation;
+
+    var out: VertexOutput;
+    var model = mesh_functions::get_model_matrix(0u);
+    out.clip_position = mesh_functions::mesh2d_position_local_to_clip(
+        model,
+        vec4<f32>(position, 1.0)
+    );
+    out.color = instance.color;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
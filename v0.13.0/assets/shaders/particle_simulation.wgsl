@@ -0,0 +1,87 @@
+// Moves the particles of the `gpu_particles` example, see `simulation.rs` for the bindings.
+
+struct SimulationParams {
+    time: f32,
+    delta: f32,
+    count: u32,
+    // 1 in the first frame after the instances were written, with the state zeroed
+    reset: u32,
+};
+
+// Mirrors the memory layout of `InstanceData`. Arrays are used instead of vectors because
+// `vec3`/`vec4` members would be 16 byte aligned.
+struct InstanceData {
+    position: array<f32, 3>,
+    scale: f32,
+    color: array<f32, 4>,
+    rotation: f32,
+    atlas_index: u32,
+    uv_offset: array<f32, 2>,
+    uv_scale: array<f32, 2>,
+    emissive: array<f32, 3>,
+    sort_key: f32,
+    border_color: array<f32, 4>,
+    border_width: f32,
+    corner_radius: f32,
+    anchor: array<f32, 2>,
+};
+
+// 16 bytes, the `state_size` of the example
+struct Particle {
+    velocity: vec2<f32>,
+    // seconds since spawn, waiting to be spawned while negative
+    age: f32,
+    lifetime: f32,
+};
+
+const GRAVITY: vec2<f32> = vec2<f32>(0.0, -300.0);
+
+@group(0) @binding(0) var<uniform> params: SimulationParams;
+@group(0) @binding(1) var<storage, read_write> instances: array<InstanceData>;
+@group(0) @binding(2) var<storage, read_write> particles: array<Particle>;
+
+// A value in 0..1 that looks random.
+fn random(seed: u32) -> f32 {
+    var hash = seed * 747796405u + 2891336453u;
+    hash = ((hash >> ((hash >> 28u) + 4u)) ^ hash) * 277803737u;
+    hash = (hash >> 22u) ^ hash;
+    return f32(hash >> 8u) / f32(1u << 24u);
+}
+
+@compute @workgroup_size(64)
+fn simulate(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if index >= params.count {
+        return;
+    }
+
+    var particle = particles[index];
+    var instance = instances[index];
+
+    if params.reset == 1u {
+        // spread the first spawns over a lifetime, so the fountain doesn't start in one burst
+        particle.lifetime = 2.0 + random(index * 2u);
+        particle.age = -random(index * 2u + 1u) * particle.lifetime;
+    }
+
+    particle.age += params.delta;
+    if particle.age >= particle.lifetime {
+        particle.age -= particle.lifetime;
+        let seed = index * 3u + bitcast<u32>(params.time);
+        let angle = (0.5 + (random(seed) - 0.5) * 0.5) * 3.14159265;
+        let speed = 300.0 + 200.0 * random(seed + 1u);
+        particle.velocity = vec2<f32>(cos(angle), sin(angle)) * speed;
+        instance.position = array<f32, 3>(0.0, -200.0, 0.0);
+    }
+
+    if particle.age >= 0.0 {
+        particle.velocity += GRAVITY * params.delta;
+        instance.position[0] += particle.velocity.x * params.delta;
+        instance.position[1] += particle.velocity.y * params.delta;
+    }
+    // invisible while waiting, then fading out over the lifetime
+    instance.color[3] = select(0.0, 1.0 - particle.age / particle.lifetime, particle.age >= 0.0);
+
+    particles[index] = particle;
+    instances[index] = instance;
+}
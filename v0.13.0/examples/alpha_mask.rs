@@ -0,0 +1,112 @@
+//! Leaves drawn from a texture with soft alpha, blended on the left and cut out by an
+//! [`InstanceAlphaMask`] on the right. The cutouts have hard edges and are drawn without blending.
+//! Up and down move the cutoff, which eats into the leaves from their edges.
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use instancing::{
+    atlas::InstanceAtlas, InstanceAlphaMask, InstanceData, InstancedMeshBundle, InstancingPlugin,
+};
+
+const SIZE: u32 = 64;
+const LEAVES: u32 = 300;
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, InstancingPlugin::<InstanceData>::default()))
+        .add_systems(Startup, setup)
+        .add_systems(Update, change_cutoff)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let quad = meshes.add(Rectangle::new(1.0, 1.0));
+    let atlas = InstanceAtlas {
+        image: images.add(leaf()),
+        columns: 1,
+        rows: 1,
+    };
+
+    for side in [-1.0, 1.0] {
+        let instances = (0..LEAVES).map(move |index| {
+            let seed = index * 4;
+            InstanceData {
+                position: Vec3::new(
+                    side * (40.0 + 500.0 * random(seed)),
+                    600.0 * random(seed + 1) - 300.0,
+                    0.0,
+                ),
+                scale: 40.0 + 40.0 * random(seed + 2),
+                color: Color::hsl(90.0 + 60.0 * random(seed + 3), 0.6, 0.45).as_linear_rgba_f32(),
+                rotation: index as f32,
+                ..default()
+            }
+        });
+        let mut host = commands.spawn((
+            InstancedMeshBundle::<InstanceData>::new(quad.clone(), instances),
+            atlas.clone(),
+        ));
+        if side > 0.0 {
+            host.insert(InstanceAlphaMask(0.5));
+        }
+    }
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+/// A white leaf whose alpha falls off towards its edge and its tip.
+fn leaf() -> Image {
+    let texels: Vec<u8> = (0..SIZE * SIZE)
+        .flat_map(|index| {
+            let uv = Vec2::new((index % SIZE) as f32, (index / SIZE) as f32) / SIZE as f32;
+            // narrower towards the tip at the top
+            let width = 0.45 * (1.0 - uv.y).sqrt() * uv.y.sqrt() * 2.0;
+            let distance = (uv.x - 0.5).abs() / width.max(1e-3);
+            let alpha = ((1.0 - distance) * 2.0).clamp(0.0, 1.0);
+            [255, 255, 255, (alpha * 255.0) as u8]
+        })
+        .collect();
+
+    Image::new(
+        Extent3d {
+            width: SIZE,
+            height: SIZE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        texels,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
+
+fn change_cutoff(keys: Res<ButtonInput<KeyCode>>, mut masks: Query<&mut InstanceAlphaMask>) {
+    let step = if keys.pressed(KeyCode::ArrowUp) {
+        0.01
+    } else if keys.pressed(KeyCode::ArrowDown) {
+        -0.01
+    } else {
+        return;
+    };
+    for mut mask in &mut masks {
+        mask.0 = (mask.0 + step).clamp(0.0, 1.0);
+    }
+}
+
+/// A value in `0..1` that looks random, to keep the example free of dependencies.
+fn random(seed: u32) -> f32 {
+    let hash = seed
+        .wrapping_mul(0x9e37_79b9)
+        .rotate_left(13)
+        .wrapping_mul(0x85eb_ca6b);
+    (hash >> 8) as f32 / (1 << 24) as f32
+}
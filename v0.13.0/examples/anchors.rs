@@ -0,0 +1,103 @@
+//! Health bars and clock hands placed by their [`anchor`](StretchedInstanceData::anchor) instead
+//! of their center. The bars are anchored at their left edge and grow to the right as their
+//! health changes, the hands at their base and rotate around it, marked by a dot.
+
+use bevy::prelude::*;
+use instancing::{
+    InstanceMaterialData, InstancedMeshBundle, InstancingPlugin, StretchedInstanceData,
+};
+
+const BARS: u32 = 6;
+const BAR: Vec2 = Vec2::new(160.0, 16.0);
+const HANDS: u32 = 3;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            InstancingPlugin::<StretchedInstanceData>::default(),
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, animate)
+        .run();
+}
+
+/// Marks the host whose instances are animated.
+#[derive(Component)]
+struct Animated;
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let quad = meshes.add(Rectangle::new(1.0, 1.0));
+
+    // the frames of the bars and the dots at the bases of the hands, centered
+    let frames = (0..BARS).map(|bar| StretchedInstanceData {
+        position: bar_left(bar).extend(0.0) + Vec3::new(BAR.x / 2.0, 0.0, 0.0),
+        scale: BAR + 6.0,
+        color: Color::rgb(0.15, 0.15, 0.2).as_linear_rgba_f32(),
+        corner_radius: 4.0,
+        ..default()
+    });
+    let dots = (0..HANDS).map(|hand| StretchedInstanceData {
+        position: hand_base(hand).extend(0.0),
+        scale: Vec2::splat(20.0),
+        color: Color::WHITE.as_linear_rgba_f32(),
+        corner_radius: 10.0,
+        ..default()
+    });
+    commands.spawn(InstancedMeshBundle::<StretchedInstanceData>::new(
+        quad.clone(),
+        frames.chain(dots),
+    ));
+
+    // the fills and the hands, drawn above the frames and the dots
+    let fills = (0..BARS).map(|bar| StretchedInstanceData {
+        position: bar_left(bar).extend(0.0),
+        scale: BAR,
+        // the left edge of the quad stays at the position
+        anchor: Vec2::new(-0.5, 0.0),
+        ..default()
+    });
+    let hands = (0..HANDS).map(|hand| StretchedInstanceData {
+        position: hand_base(hand).extend(0.0),
+        scale: Vec2::new(10.0, 120.0),
+        color: Color::hsl(hand as f32 * 120.0, 0.7, 0.6).as_linear_rgba_f32(),
+        corner_radius: 5.0,
+        // the bottom edge, the hand rotates around it
+        anchor: Vec2::new(0.0, -0.5),
+        ..default()
+    });
+    commands.spawn((
+        InstancedMeshBundle::<StretchedInstanceData>::new(quad, fills.chain(hands))
+            .with_transform(Transform::from_xyz(0.0, 0.0, 1.0)),
+        Animated,
+    ));
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn bar_left(bar: u32) -> Vec2 {
+    Vec2::new(-420.0, 150.0 - bar as f32 * 60.0)
+}
+
+fn hand_base(hand: u32) -> Vec2 {
+    Vec2::new(120.0 + hand as f32 * 150.0, -60.0)
+}
+
+fn animate(
+    time: Res<Time>,
+    mut hosts: Query<&mut InstanceMaterialData<StretchedInstanceData>, With<Animated>>,
+) {
+    let t = time.elapsed_seconds();
+    for mut instances in &mut hosts {
+        for (index, instance) in instances.iter_mut().enumerate() {
+            let phase = index as f32 * 0.9;
+            if index < BARS as usize {
+                let health = 0.5 + 0.5 * (t * 0.8 + phase).sin();
+                instance.scale.x = BAR.x * health;
+                instance.color = Color::hsl(120.0 * health, 0.8, 0.5).as_linear_rgba_f32();
+            } else {
+                instance.rotation = -t * (1.0 + phase * 0.3);
+            }
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! Two batches kept as [`InstanceBatch`] handles in a resource instead of raw entities. Click to
+//! add a dot to the batch on that side of the screen, press space to scatter both batches anew
+//! through [`Commands`] and backspace to clear them.
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use instancing::{
+    batch::{InstanceBatch, InstanceBatches, SpawnInstanceBatch},
+    InstanceData, InstanceMaterialData, InstancedMeshBundle, InstancingPlugin,
+};
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, InstancingPlugin::<InstanceData>::default()))
+        .add_systems(Startup, setup)
+        .add_systems(Update, (add_on_click, scatter_or_clear, log_counts))
+        .run();
+}
+
+/// The batch drawn on the left and the one drawn on the right of the screen.
+#[derive(Resource)]
+struct Batches {
+    left: InstanceBatch<InstanceData>,
+    right: InstanceBatch<InstanceData>,
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let circle = meshes.add(Circle::new(0.5));
+    let square = meshes.add(Rectangle::new(1.0, 1.0));
+    let batches = Batches {
+        left: commands.spawn_instances(InstancedMeshBundle::<InstanceData>::new(
+            circle,
+            scatter(-1.0, 0),
+        )),
+        right: commands.spawn_instances(InstancedMeshBundle::<InstanceData>::new(
+            square,
+            scatter(1.0, 0),
+        )),
+    };
+    commands.insert_resource(batches);
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+/// 40 dots on the `side` of the screen, -1 for the left and 1 for the right.
+fn scatter(side: f32, seed: u32) -> Vec<InstanceData> {
+    (0..40)
+        .map(|index| {
+            let seed = seed + index * 3;
+            dot(Vec2::new(
+                side * (60.0 + 500.0 * random(seed)),
+                600.0 * random(seed + 1) - 300.0,
+            ))
+        })
+        .collect()
+}
+
+fn dot(position: Vec2) -> InstanceData {
+    InstanceData {
+        position: position.extend(0.0),
+        scale: 24.0,
+        color: Color::hsl(position.y.rem_euclid(360.0), 0.7, 0.6).as_linear_rgba_f32(),
+        ..default()
+    }
+}
+
+/// Edits the batches right away, through the system param.
+fn add_on_click(
+    mouse: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    batches: Res<Batches>,
+    mut instances: InstanceBatches<InstanceData>,
+) {
+    if !mouse.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let Some(cursor) = windows.get_single().ok().and_then(Window::cursor_position) else {
+        return;
+    };
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let Some(position) = camera.viewport_to_world_2d(camera_transform, cursor) else {
+        return;
+    };
+
+    let batch = if position.x < 0.0 {
+        batches.left
+    } else {
+        batches.right
+    };
+    if let Some(mut batch) = instances.get_mut(batch) {
+        batch.push(dot(position));
+    }
+}
+
+/// Edits the batches once the commands are applied.
+fn scatter_or_clear(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    batches: Res<Batches>,
+) {
+    if keys.just_pressed(KeyCode::Space) {
+        let seed = time.elapsed().as_millis() as u32;
+        batches
+            .left
+            .set_instances(&mut commands, scatter(-1.0, seed));
+        batches
+            .right
+            .set_instances(&mut commands, scatter(1.0, seed + 1000));
+    }
+    if keys.just_pressed(KeyCode::Backspace) {
+        batches.left.clear(&mut commands);
+        batches.right.clear(&mut commands);
+    }
+}
+
+fn log_counts(
+    batches: Res<Batches>,
+    instances: InstanceBatches<InstanceData>,
+    changed: Query<(), Changed<InstanceMaterialData<InstanceData>>>,
+) {
+    if !changed.is_empty() {
+        info!(
+            "left: {} instances, right: {} instances",
+            instances.len(batches.left),
+            instances.len(batches.right)
+        );
+    }
+}
+
+/// A value in `0..1` that looks random, to keep the example free of dependencies.
+fn random(seed: u32) -> f32 {
+    let hash = seed
+        .wrapping_mul(0x9e37_79b9)
+        .rotate_left(13)
+        .wrapping_mul(0x85eb_ca6b);
+    (hash >> 8) as f32 / (1 << 24) as f32
+}
@@ -0,0 +1,70 @@
+//! A grid of progress rings, drawn from [`CustomInstanceData`] by the fragment shader of an
+//! [`InstancedMaterial`]. The built-in vertex shader passes the `custom` field of every instance on
+//! to the fragment shader, which reads it as the progress, the thickness of the ring and the
+//! brightness of its empty part, without an instance type of its own.
+
+use bevy::{prelude::*, render::render_resource::ShaderRef};
+use instancing::{
+    CustomInstanceData, InstanceMaterialData, InstancedMaterial, InstancedMeshBundle,
+    InstancingPlugin,
+};
+
+const COLUMNS: u32 = 16;
+const ROWS: u32 = 9;
+/// Distance between the rings in pixels.
+const SPACING: f32 = 72.0;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            InstancingPlugin::<CustomInstanceData, ProgressMaterial>::default(),
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, advance)
+        .run();
+}
+
+#[derive(Component, Clone)]
+struct ProgressMaterial;
+
+impl InstancedMaterial for ProgressMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/custom_params.wgsl".into()
+    }
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let rings = (0..ROWS).flat_map(|row| {
+        (0..COLUMNS).map(move |column| {
+            let index = row * COLUMNS + column;
+            let position = (Vec2::new(column as f32, row as f32)
+                - 0.5 * Vec2::new(COLUMNS as f32 - 1.0, ROWS as f32 - 1.0))
+                * SPACING;
+            CustomInstanceData {
+                position: position.extend(0.0),
+                scale: 0.8 * SPACING,
+                color: Color::hsl(index as f32 / (COLUMNS * ROWS) as f32 * 360.0, 0.7, 0.6)
+                    .as_linear_rgba_f32(),
+                custom: [0.0, 0.1 + 0.3 * row as f32 / ROWS as f32, 0.2, 0.0],
+                ..default()
+            }
+        })
+    });
+
+    commands.spawn(
+        InstancedMeshBundle::<CustomInstanceData>::new(meshes.add(Rectangle::new(1.0, 1.0)), rings)
+            .with_material(ProgressMaterial),
+    );
+    commands.spawn(Camera2dBundle::default());
+}
+
+/// Fills every ring at its own pace, starting over once full.
+fn advance(time: Res<Time>, mut hosts: Query<&mut InstanceMaterialData<CustomInstanceData>>) {
+    for mut rings in &mut hosts {
+        for (index, ring) in rings.iter_mut().enumerate() {
+            let speed = 0.1 + 0.05 * (index as u32 % COLUMNS) as f32;
+            ring.custom[0] = (ring.custom[0] + speed * time.delta_seconds()).fract();
+        }
+    }
+}
@@ -0,0 +1,220 @@
+//! Instances drawn in a render phase of the app's own instead of [`Transparent2d`], in a pass that
+//! runs after bevy's main 2D pass. The ring of bars is drawn in that overlay phase and stays on top
+//! of the circles, although they are closer to the camera.
+//!
+//! [`Transparent2d`]: bevy::core_pipeline::core_2d::Transparent2d
+
+use bevy::{
+    core_pipeline::core_2d::graph::{Core2d, Node2d},
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{
+        camera::ExtractedCamera,
+        render_graph::{
+            NodeRunError, RenderGraphApp, RenderGraphContext, RenderLabel, ViewNode, ViewNodeRunner,
+        },
+        render_phase::{
+            sort_phase_system, CachedRenderPipelinePhaseItem, DrawFunctionId, PhaseItem,
+            RenderPhase,
+        },
+        render_resource::{CachedRenderPipelineId, RenderPassDescriptor},
+        renderer::RenderContext,
+        view::ViewTarget,
+        Extract, Render, RenderApp, RenderSet,
+    },
+    utils::{nonmax::NonMaxU32, FloatOrd},
+};
+use instancing::{
+    DefaultInstancedMaterial, InstanceData, InstanceMaterialData, InstancePhaseItem,
+    InstancedMeshBundle, InstancingPlugin, StretchedInstanceData,
+};
+use std::{f32::consts::TAU, ops::Range};
+
+const BARS: u32 = 24;
+
+type OverlayInstancingPlugin =
+    InstancingPlugin<StretchedInstanceData, DefaultInstancedMaterial, Overlay2d>;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            OverlayPlugin,
+            // the bars in the overlay, anything else in the main pass
+            OverlayInstancingPlugin::default(),
+            InstancingPlugin::<InstanceData>::default(),
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, spin)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let bars = (0..BARS).map(|bar| {
+        let angle = bar as f32 / BARS as f32 * TAU;
+        StretchedInstanceData {
+            position: (Vec2::from_angle(angle) * 160.0).extend(0.0),
+            scale: Vec2::new(60.0, 12.0),
+            rotation: angle,
+            color: Color::hsl(angle.to_degrees(), 0.8, 0.6).as_linear_rgba_f32(),
+            ..default()
+        }
+    });
+    commands.spawn(InstancedMeshBundle::<StretchedInstanceData>::new(
+        meshes.add(Rectangle::new(1.0, 1.0)),
+        bars,
+    ));
+
+    // in front of the bars by z, yet drawn below them
+    commands.spawn(InstancedMeshBundle::<InstanceData>::new(
+        meshes.add(Circle::new(0.5)),
+        (0..3).map(|index| InstanceData {
+            position: Vec3::new((index as f32 - 1.0) * 160.0, 0.0, 10.0),
+            scale: 200.0,
+            color: Color::rgba(1.0, 1.0, 1.0, 0.8).as_linear_rgba_f32(),
+            ..default()
+        }),
+    ));
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn spin(
+    time: Res<Time>,
+    mut hosts: Query<&mut Transform, With<InstanceMaterialData<StretchedInstanceData>>>,
+) {
+    for mut transform in &mut hosts {
+        transform.rotate_z(0.3 * time.delta_seconds());
+    }
+}
+
+/// The overlay phase, sorted like [`Transparent2d`](bevy::core_pipeline::core_2d::Transparent2d).
+struct Overlay2d {
+    sort_key: FloatOrd,
+    entity: Entity,
+    pipeline: CachedRenderPipelineId,
+    draw_function: DrawFunctionId,
+    batch_range: Range<u32>,
+    dynamic_offset: Option<NonMaxU32>,
+}
+
+impl PhaseItem for Overlay2d {
+    type SortKey = FloatOrd;
+
+    fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    fn sort_key(&self) -> Self::SortKey {
+        self.sort_key
+    }
+
+    fn draw_function(&self) -> DrawFunctionId {
+        self.draw_function
+    }
+
+    fn batch_range(&self) -> &Range<u32> {
+        &self.batch_range
+    }
+
+    fn batch_range_mut(&mut self) -> &mut Range<u32> {
+        &mut self.batch_range
+    }
+
+    fn dynamic_offset(&self) -> Option<NonMaxU32> {
+        self.dynamic_offset
+    }
+
+    fn dynamic_offset_mut(&mut self) -> &mut Option<NonMaxU32> {
+        &mut self.dynamic_offset
+    }
+}
+
+impl CachedRenderPipelinePhaseItem for Overlay2d {
+    fn cached_pipeline(&self) -> CachedRenderPipelineId {
+        self.pipeline
+    }
+}
+
+impl InstancePhaseItem for Overlay2d {
+    fn new(
+        entity: Entity,
+        sort_key: FloatOrd,
+        pipeline: CachedRenderPipelineId,
+        draw_function: DrawFunctionId,
+    ) -> Self {
+        Overlay2d {
+            sort_key,
+            entity,
+            pipeline,
+            draw_function,
+            batch_range: 0..1,
+            dynamic_offset: None,
+        }
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct OverlayPass;
+
+/// Adds the overlay phase to 2D cameras and draws it after the main pass.
+struct OverlayPlugin;
+
+impl Plugin for OverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.sub_app_mut(RenderApp)
+            .add_systems(ExtractSchedule, extract_overlay_phases)
+            .add_systems(
+                Render,
+                sort_phase_system::<Overlay2d>.in_set(RenderSet::PhaseSort),
+            )
+            .add_render_graph_node::<ViewNodeRunner<OverlayNode>>(Core2d, OverlayPass)
+            .add_render_graph_edges(Core2d, (Node2d::MainPass, OverlayPass, Node2d::Tonemapping));
+    }
+}
+
+fn extract_overlay_phases(
+    mut commands: Commands,
+    cameras: Extract<Query<(Entity, &Camera), With<Camera2d>>>,
+) {
+    for (entity, camera) in &cameras {
+        if camera.is_active {
+            commands
+                .get_or_spawn(entity)
+                .insert(RenderPhase::<Overlay2d>::default());
+        }
+    }
+}
+
+#[derive(Default)]
+struct OverlayNode;
+
+impl ViewNode for OverlayNode {
+    type ViewQuery = (
+        &'static ExtractedCamera,
+        &'static RenderPhase<Overlay2d>,
+        &'static ViewTarget,
+    );
+
+    fn run<'w>(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        (camera, phase, target): QueryItem<'w, Self::ViewQuery>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("overlay_pass"),
+            color_attachments: &[Some(target.get_color_attachment())],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        if let Some(viewport) = camera.viewport.as_ref() {
+            render_pass.set_camera_viewport(viewport);
+        }
+
+        phase.render(&mut render_pass, world, graph.view_entity());
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+//! 32768 tufts of grass scattered over a field, read from a float texture with
+//! [`InstanceDataTexture`] instead of an instance buffer. The host holds a single template
+//! instance, which every pair of texels moves, scales and tints.
+//!
+//! The texture is baked at startup here, loading one exported from an image tool as
+//! `Rgba32Float`, like an EXR, works the same.
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use instancing::{
+    bounds::InstanceFrustumCulling, data_texture::InstanceDataTexture, InstanceData,
+    InstancedMeshBundle, InstancingPlugin,
+};
+
+/// Texels of a row, room for half as many instances.
+const WIDTH: u32 = 512;
+const ROWS: u32 = 128;
+const FIELD: Vec2 = Vec2::new(1200.0, 640.0);
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, InstancingPlugin::<InstanceData>::default()))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    // a blade, the texels only scale it
+    let template = InstanceData {
+        scale: 6.0,
+        corner_radius: 3.0,
+        ..default()
+    };
+
+    commands.spawn((
+        InstancedMeshBundle::<InstanceData>::new(meshes.add(Rectangle::new(1.0, 2.0)), [template]),
+        InstanceDataTexture {
+            image: images.add(scatter()),
+        },
+        // the bounds only cover the template
+        InstanceFrustumCulling(false),
+    ));
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+/// The instances, two texels each: position and scale, then the color.
+fn scatter() -> Image {
+    let mut tufts: Vec<_> = (0..WIDTH * ROWS / 2)
+        .map(|index| {
+            let seed = index * 4;
+            let position = (Vec2::new(random(seed), random(seed + 1)) - 0.5) * FIELD;
+            // taller and lighter towards the top of the field
+            let height = position.y / FIELD.y + 0.5;
+            let scale = 0.6 + 0.8 * random(seed + 2) * (0.5 + height);
+            let color = Color::hsl(80.0 + 50.0 * random(seed + 3), 0.6, 0.2 + 0.3 * height);
+            (position, scale, color)
+        })
+        .collect();
+    // drawn in the order of the texels, so the lower tufts cover the higher ones
+    tufts.sort_by(|(a, ..), (b, ..)| b.y.total_cmp(&a.y));
+
+    let texels: Vec<[f32; 4]> = tufts
+        .into_iter()
+        .flat_map(|(position, scale, color)| {
+            [
+                [position.x, position.y, 0.0, scale],
+                color.as_linear_rgba_f32(),
+            ]
+        })
+        .collect();
+
+    Image::new(
+        Extent3d {
+            width: WIDTH,
+            height: ROWS,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        bytemuck::cast_slice(&texels).to_vec(),
+        TextureFormat::Rgba32Float,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
+
+/// A value in `0..1` that looks random, to keep the example free of dependencies.
+fn random(seed: u32) -> f32 {
+    let hash = seed
+        .wrapping_mul(0x9e37_79b9)
+        .rotate_left(13)
+        .wrapping_mul(0x85eb_ca6b);
+    (hash >> 8) as f32 / (1 << 24) as f32
+}
@@ -0,0 +1,70 @@
+//! A field of dots larger than the screen, culled on the GPU while the camera pans across it. The
+//! window title shows the instances of the host against the ones actually drawn, read from its
+//! [`DrawnInstanceCount`].
+
+use bevy::prelude::*;
+use instancing::{
+    culling::{GpuCulling, InstanceCullReadback},
+    diagnostics::DrawnInstanceCount,
+    InstanceData, InstanceMaterialData, InstancedMeshBundle, InstancingPlugin,
+};
+
+const SIDE: u32 = 200;
+const SPACING: f32 = 16.0;
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, InstancingPlugin::<InstanceData>::default()))
+        .add_systems(Startup, setup)
+        .add_systems(Update, (pan, show_count))
+        .run();
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let instances = (0..SIDE * SIDE).map(|index| {
+        let (column, row) = (index % SIDE, index / SIDE);
+        InstanceData {
+            position: Vec3::new(
+                (column as f32 - (SIDE - 1) as f32 / 2.0) * SPACING,
+                (row as f32 - (SIDE - 1) as f32 / 2.0) * SPACING,
+                0.0,
+            ),
+            scale: 10.0,
+            color: Color::hsl(index as f32 * 0.01 % 360.0, 0.7, 0.6).as_linear_rgba_f32(),
+            ..default()
+        }
+    });
+
+    commands.spawn((
+        InstancedMeshBundle::<InstanceData>::new(meshes.add(Circle::new(0.5)), instances),
+        GpuCulling,
+        // without it, the count stops at the instances uploaded
+        InstanceCullReadback::default(),
+        DrawnInstanceCount::default(),
+    ));
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+/// Circles the camera around the field, zooming out past its edges now and then.
+fn pan(time: Res<Time>, mut cameras: Query<(&mut Transform, &mut OrthographicProjection)>) {
+    let t = time.elapsed_seconds();
+    for (mut transform, mut projection) in &mut cameras {
+        transform.translation = (Vec2::from_angle(t * 0.2) * 1200.0).extend(0.0);
+        projection.scale = 1.5 + (t * 0.3).sin();
+    }
+}
+
+fn show_count(
+    hosts: Query<
+        (&InstanceMaterialData<InstanceData>, &DrawnInstanceCount),
+        Changed<DrawnInstanceCount>,
+    >,
+    mut windows: Query<&mut Window>,
+) {
+    for (instances, drawn) in &hosts {
+        for mut window in &mut windows {
+            window.title = format!("drawn {} of {} instances", drawn.0, instances.len());
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Dots that are ordinary child entities, spawned, moved and despawned by systems, yet drawn as
+//! the instances of their parent in one draw call. The parent spins, taking all dots with it.
+
+use bevy::prelude::*;
+use instancing::{
+    entity_instances::{EntityInstances, EntityInstancesPlugin, InstanceChild},
+    InstanceData, InstancedMeshBundle, InstancingPlugin,
+};
+
+/// Seconds between two dots.
+const SPAWN_INTERVAL: f32 = 0.02;
+/// Seconds a dot lives.
+const LIFETIME: f32 = 4.0;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            InstancingPlugin::<InstanceData>::default(),
+            EntityInstancesPlugin::<InstanceData, Dot>::default(),
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, (spawn_dots, move_dots, spin_hosts))
+        .run();
+}
+
+/// The instance drawn for a child entity, its color and scale are copied into the instance.
+#[derive(Component, InstanceChild)]
+struct Dot {
+    color: [f32; 4],
+    scale: f32,
+    /// Ignored by the derive, as [`InstanceData`] has no such field.
+    velocity: Vec3,
+    age: f32,
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    commands.spawn((
+        InstancedMeshBundle::<InstanceData>::new(meshes.add(Circle::new(0.5)), []),
+        EntityInstances::<Dot>::default(),
+    ));
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn spawn_dots(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut pending: Local<f32>,
+    mut spawned: Local<u32>,
+    hosts: Query<Entity, With<EntityInstances<Dot>>>,
+) {
+    *pending += time.delta_seconds();
+    for host in &hosts {
+        while *pending >= SPAWN_INTERVAL {
+            *pending -= SPAWN_INTERVAL;
+            *spawned += 1;
+
+            let angle = *spawned as f32 * 2.4;
+            let direction = Vec3::new(angle.cos(), angle.sin(), 0.0);
+            let dot = commands
+                .spawn((
+                    Dot {
+                        color: Color::hsl(*spawned as f32 * 7.0 % 360.0, 0.8, 0.6)
+                            .as_linear_rgba_f32(),
+                        scale: 12.0,
+                        velocity: direction * 80.0,
+                        age: 0.0,
+                    },
+                    TransformBundle::from_transform(Transform::from_translation(direction * 20.0)),
+                ))
+                .id();
+            commands.entity(host).add_child(dot);
+        }
+    }
+}
+
+fn move_dots(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut dots: Query<(Entity, &mut Dot, &mut Transform)>,
+) {
+    let delta = time.delta_seconds();
+    for (entity, mut dot, mut transform) in &mut dots {
+        dot.age += delta;
+        if dot.age >= LIFETIME {
+            // removes the dot from the children of the host, and its instance with it
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        transform.translation += dot.velocity * delta;
+        transform.scale = Vec3::splat(1.0 - dot.age / LIFETIME);
+    }
+}
+
+fn spin_hosts(time: Res<Time>, mut hosts: Query<&mut Transform, With<EntityInstances<Dot>>>) {
+    for mut transform in &mut hosts {
+        transform.rotate_z(0.5 * time.delta_seconds());
+    }
+}
@@ -0,0 +1,113 @@
+//! Explosions spawned in bursts, one short-lived host each. The buffers of the burnt out
+//! explosions go back to the [`InstanceBufferPool`](instancing::pool::InstanceBufferPool) when
+//! they are despawned and the next ones take them over, so the logged reallocations drop to zero
+//! once the first bursts filled the pool.
+
+use bevy::{diagnostic::LogDiagnosticsPlugin, prelude::*};
+use instancing::{
+    diagnostics::InstancingDiagnosticsPlugin, InstanceData, InstanceMaterialData,
+    InstancedMeshBundle, InstancingPlugin,
+};
+
+const LIFETIME: f32 = 1.2;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            LogDiagnosticsPlugin::default(),
+            InstancingPlugin::<InstanceData>::default(),
+            InstancingDiagnosticsPlugin,
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, (spawn_explosions, burn))
+        .run();
+}
+
+#[derive(Component)]
+struct Explosion {
+    age: f32,
+    /// The direction and speed of each spark.
+    velocities: Vec<Vec2>,
+}
+
+#[derive(Resource)]
+struct Spark(Handle<Mesh>);
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    commands.insert_resource(Spark(meshes.add(Circle::new(0.5))));
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn spawn_explosions(
+    mut commands: Commands,
+    time: Res<Time>,
+    spark: Res<Spark>,
+    mut seed: Local<u32>,
+) {
+    // a few explosions every frame, with between 50 and 400 sparks
+    for _ in 0..3 {
+        *seed += 1;
+        if random(*seed * 7) > 0.2 {
+            continue;
+        }
+        let center = Vec2::new(random(*seed * 7 + 1) - 0.5, random(*seed * 7 + 2) - 0.5)
+            * Vec2::new(1100.0, 600.0);
+        let sparks = 50 + (350.0 * random(*seed * 7 + 3)) as u32;
+        let velocities: Vec<_> = (0..sparks)
+            .map(|spark| {
+                let spark_seed = *seed * 1000 + spark * 2;
+                let angle = random(spark_seed) * std::f32::consts::TAU;
+                Vec2::from_angle(angle) * (40.0 + 160.0 * random(spark_seed + 1))
+            })
+            .collect();
+        let hue = 360.0 * random(*seed * 7 + 4);
+        let instances = velocities.iter().map(|_| InstanceData {
+            position: center.extend(0.0),
+            scale: 6.0,
+            color: Color::hsl(hue, 0.9, 0.6).as_linear_rgba_f32(),
+            ..default()
+        });
+        commands.spawn((
+            InstancedMeshBundle::<InstanceData>::new(spark.0.clone(), instances)
+                .with_transform(Transform::from_xyz(0.0, 0.0, time.elapsed_seconds() % 1.0)),
+            Explosion {
+                age: 0.0,
+                velocities,
+            },
+        ));
+    }
+}
+
+fn burn(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut explosions: Query<(
+        Entity,
+        &mut Explosion,
+        &mut InstanceMaterialData<InstanceData>,
+    )>,
+) {
+    let delta = time.delta_seconds();
+    for (entity, mut explosion, mut instances) in &mut explosions {
+        explosion.age += delta;
+        if explosion.age > LIFETIME {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let fade = 1.0 - explosion.age / LIFETIME;
+        for (instance, velocity) in instances.iter_mut().zip(&explosion.velocities) {
+            instance.position += (*velocity * delta * fade).extend(0.0);
+            instance.color[3] = fade;
+        }
+    }
+}
+
+/// A value in `0..1` that looks random, to keep the example free of dependencies.
+fn random(seed: u32) -> f32 {
+    let hash = seed
+        .wrapping_mul(0x9e37_79b9)
+        .rotate_left(13)
+        .wrapping_mul(0x85eb_ca6b);
+    (hash >> 8) as f32 / (1 << 24) as f32
+}
@@ -0,0 +1,62 @@
+//! Two rings of orbiting dots, moved by a simulation running at only 10 updates per second. The
+//! left ring jumps from update to update, the right one has [`InterpolateInstances`] and glides
+//! in between.
+
+use bevy::prelude::*;
+use instancing::{
+    interpolation::InterpolateInstances, InstanceData, InstanceMaterialData, InstancedMeshBundle,
+    InstancingPlugin,
+};
+
+const DOTS: u32 = 24;
+
+fn main() {
+    App::new()
+        .insert_resource(Time::<Fixed>::from_hz(10.0))
+        .add_plugins((DefaultPlugins, InstancingPlugin::<InstanceData>::default()))
+        .add_systems(Startup, setup)
+        .add_systems(FixedUpdate, orbit)
+        .run();
+}
+
+#[derive(Component)]
+struct Orbit {
+    center: Vec2,
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let mesh = meshes.add(Circle::new(0.5));
+    for (center, interpolated) in [
+        (Vec2::new(-250.0, 0.0), false),
+        (Vec2::new(250.0, 0.0), true),
+    ] {
+        let dots = (0..DOTS).map(|_| InstanceData {
+            scale: 16.0,
+            color: Color::hsl(if interpolated { 140.0 } else { 0.0 }, 0.7, 0.6)
+                .as_linear_rgba_f32(),
+            ..default()
+        });
+
+        let mut host = commands.spawn((
+            InstancedMeshBundle::<InstanceData>::new(mesh.clone(), dots),
+            Orbit { center },
+        ));
+        if interpolated {
+            host.insert(InterpolateInstances);
+        }
+    }
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn orbit(time: Res<Time>, mut hosts: Query<(&mut InstanceMaterialData<InstanceData>, &Orbit)>) {
+    // the fixed time inside of `FixedUpdate`
+    let elapsed = time.elapsed_seconds();
+    for (mut dots, orbit) in &mut hosts {
+        for (index, dot) in dots.iter_mut().enumerate() {
+            let angle = elapsed * 1.5 + index as f32 * std::f32::consts::TAU / DOTS as f32;
+            dot.position = (orbit.center + Vec2::from_angle(angle) * 150.0).extend(0.0);
+            dot.rotation = angle;
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! A grid of arrows from the cells of an atlas, each column flipped differently: unflipped,
+//! flipped on x, on y and on both. Every arrow stays within its own cell, and its neighbours keep
+//! pointing their own way, as the flags belong to each instance.
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use instancing::{atlas::InstanceAtlas, InstanceData, InstancedMeshBundle, InstancingPlugin};
+
+/// Pixels of a square atlas cell.
+const CELL: u32 = 32;
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, InstancingPlugin::<InstanceData>::default()))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    // one row per atlas cell, one column per combination of flags
+    let instances = (0..4).flat_map(|row| {
+        (0..4).map(move |column| {
+            let mut instance = InstanceData {
+                position: Vec3::new(
+                    (column as f32 - 1.5) * 120.0,
+                    (1.5 - row as f32) * 120.0,
+                    0.0,
+                ),
+                scale: 96.0,
+                atlas_index: row,
+                ..default()
+            };
+            instance.set_flip_x(column & 1 != 0);
+            instance.set_flip_y(column & 2 != 0);
+            instance
+        })
+    });
+
+    commands.spawn((
+        InstancedMeshBundle::<InstanceData>::new(meshes.add(Rectangle::new(1.0, 1.0)), instances),
+        InstanceAtlas {
+            image: images.add(arrows_atlas()),
+            columns: 2,
+            rows: 2,
+        },
+    ));
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+/// 2x2 cells, each an arrow pointing to the top right on a background of its own color, so an
+/// arrow flipped across the whole atlas would show the background of another cell.
+fn arrows_atlas() -> Image {
+    const BACKGROUNDS: [[u8; 3]; 4] = [[160, 40, 40], [40, 140, 40], [40, 60, 170], [150, 120, 30]];
+
+    let mut data = Vec::with_capacity((4 * CELL * CELL * 4) as usize);
+    for y in 0..2 * CELL {
+        for x in 0..2 * CELL {
+            let cell = (y / CELL) * 2 + x / CELL;
+            // position within the cell, from 0 to 1 with y pointing up
+            let p =
+                (Vec2::new((x % CELL) as f32, (CELL - 1 - y % CELL) as f32) + 0.5) / CELL as f32;
+            let shaft = (p.x - p.y).abs() < 0.08 && p.x > 0.15 && p.x < 0.8;
+            let head = p.x > 0.55 && p.y > 0.55 && p.x + p.y > 1.5;
+            let [r, g, b] = BACKGROUNDS[cell as usize];
+            data.extend(if shaft || head {
+                [255, 255, 255, 255]
+            } else {
+                [r, g, b, 255]
+            });
+        }
+    }
+
+    Image::new(
+        Extent3d {
+            width: 2 * CELL,
+            height: 2 * CELL,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
@@ -0,0 +1,47 @@
+//! 100k particles of a fountain, simulated on the GPU by `particle_simulation.wgsl`. The CPU
+//! only seeds their colors once, the instances are never uploaded again.
+
+use bevy::{
+    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    prelude::*,
+};
+use instancing::{
+    simulation::GpuSimulation, InstanceBlendMode, InstanceData, InstancedMeshBundle,
+    InstancingPlugin,
+};
+
+const PARTICLES: usize = 100_000;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            FrameTimeDiagnosticsPlugin,
+            LogDiagnosticsPlugin::default(),
+            InstancingPlugin::<InstanceData>::default(),
+        ))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, asset_server: Res<AssetServer>) {
+    let instances = (0..PARTICLES).map(|index| InstanceData {
+        scale: 3.0,
+        color: Color::hsl(20.0 + 40.0 * (index % 7) as f32 / 7.0, 0.9, 0.5).as_linear_rgba_f32(),
+        ..default()
+    });
+
+    commands.spawn((
+        // the particles leave the bounds of the seeded instances
+        InstancedMeshBundle::<InstanceData>::new(meshes.add(Circle::new(0.5)), instances)
+            .with_frustum_culling(false),
+        InstanceBlendMode::Additive,
+        GpuSimulation {
+            shader: asset_server.load("shaders/particle_simulation.wgsl"),
+            // velocity, age and lifetime
+            state_size: 16,
+        },
+    ));
+
+    commands.spawn(Camera2dBundle::default());
+}
@@ -0,0 +1,71 @@
+//! Overlapping, rotated stars whose instance under the cursor is highlighted. The ids of the
+//! instances are drawn into an offscreen target and the one under the cursor read back, so the
+//! exact shape of the stars and the order they overlap in decide what is picked.
+
+use bevy::prelude::*;
+use instancing::{
+    gpu_picking::{GpuPicking, GpuPickingPlugin, PickedInstance},
+    InstanceData, InstanceMaterialData, InstancedMeshBundle, InstancingPlugin,
+};
+
+const STARS: u32 = 400;
+
+fn main() {
+    App::new()
+        // the instance ids can't be multisampled
+        .insert_resource(Msaa::Off)
+        .add_plugins((
+            DefaultPlugins,
+            InstancingPlugin::<InstanceData>::default(),
+            GpuPickingPlugin,
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, highlight)
+        .run();
+}
+
+/// The colors of the instances before highlighting.
+#[derive(Component)]
+struct BaseColors(Vec<[f32; 4]>);
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let stars: Vec<_> = (0..STARS)
+        .map(|index| {
+            let angle = index as f32 * 2.399;
+            let radius = 18.0 * (index as f32).sqrt();
+            InstanceData {
+                position: Vec3::new(angle.cos() * radius, angle.sin() * radius, 0.0),
+                scale: 60.0,
+                color: Color::hsl(index as f32 * 3.0, 0.7, 0.45).as_linear_rgba_f32(),
+                rotation: index as f32,
+                ..default()
+            }
+        })
+        .collect();
+    let colors = stars.iter().map(|star| star.color).collect();
+
+    commands.spawn((
+        InstancedMeshBundle::<InstanceData>::new(meshes.add(RegularPolygon::new(0.5, 5)), stars),
+        BaseColors(colors),
+    ));
+
+    commands.spawn((Camera2dBundle::default(), GpuPicking));
+}
+
+fn highlight(
+    picked: Res<PickedInstance>,
+    mut hosts: Query<(&mut InstanceMaterialData<InstanceData>, &BaseColors)>,
+) {
+    if !picked.is_changed() {
+        return;
+    }
+
+    for (mut instances, colors) in &mut hosts {
+        for (index, (instance, color)) in instances.iter_mut().zip(&colors.0).enumerate() {
+            instance.color = match picked.0 == Some(index) {
+                true => [1.0; 4],
+                false => *color,
+            };
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! Two clusters of instances crossfading like a scene transition. [`InstanceGroupAlpha`] fades
+//! each cluster as a whole, the instances themselves are only uploaded once.
+
+use bevy::prelude::*;
+use instancing::{InstanceData, InstanceGroupAlpha, InstancedMeshBundle, InstancingPlugin};
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, InstancingPlugin::<InstanceData>::default()))
+        .add_systems(Startup, setup)
+        .add_systems(Update, crossfade)
+        .run();
+}
+
+/// The cluster fully shown at the start of the crossfade.
+#[derive(Component)]
+struct First;
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let cluster = |hue: f32, radius: f32| {
+        (0..400).map(move |index| {
+            let angle = index as f32 * 2.4;
+            let distance = radius * (index as f32 / 400.0).sqrt();
+            InstanceData {
+                position: Vec3::new(angle.cos() * distance, angle.sin() * distance, 0.0),
+                scale: 14.0,
+                color: Color::hsl(hue + index as f32 * 0.1, 0.7, 0.55).as_linear_rgba_f32(),
+                ..default()
+            }
+        })
+    };
+
+    commands.spawn((
+        InstancedMeshBundle::<InstanceData>::new(meshes.add(Circle::new(0.5)), cluster(0.0, 250.0)),
+        InstanceGroupAlpha(1.0),
+        First,
+    ));
+    commands.spawn((
+        InstancedMeshBundle::<InstanceData>::new(
+            meshes.add(Rectangle::new(1.0, 1.0)),
+            cluster(180.0, 200.0),
+        ),
+        InstanceGroupAlpha(0.0),
+    ));
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn crossfade(time: Res<Time>, mut hosts: Query<(&mut InstanceGroupAlpha, Has<First>)>) {
+    let fade = 0.5 + 0.5 * (time.elapsed_seconds() * 0.8).cos();
+    for (mut group_alpha, first) in &mut hosts {
+        group_alpha.0 = if first { fade } else { 1.0 - fade };
+    }
+}
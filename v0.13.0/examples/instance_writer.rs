@@ -0,0 +1,92 @@
+//! 100k particles on a rotating spiral, written straight into the instance buffer by an
+//! [`InstanceGenerator`]. Run with `--vec` to build an [`InstanceMaterialData`] every frame
+//! instead and compare the frame times logged by both.
+
+use bevy::{
+    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    prelude::*,
+    sprite::Mesh2dHandle,
+};
+use instancing::{
+    writer::InstanceGenerator, DefaultInstancedMaterial, InstanceData, InstanceMaterialData,
+    InstancingPlugin,
+};
+
+const PARTICLES: usize = 100_000;
+
+fn main() {
+    let use_vec = std::env::args().any(|arg| arg == "--vec");
+
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            FrameTimeDiagnosticsPlugin,
+            LogDiagnosticsPlugin::default(),
+            InstancingPlugin::<InstanceData>::default(),
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(
+            Update,
+            (
+                generate_particles.run_if(move || !use_vec),
+                build_particles.run_if(move || use_vec),
+            ),
+        )
+        .run();
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    commands.spawn((
+        Mesh2dHandle(meshes.add(Rectangle::new(1.0, 1.0))),
+        SpatialBundle::default(),
+        DefaultInstancedMaterial,
+    ));
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn particle(index: usize, time: f32) -> InstanceData {
+    let t = index as f32 / PARTICLES as f32;
+    let angle = t * 60.0 + time;
+    let radius = 20.0 + t * 300.0;
+
+    InstanceData {
+        position: Vec3::new(angle.cos() * radius, angle.sin() * radius, 0.0),
+        scale: 2.0,
+        color: Color::hsl(t * 360.0, 0.8, 0.6).as_linear_rgba_f32(),
+        rotation: angle,
+        ..default()
+    }
+}
+
+/// Replaces the generator every frame to capture the current time.
+fn generate_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    hosts: Query<Entity, With<Mesh2dHandle>>,
+) {
+    let elapsed = time.elapsed_seconds();
+    for host in &hosts {
+        commands
+            .entity(host)
+            .insert(InstanceGenerator::new(PARTICLES, move |writer| {
+                for index in 0..PARTICLES {
+                    writer.push(particle(index, elapsed));
+                }
+            }));
+    }
+}
+
+fn build_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    hosts: Query<Entity, With<Mesh2dHandle>>,
+) {
+    let elapsed = time.elapsed_seconds();
+    for host in &hosts {
+        let instances = (0..PARTICLES).map(|index| particle(index, elapsed));
+        commands
+            .entity(host)
+            .insert(instances.collect::<InstanceMaterialData<_>>());
+    }
+}
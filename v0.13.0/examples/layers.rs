@@ -0,0 +1,59 @@
+//! A grid of UI-like panels, each drawn from three batches: shadows, panels and badges. The
+//! batches are child hosts of one parent at the same z, [`InstanceLayer`] draws them in order
+//! even though they are spawned out of order.
+
+use bevy::prelude::*;
+use instancing::{InstanceData, InstanceLayer, InstancedMeshBundle, InstancingPlugin};
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, InstancingPlugin::<InstanceData>::default()))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let quad = meshes.add(Rectangle::new(1.0, 1.0));
+    let circle = meshes.add(Circle::new(0.5));
+
+    let panels = |offset: Vec2, scale: f32, color: Color| {
+        (0..4 * 3).map(move |index| {
+            let x = (index % 4) as f32 - 1.5;
+            let y = (index / 4) as f32 - 1.0;
+            InstanceData {
+                position: (Vec2::new(x * 110.0, y * 90.0) + offset).extend(0.0),
+                scale,
+                color: color.as_linear_rgba_f32(),
+                ..default()
+            }
+        })
+    };
+
+    commands
+        .spawn(SpatialBundle::default())
+        .with_children(|parent| {
+            parent.spawn((
+                InstancedMeshBundle::<InstanceData>::new(
+                    circle,
+                    panels(Vec2::new(30.0, 25.0), 24.0, Color::ORANGE_RED),
+                ),
+                InstanceLayer(2),
+            ));
+            parent.spawn((
+                InstancedMeshBundle::<InstanceData>::new(
+                    quad.clone(),
+                    panels(Vec2::new(6.0, -6.0), 72.0, Color::rgba(0.0, 0.0, 0.0, 0.5)),
+                ),
+                InstanceLayer(0),
+            ));
+            parent.spawn((
+                InstancedMeshBundle::<InstanceData>::new(
+                    quad,
+                    panels(Vec2::ZERO, 72.0, Color::SEA_GREEN),
+                ),
+                InstanceLayer(1),
+            ));
+        });
+
+    commands.spawn(Camera2dBundle::default());
+}
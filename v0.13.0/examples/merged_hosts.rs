@@ -0,0 +1,89 @@
+//! A meadow of 1000 flowers, each a host entity of its own with a few petals, turning on its own
+//! [`Transform`]. With [`MergeInstances`], all flowers share one draw call instead of one each.
+//! Press space to toggle the merging, the flowers look the same either way.
+
+use bevy::prelude::*;
+use instancing::{merging::MergeInstances, InstanceData, InstancedMeshBundle, InstancingPlugin};
+use std::f32::consts::TAU;
+
+const FLOWERS: u32 = 1000;
+const PETALS: u32 = 6;
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, InstancingPlugin::<InstanceData>::default()))
+        .add_systems(Startup, setup)
+        .add_systems(Update, (turn_flowers, toggle_merging))
+        .run();
+}
+
+#[derive(Component)]
+struct Flower {
+    speed: f32,
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let petal = meshes.add(Ellipse::new(0.5, 0.2));
+
+    for index in 0..FLOWERS {
+        let seed = index * 4;
+        let position = Vec2::new(random(seed), random(seed + 1)) * Vec2::new(1200.0, 660.0)
+            - Vec2::new(600.0, 330.0);
+        let hue = 360.0 * random(seed + 2);
+        let petals = (0..PETALS).map(|petal| {
+            let angle = petal as f32 / PETALS as f32 * TAU;
+            InstanceData {
+                position: (Vec2::from_angle(angle) * 8.0).extend(0.0),
+                scale: 16.0,
+                rotation: angle,
+                color: Color::hsl(hue, 0.7, 0.6).as_linear_rgba_f32(),
+                ..default()
+            }
+        });
+
+        commands.spawn((
+            // the merged batch would be culled with the bounds of a single flower
+            InstancedMeshBundle::<InstanceData>::new(petal.clone(), petals)
+                .with_transform(Transform::from_translation(position.extend(0.0)))
+                .with_frustum_culling(false),
+            MergeInstances,
+            Flower {
+                speed: random(seed + 3) * 2.0 - 1.0,
+            },
+        ));
+    }
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn turn_flowers(time: Res<Time>, mut flowers: Query<(&Flower, &mut Transform)>) {
+    for (flower, mut transform) in &mut flowers {
+        transform.rotate_z(flower.speed * time.delta_seconds());
+    }
+}
+
+fn toggle_merging(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    flowers: Query<(Entity, Has<MergeInstances>), With<Flower>>,
+) {
+    if !keys.just_pressed(KeyCode::Space) {
+        return;
+    }
+    for (flower, merged) in &flowers {
+        if merged {
+            commands.entity(flower).remove::<MergeInstances>();
+        } else {
+            commands.entity(flower).insert(MergeInstances);
+        }
+    }
+}
+
+/// A value in `0..1` that looks random, to keep the example free of dependencies.
+fn random(seed: u32) -> f32 {
+    let hash = seed
+        .wrapping_mul(0x9e37_79b9)
+        .rotate_left(13)
+        .wrapping_mul(0x85eb_ca6b);
+    (hash >> 8) as f32 / (1 << 24) as f32
+}
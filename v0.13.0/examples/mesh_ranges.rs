@@ -0,0 +1,111 @@
+//! One mesh holding a square, a triangle and a hexagon, drawn by a single host. Each row of
+//! instances selects one of the shapes with [`InstanceMeshRanges`], so all rows share one vertex
+//! and index buffer.
+
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology, VertexAttributeValues},
+        render_asset::RenderAssetUsages,
+    },
+};
+use instancing::{
+    InstanceData, InstanceMeshRange, InstanceMeshRanges, InstancedMeshBundle, InstancingPlugin,
+};
+
+const COLUMNS: u32 = 10;
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, InstancingPlugin::<InstanceData>::default()))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let (mesh, index_ranges) = merge_meshes(&[
+        Rectangle::new(1.0, 1.0).into(),
+        RegularPolygon::new(0.6, 3).into(),
+        RegularPolygon::new(0.6, 6).into(),
+    ]);
+
+    let rows = index_ranges.len() as u32;
+    let instances = (0..rows * COLUMNS).map(|index| {
+        let x = (index % COLUMNS) as f32 - (COLUMNS - 1) as f32 / 2.0;
+        let y = (index / COLUMNS) as f32 - (rows - 1) as f32 / 2.0;
+        InstanceData {
+            position: Vec3::new(x * 50.0, y * 60.0, 0.0),
+            scale: 36.0,
+            color: Color::hsl(index as f32 * 12.0, 0.7, 0.55).as_linear_rgba_f32(),
+            ..default()
+        }
+    });
+
+    // the instances are in row order, so each row is one group
+    let groups = index_ranges
+        .into_iter()
+        .enumerate()
+        .map(|(row, elements)| InstanceMeshRange {
+            instances: row as u32 * COLUMNS..(row as u32 + 1) * COLUMNS,
+            elements,
+        })
+        .collect();
+
+    commands.spawn((
+        InstancedMeshBundle::<InstanceData>::new(meshes.add(mesh), instances),
+        InstanceMeshRanges(groups),
+    ));
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+/// Appends the vertices and indices of `meshes` into one mesh, and returns the range of indices
+/// of each of them.
+fn merge_meshes(meshes: &[Mesh]) -> (Mesh, Vec<std::ops::Range<u32>>) {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut indices = Vec::new();
+    let mut index_ranges = Vec::new();
+
+    for mesh in meshes {
+        let attribute = |id| mesh.attribute(id).unwrap();
+        let (
+            VertexAttributeValues::Float32x3(mesh_positions),
+            VertexAttributeValues::Float32x3(mesh_normals),
+            VertexAttributeValues::Float32x2(mesh_uvs),
+        ) = (
+            attribute(Mesh::ATTRIBUTE_POSITION),
+            attribute(Mesh::ATTRIBUTE_NORMAL),
+            attribute(Mesh::ATTRIBUTE_UV_0),
+        )
+        else {
+            panic!("unexpected vertex formats");
+        };
+
+        let first_vertex = positions.len() as u32;
+        let start = indices.len() as u32;
+        indices.extend(
+            mesh.indices()
+                .unwrap()
+                .iter()
+                .map(|index| first_vertex + index as u32),
+        );
+        index_ranges.push(start..indices.len() as u32);
+
+        positions.extend_from_slice(mesh_positions);
+        normals.extend_from_slice(mesh_normals);
+        uvs.extend_from_slice(mesh_uvs);
+    }
+
+    let mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+    .with_inserted_indices(Indices::U32(indices));
+
+    (mesh, index_ranges)
+}
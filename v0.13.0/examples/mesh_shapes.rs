@@ -0,0 +1,88 @@
+//! One batch per kind of 2D mesh: regular polygons from 3 to 8 sides, a circle, an ellipse, a
+//! triangle and a hand-built arrow drawn as an unindexed triangle strip. Each mesh brings its own
+//! vertex and index count and its own attributes, the instance attributes take the locations they
+//! leave free.
+
+use bevy::{
+    prelude::*,
+    render::{render_asset::RenderAssetUsages, render_resource::PrimitiveTopology},
+};
+use instancing::{InstanceData, InstanceMaterialData, InstancedMeshBundle, InstancingPlugin};
+
+const COLUMNS: u32 = 12;
+const SPACING: f32 = 90.0;
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, InstancingPlugin::<InstanceData>::default()))
+        .add_systems(Startup, setup)
+        .add_systems(Update, rotate)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let mut shapes: Vec<Mesh> = (3..=8)
+        .map(|sides| RegularPolygon::new(0.5, sides).into())
+        .collect();
+    shapes.extend([
+        Circle::new(0.5).mesh().resolution(48).build(),
+        Ellipse::new(0.5, 0.25).into(),
+        Triangle2d::new(
+            Vec2::new(-0.5, -0.4),
+            Vec2::new(0.5, -0.4),
+            Vec2::new(0.0, 0.5),
+        )
+        .into(),
+        arrow(),
+    ]);
+
+    let rows = shapes.len() as u32;
+    for (row, shape) in shapes.into_iter().enumerate() {
+        let row = row as u32;
+        let instances = (0..COLUMNS).map(|column| InstanceData {
+            position: Vec3::new(
+                (column as f32 - (COLUMNS - 1) as f32 / 2.0) * SPACING,
+                ((rows - 1) as f32 / 2.0 - row as f32) * SPACING * 0.7,
+                0.0,
+            ),
+            scale: 50.0,
+            color: Color::hsl(row as f32 * 360.0 / rows as f32, 0.7, 0.6).as_linear_rgba_f32(),
+            ..default()
+        });
+        commands.spawn(InstancedMeshBundle::<InstanceData>::new(
+            meshes.add(shape),
+            instances,
+        ));
+    }
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+/// An arrow pointing up, as a triangle strip without indices and with only positions.
+fn arrow() -> Mesh {
+    let positions = vec![
+        [-0.15, -0.5, 0.0],
+        [0.15, -0.5, 0.0],
+        [-0.15, 0.1, 0.0],
+        [0.15, 0.1, 0.0],
+        // the strip turns back to start the head with a degenerate triangle
+        [0.15, 0.1, 0.0],
+        [-0.4, 0.1, 0.0],
+        [0.4, 0.1, 0.0],
+        [0.0, 0.5, 0.0],
+    ];
+    Mesh::new(
+        PrimitiveTopology::TriangleStrip,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+}
+
+fn rotate(time: Res<Time>, mut hosts: Query<&mut InstanceMaterialData<InstanceData>>) {
+    let t = time.elapsed_seconds();
+    for mut instances in &mut hosts {
+        for (column, instance) in instances.iter_mut().enumerate() {
+            instance.rotation = t * (0.3 + column as f32 * 0.1);
+        }
+    }
+}
@@ -0,0 +1,58 @@
+//! A block of cubes in one 3D host, where every third cube is translucent glass. With
+//! [`MixedOpacity`] the solid cubes write depth and the glass blends over them, no matter in which
+//! order the cubes were pushed. Without it the glass would hide the solid cubes behind it.
+
+use bevy::prelude::*;
+use instancing::{opacity::MixedOpacity, InstanceData, InstancedMeshBundle, Instancing3dPlugin};
+
+const SIZE: i32 = 8;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            Instancing3dPlugin::<InstanceData>::default(),
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, rotate)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let cubes = (0..SIZE.pow(3)).map(|index| {
+        let (x, y, z) = (index % SIZE, index / SIZE % SIZE, index / SIZE.pow(2));
+        let position =
+            Vec3::new(x as f32, y as f32, z as f32) - Vec3::splat((SIZE - 1) as f32 / 2.0);
+        let glass = index % 3 == 0;
+
+        InstanceData {
+            position,
+            scale: 0.6,
+            color: match glass {
+                true => Color::rgba(0.6, 0.85, 1.0, 0.3),
+                false => Color::hsl(index as f32 * 0.7, 0.7, 0.5),
+            }
+            .as_linear_rgba_f32(),
+            ..default()
+        }
+    });
+
+    commands.spawn((
+        InstancedMeshBundle::<InstanceData, Handle<Mesh>>::new(
+            meshes.add(Cuboid::new(1.0, 1.0, 1.0)),
+            cubes,
+        ),
+        MixedOpacity,
+    ));
+
+    commands.spawn(Camera3dBundle {
+        transform: Transform::from_xyz(0.0, -12.0, 10.0).looking_at(Vec3::ZERO, Vec3::Z),
+        ..default()
+    });
+}
+
+fn rotate(time: Res<Time>, mut hosts: Query<&mut Transform, With<MixedOpacity>>) {
+    for mut transform in &mut hosts {
+        transform.rotation = Quat::from_rotation_z(time.elapsed_seconds() * 0.3);
+    }
+}
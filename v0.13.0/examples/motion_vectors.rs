@@ -0,0 +1,46 @@
+//! Spinning dots that write their motion into a motion vector target, the input of a motion blur
+//! or temporal anti-aliasing pass. The render world finds the target of the camera in its
+//! `ViewInstanceTargets`, next to the view target.
+
+use bevy::prelude::*;
+use instancing::{
+    motion_vectors::InstanceMotionVectors, targets::InstanceTargets, InstanceData,
+    InstanceMaterialData, InstancedMeshBundle, InstancingPlugin,
+};
+
+const DOTS: u32 = 64;
+
+fn main() {
+    App::new()
+        // motion vectors of overlapping instances can't be resolved
+        .insert_resource(Msaa::Off)
+        .add_plugins((DefaultPlugins, InstancingPlugin::<InstanceData>::default()))
+        .add_systems(Startup, setup)
+        .add_systems(Update, spin)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let dots = (0..DOTS).map(|index| InstanceData {
+        scale: 20.0,
+        color: Color::hsl(index as f32 * 360.0 / DOTS as f32, 0.7, 0.6).as_linear_rgba_f32(),
+        ..default()
+    });
+
+    commands.spawn((
+        InstancedMeshBundle::<InstanceData>::new(meshes.add(Circle::new(0.5)), dots),
+        InstanceMotionVectors,
+    ));
+
+    commands.spawn((Camera2dBundle::default(), InstanceTargets::motion_vectors()));
+}
+
+fn spin(time: Res<Time>, mut hosts: Query<&mut InstanceMaterialData<InstanceData>>) {
+    for mut dots in &mut hosts {
+        for (index, dot) in dots.iter_mut().enumerate() {
+            let ring = 1 + index as u32 % 4;
+            let angle = time.elapsed_seconds() * 4.0 / ring as f32 + index as f32;
+            dot.position = (Vec2::from_angle(angle) * 60.0 * ring as f32).extend(0.0);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+//! 50k static instances of which one changes color every frame. The edit goes through
+//! [`InstanceMaterialData::set`], so only that instance is written to the instance buffer. Run with
+//! `--full` to edit it through the `Vec` instead, which writes all instances again, and compare
+//! the frame times logged by both.
+
+use bevy::{
+    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    prelude::*,
+};
+use instancing::{InstanceData, InstanceMaterialData, InstancedMeshBundle, InstancingPlugin};
+
+const COLUMNS: usize = 250;
+const INSTANCES: usize = 50_000;
+
+fn main() {
+    let full = std::env::args().any(|arg| arg == "--full");
+
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            FrameTimeDiagnosticsPlugin,
+            LogDiagnosticsPlugin::default(),
+            InstancingPlugin::<InstanceData>::default(),
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(
+            Update,
+            (
+                edit_one.run_if(move || !full),
+                edit_one_full.run_if(move || full),
+            ),
+        )
+        .run();
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let instances = (0..INSTANCES).map(|index| {
+        let x = (index % COLUMNS) as f32 - COLUMNS as f32 / 2.0;
+        let y = (index / COLUMNS) as f32 - (INSTANCES / COLUMNS) as f32 / 2.0;
+        InstanceData {
+            position: Vec3::new(x * 4.0, y * 4.0, 0.0),
+            scale: 3.0,
+            color: Color::GRAY.as_linear_rgba_f32(),
+            ..default()
+        }
+    });
+
+    commands.spawn(InstancedMeshBundle::<InstanceData>::new(
+        meshes.add(Rectangle::new(1.0, 1.0)),
+        instances,
+    ));
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+/// The instance lit up in the given frame.
+fn edited(frame: u32) -> (usize, [f32; 4]) {
+    let index = frame as usize * 7919 % INSTANCES;
+    let color = Color::hsl(frame as f32 % 360.0, 0.9, 0.6).as_linear_rgba_f32();
+    (index, color)
+}
+
+fn edit_one(mut frame: Local<u32>, mut hosts: Query<&mut InstanceMaterialData<InstanceData>>) {
+    *frame += 1;
+    let (index, color) = edited(*frame);
+    for mut instances in &mut hosts {
+        let instance = InstanceData {
+            color,
+            ..instances[index]
+        };
+        instances.set(index, instance);
+    }
+}
+
+fn edit_one_full(mut frame: Local<u32>, mut hosts: Query<&mut InstanceMaterialData<InstanceData>>) {
+    *frame += 1;
+    let (index, color) = edited(*frame);
+    for mut instances in &mut hosts {
+        // indexing the `Vec` mutably can't be tracked, so every instance is written again
+        instances[index].color = color;
+    }
+}
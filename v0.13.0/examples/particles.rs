@@ -0,0 +1,151 @@
+//! A fountain of a few thousand additively blended particles that fade out over their lifetime.
+//!
+//! The particles live in an [`InstanceMaterialData`] that is updated in place every frame. Its
+//! length never changes, so the instance buffer is reused instead of reallocated, and dead
+//! particles are recycled by the spawner rather than removed. All of them move every frame, so the
+//! host uploads into an [`InstanceBufferRing`] instead of waiting on the buffer drawn last frame.
+//! The [`InstancingDiagnosticsPlugin`] logs that the buffers are allocated once and then reused.
+
+use bevy::{
+    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    prelude::*,
+};
+use instancing::{
+    diagnostics::InstancingDiagnosticsPlugin, InstanceBlendMode, InstanceBufferRing, InstanceData,
+    InstanceMaterialData, InstancedMeshBundle, InstancingPlugin,
+};
+
+const PARTICLES: usize = 4_000;
+/// Seconds a particle lives.
+const LIFETIME: f32 = 2.5;
+/// Particles spawned per second, about as many as die so the fountain stays full.
+const SPAWN_RATE: f32 = PARTICLES as f32 / LIFETIME;
+const GRAVITY: Vec3 = Vec3::new(0.0, -300.0, 0.0);
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            FrameTimeDiagnosticsPlugin,
+            LogDiagnosticsPlugin::default(),
+            InstancingPlugin::<InstanceData>::default(),
+            InstancingDiagnosticsPlugin,
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, (spawn_particles, update_particles).chain())
+        .run();
+}
+
+/// The simulation state of the particles, indexed like the instances of the host.
+#[derive(Component)]
+struct Particles {
+    velocities: Vec<Vec3>,
+    /// Seconds since spawn, particles at or past [`LIFETIME`] are dead.
+    ages: Vec<f32>,
+}
+
+/// Revives dead particles at the bottom of the fountain.
+#[derive(Component)]
+struct Spawner {
+    /// Particles owed from previous frames, as the rate rarely divides a frame evenly.
+    pending: f32,
+    seed: u32,
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let dead = InstanceData {
+        color: [0.0; 4],
+        ..default()
+    };
+
+    commands.spawn((
+        InstancedMeshBundle::<InstanceData>::new(
+            meshes.add(Circle::new(0.5)),
+            std::iter::repeat_n(dead, PARTICLES),
+        ),
+        InstanceBlendMode::Additive,
+        InstanceBufferRing::default(),
+        Particles {
+            velocities: vec![Vec3::ZERO; PARTICLES],
+            ages: vec![LIFETIME; PARTICLES],
+        },
+        Spawner {
+            pending: 0.0,
+            seed: 0,
+        },
+    ));
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn spawn_particles(
+    time: Res<Time>,
+    mut hosts: Query<(
+        &mut InstanceMaterialData<InstanceData>,
+        &mut Particles,
+        &mut Spawner,
+    )>,
+) {
+    for (mut instances, mut particles, mut spawner) in &mut hosts {
+        spawner.pending += SPAWN_RATE * time.delta_seconds();
+
+        let Particles { velocities, ages } = &mut *particles;
+        for (index, age) in ages.iter_mut().enumerate() {
+            if spawner.pending < 1.0 {
+                break;
+            }
+            if *age < LIFETIME {
+                continue;
+            }
+
+            spawner.seed += 2;
+            let angle = (0.5 + (random(spawner.seed) - 0.5) * 0.6) * std::f32::consts::PI;
+            let speed = 250.0 + 200.0 * random(spawner.seed + 1);
+
+            *age = 0.0;
+            velocities[index] = Vec3::new(angle.cos(), angle.sin(), 0.0) * speed;
+            instances[index] = InstanceData {
+                position: Vec3::new(0.0, -200.0, 0.0),
+                scale: 6.0,
+                color: Color::hsl(20.0 + 40.0 * random(spawner.seed), 0.9, 0.5)
+                    .as_linear_rgba_f32(),
+                ..default()
+            };
+            spawner.pending -= 1.0;
+        }
+
+        // all particles are alive, drop the rest instead of piling them up
+        spawner.pending = spawner.pending.min(1.0);
+    }
+}
+
+fn update_particles(
+    time: Res<Time>,
+    mut hosts: Query<(&mut InstanceMaterialData<InstanceData>, &mut Particles)>,
+) {
+    let delta = time.delta_seconds();
+
+    for (mut instances, mut particles) in &mut hosts {
+        let Particles { velocities, ages } = &mut *particles;
+        for ((instance, velocity), age) in instances.iter_mut().zip(velocities).zip(ages) {
+            if *age >= LIFETIME {
+                continue;
+            }
+
+            *age += delta;
+            *velocity += GRAVITY * delta;
+            instance.position += *velocity * delta;
+            // a dead particle is fully transparent and adds nothing until it is recycled
+            instance.color[3] = (1.0 - *age / LIFETIME).max(0.0);
+        }
+    }
+}
+
+/// A value in `0..1` that looks random, to keep the example free of dependencies.
+fn random(seed: u32) -> f32 {
+    let hash = seed
+        .wrapping_mul(0x9e37_79b9)
+        .rotate_left(13)
+        .wrapping_mul(0x85eb_ca6b);
+    (hash >> 8) as f32 / (1 << 24) as f32
+}
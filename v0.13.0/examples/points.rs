@@ -0,0 +1,54 @@
+//! A star field of 200k points between 1 and 4 pixels wide, which keep their size on screen while
+//! the camera zooms in and out. [`PointInstances`] draws the scale of the instances in pixels.
+
+use bevy::prelude::*;
+use instancing::{InstanceData, InstancedMeshBundle, InstancingPlugin, PointInstances};
+
+const STARS: u32 = 200_000;
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, InstancingPlugin::<InstanceData>::default()))
+        .add_systems(Startup, setup)
+        .add_systems(Update, zoom)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let stars = (0..STARS).map(|index| {
+        let seed = index * 4;
+        let position = Vec2::new(random(seed), random(seed + 1)) * 4000.0 - 2000.0;
+        let brightness = random(seed + 2);
+        InstanceData {
+            position: position.extend(0.0),
+            scale: 1.0 + 3.0 * brightness * brightness,
+            color: Color::hsl(200.0 + 60.0 * random(seed + 3), 0.5, 0.4 + 0.5 * brightness)
+                .as_linear_rgba_f32(),
+            ..default()
+        }
+    });
+
+    commands.spawn((
+        // zoomed out, the stars span more world units than their scale
+        InstancedMeshBundle::<InstanceData>::new(meshes.add(Circle::new(0.5)), stars)
+            .with_frustum_culling(false),
+        PointInstances,
+    ));
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn zoom(time: Res<Time>, mut projections: Query<&mut OrthographicProjection>) {
+    for mut projection in &mut projections {
+        projection.scale = 2.0 + 1.5 * (time.elapsed_seconds() * 0.3).sin();
+    }
+}
+
+/// A value in `0..1` that looks random, to keep the example free of dependencies.
+fn random(seed: u32) -> f32 {
+    let hash = seed
+        .wrapping_mul(0x9e37_79b9)
+        .rotate_left(13)
+        .wrapping_mul(0x85eb_ca6b);
+    (hash >> 8) as f32 / (1 << 24) as f32
+}
@@ -0,0 +1,61 @@
+//! Stars from a hand-built mesh with only positions, without normals or UVs. The instance
+//! attributes take the locations the missing mesh attributes leave free.
+
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_asset::RenderAssetUsages, render_resource::PrimitiveTopology},
+};
+use instancing::{InstanceData, InstancedMeshBundle, InstancingPlugin};
+
+const POINTS: u32 = 5;
+const COLUMNS: u32 = 12;
+const ROWS: u32 = 7;
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, InstancingPlugin::<InstanceData>::default()))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    // a fan around the center, alternating between the outer and inner corners
+    let positions: Vec<_> = std::iter::once([0.0, 0.0, 0.0])
+        .chain((0..POINTS * 2).map(|corner| {
+            let angle = corner as f32 * std::f32::consts::PI / POINTS as f32;
+            let radius = if corner % 2 == 0 { 0.5 } else { 0.2 };
+            [-angle.sin() * radius, angle.cos() * radius, 0.0]
+        }))
+        .collect();
+    let indices = (1..=POINTS * 2)
+        .flat_map(|corner| [0, corner, corner % (POINTS * 2) + 1])
+        .collect();
+    let mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_indices(Indices::U32(indices));
+
+    let instances = (0..COLUMNS * ROWS).map(|index| {
+        let (column, row) = (index % COLUMNS, index / COLUMNS);
+        InstanceData {
+            position: Vec3::new(
+                (column as f32 - (COLUMNS - 1) as f32 / 2.0) * 90.0,
+                (row as f32 - (ROWS - 1) as f32 / 2.0) * 90.0,
+                0.0,
+            ),
+            scale: 80.0,
+            color: Color::hsl(index as f32 * 9.0, 0.9, 0.6).as_linear_rgba_f32(),
+            rotation: index as f32,
+            ..default()
+        }
+    });
+
+    commands.spawn(InstancedMeshBundle::<InstanceData>::new(
+        meshes.add(mesh),
+        instances,
+    ));
+
+    commands.spawn(Camera2dBundle::default());
+}
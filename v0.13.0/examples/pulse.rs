@@ -0,0 +1,58 @@
+//! A field of pickups drawn from [`PulsingInstanceData`], each pulsing with its own amplitude,
+//! frequency and phase. The pulse runs in the vertex shader, so the instances are uploaded once and
+//! never written again, and the logged reallocations stay at zero while they pulse. A wave runs
+//! across the field as the phase grows with the distance from the center.
+
+use bevy::{diagnostic::LogDiagnosticsPlugin, prelude::*};
+use instancing::{
+    diagnostics::InstancingDiagnosticsPlugin, InstancedMeshBundle, InstancingPlugin,
+    PulsingInstanceData,
+};
+
+const COLUMNS: u32 = 24;
+const ROWS: u32 = 14;
+/// Distance between the pickups in pixels.
+const SPACING: f32 = 48.0;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            LogDiagnosticsPlugin::default(),
+            InstancingPlugin::<PulsingInstanceData>::default(),
+            InstancingDiagnosticsPlugin,
+        ))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let pickups = (0..ROWS).flat_map(|row| {
+        (0..COLUMNS).map(move |column| {
+            let position = (Vec2::new(column as f32, row as f32)
+                - 0.5 * Vec2::new(COLUMNS as f32 - 1.0, ROWS as f32 - 1.0))
+                * SPACING;
+            // every third pickup beats faster and harder, like a rare item
+            let rare = (row * COLUMNS + column).is_multiple_of(3);
+            let (amplitude, frequency) = if rare { (0.3, 6.0) } else { (0.12, 3.0) };
+            PulsingInstanceData {
+                position: position.extend(0.0),
+                scale: 0.5 * SPACING,
+                rotation: std::f32::consts::FRAC_PI_4,
+                color: if rare {
+                    Color::GOLD.as_linear_rgba_f32()
+                } else {
+                    Color::TURQUOISE.as_linear_rgba_f32()
+                },
+                pulse: [amplitude, frequency, -position.length() / 60.0],
+                ..default()
+            }
+        })
+    });
+
+    commands.spawn(InstancedMeshBundle::<PulsingInstanceData>::new(
+        meshes.add(Rectangle::new(1.0, 1.0)),
+        pickups,
+    ));
+    commands.spawn(Camera2dBundle::default());
+}
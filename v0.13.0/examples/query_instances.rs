@@ -0,0 +1,69 @@
+//! Fireflies that are plain top level entities with a `Transform` and a `Firefly` component, drawn
+//! as the instances of a single host that doesn't own them. The closure of the
+//! [`QueryInstancesPlugin`] takes the color and the glow of each firefly from its component.
+
+use bevy::prelude::*;
+use instancing::{
+    entity_instances::{QueryInstances, QueryInstancesPlugin},
+    InstanceData, InstancedMeshBundle, InstancingPlugin,
+};
+
+const FIREFLIES: u32 = 300;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            InstancingPlugin::<InstanceData>::default(),
+            QueryInstancesPlugin::new(|transform, firefly: &Firefly| InstanceData {
+                scale: 6.0 + 6.0 * firefly.glow,
+                color: firefly.color.with_a(firefly.glow).as_linear_rgba_f32(),
+                ..InstanceData::from_transform(transform)
+            }),
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, fly)
+        .run();
+}
+
+#[derive(Component)]
+struct Firefly {
+    color: Color,
+    /// In `0..1`, pulsing over time.
+    glow: f32,
+    phase: f32,
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    commands.spawn((
+        InstancedMeshBundle::<InstanceData>::new(meshes.add(Circle::new(0.5)), []),
+        QueryInstances::<Firefly>::default(),
+    ));
+
+    for index in 0..FIREFLIES {
+        let phase = index as f32 * 2.4;
+        commands.spawn((
+            Firefly {
+                color: Color::hsl(40.0 + 40.0 * (phase * 0.37).sin(), 0.9, 0.6),
+                glow: 0.0,
+                phase,
+            },
+            TransformBundle::default(),
+        ));
+    }
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn fly(time: Res<Time>, mut fireflies: Query<(&mut Firefly, &mut Transform)>) {
+    let t = time.elapsed_seconds();
+    for (mut firefly, mut transform) in &mut fireflies {
+        let phase = firefly.phase;
+        firefly.glow = 0.5 + 0.5 * (t * 2.0 + phase).sin();
+        transform.translation = Vec3::new(
+            500.0 * (t * 0.11 + phase).sin() * (t * 0.07 + phase * 1.3).cos(),
+            300.0 * (t * 0.13 + phase * 0.7).cos(),
+            0.0,
+        );
+    }
+}
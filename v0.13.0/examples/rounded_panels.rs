@@ -0,0 +1,46 @@
+//! A grid of UI-like panels with rounded corners, drawn from a single unit quad. The corner radius
+//! grows from left to right, up to pills, and every other row has a border that follows the
+//! corners.
+
+use bevy::prelude::*;
+use instancing::{InstancedMeshBundle, InstancingPlugin, StretchedInstanceData};
+
+const COLUMNS: usize = 6;
+const ROWS: usize = 4;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            InstancingPlugin::<StretchedInstanceData>::default(),
+        ))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let instances = (0..COLUMNS * ROWS).map(|index| {
+        let column = index % COLUMNS;
+        let row = index / COLUMNS;
+        let x = column as f32 - (COLUMNS - 1) as f32 / 2.0;
+        let y = row as f32 - (ROWS - 1) as f32 / 2.0;
+
+        StretchedInstanceData {
+            position: Vec3::new(x * 130.0, y * 90.0, 0.0),
+            scale: Vec2::new(110.0, 64.0),
+            color: Color::hsl(200.0 + 25.0 * row as f32, 0.5, 0.35).as_linear_rgba_f32(),
+            border_color: Color::rgb(0.9, 0.9, 0.8).as_linear_rgba_f32(),
+            border_width: if row.is_multiple_of(2) { 3.0 } else { 0.0 },
+            // the last column exceeds half the height and is limited to it
+            corner_radius: column as f32 * 8.0,
+            ..default()
+        }
+    });
+
+    commands.spawn(InstancedMeshBundle::<StretchedInstanceData>::new(
+        meshes.add(Rectangle::new(1.0, 1.0)),
+        instances,
+    ));
+
+    commands.spawn(Camera2dBundle::default());
+}
@@ -0,0 +1,91 @@
+//! A simulation running on its own thread without bevy, like a headless server, fills the plain
+//! instance types of the `instancing_data` crate and sends their bytes over a channel. The app
+//! casts the bytes it receives straight into [`InstanceData`] and draws them.
+
+use bevy::prelude::*;
+use instancing::{
+    plain::{self, glam},
+    InstanceData, InstanceMaterialData, InstancedMeshBundle, InstancingPlugin,
+};
+use std::{
+    sync::{
+        mpsc::{self, Receiver},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+const BOIDS: usize = 300;
+
+fn main() {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut tick = 0;
+        // stops once the app closed the channel
+        while sender.send(simulate(tick)).is_ok() {
+            tick += 1;
+            thread::sleep(Duration::from_millis(16));
+        }
+    });
+
+    App::new()
+        .add_plugins((DefaultPlugins, InstancingPlugin::<InstanceData>::default()))
+        .insert_resource(Snapshots(Mutex::new(receiver)))
+        .add_systems(Startup, setup)
+        .add_systems(Update, apply_snapshots)
+        .run();
+}
+
+/// The "server" side, which only knows the plain types and glam.
+fn simulate(tick: u32) -> Vec<u8> {
+    let time = tick as f32 / 60.0;
+    let instances: Vec<_> = (0..BOIDS)
+        .map(|index| {
+            let angle = time * 0.4 + index as f32 * 0.37;
+            let radius = 60.0 + (index % 25) as f32 * 12.0;
+            plain::InstanceData {
+                position: glam::Vec3::new(angle.cos() * radius, angle.sin() * radius, 0.0),
+                scale: 8.0,
+                rotation: angle + std::f32::consts::FRAC_PI_2,
+                color: [0.3 + 0.7 * (index % 3) as f32 / 2.0, 0.6, 0.9, 1.0],
+                ..default()
+            }
+        })
+        .collect();
+    bytemuck::cast_slice(&instances).to_vec()
+}
+
+/// The receiving end of the channel, a `Mutex` as the `Receiver` isn't `Sync`.
+#[derive(Resource)]
+struct Snapshots(Mutex<Receiver<Vec<u8>>>);
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    commands.spawn(InstancedMeshBundle::<InstanceData>::new(
+        meshes.add(Triangle2d::new(
+            Vec2::new(0.0, 1.0),
+            Vec2::new(-0.6, -0.8),
+            Vec2::new(0.6, -0.8),
+        )),
+        [],
+    ));
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn apply_snapshots(
+    snapshots: Res<Snapshots>,
+    mut hosts: Query<&mut InstanceMaterialData<InstanceData>>,
+) {
+    // only the latest snapshot is drawn
+    let Some(bytes) = snapshots.0.lock().unwrap().try_iter().last() else {
+        return;
+    };
+    // the bytes of a `Vec<u8>` aren't aligned for the instances, so they are copied
+    let instances: Vec<InstanceData> = bytes
+        .chunks_exact(std::mem::size_of::<InstanceData>())
+        .map(bytemuck::pod_read_unaligned)
+        .collect();
+    for mut host in &mut hosts {
+        **host = instances.clone();
+    }
+}
@@ -0,0 +1,81 @@
+//! Cards dealt onto a table one after another, each a host of its own at the same z. With
+//! [`SortKeyStrategy::SpawnOrder`] every new card lands on top of the ones dealt before it,
+//! instead of in whatever order the hosts at the same z happen to be queued in.
+
+use bevy::prelude::*;
+use instancing::{InstanceData, InstancedMeshBundle, InstancingPlugin, SortKeyStrategy};
+
+/// Seconds between two cards.
+const DEAL_INTERVAL: f32 = 0.25;
+const MAX_CARDS: u32 = 60;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            InstancingPlugin::<InstanceData>::default()
+                .with_sort_key_strategy(SortKeyStrategy::SpawnOrder),
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, deal)
+        .run();
+}
+
+#[derive(Resource)]
+struct Dealer {
+    timer: Timer,
+    dealt: u32,
+    card: Handle<Mesh>,
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    commands.insert_resource(Dealer {
+        timer: Timer::from_seconds(DEAL_INTERVAL, TimerMode::Repeating),
+        dealt: 0,
+        card: meshes.add(Rectangle::new(1.0, 1.4)),
+    });
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn deal(mut commands: Commands, time: Res<Time>, mut dealer: ResMut<Dealer>) {
+    if dealer.dealt == MAX_CARDS || !dealer.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let seed = dealer.dealt * 3;
+    let position = Vec2::new(random(seed) - 0.5, random(seed + 1) - 0.5) * Vec2::new(500.0, 300.0);
+    let hue = 360.0 * random(seed + 2);
+    // a border and a face, so the card on top is easy to tell apart
+    let card = [
+        InstanceData {
+            scale: 110.0,
+            color: Color::WHITE.as_linear_rgba_f32(),
+            rotation: random(seed + 2) - 0.5,
+            corner_radius: 8.0,
+            border_width: 4.0,
+            border_color: Color::hsl(hue, 0.7, 0.3).as_linear_rgba_f32(),
+            ..default()
+        },
+        InstanceData {
+            scale: 60.0,
+            color: Color::hsl(hue, 0.7, 0.6).as_linear_rgba_f32(),
+            rotation: random(seed + 2) - 0.5,
+            corner_radius: 30.0,
+            ..default()
+        },
+    ];
+    commands.spawn(
+        InstancedMeshBundle::<InstanceData>::new(dealer.card.clone(), card)
+            .with_transform(Transform::from_translation(position.extend(0.0))),
+    );
+    dealer.dealt += 1;
+}
+
+/// A value in `0..1` that looks random, to keep the example free of dependencies.
+fn random(seed: u32) -> f32 {
+    let hash = seed
+        .wrapping_mul(0x9e37_79b9)
+        .rotate_left(13)
+        .wrapping_mul(0x85eb_ca6b);
+    (hash >> 8) as f32 / (1 << 24) as f32
+}
@@ -0,0 +1,73 @@
+//! A single host with more instances than fit into one buffer on most adapters, 1.5 million
+//! instances of 96 bytes exceed the default 128 MiB a storage buffer can be bound with. The
+//! instances are split across several buffers and drawn one buffer after the other, so the whole
+//! gradient shows up. Logs how many instances a buffer holds on this adapter.
+
+use bevy::{
+    prelude::*,
+    render::{Render, RenderApp},
+};
+use instancing::{InstanceData, InstancePipeline, InstancedMeshBundle, InstancingPlugin};
+
+const COLUMNS: usize = 1500;
+const ROWS: usize = 1000;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            InstancingPlugin::<InstanceData>::default(),
+            LogLimitPlugin,
+        ))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let instances = (0..COLUMNS * ROWS).map(|index| {
+        let column = index % COLUMNS;
+        let row = index / COLUMNS;
+        let (x, y) = (column as f32 / COLUMNS as f32, row as f32 / ROWS as f32);
+
+        InstanceData {
+            position: Vec3::new((x - 0.5) * COLUMNS as f32, (y - 0.5) * ROWS as f32, 0.0),
+            scale: 0.8,
+            // the buffers hold consecutive rows, a missing buffer leaves a band of the gradient out
+            color: Color::hsl(y * 300.0, 0.8, 0.3 + 0.4 * x).as_linear_rgba_f32(),
+            ..default()
+        }
+    });
+
+    commands.spawn(InstancedMeshBundle::<InstanceData>::new(
+        meshes.add(Rectangle::new(1.0, 1.0)),
+        instances,
+    ));
+
+    commands.spawn(Camera2dBundle {
+        projection: OrthographicProjection {
+            scale: 1.25,
+            ..default()
+        },
+        ..default()
+    });
+}
+
+struct LogLimitPlugin;
+
+impl Plugin for LogLimitPlugin {
+    fn build(&self, _app: &mut App) {}
+
+    fn finish(&self, app: &mut App) {
+        app.sub_app_mut(RenderApp)
+            .add_systems(Render, log_limit.run_if(run_once()));
+    }
+}
+
+fn log_limit(instance_pipeline: Res<InstancePipeline<InstanceData>>) {
+    let per_buffer = instance_pipeline.max_instances_per_buffer();
+    info!(
+        "a buffer holds {per_buffer} instances, drawing {} instances from {} buffers",
+        COLUMNS * ROWS,
+        (COLUMNS * ROWS).div_ceil(per_buffer)
+    );
+}
@@ -0,0 +1,67 @@
+//! Two cameras side by side looking at the same instanced grid. Both views draw the host from its
+//! shared instance buffer, the right one is zoomed in and only sees the center of the grid.
+
+use bevy::{prelude::*, render::camera::Viewport, window::WindowResized};
+use instancing::{InstanceData, InstancedMeshBundle, InstancingPlugin};
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, InstancingPlugin::<InstanceData>::default()))
+        .add_systems(Startup, setup)
+        .add_systems(Update, set_camera_viewports)
+        .run();
+}
+
+/// Which half of the window a camera renders to, from the left.
+#[derive(Component)]
+struct Side(u32);
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let instances = (0..20 * 20).map(|index| {
+        let x = (index % 20) as f32;
+        let y = (index / 20) as f32;
+        InstanceData {
+            position: Vec3::new((x - 9.5) * 30.0, (y - 9.5) * 30.0, 0.0),
+            scale: 20.0,
+            color: Color::hsl(x * 18.0, 0.8, 0.3 + y / 40.0).as_linear_rgba_f32(),
+            ..default()
+        }
+    });
+
+    commands.spawn(InstancedMeshBundle::<InstanceData>::new(
+        meshes.add(Rectangle::new(1.0, 1.0)),
+        instances,
+    ));
+
+    for (side, zoom) in [1.0, 0.4].into_iter().enumerate() {
+        let mut camera = Camera2dBundle::default();
+        camera.camera.order = side as isize;
+        camera.projection.scale = zoom;
+        if side > 0 {
+            // the first camera already cleared the window
+            camera.camera.clear_color = ClearColorConfig::None;
+        }
+        commands.spawn((camera, Side(side as u32)));
+    }
+}
+
+fn set_camera_viewports(
+    windows: Query<&Window>,
+    mut resize_events: EventReader<WindowResized>,
+    mut cameras: Query<(&mut Camera, &Side)>,
+) {
+    for event in resize_events.read() {
+        let Ok(window) = windows.get(event.window) else {
+            continue;
+        };
+        let half = UVec2::new(window.physical_width() / 2, window.physical_height());
+
+        for (mut camera, side) in &mut cameras {
+            camera.viewport = Some(Viewport {
+                physical_position: UVec2::new(side.0 * half.x, 0),
+                physical_size: half,
+                ..default()
+            });
+        }
+    }
+}
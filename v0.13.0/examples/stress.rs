@@ -0,0 +1,203 @@
+//! Draws a configurable number of quads, up to a few million, to find where the frame time of a
+//! machine degrades. Doubles as a manual benchmark to compare across bevy versions and commits.
+//!
+//! `cargo run --release --example stress -- 2000000` draws 2 million quads, the count can also be
+//! set with the `INSTANCES` environment variable and defaults to 100k. Up and down double and
+//! halve it at runtime, which respawns the hosts and reuses their buffers through the
+//! [`InstanceBufferPool`](instancing::pool::InstanceBufferPool). The frame rate, the instances
+//! drawn, the bytes of the instance buffers and the buffers created per second are shown on
+//! screen.
+//!
+//! - Without flags the instances never change after they were spawned, so they are uploaded once
+//!   and the frame time is bound by drawing them, by the vertex work and the overdraw of the quads.
+//! - `--animate` rotates every instance every frame, which writes them all on the CPU and uploads
+//!   them again, so the frame time is bound by the upload. The gap to the static frame time is
+//!   the cost of the upload.
+//! - `--storage` reads the instances from storage buffers instead of vertex buffers, see
+//!   [`InstanceBufferMode::Storage`], compare it with the default to see which path the GPU
+//!   prefers.
+//!
+//! The instances are split into hosts of [`HOST_INSTANCES`], as a single buffer can't hold
+//! millions of them: an [`InstanceData`] takes 104 bytes, and wgpu limits buffers to 256 MiB and
+//! storage buffer bindings to 128 MiB by default, about 2.5 million and 1.3 million instances. A
+//! single host can't draw more than that however fast the GPU is.
+//!
+//! Measure with `--release`. The window runs without vsync, so the frame rate isn't capped at the
+//! refresh rate of the display. Note the GPU, the backend, the mode and the count at which the
+//! frame time passes 16.7 ms when adding measurements:
+//!
+//! - llvmpipe (LLVM 15.0.6, 256 bits) on one CPU core, GL, bevy 0.13.2, rendered off-screen at
+//!   1280x720 without pipelined rendering: never below 16.7 ms, an empty frame takes 29 ms. 100k
+//!   instances take 226 ms, 281 ms with `--animate` and 251 ms with `--storage`. A software
+//!   rasterizer, only useful as a floor.
+
+use bevy::{
+    diagnostic::{DiagnosticPath, DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+    window::PresentMode,
+};
+use instancing::{
+    diagnostics::InstancingDiagnosticsPlugin, InstanceBufferMode, InstanceData,
+    InstanceMaterialData, InstancedMeshBundle, InstancingPlugin,
+};
+
+/// Instances of each host, 26 MB of instance buffer.
+const HOST_INSTANCES: usize = 250_000;
+/// Side of the square the instances are spread over.
+const EXTENT: f32 = 1000.0;
+
+fn main() {
+    let count = std::env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse().ok())
+        .or_else(|| std::env::var("INSTANCES").ok()?.parse().ok())
+        .unwrap_or(100_000);
+    let animate = std::env::args().any(|arg| arg == "--animate");
+    let buffer_mode = if std::env::args().any(|arg| arg == "--storage") {
+        InstanceBufferMode::Storage
+    } else {
+        InstanceBufferMode::Vertex
+    };
+
+    App::new()
+        .add_plugins((
+            DefaultPlugins.set(WindowPlugin {
+                primary_window: Some(Window {
+                    present_mode: PresentMode::AutoNoVsync,
+                    ..default()
+                }),
+                ..default()
+            }),
+            FrameTimeDiagnosticsPlugin,
+            InstancingPlugin::<InstanceData>::default().with_buffer_mode(buffer_mode),
+            InstancingDiagnosticsPlugin,
+        ))
+        .insert_resource(InstanceCount(count))
+        .add_systems(Startup, setup)
+        .add_systems(
+            Update,
+            (
+                change_count,
+                respawn_hosts.run_if(resource_changed::<InstanceCount>),
+                rotate.run_if(move || animate),
+                show_stats,
+            )
+                .chain(),
+        )
+        .run();
+}
+
+#[derive(Resource)]
+struct InstanceCount(usize);
+
+#[derive(Resource)]
+struct Quad(Handle<Mesh>);
+
+#[derive(Component)]
+struct Stats;
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    commands.insert_resource(Quad(meshes.add(Rectangle::new(1.0, 1.0))));
+    commands.spawn(Camera2dBundle::default());
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 20.0,
+                ..default()
+            },
+        )
+        .with_background_color(Color::rgba(0.0, 0.0, 0.0, 0.7))
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(8.0),
+            top: Val::Px(8.0),
+            ..default()
+        }),
+        Stats,
+    ));
+}
+
+fn change_count(keys: Res<ButtonInput<KeyCode>>, mut count: ResMut<InstanceCount>) {
+    if keys.just_pressed(KeyCode::ArrowUp) {
+        count.0 = (count.0 * 2).max(1);
+    }
+    if keys.just_pressed(KeyCode::ArrowDown) {
+        count.0 /= 2;
+    }
+}
+
+/// Spawns the instances in a grid filling the square, split into hosts.
+fn respawn_hosts(
+    mut commands: Commands,
+    count: Res<InstanceCount>,
+    quad: Res<Quad>,
+    hosts: Query<Entity, With<InstanceMaterialData<InstanceData>>>,
+) {
+    for host in &hosts {
+        commands.entity(host).despawn();
+    }
+
+    let columns = (count.0 as f32).sqrt().ceil().max(1.0) as usize;
+    let spacing = EXTENT / columns as f32;
+    for start in (0..count.0).step_by(HOST_INSTANCES) {
+        let end = (start + HOST_INSTANCES).min(count.0);
+        let instances = (start..end).map(|index| {
+            let column = (index % columns) as f32;
+            let row = (index / columns) as f32;
+            InstanceData {
+                position: Vec3::new(
+                    (column + 0.5) * spacing - EXTENT / 2.0,
+                    (row + 0.5) * spacing - EXTENT / 2.0,
+                    0.0,
+                ),
+                scale: spacing * 0.8,
+                color: Color::hsl(index as f32 / count.0 as f32 * 360.0, 0.8, 0.6)
+                    .as_linear_rgba_f32(),
+                ..default()
+            }
+        });
+        commands.spawn(InstancedMeshBundle::<InstanceData>::new(
+            quad.0.clone(),
+            instances,
+        ));
+    }
+}
+
+/// Writes every instance, so all of them are uploaded again.
+fn rotate(time: Res<Time>, mut hosts: Query<&mut InstanceMaterialData<InstanceData>>) {
+    let rotation = time.elapsed_seconds();
+    for mut instances in &mut hosts {
+        for instance in instances.iter_mut() {
+            instance.rotation = rotation;
+        }
+    }
+}
+
+fn show_stats(
+    diagnostics: Res<DiagnosticsStore>,
+    count: Res<InstanceCount>,
+    mut text: Query<&mut Text, With<Stats>>,
+) {
+    let value = |path: &DiagnosticPath| {
+        diagnostics
+            .get(path)
+            .and_then(|diagnostic| diagnostic.smoothed())
+            .unwrap_or(0.0)
+    };
+    let fps = value(&FrameTimeDiagnosticsPlugin::FPS);
+    let frame_time = value(&FrameTimeDiagnosticsPlugin::FRAME_TIME);
+    let drawn = value(&InstancingDiagnosticsPlugin::INSTANCES);
+    let bytes = value(&InstancingDiagnosticsPlugin::BUFFER_BYTES);
+    let reallocations = value(&InstancingDiagnosticsPlugin::REALLOCATIONS);
+
+    text.single_mut().sections[0].value = format!(
+        "{fps:.0} fps, {frame_time:.2} ms\n\
+         {} instances, {drawn:.0} drawn\n\
+         {:.1} MiB of instance buffers\n\
+         {reallocations:.1} buffers created/s\n\
+         up/down: double/halve",
+        count.0,
+        bytes / (1024.0 * 1024.0),
+    );
+}
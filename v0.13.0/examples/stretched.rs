@@ -0,0 +1,50 @@
+//! Rectangles of random aspect ratios drawn from a single unit quad with
+//! [`StretchedInstanceData`], which scales the x and y axes separately. Their borders keep the
+//! same width on every side, and fill the rectangles too thin to hold them.
+
+use bevy::prelude::*;
+use instancing::{InstancedMeshBundle, InstancingPlugin, StretchedInstanceData};
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            InstancingPlugin::<StretchedInstanceData>::default(),
+        ))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let instances = (0..12 * 12).map(|index| {
+        let x = (index % 12) as f32;
+        let y = (index / 12) as f32;
+        let width = 4.0 + 36.0 * random(index * 2);
+        let height = 4.0 + 36.0 * random(index * 2 + 1);
+
+        StretchedInstanceData {
+            position: Vec3::new((x - 5.5) * 48.0, (y - 5.5) * 48.0, 0.0),
+            scale: Vec2::new(width, height),
+            color: Color::hsl(width / height * 90.0 % 360.0, 0.7, 0.5).as_linear_rgba_f32(),
+            border_color: Color::rgb(0.9, 0.9, 0.8).as_linear_rgba_f32(),
+            border_width: 3.0,
+            ..default()
+        }
+    });
+
+    commands.spawn(InstancedMeshBundle::<StretchedInstanceData>::new(
+        meshes.add(Rectangle::new(1.0, 1.0)),
+        instances,
+    ));
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+/// A value in `0..1` that looks random, to keep the example free of dependencies.
+fn random(seed: u32) -> f32 {
+    let hash = seed
+        .wrapping_mul(0x9e37_79b9)
+        .rotate_left(13)
+        .wrapping_mul(0x85eb_ca6b);
+    (hash >> 8) as f32 / (1 << 24) as f32
+}
@@ -0,0 +1,46 @@
+//! Sprites from the layers of a KTX2 array texture, each instance picks its layer with its
+//! `atlas_index`. Unlike the cells of an atlas, every layer keeps the full resolution of the
+//! texture.
+
+use bevy::prelude::*;
+use instancing::{
+    atlas::InstanceTextureArray, InstanceData, InstancedMeshBundle, InstancingPlugin,
+};
+
+/// Number of layers of `textures/sprites_array.ktx2`.
+const LAYERS: u32 = 4;
+const COLUMNS: u32 = 24;
+const ROWS: u32 = 14;
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, InstancingPlugin::<InstanceData>::default()))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, asset_server: Res<AssetServer>) {
+    let instances = (0..COLUMNS * ROWS).map(|index| {
+        let (column, row) = (index % COLUMNS, index / COLUMNS);
+        InstanceData {
+            position: Vec3::new(
+                (column as f32 - (COLUMNS - 1) as f32 / 2.0) * 48.0,
+                (row as f32 - (ROWS - 1) as f32 / 2.0) * 48.0,
+                0.0,
+            ),
+            scale: 40.0,
+            color: Color::hsl(index as f32 * 7.0, 0.6, 0.6).as_linear_rgba_f32(),
+            atlas_index: (column + row) % LAYERS,
+            ..default()
+        }
+    });
+
+    commands.spawn((
+        InstancedMeshBundle::<InstanceData>::new(meshes.add(Rectangle::new(1.0, 1.0)), instances),
+        InstanceTextureArray {
+            image: asset_server.load("textures/sprites_array.ktx2"),
+        },
+    ));
+
+    commands.spawn(Camera2dBundle::default());
+}
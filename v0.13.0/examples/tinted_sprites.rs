@@ -0,0 +1,152 @@
+//! A single grayscale texture tinted into many colors, one per instance, like the color of a
+//! sprite. The tint multiplies the texel and its alpha, so the lower rows fade out.
+//!
+//! Press space to switch between straight alpha and [`InstanceBlendMode::Premultiplied`], with the
+//! texture and the tints premultiplied to match. Both look the same, as the tint is applied in the
+//! alpha convention of the blend mode, down to the anti-aliased edges.
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use instancing::{
+    atlas::InstanceAtlas, InstanceBlendMode, InstanceData, InstanceMaterialData,
+    InstancedMeshBundle, InstancingPlugin,
+};
+
+const COLUMNS: u32 = 12;
+const ROWS: u32 = 6;
+/// Pixels of the texture.
+const SIZE: u32 = 64;
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, InstancingPlugin::<InstanceData>::default()))
+        .add_systems(Startup, setup)
+        .add_systems(Update, toggle_premultiplied)
+        .run();
+}
+
+/// The texture with straight and with premultiplied alpha.
+#[derive(Resource)]
+struct Textures {
+    straight: Handle<Image>,
+    premultiplied: Handle<Image>,
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let textures = Textures {
+        straight: images.add(ball(false)),
+        premultiplied: images.add(ball(true)),
+    };
+
+    let instances = (0..COLUMNS * ROWS).map(|index| {
+        let (column, row) = (index % COLUMNS, index / COLUMNS);
+        let tint = Color::hsla(
+            column as f32 / COLUMNS as f32 * 360.0,
+            0.8,
+            0.6,
+            1.0 - row as f32 / ROWS as f32 * 0.8,
+        );
+        InstanceData {
+            position: Vec3::new(
+                (column as f32 - (COLUMNS - 1) as f32 / 2.0) * 80.0,
+                ((ROWS - 1) as f32 / 2.0 - row as f32) * 80.0,
+                0.0,
+            ),
+            scale: 72.0,
+            color: tint.as_linear_rgba_f32(),
+            ..default()
+        }
+    });
+
+    // a single cell spanning the whole texture
+    commands.spawn((
+        InstancedMeshBundle::<InstanceData>::new(meshes.add(Rectangle::new(1.0, 1.0)), instances),
+        InstanceAtlas {
+            image: textures.straight.clone(),
+            columns: 1,
+            rows: 1,
+        },
+    ));
+    commands.insert_resource(textures);
+
+    commands.spawn(Camera2dBundle {
+        camera: Camera {
+            clear_color: ClearColorConfig::Custom(Color::WHITE),
+            ..default()
+        },
+        ..default()
+    });
+}
+
+fn toggle_premultiplied(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    textures: Res<Textures>,
+    mut hosts: Query<(
+        Entity,
+        &mut InstanceMaterialData<InstanceData>,
+        &mut InstanceAtlas,
+        Has<InstanceBlendMode>,
+    )>,
+) {
+    if !keys.just_pressed(KeyCode::Space) {
+        return;
+    }
+    for (host, mut instances, mut atlas, premultiplied) in &mut hosts {
+        for instance in instances.iter_mut() {
+            let [r, g, b, a] = instance.color;
+            instance.color = if premultiplied {
+                [r / a, g / a, b / a, a]
+            } else {
+                [r * a, g * a, b * a, a]
+            };
+        }
+        if premultiplied {
+            commands.entity(host).remove::<InstanceBlendMode>();
+            atlas.image = textures.straight.clone();
+        } else {
+            commands
+                .entity(host)
+                .insert(InstanceBlendMode::Premultiplied);
+            atlas.image = textures.premultiplied.clone();
+        }
+        info!("premultiplied alpha: {}", !premultiplied);
+    }
+}
+
+/// A gray ball, lit from the top left, whose alpha fades out at its edge.
+fn ball(premultiplied: bool) -> Image {
+    let mut data = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let p = (Vec2::new(x as f32, y as f32) + 0.5) / SIZE as f32 * 2.0 - 1.0;
+            let alpha = ((1.0 - p.length()) * 8.0).clamp(0.0, 1.0);
+            let light = (1.0 - (p - Vec2::splat(-0.4)).length() * 0.6).clamp(0.2, 1.0);
+            let gray = if premultiplied { light * alpha } else { light };
+            let [gray, alpha] = [gray, alpha].map(|value| (value * 255.0) as u8);
+            data.extend([gray, gray, gray, alpha]);
+        }
+    }
+
+    Image::new(
+        Extent3d {
+            width: SIZE,
+            height: SIZE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        // stored linearly, so premultiplying by the alpha matches the blending in linear space
+        TextureFormat::Rgba8Unorm,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
@@ -0,0 +1,69 @@
+//! A trail following the cursor, which only ever grows until a click resets it. Every frame the
+//! new dots go through [`InstanceMaterialData::append_and_upload`], so only they are written to the
+//! instance buffer instead of the whole trail.
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use instancing::{InstanceData, InstanceMaterialData, InstancedMeshBundle, InstancingPlugin};
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, InstancingPlugin::<InstanceData>::default()))
+        .add_systems(Startup, setup)
+        .add_systems(Update, (extend_trail, reset_trail))
+        .run();
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    commands.spawn(InstancedMeshBundle::<InstanceData>::new(
+        meshes.add(Circle::new(0.5)),
+        [],
+    ));
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn extend_trail(
+    time: Res<Time>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut hosts: Query<&mut InstanceMaterialData<InstanceData>>,
+    mut last: Local<Option<Vec2>>,
+) {
+    let Some(cursor) = windows.get_single().ok().and_then(Window::cursor_position) else {
+        return;
+    };
+    let Some(position) = cameras
+        .iter()
+        .find_map(|(camera, transform)| camera.viewport_to_world_2d(transform, cursor))
+    else {
+        return;
+    };
+
+    // fill the gap to the last dot, so fast movements leave a continuous trail
+    let from = last.replace(position).unwrap_or(position);
+    let steps = (from.distance(position) / 4.0).ceil().max(1.0) as usize;
+    let hue = time.elapsed_seconds() * 60.0 % 360.0;
+    let dots: Vec<_> = (1..=steps)
+        .map(|step| InstanceData {
+            position: from.lerp(position, step as f32 / steps as f32).extend(0.0),
+            scale: 10.0,
+            color: Color::hsl(hue, 0.8, 0.6).as_linear_rgba_f32(),
+            ..default()
+        })
+        .collect();
+
+    for mut instances in &mut hosts {
+        instances.append_and_upload(&dots);
+    }
+}
+
+fn reset_trail(
+    buttons: Res<ButtonInput<MouseButton>>,
+    mut hosts: Query<&mut InstanceMaterialData<InstanceData>>,
+) {
+    if buttons.just_pressed(MouseButton::Left) {
+        for mut instances in &mut hosts {
+            instances.reset();
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! A flow field of about 7k vectors, each an instance of a single 2-vertex `LineList` mesh
+//! pointing along +X. The rotation of an instance turns it into the direction of the field, its
+//! scale stretches it to the strength, and its color shows the direction.
+//!
+//! The corner marks around the field are a `LineStrip` mesh holding four separate strips, split by
+//! the largest index, which restarts a strip.
+
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_asset::RenderAssetUsages, render_resource::PrimitiveTopology},
+};
+use instancing::{InstanceData, InstanceMaterialData, InstancedMeshBundle, InstancingPlugin};
+use std::f32::consts::TAU;
+
+const COLUMNS: u32 = 112;
+const ROWS: u32 = 64;
+/// Distance between the vectors in pixels.
+const SPACING: f32 = 10.0;
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, InstancingPlugin::<InstanceData>::default()))
+        .add_systems(Startup, setup)
+        .add_systems(Update, update_field)
+        .run();
+}
+
+/// A host whose instances follow the field, one per grid point.
+#[derive(Component)]
+struct Field;
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let line = Mesh::new(PrimitiveTopology::LineList, RenderAssetUsages::RENDER_WORLD)
+        .with_inserted_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0]],
+        );
+
+    let vectors = grid().map(|position| InstanceData {
+        position: position.extend(0.0),
+        ..default()
+    });
+    commands.spawn((
+        InstancedMeshBundle::<InstanceData>::new(meshes.add(line), vectors),
+        Field,
+    ));
+
+    let half = 0.5 * SPACING * Vec2::new(COLUMNS as f32, ROWS as f32) + SPACING;
+    let corner = |x: f32, y: f32| {
+        let (x, y) = (x * half.x, y * half.y);
+        let arm = 4.0 * SPACING;
+        [
+            [x - arm * x.signum(), y, 0.0],
+            [x, y, 0.0],
+            [x, y - arm * y.signum(), 0.0],
+        ]
+    };
+    let positions: Vec<_> = [(-1.0, 1.0), (1.0, 1.0), (1.0, -1.0), (-1.0, -1.0)]
+        .into_iter()
+        .flat_map(|(x, y)| corner(x, y))
+        .collect();
+    let indices = (0..4u32)
+        .flat_map(|strip| [strip * 3, strip * 3 + 1, strip * 3 + 2, u32::MAX])
+        .collect();
+    let corners = Mesh::new(
+        PrimitiveTopology::LineStrip,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_indices(Indices::U32(indices));
+    commands.spawn(InstancedMeshBundle::<InstanceData>::new(
+        meshes.add(corners),
+        [InstanceData::default()],
+    ));
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+/// The grid points, centered on the origin.
+fn grid() -> impl Iterator<Item = Vec2> {
+    (0..ROWS).flat_map(|row| {
+        (0..COLUMNS).map(move |column| {
+            (Vec2::new(column as f32, row as f32) - 0.5 * Vec2::new(COLUMNS as f32, ROWS as f32)
+                + 0.5)
+                * SPACING
+        })
+    })
+}
+
+/// Two vortices circling the center, plus a slow drift.
+fn field(position: Vec2, time: f32) -> Vec2 {
+    let centers = [
+        Vec2::from_angle(time * 0.4) * 250.0,
+        Vec2::from_angle(time * 0.4 + TAU / 2.0) * 250.0,
+    ];
+    let vortex = |center: Vec2, spin: f32| {
+        let offset = position - center;
+        offset.perp() * spin * 200.0 / (offset.length_squared() + 2000.0)
+    };
+    vortex(centers[0], 1.0) + vortex(centers[1], -1.0) + Vec2::new(0.2, 0.1)
+}
+
+fn update_field(
+    time: Res<Time>,
+    mut hosts: Query<&mut InstanceMaterialData<InstanceData>, With<Field>>,
+) {
+    let time = time.elapsed_seconds();
+    for mut instances in &mut hosts {
+        for (instance, position) in instances.iter_mut().zip(grid()) {
+            let vector = field(position, time);
+            let angle = vector.y.atan2(vector.x);
+            instance.rotation = angle;
+            instance.scale = (vector.length() * SPACING).min(2.0 * SPACING);
+            instance.color =
+                Color::hsl(angle.to_degrees().rem_euclid(360.0), 0.8, 0.6).as_linear_rgba_f32();
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! Hexagons whose mesh carries a vertex color per corner, fading from white to black around the
+//! hexagon. The vertex colors are multiplied with the color of each instance, and the instance
+//! attributes move to the locations left free by the mesh's color attribute.
+
+use bevy::{prelude::*, render::mesh::VertexAttributeValues};
+use instancing::{InstanceData, InstancedMeshBundle, InstancingPlugin};
+
+const COLUMNS: u32 = 16;
+const ROWS: u32 = 9;
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, InstancingPlugin::<InstanceData>::default()))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let mut mesh = Mesh::from(RegularPolygon::new(0.5, 6));
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        unreachable!("primitive meshes have Float32x3 positions");
+    };
+    let colors: Vec<_> = positions
+        .iter()
+        .map(|[x, y, _]| {
+            let brightness = (y.atan2(*x) / std::f32::consts::TAU).rem_euclid(1.0);
+            Color::rgb(brightness, brightness, brightness).as_linear_rgba_f32()
+        })
+        .collect();
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+
+    let instances = (0..COLUMNS * ROWS).map(|index| {
+        let (column, row) = (index % COLUMNS, index / COLUMNS);
+        InstanceData {
+            position: Vec3::new(
+                (column as f32 - (COLUMNS - 1) as f32 / 2.0) * 70.0,
+                (row as f32 - (ROWS - 1) as f32 / 2.0) * 70.0,
+                0.0,
+            ),
+            scale: 64.0,
+            color: Color::hsl(index as f32 * 11.0, 0.8, 0.6).as_linear_rgba_f32(),
+            ..default()
+        }
+    });
+
+    commands.spawn(InstancedMeshBundle::<InstanceData>::new(
+        meshes.add(mesh),
+        instances,
+    ));
+
+    commands.spawn(Camera2dBundle::default());
+}
@@ -0,0 +1,74 @@
+//! A grid of 4800 dots rippling in a wave, every instance is moved on the CPU every frame. The
+//! whole [`InstanceMaterialData`] is rewritten and uploaded each frame, into the same instance
+//! buffer, so the logged reallocations stay at zero after the first frame.
+//!
+//! Doubles as a manual performance check of per-frame updates, the log shows the frame time next
+//! to the instance and buffer diagnostics.
+
+use bevy::{
+    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    prelude::*,
+};
+use instancing::{
+    diagnostics::InstancingDiagnosticsPlugin, InstanceData, InstanceMaterialData,
+    InstancedMeshBundle, InstancingPlugin,
+};
+
+const COLUMNS: u32 = 120;
+const ROWS: u32 = 40;
+const SPACING: f32 = 10.0;
+const AMPLITUDE: f32 = 40.0;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            InstancingPlugin::<InstanceData>::default(),
+            InstancingDiagnosticsPlugin,
+            FrameTimeDiagnosticsPlugin,
+            LogDiagnosticsPlugin::default(),
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, wave)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let instances = (0..COLUMNS * ROWS).map(|index| {
+        let (column, row) = (index % COLUMNS, index / COLUMNS);
+        InstanceData {
+            position: rest_position(column, row),
+            scale: 6.0,
+            color: Color::hsl(row as f32 / ROWS as f32 * 240.0, 0.7, 0.6).as_linear_rgba_f32(),
+            ..default()
+        }
+    });
+
+    // the batch bounds follow the instances, so the host is frustum culled as they move
+    commands.spawn(InstancedMeshBundle::<InstanceData>::new(
+        meshes.add(Circle::new(0.5)),
+        instances,
+    ));
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn rest_position(column: u32, row: u32) -> Vec3 {
+    Vec3::new(
+        (column as f32 - (COLUMNS - 1) as f32 / 2.0) * SPACING,
+        (row as f32 - (ROWS - 1) as f32 / 2.0) * SPACING,
+        0.0,
+    )
+}
+
+/// Moves every dot up and down by a sine of the time and its x position.
+fn wave(time: Res<Time>, mut hosts: Query<&mut InstanceMaterialData<InstanceData>>) {
+    let t = time.elapsed_seconds() * 3.0;
+    for mut instances in &mut hosts {
+        for (index, instance) in instances.iter_mut().enumerate() {
+            let (column, row) = (index as u32 % COLUMNS, index as u32 / COLUMNS);
+            let rest = rest_position(column, row);
+            instance.position.y = rest.y + AMPLITUDE * (t + rest.x * 0.02 + row as f32 * 0.1).sin();
+        }
+    }
+}
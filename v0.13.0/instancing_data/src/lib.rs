@@ -0,0 +1,161 @@
+//! The instance types of `instancing` as plain data, without bevy.
+//!
+//! [`InstanceData`], [`StretchedInstanceData`], [`CustomInstanceData`], [`PulsingInstanceData`]
+//! and [`DepthInstanceData`] have the fields and the `#[repr(C)]` layout of the types of the same
+//! name in `instancing`, built from [`glam`] types, which bevy re-exports. A server or simulation
+//! without bevy can fill them and send their bytes, `bytemuck::cast_slice(&instances)`, to clients
+//! that cast the bytes back into the instance types of `instancing`, or convert them with `From`.
+//! `instancing` checks at compile time that the layouts match.
+//!
+//! Builds without std with `default-features = false, features = ["libm"]`. glam needs std or
+//! libm for its math and fails to build with neither, before this crate could report it.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use bytemuck::{Pod, Zeroable};
+pub use glam;
+use glam::{Vec2, Vec3};
+
+/// See `instancing::InstanceData`.
+#[derive(Clone, Copy, Debug, PartialEq, Pod, Zeroable)]
+#[repr(C)]
+pub struct InstanceData {
+    pub position: Vec3,
+    pub scale: f32,
+    pub color: [f32; 4],
+    pub rotation: f32,
+    pub atlas_index: u32,
+    pub uv_offset: Vec2,
+    pub uv_scale: Vec2,
+    pub emissive: [f32; 3],
+    pub sort_key: f32,
+    pub border_color: [f32; 4],
+    pub border_width: f32,
+    pub corner_radius: f32,
+    pub anchor: Vec2,
+}
+
+impl Default for InstanceData {
+    fn default() -> Self {
+        InstanceData {
+            position: Vec3::ZERO,
+            scale: 1.0,
+            color: [1.0; 4],
+            rotation: 0.0,
+            atlas_index: 0,
+            uv_offset: Vec2::ZERO,
+            uv_scale: Vec2::ONE,
+            emissive: [0.0; 3],
+            sort_key: 0.0,
+            border_color: [0.0, 0.0, 0.0, 1.0],
+            border_width: 0.0,
+            corner_radius: 0.0,
+            anchor: Vec2::ZERO,
+        }
+    }
+}
+
+/// Declares the plain counterpart of a variant of `instancing::InstanceData`, with a scale of the
+/// given type and the given fields after the ones of [`InstanceData`].
+macro_rules! instance_variant {
+    (
+        $name:ident {
+            scale: $scale:ty = $scale_default:expr,
+            $($field:ident: $field_ty:ty = $field_default:expr,)*
+        }
+    ) => {
+        #[doc = concat!("See `instancing::", stringify!($name), "`.")]
+        #[derive(Clone, Copy, Debug, PartialEq, Pod, Zeroable)]
+        #[repr(C)]
+        pub struct $name {
+            pub position: Vec3,
+            pub scale: $scale,
+            pub color: [f32; 4],
+            pub rotation: f32,
+            pub atlas_index: u32,
+            pub uv_offset: Vec2,
+            pub uv_scale: Vec2,
+            pub emissive: [f32; 3],
+            pub sort_key: f32,
+            pub border_color: [f32; 4],
+            pub border_width: f32,
+            pub corner_radius: f32,
+            pub anchor: Vec2,
+            $(pub $field: $field_ty,)*
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                let InstanceData {
+                    position,
+                    scale: _,
+                    color,
+                    rotation,
+                    atlas_index,
+                    uv_offset,
+                    uv_scale,
+                    emissive,
+                    sort_key,
+                    border_color,
+                    border_width,
+                    corner_radius,
+                    anchor,
+                } = InstanceData::default();
+                $name {
+                    position,
+                    scale: $scale_default,
+                    color,
+                    rotation,
+                    atlas_index,
+                    uv_offset,
+                    uv_scale,
+                    emissive,
+                    sort_key,
+                    border_color,
+                    border_width,
+                    corner_radius,
+                    anchor,
+                    $($field: $field_default,)*
+                }
+            }
+        }
+    };
+}
+
+instance_variant!(StretchedInstanceData {
+    scale: Vec2 = Vec2::ONE,
+});
+
+instance_variant!(CustomInstanceData {
+    scale: f32 = 1.0,
+    custom: [f32; 4] = [0.0; 4],
+});
+
+instance_variant!(PulsingInstanceData {
+    scale: f32 = 1.0,
+    pulse: [f32; 3] = [0.0; 3],
+});
+
+instance_variant!(DepthInstanceData {
+    scale: f32 = 1.0,
+    draw_z: f32 = 0.5,
+});
+
+macro_rules! impl_flip {
+    ($($instance:ty),*) => {$(
+        impl $instance {
+            /// Bit of the `atlas_index` that mirrors the UVs horizontally.
+            pub const FLIP_X: u32 = 1 << 31;
+            /// Bit of the `atlas_index` that mirrors the UVs vertically.
+            pub const FLIP_Y: u32 = 1 << 30;
+        }
+    )*};
+}
+
+impl_flip!(
+    InstanceData,
+    StretchedInstanceData,
+    CustomInstanceData,
+    PulsingInstanceData,
+    DepthInstanceData
+);
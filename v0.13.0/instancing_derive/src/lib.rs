@@ -0,0 +1,546 @@
+//! Derive macros generating the instance vertex layout for `#[repr(C)]` instance structs, and the
+//! `InstanceData` of child entity components.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{quote, quote_spanned};
+use syn::{
+    parse_macro_input, spanned::Spanned, Data, DeriveInput, Expr, Fields, FieldsNamed, Lit, LitStr,
+    Type,
+};
+
+/// Follows the Position, Normal and UV attributes of most meshes, the pipeline moves the attributes
+/// to the locations the actual mesh leaves free.
+const FIRST_SHADER_LOCATION: u32 = 3;
+
+/// Implements `Instance` for a `#[repr(C)]` struct, emitting one vertex attribute per field.
+///
+/// Attributes are assigned consecutive shader locations in field order, starting at location 3.
+/// The pipeline moves them to the locations left free by the mesh, so shaders declare them at the
+/// `INSTANCE_LOCATION_n` shader defs.
+/// Mark the `Vec3` position and the `f32` or `Vec2` scale fields with `#[instance(position)]` and
+/// `#[instance(scale)]` to make the instances cullable, and an `f32` rotation around the Z axis
+/// with `#[instance(rotation)]` and the `Vec2` anchor with `#[instance(anchor)]` to take them into
+/// account when picking. An `f32` marked with `#[instance(sort_key)]` orders the instances of hosts
+/// with `SortInstances::Key`, and the alpha of an RGBA `[f32; 4]` or `Vec4` marked with
+/// `#[instance(color)]` splits the instances of hosts with `MixedOpacity` into opaque and
+/// transparent ones. An `[f32; 4]` or `Vec4` marked with `#[instance(custom)]` is passed on to the
+/// fragment shader, see `CustomInstanceData`, and the amplitude, frequency and phase in an
+/// `[f32; 3]` or `Vec3` marked with `#[instance(pulse)]` animate the scale on the GPU, see
+/// `PulsingInstanceData`. An `f32` marked with `#[instance(draw_z)]` replaces the depth the 3D
+/// shader writes, see `DepthInstanceData`. A struct level `#[instance(shader = "path")]` overrides
+/// the shader drawing the instances of 2D meshes and `#[instance(shader_3d = "path")]` the one
+/// drawing the instances of 3D meshes.
+///
+/// Fields marked `#[instance(skip)]` get no attribute, for data only read on the CPU like a sort
+/// key, which leaves their location to the mesh. They may have any type, and the other fields keep
+/// their locations.
+///
+/// The layout is checked at compile time: the size of every field has to match its vertex format,
+/// and the attributes have to fit the struct, see `check_instance_layout`. Generic structs are
+/// checked for each instantiation that is drawn.
+#[proc_macro_derive(InstanceLayout, attributes(instance))]
+pub fn derive_instance_layout(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Returns the fields of a struct with named fields, the only kind the derives support.
+fn named_fields<'a>(input: &'a DeriveInput, derive: &str) -> syn::Result<&'a FieldsNamed> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            format!("{derive} can only be derived for structs"),
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            format!("{derive} can only be derived for structs with named fields"),
+        ));
+    };
+    Ok(fields)
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    if !is_repr_c(input)? {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "InstanceLayout can only be derived for #[repr(C)] structs",
+        ));
+    }
+
+    let fields = named_fields(input, "InstanceLayout")?;
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    // generic types are checked once instantiated, when their attributes are built
+    let generic = !input.generics.params.is_empty();
+    let checked_ty = if generic {
+        quote! { Self }
+    } else {
+        quote! { #ident }
+    };
+
+    let mut attributes = Vec::new();
+    let mut size_checks = Vec::new();
+
+    for (field, shader_location) in fields.named.iter().zip(FIRST_SHADER_LOCATION..) {
+        if is_skipped(field)? {
+            continue;
+        }
+        let field_ident = field.ident.as_ref().unwrap();
+        let format = vertex_format(&field.ty)?;
+
+        attributes.push(quote! {
+            ::bevy::render::render_resource::VertexAttribute {
+                format: ::bevy::render::render_resource::VertexFormat::#format,
+                offset: ::core::mem::offset_of!(#checked_ty, #field_ident) as u64,
+                shader_location: #shader_location,
+            }
+        });
+
+        // the type is only matched by name, an alias or another type of the same name may differ
+        let ty = &field.ty;
+        let message = LitStr::new(
+            &format!(
+                "the size of `{ident}::{field_ident}` doesn't match the vertex format {format}"
+            ),
+            field.ty.span(),
+        );
+        size_checks.push(quote_spanned! { field.ty.span()=>
+            ::core::assert!(
+                ::core::mem::size_of::<#ty>() as u64
+                    == ::bevy::render::render_resource::VertexFormat::#format.size(),
+                #message
+            );
+        });
+    }
+
+    let layout_check = quote! {
+        #(#size_checks)*
+        ::instancing::check_instance_layout::<#checked_ty>(&[#(#attributes),*]);
+    };
+    let (item_check, attributes_check) = if generic {
+        (None, Some(quote! { const { #layout_check } }))
+    } else {
+        (Some(quote! { const _: () = { #layout_check }; }), None)
+    };
+
+    let mut position = None;
+    let mut scale = None;
+    let mut rotation = None;
+    let mut anchor = None;
+    let mut sort_key = None;
+    let mut color = None;
+    let mut custom = None;
+    let mut pulse = None;
+    let mut draw_z = None;
+
+    for field in &fields.named {
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("instance"))
+        {
+            attr.parse_nested_meta(|meta| {
+                let slot = if meta.path.is_ident("position") {
+                    &mut position
+                } else if meta.path.is_ident("scale") {
+                    &mut scale
+                } else if meta.path.is_ident("rotation") {
+                    &mut rotation
+                } else if meta.path.is_ident("anchor") {
+                    &mut anchor
+                } else if meta.path.is_ident("sort_key") {
+                    &mut sort_key
+                } else if meta.path.is_ident("color") {
+                    &mut color
+                } else if meta.path.is_ident("custom") {
+                    &mut custom
+                } else if meta.path.is_ident("pulse") {
+                    &mut pulse
+                } else if meta.path.is_ident("draw_z") {
+                    &mut draw_z
+                } else if meta.path.is_ident("skip") {
+                    return Ok(());
+                } else {
+                    return Err(meta.error(
+                        "expected `position`, `scale`, `rotation`, `anchor`, `sort_key`, `color`, \
+                         `custom`, `pulse`, `draw_z` or `skip`",
+                    ));
+                };
+                if slot.replace(field).is_some() {
+                    return Err(meta.error("duplicate instance field"));
+                }
+                Ok(())
+            })?;
+        }
+    }
+
+    let bounds = match (position, scale) {
+        (Some(position), Some(scale)) => {
+            let scale_components: u32 = match vertex_format(&scale.ty)?.to_string().as_str() {
+                "Float32" => 1,
+                "Float32x2" => 2,
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        &scale.ty,
+                        "#[instance(scale)] has to be an f32 or a Vec2",
+                    ))
+                }
+            };
+            let position = &position.ident;
+            let scale = &scale.ident;
+            quote! {
+                const BOUNDS: ::core::option::Option<::instancing::InstanceBounds> =
+                    ::core::option::Option::Some(::instancing::InstanceBounds {
+                        position_offset: ::core::mem::offset_of!(Self, #position) as u32,
+                        scale_offset: ::core::mem::offset_of!(Self, #scale) as u32,
+                        scale_components: #scale_components,
+                    });
+            }
+        }
+        (None, None) => quote! {},
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "#[instance(position)] and #[instance(scale)] have to be used together",
+            ))
+        }
+    };
+
+    let rotation = rotation.map(|rotation| {
+        let rotation = &rotation.ident;
+        quote! {
+            const ROTATION_OFFSET: ::core::option::Option<u32> =
+                ::core::option::Option::Some(::core::mem::offset_of!(Self, #rotation) as u32);
+        }
+    });
+
+    let anchor = match anchor {
+        Some(anchor) => {
+            if vertex_format(&anchor.ty)? != "Float32x2" {
+                return Err(syn::Error::new_spanned(
+                    &anchor.ty,
+                    "#[instance(anchor)] has to be an [f32; 2] or a Vec2",
+                ));
+            }
+            let anchor = &anchor.ident;
+            Some(quote! {
+                const ANCHOR_OFFSET: ::core::option::Option<u32> =
+                    ::core::option::Option::Some(::core::mem::offset_of!(Self, #anchor) as u32);
+            })
+        }
+        None => None,
+    };
+
+    let sort_key = sort_key.map(|sort_key| {
+        let sort_key = &sort_key.ident;
+        quote! {
+            const SORT_KEY_OFFSET: ::core::option::Option<u32> =
+                ::core::option::Option::Some(::core::mem::offset_of!(Self, #sort_key) as u32);
+        }
+    });
+
+    let color = match color {
+        Some(color) => {
+            if vertex_format(&color.ty)? != "Float32x4" {
+                return Err(syn::Error::new_spanned(
+                    &color.ty,
+                    "#[instance(color)] has to be an [f32; 4] or a Vec4",
+                ));
+            }
+            let color = &color.ident;
+            Some(quote! {
+                const COLOR_OFFSET: ::core::option::Option<u32> =
+                    ::core::option::Option::Some(::core::mem::offset_of!(Self, #color) as u32);
+            })
+        }
+        None => None,
+    };
+
+    let custom = match custom {
+        Some(custom) => {
+            if vertex_format(&custom.ty)? != "Float32x4" {
+                return Err(syn::Error::new_spanned(
+                    &custom.ty,
+                    "#[instance(custom)] has to be an [f32; 4] or a Vec4",
+                ));
+            }
+            let custom = &custom.ident;
+            Some(quote! {
+                const CUSTOM_OFFSET: ::core::option::Option<u32> =
+                    ::core::option::Option::Some(::core::mem::offset_of!(Self, #custom) as u32);
+            })
+        }
+        None => None,
+    };
+
+    let pulse = match pulse {
+        Some(pulse) => {
+            if vertex_format(&pulse.ty)? != "Float32x3" {
+                return Err(syn::Error::new_spanned(
+                    &pulse.ty,
+                    "#[instance(pulse)] has to be an [f32; 3] or a Vec3",
+                ));
+            }
+            let pulse = &pulse.ident;
+            Some(quote! {
+                const PULSE_OFFSET: ::core::option::Option<u32> =
+                    ::core::option::Option::Some(::core::mem::offset_of!(Self, #pulse) as u32);
+            })
+        }
+        None => None,
+    };
+
+    let draw_z = match draw_z {
+        Some(draw_z) => {
+            if vertex_format(&draw_z.ty)? != "Float32" {
+                return Err(syn::Error::new_spanned(
+                    &draw_z.ty,
+                    "#[instance(draw_z)] has to be an f32",
+                ));
+            }
+            let draw_z = &draw_z.ident;
+            Some(quote! {
+                const DRAW_Z_OFFSET: ::core::option::Option<u32> =
+                    ::core::option::Option::Some(::core::mem::offset_of!(Self, #draw_z) as u32);
+            })
+        }
+        None => None,
+    };
+
+    let mut shaders = Vec::new();
+
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("instance"))
+    {
+        attr.parse_nested_meta(|meta| {
+            let function = if meta.path.is_ident("shader") {
+                quote! { shader }
+            } else if meta.path.is_ident("shader_3d") {
+                quote! { shader_3d }
+            } else {
+                return Err(meta.error("expected `shader` or `shader_3d`"));
+            };
+            let path: LitStr = meta.value()?.parse()?;
+            shaders.push(quote! {
+                fn #function() -> ::bevy::render::render_resource::ShaderRef {
+                    ::bevy::render::render_resource::ShaderRef::from(#path)
+                }
+            });
+            Ok(())
+        })?;
+    }
+
+    Ok(quote! {
+        #item_check
+
+        impl #impl_generics ::instancing::Instance for #ident #ty_generics #where_clause {
+            const ARRAY_STRIDE: u64 = ::core::mem::size_of::<Self>() as u64;
+            #bounds
+            #rotation
+            #anchor
+            #sort_key
+            #color
+            #custom
+            #pulse
+            #draw_z
+
+            fn attributes() -> ::std::vec::Vec<::bevy::render::render_resource::VertexAttribute> {
+                #attributes_check
+                ::std::vec![#(#attributes),*]
+            }
+
+            #(#shaders)*
+        }
+    })
+}
+
+/// Implements `InstanceChild<InstanceData>` for a component struct, building the instance of a
+/// child entity from its `Transform` and the fields of the struct.
+///
+/// The position and the rotation around the Z axis come from the `Transform`, and the scale is its
+/// x scale, multiplied by an `f32` field named `scale` if there is one. Fields named like the other
+/// fields of `InstanceData` are copied into the instance. Fields with other names are ignored, so
+/// the component can hold game state as well.
+#[proc_macro_derive(InstanceChild)]
+pub fn derive_instance_child(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand_child(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Fields of `InstanceData` that `#[derive(InstanceChild)]` copies from the component.
+const CHILD_FIELDS: &[&str] = &[
+    "color",
+    "atlas_index",
+    "uv_offset",
+    "uv_scale",
+    "emissive",
+    "sort_key",
+    "border_color",
+    "border_width",
+    "corner_radius",
+    "anchor",
+];
+
+fn expand_child(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = named_fields(input, "InstanceChild")?;
+
+    let mut scale = quote! { transform.scale.x };
+    let mut copied = Vec::new();
+
+    for field in &fields.named {
+        let ident = field.ident.as_ref().unwrap();
+        match ident.to_string().as_str() {
+            "position" | "rotation" => {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    format!("the {ident} of the instance is taken from the Transform"),
+                ))
+            }
+            "scale" => {
+                scale = quote_spanned! { field.span()=> transform.scale.x * self.#ident };
+            }
+            name if CHILD_FIELDS.contains(&name) => {
+                copied.push(quote_spanned! { field.span()=> #ident: self.#ident });
+            }
+            _ => {}
+        }
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::instancing::entity_instances::InstanceChild<::instancing::InstanceData>
+            for #ident #ty_generics #where_clause
+        {
+            fn instance(
+                &self,
+                transform: &::bevy::transform::components::Transform,
+            ) -> ::instancing::InstanceData {
+                ::instancing::InstanceData {
+                    position: transform.translation,
+                    rotation: transform.rotation.to_euler(::bevy::math::EulerRot::ZYX).0,
+                    scale: #scale,
+                    #(#copied,)*
+                    ..::core::default::Default::default()
+                }
+            }
+        }
+    })
+}
+
+/// Whether the field is marked `#[instance(skip)]`, the other markers are parsed later.
+fn is_skipped(field: &syn::Field) -> syn::Result<bool> {
+    let mut skip = false;
+
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("instance"))
+    {
+        attr.parse_nested_meta(|meta| {
+            skip |= meta.path.is_ident("skip");
+            Ok(())
+        })?;
+    }
+
+    Ok(skip)
+}
+
+fn is_repr_c(input: &DeriveInput) -> syn::Result<bool> {
+    let mut repr_c = false;
+
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("repr"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("C") {
+                repr_c = true;
+            } else if meta.input.peek(syn::token::Paren) {
+                // Skip the arguments of e.g. `align(16)`.
+                let _content;
+                syn::parenthesized!(_content in meta.input);
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(repr_c)
+}
+
+/// Maps a field type to the name of the matching `VertexFormat` variant.
+fn vertex_format(ty: &Type) -> syn::Result<syn::Ident> {
+    let format = match ty {
+        // the invisible group of a type passed to `macro_rules!` as a `$ty:ty`
+        Type::Group(group) => return vertex_format(&group.elem),
+        Type::Path(path) => path.path.segments.last().and_then(|segment| {
+            let format = match segment.ident.to_string().as_str() {
+                "f32" => "Float32",
+                "u32" => "Uint32",
+                "i32" => "Sint32",
+                "Vec2" => "Float32x2",
+                "Vec3" => "Float32x3",
+                "Vec4" => "Float32x4",
+                "UVec2" => "Uint32x2",
+                "UVec3" => "Uint32x3",
+                "UVec4" => "Uint32x4",
+                "IVec2" => "Sint32x2",
+                "IVec3" => "Sint32x3",
+                "IVec4" => "Sint32x4",
+                _ => return None,
+            };
+            Some(format.to_string())
+        }),
+        Type::Array(array) => array_len(&array.len).and_then(|len| {
+            let Type::Path(elem) = array.elem.as_ref() else {
+                return None;
+            };
+            let scalar = match elem.path.get_ident()?.to_string().as_str() {
+                "f32" => "Float32",
+                "u32" => "Uint32",
+                "i32" => "Sint32",
+                _ => return None,
+            };
+            match len {
+                1 => Some(scalar.to_string()),
+                2..=4 => Some(format!("{scalar}x{len}")),
+                _ => None,
+            }
+        }),
+        _ => None,
+    };
+
+    match format {
+        Some(format) => Ok(syn::Ident::new(&format, Span::call_site())),
+        None => Err(syn::Error::new_spanned(
+            ty,
+            "unsupported instance field type, expected f32, u32, i32, a glam vector or an array of up to 4 of those scalars",
+        )),
+    }
+}
+
+fn array_len(len: &Expr) -> Option<usize> {
+    match len {
+        Expr::Lit(expr) => match &expr.lit {
+            Lit::Int(int) => int.base10_parse().ok(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
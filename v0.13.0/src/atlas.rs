@@ -0,0 +1,242 @@
+//! Per-instance cells of a texture atlas, or layers of a texture array.
+//!
+//! A host entity with an [`InstanceAtlas`] samples the cell selected by each instance's atlas
+//! index, counted row by row from the top left cell, and multiplies it with the instance color.
+//! A host entity with an [`InstanceTextureArray`] samples the layer selected by the atlas index
+//! instead, which keeps the full resolution of many distinct large sprites. Only supported by the
+//! 2D pipeline.
+//!
+//! With [`InstanceSdf`], the atlas holds signed distance fields, like the glyphs of a font, which
+//! stay sharp at any scale. Each instance picks its glyph with its `uv_offset` and `uv_scale`,
+//! the rectangle of the glyph in the UVs of an atlas with a single cell.
+
+use bevy::{
+    ecs::system::{lifetimeless::*, SystemParamItem},
+    prelude::*,
+    render::{
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        render_asset::RenderAssets,
+        render_phase::{PhaseItem, RenderCommand, RenderCommandResult, TrackedRenderPass},
+        render_resource::{binding_types::*, *},
+        renderer::RenderDevice,
+        Render, RenderApp, RenderSet,
+    },
+};
+use bytemuck::{Pod, Zeroable};
+use std::marker::PhantomData;
+
+use crate::{Instance, InstancePipeline};
+
+/// The texture atlas sampled by the instances of this host entity.
+#[derive(Component, Clone, ExtractComponent)]
+pub struct InstanceAtlas {
+    pub image: Handle<Image>,
+    pub columns: u32,
+    pub rows: u32,
+}
+
+/// The texture array sampled by the instances of this host entity, one layer per atlas index.
+///
+/// The image needs array layers, like a KTX2 array texture or a stacked image reinterpreted with
+/// [`Image::reinterpret_stacked_2d_as_array`]. Ignored by hosts that also have an
+/// [`InstanceAtlas`].
+#[derive(Component, Clone, ExtractComponent)]
+pub struct InstanceTextureArray {
+    pub image: Handle<Image>,
+}
+
+/// Draws the [`InstanceAtlas`] of this host entity as a signed distance field, ignored without an
+/// atlas.
+///
+/// The red channel holds the distance, 0.5 on the edge and greater inside, like a single channel
+/// SDF font atlas. The instance color is drawn where the distance exceeds 0.5, with an edge
+/// anti-aliased over `smoothing` times the change of the distance across a pixel, so 1 is crisp
+/// and larger values blur the edge.
+#[derive(Component, Clone, Copy, Debug, ExtractComponent)]
+pub struct InstanceSdf {
+    pub smoothing: f32,
+}
+
+impl Default for InstanceSdf {
+    fn default() -> Self {
+        InstanceSdf { smoothing: 1.0 }
+    }
+}
+
+pub struct InstanceAtlasPlugin;
+
+impl Plugin for InstanceAtlasPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            ExtractComponentPlugin::<InstanceAtlas>::default(),
+            ExtractComponentPlugin::<InstanceTextureArray>::default(),
+            ExtractComponentPlugin::<InstanceSdf>::default(),
+        ));
+
+        app.sub_app_mut(RenderApp).add_systems(
+            Render,
+            (prepare_atlas_bind_groups, prepare_texture_array_bind_groups)
+                .in_set(RenderSet::PrepareBindGroups),
+        );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.sub_app_mut(RenderApp)
+            .init_resource::<InstanceAtlasLayout>()
+            .init_resource::<InstanceTextureArrayLayout>();
+    }
+}
+
+/// Layout of the bind group holding the atlas texture, its sampler and [`AtlasParams`].
+#[derive(Resource, Deref)]
+pub struct InstanceAtlasLayout(BindGroupLayout);
+
+impl FromWorld for InstanceAtlasLayout {
+    fn from_world(world: &mut World) -> Self {
+        InstanceAtlasLayout(world.resource::<RenderDevice>().create_bind_group_layout(
+            "instance atlas layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::VERTEX_FRAGMENT,
+                (
+                    texture_2d(TextureSampleType::Float { filterable: true }),
+                    sampler(SamplerBindingType::Filtering),
+                    uniform_buffer_sized(false, None),
+                ),
+            ),
+        ))
+    }
+}
+
+/// Layout of the bind group holding the texture array and its sampler, which takes the place of
+/// the atlas bind group.
+#[derive(Resource, Deref)]
+pub struct InstanceTextureArrayLayout(BindGroupLayout);
+
+impl FromWorld for InstanceTextureArrayLayout {
+    fn from_world(world: &mut World) -> Self {
+        InstanceTextureArrayLayout(world.resource::<RenderDevice>().create_bind_group_layout(
+            "instance texture array layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    texture_2d_array(TextureSampleType::Float { filterable: true }),
+                    sampler(SamplerBindingType::Filtering),
+                ),
+            ),
+        ))
+    }
+}
+
+/// Mirrors `AtlasParams` in `instancing.wgsl`.
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+struct AtlasParams {
+    columns: u32,
+    rows: u32,
+    /// The [`InstanceSdf::smoothing`], unused without it.
+    sdf_smoothing: f32,
+    _padding: u32,
+}
+
+#[derive(Component)]
+pub struct InstanceAtlasBindGroup(BindGroup);
+
+fn prepare_atlas_bind_groups(
+    mut commands: Commands,
+    query: Query<(Entity, &InstanceAtlas, Option<&InstanceSdf>)>,
+    images: Res<RenderAssets<Image>>,
+    atlas_layout: Res<InstanceAtlasLayout>,
+    render_device: Res<RenderDevice>,
+) {
+    for (entity, atlas, sdf) in &query {
+        // drawn once the image is loaded
+        let Some(image) = images.get(&atlas.image) else {
+            continue;
+        };
+
+        let params = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("instance atlas params"),
+            contents: bytemuck::bytes_of(&AtlasParams {
+                columns: atlas.columns.max(1),
+                rows: atlas.rows.max(1),
+                sdf_smoothing: sdf.map_or(1.0, |sdf| sdf.smoothing),
+                _padding: 0,
+            }),
+            usage: BufferUsages::UNIFORM,
+        });
+
+        let bind_group = render_device.create_bind_group(
+            "instance atlas bind group",
+            &atlas_layout,
+            &BindGroupEntries::sequential((
+                &image.texture_view,
+                &image.sampler,
+                params.as_entire_binding(),
+            )),
+        );
+
+        commands
+            .entity(entity)
+            .insert(InstanceAtlasBindGroup(bind_group));
+    }
+}
+
+fn prepare_texture_array_bind_groups(
+    mut commands: Commands,
+    query: Query<(Entity, &InstanceTextureArray), Without<InstanceAtlas>>,
+    images: Res<RenderAssets<Image>>,
+    texture_array_layout: Res<InstanceTextureArrayLayout>,
+    render_device: Res<RenderDevice>,
+) {
+    for (entity, texture_array) in &query {
+        // drawn once the image is loaded
+        let Some(image) = images.get(&texture_array.image) else {
+            continue;
+        };
+
+        // the default view of an image with a single layer isn't an array
+        let texture_view = image.texture.create_view(&TextureViewDescriptor {
+            label: Some("instance texture array view"),
+            dimension: Some(TextureViewDimension::D2Array),
+            ..default()
+        });
+
+        let bind_group = render_device.create_bind_group(
+            "instance texture array bind group",
+            &texture_array_layout,
+            &BindGroupEntries::sequential((&texture_view, &image.sampler)),
+        );
+
+        commands
+            .entity(entity)
+            .insert(InstanceAtlasBindGroup(bind_group));
+    }
+}
+
+/// Binds the atlas of hosts with an [`InstanceAtlas`], or their [`InstanceTextureArray`], in the
+/// bind group after the instance storage buffer if there is one.
+pub struct SetInstanceAtlasBindGroup<T>(PhantomData<T>);
+
+impl<P: PhaseItem, T: Instance> RenderCommand<P> for SetInstanceAtlasBindGroup<T> {
+    type Param = SRes<InstancePipeline<T>>;
+    type ViewQuery = ();
+    type ItemQuery = Option<Read<InstanceAtlasBindGroup>>;
+
+    #[inline]
+    fn render<'w>(
+        _item: &P,
+        _view: (),
+        bind_group: Option<Option<&'w InstanceAtlasBindGroup>>,
+        instance_pipeline: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        if let Some(bind_group) = bind_group.flatten() {
+            pass.set_bind_group(
+                instance_pipeline.atlas_bind_group_index(),
+                &bind_group.0,
+                &[],
+            );
+        }
+        RenderCommandResult::Success
+    }
+}
@@ -0,0 +1,168 @@
+//! Typed handles of host entities, to edit their instances without querying them by hand.
+//!
+//! [`SpawnInstanceBatch::spawn_instances`] spawns a host and returns its [`InstanceBatch`]. The
+//! handle edits the instances through [`Commands`], applied with the other commands of the
+//! system, or through a [`World`] right away. Systems edit them right away through the
+//! [`InstanceBatches`] system param instead.
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+};
+
+use crate::{HostMesh, Instance, InstanceMaterialData, InstancedMaterial, InstancedMeshBundle};
+
+/// A host entity drawing instances of `T`.
+///
+/// Only a typed [`Entity`], so copy it around freely. Its methods do nothing, or find no
+/// instances, once the host is despawned or lost its [`InstanceMaterialData`].
+pub struct InstanceBatch<T: Instance> {
+    entity: Entity,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Instance> InstanceBatch<T> {
+    /// The handle of an existing host entity.
+    pub fn from_entity(entity: Entity) -> Self {
+        InstanceBatch {
+            entity,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn entity(self) -> Entity {
+        self.entity
+    }
+
+    /// Replaces the instances once `commands` are applied.
+    pub fn set_instances(self, commands: &mut Commands, instances: impl IntoIterator<Item = T>) {
+        let instances: Vec<T> = instances.into_iter().collect();
+        commands.add(move |world: &mut World| {
+            if let Some(mut batch) = self.get_mut(world) {
+                **batch = instances;
+            }
+        });
+    }
+
+    /// Removes all instances once `commands` are applied.
+    pub fn clear(self, commands: &mut Commands) {
+        commands.add(move |world: &mut World| {
+            if let Some(mut batch) = self.get_mut(world) {
+                batch.reset();
+            }
+        });
+    }
+
+    /// Number of instances, 0 without any.
+    pub fn len(self, world: &World) -> usize {
+        self.get(world).map_or(0, |instances| instances.len())
+    }
+
+    pub fn get(self, world: &World) -> Option<&InstanceMaterialData<T>> {
+        world.get::<InstanceMaterialData<T>>(self.entity)
+    }
+
+    pub fn get_mut(self, world: &mut World) -> Option<Mut<'_, InstanceMaterialData<T>>> {
+        world.get_mut::<InstanceMaterialData<T>>(self.entity)
+    }
+}
+
+// derived, these would require `T` to implement the traits as well
+impl<T: Instance> Clone for InstanceBatch<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: Instance> Copy for InstanceBatch<T> {}
+
+impl<T: Instance> PartialEq for InstanceBatch<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.entity == other.entity
+    }
+}
+
+impl<T: Instance> Eq for InstanceBatch<T> {}
+
+impl<T: Instance> Hash for InstanceBatch<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.entity.hash(state);
+    }
+}
+
+impl<T: Instance> fmt::Debug for InstanceBatch<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("InstanceBatch").field(&self.entity).finish()
+    }
+}
+
+impl<T: Instance> From<InstanceBatch<T>> for Entity {
+    fn from(batch: InstanceBatch<T>) -> Self {
+        batch.entity
+    }
+}
+
+/// Spawns host entities and returns their [`InstanceBatch`].
+pub trait SpawnInstanceBatch {
+    fn spawn_instances<T: Instance, M: HostMesh, Mat: InstancedMaterial>(
+        &mut self,
+        bundle: InstancedMeshBundle<T, M, Mat>,
+    ) -> InstanceBatch<T>;
+}
+
+impl SpawnInstanceBatch for Commands<'_, '_> {
+    fn spawn_instances<T: Instance, M: HostMesh, Mat: InstancedMaterial>(
+        &mut self,
+        bundle: InstancedMeshBundle<T, M, Mat>,
+    ) -> InstanceBatch<T> {
+        InstanceBatch::from_entity(self.spawn(bundle).id())
+    }
+}
+
+impl SpawnInstanceBatch for World {
+    fn spawn_instances<T: Instance, M: HostMesh, Mat: InstancedMaterial>(
+        &mut self,
+        bundle: InstancedMeshBundle<T, M, Mat>,
+    ) -> InstanceBatch<T> {
+        InstanceBatch::from_entity(self.spawn(bundle).id())
+    }
+}
+
+/// The instances of every [`InstanceBatch`] of `T`, edited right away.
+#[derive(SystemParam)]
+pub struct InstanceBatches<'w, 's, T: Instance> {
+    batches: Query<'w, 's, &'static mut InstanceMaterialData<T>>,
+}
+
+impl<T: Instance> InstanceBatches<'_, '_, T> {
+    pub fn get(&self, batch: InstanceBatch<T>) -> Option<&InstanceMaterialData<T>> {
+        self.batches.get(batch.entity).ok()
+    }
+
+    pub fn get_mut(&mut self, batch: InstanceBatch<T>) -> Option<Mut<'_, InstanceMaterialData<T>>> {
+        self.batches.get_mut(batch.entity).ok()
+    }
+
+    /// Number of instances of `batch`, 0 without any.
+    pub fn len(&self, batch: InstanceBatch<T>) -> usize {
+        self.get(batch).map_or(0, |instances| instances.len())
+    }
+
+    pub fn set_instances(
+        &mut self,
+        batch: InstanceBatch<T>,
+        instances: impl IntoIterator<Item = T>,
+    ) {
+        if let Some(mut batch) = self.get_mut(batch) {
+            **batch = instances.into_iter().collect();
+        }
+    }
+
+    pub fn clear(&mut self, batch: InstanceBatch<T>) {
+        if let Some(mut batch) = self.get_mut(batch) {
+            batch.reset();
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! CPU side bounds of a whole instance batch, used by the built-in frustum culling.
+
+use bevy::{
+    ecs::entity::EntityHashSet,
+    prelude::*,
+    render::{
+        primitives::Aabb,
+        view::{NoFrustumCulling, VisibilitySystems},
+    },
+};
+
+use crate::{HostMesh, Instance, InstanceMaterialData};
+
+/// Whether the batch of this host entity is frustum culled as a whole, kept in sync with bevy's
+/// [`NoFrustumCulling`] marker by [`BatchCullingPlugin`].
+///
+/// Hosts without it get one when they are spawned, enabled only for instance types with
+/// [`Instance::BOUNDS`]. Insert it yourself to opt in or out regardless.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct InstanceFrustumCulling(pub bool);
+
+pub struct BatchCullingPlugin;
+
+impl Plugin for BatchCullingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            sync_no_frustum_culling.before(VisibilitySystems::CalculateBounds),
+        );
+    }
+}
+
+pub(crate) fn sync_no_frustum_culling(
+    mut commands: Commands,
+    query: Query<(Entity, &InstanceFrustumCulling), Changed<InstanceFrustumCulling>>,
+) {
+    for (entity, culling) in &query {
+        if culling.0 {
+            commands.entity(entity).remove::<NoFrustumCulling>();
+        } else {
+            commands.entity(entity).insert(NoFrustumCulling);
+        }
+    }
+}
+
+/// Replaces the [`Aabb`] of the host entity, which bevy derives from the mesh alone, with one
+/// enclosing every instance, so each view culls the batch as a whole once it is out of that view.
+///
+/// The bounds of an instance are the bounding sphere of the mesh around its origin, scaled and
+/// moved by the instance, so they stay valid under any instance rotation. This runs between
+/// [`VisibilitySystems::CalculateBounds`] and [`VisibilitySystems::CheckVisibility`], so the
+/// visibility of the batch is decided on the instances that get extracted this frame. Instance
+/// types without [`Instance::BOUNDS`] get the mesh's Aabb, which bevy doesn't update when the
+/// mesh of an entity is replaced. The Aabb doesn't depend on the views, resizing a window only
+/// changes the frusta it is tested against.
+pub fn update_batch_aabb<T: Instance, M: HostMesh>(
+    mut commands: Commands,
+    query: Query<(Entity, Ref<InstanceMaterialData<T>>, Ref<M>)>,
+    meshes: Res<Assets<Mesh>>,
+    // hosts whose mesh wasn't loaded yet when they changed
+    mut pending: Local<EntityHashSet>,
+) {
+    pending.retain(|entity| query.contains(*entity));
+
+    for (entity, instances, mesh) in &query {
+        let changed = match T::BOUNDS {
+            Some(_) => instances.is_changed() || mesh.is_changed(),
+            None => mesh.is_changed(),
+        };
+        if !changed && !pending.contains(&entity) {
+            continue;
+        }
+
+        let Some(mesh_aabb) = meshes.get(mesh.mesh()).and_then(Mesh::compute_aabb) else {
+            pending.insert(entity);
+            continue;
+        };
+        pending.remove(&entity);
+
+        let Some(bounds) = T::BOUNDS else {
+            commands.entity(entity).insert(mesh_aabb);
+            continue;
+        };
+        let mesh_radius = mesh_aabb.center.length() + mesh_aabb.half_extents.length();
+
+        let mut min = Vec3::splat(f32::INFINITY);
+        let mut max = Vec3::splat(f32::NEG_INFINITY);
+
+        for instance in instances.iter() {
+            let (position, scale) = bounds.read(instance);
+            let extent = Vec3::splat(mesh_radius * scale.abs());
+            min = min.min(position - extent);
+            max = max.max(position + extent);
+        }
+
+        if instances.is_empty() {
+            min = Vec3::ZERO;
+            max = Vec3::ZERO;
+        }
+
+        commands.entity(entity).insert(Aabb::from_min_max(min, max));
+    }
+}
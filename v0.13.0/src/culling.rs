@@ -0,0 +1,715 @@
+//! Optional GPU frustum culling of instances.
+//!
+//! For host entities marked with [`GpuCulling`] a compute prepass tests every instance against
+//! the frustum of each view, compacts the visible instances into a separate buffer and writes
+//! their count into an indirect draw buffer, so no instances have to be read back to the CPU.
+//! The order of the visible instances is not preserved.
+//!
+//! Hosts that are also marked with [`InstanceCullReadback`] copy the results back to the CPU, into
+//! the [`InstanceCullResults`] resource. Mapping a buffer waits for the GPU to finish the frame,
+//! so the results arrive two to three frames after the instances were culled, depending on
+//! pipelined rendering and the frames in flight.
+
+use bevy::{
+    asset::load_internal_asset,
+    ecs::entity::{EntityHashMap, EntityHashSet},
+    math::Affine3A,
+    prelude::*,
+    render::{
+        camera::ExtractedCamera,
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        mesh::GpuBufferInfo,
+        primitives::Frustum,
+        render_asset::RenderAssets,
+        render_resource::{binding_types::*, *},
+        renderer::{RenderDevice, RenderQueue},
+        view::ExtractedView,
+        Render, RenderApp, RenderSet,
+    },
+    sprite::Mesh2dHandle,
+    utils::{hashbrown::hash_map::Entry, HashMap},
+};
+use bytemuck::{Pod, Zeroable};
+use std::{
+    marker::PhantomData,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use crate::{
+    buffer_capacity, is_downlevel, lod::InstanceLodBatches, low_usage_frames,
+    opacity::InstanceOpacityBatches, HostMesh, HostMeshes, Instance, InstanceBuffer,
+    InstancePipeline, SHRINK_AFTER_FRAMES,
+};
+
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Opts the instances of this host entity into GPU frustum culling.
+///
+/// The instance type has to declare its position and scale, see [`Instance::BOUNDS`].
+#[derive(Component, Clone, Copy, Default)]
+pub struct GpuCulling;
+
+/// Radius of a sphere around the origin of the mesh that contains the whole mesh.
+///
+/// Kept up to date for entities with [`GpuCulling`] once their mesh is loaded. An instance is
+/// culled when this sphere, scaled by the largest axis of the instance's scale, is outside of the
+/// view frustum.
+#[derive(Component, Clone, Copy)]
+pub struct CullingRadius(pub f32);
+
+impl ExtractComponent for CullingRadius {
+    type QueryData = &'static CullingRadius;
+    type QueryFilter = With<GpuCulling>;
+    type Out = Self;
+
+    fn extract_component(radius: &CullingRadius) -> Option<Self> {
+        Some(*radius)
+    }
+}
+
+/// Copies the results of culling the instances of this host entity back to the CPU, see
+/// [`InstanceCullResults`]. Requires [`GpuCulling`].
+#[derive(Component, Clone, Copy, Default, ExtractComponent)]
+pub struct InstanceCullReadback {
+    /// Whether to read back the indices of the visible instances as well as their count.
+    pub indices: bool,
+}
+
+/// The visible instances of one host in one view, as culled by [`GpuCulling`].
+#[derive(Clone, Default, Debug)]
+pub struct InstanceCullResult {
+    /// Number of instances that passed culling.
+    pub visible: u32,
+    /// Indices of the visible instances, in no particular order. Only read back with
+    /// [`InstanceCullReadback::indices`].
+    ///
+    /// The indices are into the instances as uploaded, which are the instances of the
+    /// [`InstanceMaterialData`](crate::InstanceMaterialData) in order unless the host sorts them
+    /// with [`SortInstances`](crate::SortInstances) or hides some with
+    /// [`InstanceVisibility`](crate::InstanceVisibility).
+    pub indices: Vec<u32>,
+}
+
+/// The latest culling results of the hosts with [`InstanceCullReadback`], a few frames old, see
+/// the [module docs](self).
+#[derive(Resource, Default)]
+pub struct InstanceCullResults {
+    hosts: EntityHashMap<EntityHashMap<InstanceCullResult>>,
+}
+
+impl InstanceCullResults {
+    /// Returns the result of culling the instances of `host` for the camera `view`.
+    pub fn get(&self, host: Entity, view: Entity) -> Option<&InstanceCullResult> {
+        self.hosts.get(&host)?.get(&view)
+    }
+
+    /// Returns the results of culling the instances of `host` for every camera.
+    pub fn views(&self, host: Entity) -> impl Iterator<Item = (Entity, &InstanceCullResult)> {
+        self.hosts
+            .get(&host)
+            .into_iter()
+            .flat_map(|views| views.iter().map(|(view, result)| (*view, result)))
+    }
+}
+
+/// Results read back in the render world, waiting to be moved into [`InstanceCullResults`].
+#[derive(Resource, Clone, Default)]
+struct CullResultsInbox(Arc<Mutex<Vec<(Entity, Entity, InstanceCullResult)>>>);
+
+/// A buffer the results of one host and view are copied into, waiting to be mapped.
+struct PendingReadback {
+    host: Entity,
+    view: Entity,
+    buffer: Buffer,
+    /// Set by the map callback, whether mapping succeeded.
+    mapped: Arc<OnceLock<bool>>,
+}
+
+/// The readbacks of all hosts and views that aren't mapped yet.
+#[derive(Resource, Default)]
+pub struct PendingReadbacks(Vec<PendingReadback>);
+
+pub const INSTANCE_CULLING_SHADER_HANDLE: Handle<Shader> =
+    Handle::weak_from_u128(0x5f0e_a370_4be7_4ae7_8566_c7fd_c856_d767);
+
+pub struct GpuCullingPlugin;
+
+impl Plugin for GpuCullingPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            INSTANCE_CULLING_SHADER_HANDLE,
+            "shaders/instance_culling.wgsl",
+            Shader::from_wgsl
+        );
+
+        let inbox = CullResultsInbox::default();
+
+        app.add_plugins((
+            ExtractComponentPlugin::<CullingRadius>::default(),
+            ExtractComponentPlugin::<InstanceCullReadback>::default(),
+        ))
+        .init_resource::<InstanceCullResults>()
+        .insert_resource(inbox.clone())
+        .add_systems(First, receive_cull_results)
+        .add_systems(
+            PostUpdate,
+            (
+                update_culling_radius::<Mesh2dHandle>,
+                update_culling_radius::<Handle<Mesh>>,
+            ),
+        );
+
+        app.sub_app_mut(RenderApp)
+            .insert_resource(inbox)
+            .init_resource::<PendingReadbacks>()
+            .add_systems(Render, read_back_cull_results.in_set(RenderSet::Cleanup));
+    }
+
+    fn finish(&self, app: &mut App) {
+        let render_app = app.sub_app_mut(RenderApp);
+        // downlevel backends without compute shaders (WebGL2) also lack indirect draws, and fail
+        // to create the layout
+        if !is_downlevel(render_app.world.resource::<RenderDevice>()) {
+            render_app.init_resource::<InstanceCullingPipeline>();
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_culling_radius<M: HostMesh>(
+    mut commands: Commands,
+    query: Query<(Entity, Ref<M>, Option<&CullingRadius>), With<GpuCulling>>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    for (entity, mesh, radius) in &query {
+        if radius.is_some() && !mesh.is_changed() {
+            continue;
+        }
+
+        let Some(aabb) = meshes.get(mesh.mesh()).and_then(Mesh::compute_aabb) else {
+            // the radius of a replaced mesh would be stale, retry once the new mesh is loaded
+            commands.entity(entity).remove::<CullingRadius>();
+            continue;
+        };
+        let radius = aabb.center.length() + aabb.half_extents.length();
+        commands.entity(entity).insert(CullingRadius(radius));
+    }
+}
+
+/// The culled instances of one view.
+pub struct CulledInstances {
+    /// The visible instances, compacted to the front of the buffer.
+    pub output: Buffer,
+    /// `DrawIndexedIndirect` or `DrawIndirect` arguments holding the number of visible instances.
+    pub indirect: Buffer,
+    /// Binds `output` in [`InstanceBufferMode::Storage`](crate::InstanceBufferMode::Storage).
+    pub storage_bind_group: Option<BindGroup>,
+}
+
+#[derive(Component)]
+pub struct CulledInstanceBuffers<T: Instance> {
+    views: EntityHashMap<CulledInstances>,
+    marker: PhantomData<T>,
+}
+
+impl<T: Instance> CulledInstanceBuffers<T> {
+    pub fn get(&self, view: Entity) -> Option<&CulledInstances> {
+        self.views.get(&view)
+    }
+}
+
+/// The buffers the instances of one host are culled into for one view, kept across frames like
+/// the [`InstanceBufferCache`](crate::InstanceBufferCache) so culling allocates nothing while the
+/// instance count stays the same.
+struct CullBuffers {
+    params: Buffer,
+    output: Buffer,
+    indirect: Buffer,
+    /// The indices of the visible instances, only present while they are read back.
+    indices: Option<Buffer>,
+    /// Number of instances `output` and `indices` have room for.
+    capacity: usize,
+    /// Consecutive frames in which less than a quarter of `capacity` was used, or the host wasn't
+    /// culled for the view at all.
+    low_usage_frames: u32,
+    /// The instance buffer `bind_group` reads from.
+    input: BufferId,
+    /// Binds the buffers to the culling pipeline, `None` only while they are created.
+    bind_group: Option<BindGroup>,
+    /// Binds `output` in [`InstanceBufferMode::Storage`](crate::InstanceBufferMode::Storage).
+    storage_bind_group: Option<BindGroup>,
+}
+
+/// The bind group of the culling pass reading the instances from `input`, and the one binding the
+/// output as the instances of the draw if they are read from storage buffers.
+fn cull_bind_groups<T: Instance>(
+    render_device: &RenderDevice,
+    culling_pipeline: &InstanceCullingPipeline,
+    instance_pipeline: &InstancePipeline<T>,
+    input: &Buffer,
+    cull_buffers: &CullBuffers,
+) -> (Option<BindGroup>, Option<BindGroup>) {
+    let bind_group = render_device.create_bind_group(
+        "instance culling bind group",
+        &culling_pipeline.layout,
+        &BindGroupEntries::sequential((
+            cull_buffers.params.as_entire_binding(),
+            input.as_entire_binding(),
+            cull_buffers.output.as_entire_binding(),
+            cull_buffers.indirect.as_entire_binding(),
+            cull_buffers
+                .indices
+                .as_ref()
+                .unwrap_or(&culling_pipeline.no_indices)
+                .as_entire_binding(),
+        )),
+    );
+    let storage_bind_group = instance_pipeline.storage_layout.as_ref().map(|layout| {
+        render_device.create_bind_group(
+            "culled instance storage bind group",
+            layout,
+            &BindGroupEntries::single(cull_buffers.output.as_entire_binding()),
+        )
+    });
+    (Some(bind_group), storage_bind_group)
+}
+
+/// The [`CullBuffers`] of all hosts with [`GpuCulling`], keyed by the host and the view.
+#[derive(Resource)]
+pub struct CullBufferCache<T: Instance> {
+    buffers: HashMap<(Entity, Entity), CullBuffers>,
+    marker: PhantomData<T>,
+}
+
+impl<T: Instance> Default for CullBufferCache<T> {
+    fn default() -> Self {
+        CullBufferCache {
+            buffers: HashMap::default(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Instance> CullBufferCache<T> {
+    /// Number of hosts and views with culling buffers. Hosts that aren't culled for a view keep
+    /// theirs for a while.
+    pub fn len(&self) -> usize {
+        self.buffers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffers.is_empty()
+    }
+}
+
+/// Mirrors `CullParams` in `instance_culling.wgsl`.
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+struct CullParams {
+    planes: [[f32; 4]; 6],
+    model: [[f32; 4]; 4],
+    count: u32,
+    stride: u32,
+    position_offset: u32,
+    scale_offset: u32,
+    radius: f32,
+    scale_components: u32,
+    write_indices: u32,
+    _padding: u32,
+}
+
+#[derive(Resource)]
+pub struct InstanceCullingPipeline {
+    layout: BindGroupLayout,
+    pipeline: CachedComputePipelineId,
+    /// Bound in place of the visible indices of hosts that don't read them back.
+    no_indices: Buffer,
+}
+
+impl FromWorld for InstanceCullingPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let layout = render_device.create_bind_group_layout(
+            "instance culling layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    uniform_buffer_sized(false, None),
+                    storage_buffer_read_only_sized(false, None),
+                    storage_buffer_sized(false, None),
+                    storage_buffer_sized(false, None),
+                    storage_buffer_sized(false, None),
+                ),
+            ),
+        );
+
+        let no_indices = render_device.create_buffer(&BufferDescriptor {
+            label: Some("instance culling no indices buffer"),
+            size: 4,
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let pipeline =
+            world
+                .resource::<PipelineCache>()
+                .queue_compute_pipeline(ComputePipelineDescriptor {
+                    label: Some("instance culling pipeline".into()),
+                    layout: vec![layout.clone()],
+                    push_constant_ranges: Vec::new(),
+                    shader: INSTANCE_CULLING_SHADER_HANDLE,
+                    shader_defs: Vec::new(),
+                    entry_point: "cull".into(),
+                });
+
+        InstanceCullingPipeline {
+            layout,
+            pipeline,
+            no_indices,
+        }
+    }
+}
+
+/// Culls the instances of every [`GpuCulling`] host against every view and inserts the results
+/// as [`CulledInstanceBuffers`]. Hosts without results are drawn unculled.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn dispatch_instance_culling<T: Instance>(
+    mut commands: Commands,
+    instances: Query<
+        (
+            Entity,
+            &InstanceBuffer<T>,
+            &CullingRadius,
+            Option<&InstanceCullReadback>,
+        ),
+        (Without<InstanceLodBatches>, Without<InstanceOpacityBatches>),
+    >,
+    views: Query<(Entity, &Frustum, Has<ExtractedCamera>), With<ExtractedView>>,
+    host_meshes: HostMeshes,
+    meshes: Res<RenderAssets<Mesh>>,
+    culling_pipeline: Option<Res<InstanceCullingPipeline>>,
+    instance_pipeline: Res<InstancePipeline<T>>,
+    pipeline_cache: Res<PipelineCache>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    mut pending_readbacks: ResMut<PendingReadbacks>,
+    mut cache: ResMut<CullBufferCache<T>>,
+    mut warned: Local<bool>,
+    mut warned_split: Local<bool>,
+) {
+    // hosts that aren't culled for a view this frame keep their buffers for a while, in case they
+    // are culled again
+    cache.buffers.retain(|(host, view), cull_buffers| {
+        if instances.contains(*host) && views.contains(*view) {
+            return true;
+        }
+        cull_buffers.low_usage_frames += 1;
+        cull_buffers.low_usage_frames < SHRINK_AFTER_FRAMES
+    });
+
+    if instances.is_empty() {
+        return;
+    }
+
+    let (Some(bounds), Some(culling_pipeline)) = (T::BOUNDS, culling_pipeline) else {
+        if !*warned {
+            warn!("GpuCulling requires an instance type with BOUNDS and an adapter supporting compute shaders and indirect draws, drawing unculled");
+            *warned = true;
+        }
+        return;
+    };
+
+    let Some(pipeline) = pipeline_cache.get_compute_pipeline(culling_pipeline.pipeline) else {
+        return;
+    };
+
+    let mut encoder = render_device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("instance culling"),
+    });
+    let mut readbacks = Vec::new();
+
+    for (entity, instance_buffer, radius, readback) in &instances {
+        if instance_buffer.is_split() {
+            if !*warned_split {
+                warn!("GpuCulling doesn't support hosts with more instances than a buffer holds, drawing them unculled");
+                *warned_split = true;
+            }
+            continue;
+        }
+
+        let Some((mesh_asset_id, transform)) = host_meshes.get(entity) else {
+            continue;
+        };
+        let Some(gpu_mesh) = meshes.get(mesh_asset_id) else {
+            continue;
+        };
+
+        let model = Affine3A::from(transform);
+        let max_scale = model
+            .matrix3
+            .x_axis
+            .length()
+            .max(model.matrix3.y_axis.length())
+            .max(model.matrix3.z_axis.length());
+
+        let index_or_vertex_count = match &gpu_mesh.buffer_info {
+            GpuBufferInfo::Indexed { count, .. } => *count,
+            GpuBufferInfo::NonIndexed => gpu_mesh.vertex_count,
+        };
+
+        let mut culled = CulledInstanceBuffers::<T> {
+            views: EntityHashMap::default(),
+            marker: PhantomData,
+        };
+
+        for (view, frustum, camera) in &views {
+            // the results of views without a camera, like shadow views, mean nothing to the app
+            let readback = readback.filter(|_| camera);
+            let read_back_indices = readback.is_some_and(|readback| readback.indices);
+
+            let mut planes = [[0.0; 4]; 6];
+            for (plane, half_space) in planes.iter_mut().zip(&frustum.half_spaces) {
+                *plane = half_space.normal_d().to_array();
+            }
+
+            let params = CullParams {
+                planes,
+                model: Mat4::from(model).to_cols_array_2d(),
+                count: instance_buffer.length as u32,
+                stride: (T::ARRAY_STRIDE / 4) as u32,
+                position_offset: bounds.position_offset / 4,
+                scale_offset: bounds.scale_offset / 4,
+                radius: radius.0 * max_scale,
+                scale_components: bounds.scale_components,
+                write_indices: read_back_indices as u32,
+                _padding: 0,
+            };
+            let required = instance_buffer.length;
+            let per_buffer = instance_pipeline.max_instances_per_buffer;
+            if let Some(cull_buffers) = cache.buffers.get_mut(&(entity, view)) {
+                cull_buffers.low_usage_frames = low_usage_frames(
+                    required,
+                    cull_buffers.capacity,
+                    cull_buffers.low_usage_frames,
+                );
+            }
+            let resized = match cache.buffers.get(&(entity, view)) {
+                Some(cull_buffers) => buffer_capacity(
+                    required,
+                    cull_buffers.capacity,
+                    cull_buffers.low_usage_frames,
+                    per_buffer,
+                ),
+                // an empty host still binds a buffer
+                None => Some(buffer_capacity(required, 0, 0, per_buffer).unwrap_or(1)),
+            };
+
+            let create_output = |capacity: usize| {
+                render_device.create_buffer(&BufferDescriptor {
+                    label: Some("culled instance buffer"),
+                    size: capacity as u64 * T::ARRAY_STRIDE,
+                    usage: BufferUsages::VERTEX | BufferUsages::STORAGE,
+                    mapped_at_creation: false,
+                })
+            };
+            let create_indices = |capacity: usize| {
+                read_back_indices.then(|| {
+                    render_device.create_buffer(&BufferDescriptor {
+                        label: Some("visible instance indices buffer"),
+                        size: capacity as u64 * 4,
+                        usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+                        mapped_at_creation: false,
+                    })
+                })
+            };
+
+            let cull_buffers = match (cache.buffers.entry((entity, view)), resized) {
+                (Entry::Occupied(entry), resized) => {
+                    let cull_buffers = entry.into_mut();
+                    let mut rebind = false;
+                    if let Some(capacity) = resized {
+                        cull_buffers.output = create_output(capacity);
+                        cull_buffers.indices = create_indices(capacity);
+                        cull_buffers.capacity = capacity;
+                        cull_buffers.low_usage_frames = 0;
+                        rebind = true;
+                    } else if read_back_indices != cull_buffers.indices.is_some() {
+                        cull_buffers.indices = create_indices(cull_buffers.capacity);
+                        rebind = true;
+                    }
+                    if rebind || cull_buffers.input != instance_buffer.buffer.id() {
+                        cull_buffers.input = instance_buffer.buffer.id();
+                        (cull_buffers.bind_group, cull_buffers.storage_bind_group) =
+                            cull_bind_groups(
+                                &render_device,
+                                &culling_pipeline,
+                                &instance_pipeline,
+                                &instance_buffer.buffer,
+                                cull_buffers,
+                            );
+                    }
+                    cull_buffers
+                }
+                (Entry::Vacant(_), None) => unreachable!(),
+                (Entry::Vacant(entry), Some(capacity)) => {
+                    let params = render_device.create_buffer(&BufferDescriptor {
+                        label: Some("instance culling params"),
+                        size: std::mem::size_of::<CullParams>() as u64,
+                        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    });
+                    let indirect = render_device.create_buffer(&BufferDescriptor {
+                        label: Some("culled instance indirect buffer"),
+                        size: 5 * 4,
+                        usage: BufferUsages::INDIRECT
+                            | BufferUsages::STORAGE
+                            | BufferUsages::COPY_SRC
+                            | BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    });
+                    let mut cull_buffers = CullBuffers {
+                        params,
+                        output: create_output(capacity),
+                        indirect,
+                        indices: create_indices(capacity),
+                        capacity,
+                        low_usage_frames: 0,
+                        input: instance_buffer.buffer.id(),
+                        bind_group: None,
+                        storage_bind_group: None,
+                    };
+                    (cull_buffers.bind_group, cull_buffers.storage_bind_group) = cull_bind_groups(
+                        &render_device,
+                        &culling_pipeline,
+                        &instance_pipeline,
+                        &instance_buffer.buffer,
+                        &cull_buffers,
+                    );
+                    entry.insert(cull_buffers)
+                }
+            };
+
+            // written before the culling pass is submitted, which counts the visible instances up
+            // from 0 again
+            render_queue.write_buffer(&cull_buffers.params, 0, bytemuck::bytes_of(&params));
+            render_queue.write_buffer(
+                &cull_buffers.indirect,
+                0,
+                bytemuck::cast_slice(&[index_or_vertex_count, 0, 0, 0, 0]),
+            );
+
+            {
+                let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                    label: Some("instance culling pass"),
+                    timestamp_writes: None,
+                });
+                pass.set_pipeline(pipeline);
+                pass.set_bind_group(0, cull_buffers.bind_group.as_ref().unwrap(), &[]);
+                pass.dispatch_workgroups(
+                    (instance_buffer.length as u32).div_ceil(WORKGROUP_SIZE),
+                    1,
+                    1,
+                );
+            }
+
+            if readback.is_some() {
+                // the visible count followed by the visible indices
+                let indices = cull_buffers.indices.as_ref();
+                let size = 4 + indices.map_or(0, |indices| indices.size());
+                let buffer = render_device.create_buffer(&BufferDescriptor {
+                    label: Some("instance culling readback buffer"),
+                    size,
+                    usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                encoder.copy_buffer_to_buffer(&cull_buffers.indirect, 4, &buffer, 0, 4);
+                if let Some(indices) = indices {
+                    encoder.copy_buffer_to_buffer(indices, 0, &buffer, 4, indices.size());
+                }
+                readbacks.push(PendingReadback {
+                    host: entity,
+                    view,
+                    buffer,
+                    mapped: Arc::default(),
+                });
+            }
+
+            culled.views.insert(
+                view,
+                CulledInstances {
+                    output: cull_buffers.output.clone(),
+                    indirect: cull_buffers.indirect.clone(),
+                    storage_bind_group: cull_buffers.storage_bind_group.clone(),
+                },
+            );
+        }
+
+        commands.entity(entity).insert(culled);
+    }
+
+    render_queue.submit([encoder.finish()]);
+
+    // buffers can only be mapped once the copies into them are submitted
+    for readback in readbacks {
+        let mapped = readback.mapped.clone();
+        readback
+            .buffer
+            .slice(..)
+            .map_async(MapMode::Read, move |result| {
+                let _ = mapped.set(result.is_ok());
+            });
+        pending_readbacks.0.push(readback);
+    }
+}
+
+/// Moves the results of mapped readbacks into the [`CullResultsInbox`].
+fn read_back_cull_results(
+    render_device: Res<RenderDevice>,
+    inbox: Res<CullResultsInbox>,
+    mut pending_readbacks: ResMut<PendingReadbacks>,
+) {
+    if pending_readbacks.0.is_empty() {
+        return;
+    }
+
+    // runs the map callbacks of the buffers the GPU is done with
+    render_device.poll(Maintain::Poll);
+
+    let mut results = inbox.0.lock().unwrap();
+    pending_readbacks.0.retain(|readback| {
+        let Some(mapped) = readback.mapped.get() else {
+            return true;
+        };
+        if *mapped {
+            let bytes = readback.buffer.slice(..).get_mapped_range();
+            let words: &[u32] = bytemuck::cast_slice(&bytes);
+            let visible = words[0];
+            let indices = words[1..].iter().take(visible as usize).copied().collect();
+            results.push((
+                readback.host,
+                readback.view,
+                InstanceCullResult { visible, indices },
+            ));
+        }
+        false
+    });
+}
+
+/// Moves the results read back in the render world into [`InstanceCullResults`], and drops the
+/// results of hosts that no longer read them back.
+fn receive_cull_results(
+    inbox: Res<CullResultsInbox>,
+    mut results: ResMut<InstanceCullResults>,
+    hosts: Query<Entity, With<InstanceCullReadback>>,
+) {
+    for (host, view, result) in inbox.0.lock().unwrap().drain(..) {
+        results.hosts.entry(host).or_default().insert(view, result);
+    }
+
+    if results.hosts.len() > hosts.iter().len() {
+        let hosts: EntityHashSet = hosts.iter().collect();
+        results.hosts.retain(|host, _| hosts.contains(host));
+    }
+}
@@ -0,0 +1,157 @@
+//! Instances read from a float texture instead of an instance buffer.
+//!
+//! A host entity with an [`InstanceDataTexture`] draws one instance per pair of texels of its
+//! image, which the vertex shader loads by the instance index. Suits large static sets, like the
+//! scatter of a terrain, that are baked once or authored in an image tool. The instances of the
+//! host's [`InstanceMaterialData`](crate::InstanceMaterialData) aren't drawn, its first instance is
+//! the template every texel pair starts from. Only supported by the 2D pipeline and its built-in
+//! shader.
+
+use bevy::{
+    ecs::system::{lifetimeless::*, SystemParamItem},
+    prelude::*,
+    render::{
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        render_asset::RenderAssets,
+        render_phase::{PhaseItem, RenderCommand, RenderCommandResult, TrackedRenderPass},
+        render_resource::{binding_types::*, *},
+        renderer::RenderDevice,
+        Render, RenderApp, RenderSet,
+    },
+    utils::HashSet,
+};
+use std::marker::PhantomData;
+
+use crate::{atlas::InstanceAtlasBindGroup, Instance, InstancePipeline};
+
+/// The instances of this host entity, two [`TextureFormat::Rgba32Float`] texels each, in rows
+/// from the top left texel.
+///
+/// The first texel holds the position in `rgb` and the scale in `a`, the second the linear color.
+/// The position is added to the position of the first instance of the host's
+/// [`InstanceMaterialData`](crate::InstanceMaterialData), the scale and the color multiply its
+/// scale and color, and the remaining fields are taken from that instance as they are. Texels left
+/// at zero scale draw nothing, so the image may hold fewer instances than it has room for.
+///
+/// The batch is drawn unsorted and unculled, without [`GpuCulling`](crate::culling::GpuCulling),
+/// LODs, mesh ranges or motion vectors, and its bounds only cover the template, so spawn the host
+/// with [`InstanceFrustumCulling(false)`](crate::bounds::InstanceFrustumCulling).
+#[derive(Component, Clone, ExtractComponent)]
+pub struct InstanceDataTexture {
+    pub image: Handle<Image>,
+}
+
+pub struct InstanceDataTexturePlugin;
+
+impl Plugin for InstanceDataTexturePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ExtractComponentPlugin::<InstanceDataTexture>::default());
+
+        app.sub_app_mut(RenderApp).add_systems(
+            Render,
+            prepare_data_texture_bind_groups.in_set(RenderSet::PrepareBindGroups),
+        );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.sub_app_mut(RenderApp)
+            .init_resource::<InstanceDataTextureLayout>();
+    }
+}
+
+/// Layout of the bind group holding the data texture, which follows the atlas bind group.
+#[derive(Resource, Deref)]
+pub struct InstanceDataTextureLayout(BindGroupLayout);
+
+impl FromWorld for InstanceDataTextureLayout {
+    fn from_world(world: &mut World) -> Self {
+        InstanceDataTextureLayout(world.resource::<RenderDevice>().create_bind_group_layout(
+            "instance data texture layout",
+            &BindGroupLayoutEntries::single(
+                ShaderStages::VERTEX,
+                // loaded texel by texel, 32 bit floats aren't filterable everywhere
+                texture_2d(TextureSampleType::Float { filterable: false }),
+            ),
+        ))
+    }
+}
+
+#[derive(Component)]
+pub struct InstanceDataTextureBindGroup {
+    bind_group: BindGroup,
+    /// Number of instances the texture has room for.
+    pub(crate) instances: u32,
+}
+
+fn prepare_data_texture_bind_groups(
+    mut commands: Commands,
+    query: Query<(Entity, &InstanceDataTexture)>,
+    images: Res<RenderAssets<Image>>,
+    data_texture_layout: Res<InstanceDataTextureLayout>,
+    render_device: Res<RenderDevice>,
+    mut warned_format: Local<HashSet<AssetId<Image>>>,
+) {
+    for (entity, data_texture) in &query {
+        // drawn once the image is loaded
+        let Some(image) = images.get(&data_texture.image) else {
+            continue;
+        };
+        if image.texture_format != TextureFormat::Rgba32Float {
+            if warned_format.insert(data_texture.image.id()) {
+                warn!(
+                    "The InstanceDataTexture of host {entity:?} is {:?} instead of Rgba32Float, its instances aren't drawn",
+                    image.texture_format
+                );
+            }
+            continue;
+        }
+
+        let bind_group = render_device.create_bind_group(
+            "instance data texture bind group",
+            &data_texture_layout,
+            &BindGroupEntries::single(&image.texture_view),
+        );
+
+        commands
+            .entity(entity)
+            .insert(InstanceDataTextureBindGroup {
+                bind_group,
+                instances: image.size.x as u32 * image.size.y as u32 / 2,
+            });
+    }
+}
+
+/// Binds the [`InstanceDataTexture`] of the host, in the bind group after the atlas if there is
+/// one.
+pub struct SetInstanceDataTextureBindGroup<T>(PhantomData<T>);
+
+impl<P: PhaseItem, T: Instance> RenderCommand<P> for SetInstanceDataTextureBindGroup<T> {
+    type Param = SRes<InstancePipeline<T>>;
+    type ViewQuery = ();
+    type ItemQuery = (
+        Has<InstanceDataTexture>,
+        Has<InstanceAtlasBindGroup>,
+        Option<Read<InstanceDataTextureBindGroup>>,
+    );
+
+    #[inline]
+    fn render<'w>(
+        _item: &P,
+        _view: (),
+        bind_groups: Option<(bool, bool, Option<&'w InstanceDataTextureBindGroup>)>,
+        instance_pipeline: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some((true, atlas, bind_group)) = bind_groups else {
+            return RenderCommandResult::Success;
+        };
+        // the texture isn't loaded or has the wrong format
+        let Some(bind_group) = bind_group else {
+            return RenderCommandResult::Failure;
+        };
+
+        let index = instance_pipeline.atlas_bind_group_index() + atlas as usize;
+        pass.set_bind_group(index, &bind_group.bind_group, &[]);
+        RenderCommandResult::Success
+    }
+}
@@ -0,0 +1,202 @@
+//! Diagnostics of the instance buffers, to catch runaway allocations.
+//!
+//! [`InstancingDiagnosticsPlugin`] registers bevy [`Diagnostic`]s for the instances drawn, the
+//! bytes allocated for instance buffers and the reallocations of instance buffers per second,
+//! summed over all hosts and instance types. They show up in the `LogDiagnosticsPlugin` like any
+//! other diagnostic. The render world measures them, so they lag a frame behind.
+//!
+//! Only the buffers holding the instances are counted, not the buffers of
+//! [`GpuCulling`](crate::culling::GpuCulling), motion vectors or the host meshes.
+//!
+//! The instances drawn for a single host are counted into its [`DrawnInstanceCount`], for hosts
+//! spawned with one.
+
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
+    ecs::entity::EntityHashMap,
+    prelude::*,
+    render::{
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        Render, RenderApp, RenderSet,
+    },
+    utils::HashMap,
+};
+use std::{
+    any::TypeId,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    culling::{InstanceCullReadback, InstanceCullResults},
+    data_texture::InstanceDataTextureBindGroup,
+    Instance, InstanceBuffer,
+};
+
+/// Registers the diagnostics of the instance buffers, see the [module docs](self).
+pub struct InstancingDiagnosticsPlugin;
+
+impl InstancingDiagnosticsPlugin {
+    /// Instances in the instance buffers of the hosts drawn in a frame.
+    pub const INSTANCES: DiagnosticPath = DiagnosticPath::const_new("instancing/instances");
+    /// Bytes allocated for instance buffers, including the headroom of growing buffers, the
+    /// buffers of hidden hosts that are kept for a while and the free buffers of the
+    /// [`InstanceBufferPool`](crate::pool::InstanceBufferPool).
+    pub const BUFFER_BYTES: DiagnosticPath =
+        DiagnosticPath::const_new("instancing/instance_buffer_bytes");
+    /// Instance buffers created per second, for new hosts and hosts whose instances outgrew or
+    /// shrank far below their buffers, when no buffer of the size was free in the
+    /// [`InstanceBufferPool`](crate::pool::InstanceBufferPool).
+    pub const REALLOCATIONS: DiagnosticPath =
+        DiagnosticPath::const_new("instancing/instance_buffer_reallocations");
+}
+
+impl Plugin for InstancingDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        let stats = InstanceBufferStats::default();
+        app.register_diagnostic(Diagnostic::new(Self::INSTANCES))
+            .register_diagnostic(Diagnostic::new(Self::BUFFER_BYTES).with_suffix(" B"))
+            .register_diagnostic(Diagnostic::new(Self::REALLOCATIONS).with_suffix("/s"))
+            .insert_resource(stats.clone())
+            .add_systems(Update, measure_instance_buffers);
+
+        app.sub_app_mut(RenderApp).insert_resource(stats);
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    /// The instances and buffer bytes of each instance type in the last frame.
+    by_type: HashMap<TypeId, (usize, u64)>,
+    /// Instance buffers created since the start.
+    reallocations: u64,
+}
+
+/// The measurements of `prepare_instance_buffers`, shared by the main and the render world.
+#[derive(Resource, Clone, Default)]
+pub(crate) struct InstanceBufferStats(Arc<Mutex<Stats>>);
+
+impl InstanceBufferStats {
+    pub(crate) fn record<T: 'static>(&self, instances: usize, bytes: u64, reallocations: u64) {
+        let mut stats = self.0.lock().unwrap();
+        stats.by_type.insert(TypeId::of::<T>(), (instances, bytes));
+        stats.reallocations += reallocations;
+    }
+}
+
+fn measure_instance_buffers(
+    stats: Res<InstanceBufferStats>,
+    time: Res<Time<Real>>,
+    mut diagnostics: Diagnostics,
+    mut last_reallocations: Local<u64>,
+) {
+    let stats = stats.0.lock().unwrap();
+    let (instances, bytes) = stats.by_type.values().fold(
+        (0, 0),
+        |(instances, bytes), (type_instances, type_bytes)| {
+            (instances + type_instances, bytes + type_bytes)
+        },
+    );
+    diagnostics.add_measurement(&InstancingDiagnosticsPlugin::INSTANCES, || instances as f64);
+    diagnostics.add_measurement(&InstancingDiagnosticsPlugin::BUFFER_BYTES, || bytes as f64);
+
+    let reallocations = stats.reallocations - *last_reallocations;
+    *last_reallocations = stats.reallocations;
+    let delta = time.delta_seconds_f64();
+    if delta > 0.0 {
+        diagnostics.add_measurement(&InstancingDiagnosticsPlugin::REALLOCATIONS, || {
+            reallocations as f64 / delta
+        });
+    }
+}
+
+/// The number of instances of this host entity drawn in the last frame, kept up to date for hosts
+/// spawned with it.
+///
+/// Counts the instances uploaded for the host, after hidden instances, LODs and the other CPU side
+/// steps dropped theirs, and 0 while the host is hidden or culled as a batch. Hosts with
+/// [`GpuCulling`](crate::culling::GpuCulling) and [`InstanceCullReadback`] count the instances
+/// the GPU left in the view drawing the most of them, which arrive a few frames late, see
+/// [`InstanceCullResults`]. Everything else is counted in the render world, so the count lags one
+/// frame behind the instances.
+#[derive(Component, Clone, Copy, Default, PartialEq, Eq, Debug, ExtractComponent)]
+pub struct DrawnInstanceCount(pub u32);
+
+/// Keeps the [`DrawnInstanceCount`]s up to date, added along with the first instance type.
+pub struct DrawnInstanceCountPlugin;
+
+impl Plugin for DrawnInstanceCountPlugin {
+    fn build(&self, app: &mut App) {
+        let counts = DrawnInstanceCounts::default();
+        app.add_plugins(ExtractComponentPlugin::<DrawnInstanceCount>::default())
+            .insert_resource(counts.clone())
+            .add_systems(PreUpdate, update_drawn_instance_counts);
+
+        app.sub_app_mut(RenderApp)
+            .insert_resource(counts)
+            .init_resource::<FrameDrawnInstances>()
+            .add_systems(
+                Render,
+                publish_drawn_instance_counts.in_set(RenderSet::Cleanup),
+            );
+    }
+}
+
+/// The counts of the last frame the render world finished, taken by the main world.
+#[derive(Resource, Clone, Default)]
+struct DrawnInstanceCounts(Arc<Mutex<Option<EntityHashMap<u32>>>>);
+
+/// The counts of the frame being rendered, by host.
+#[derive(Resource, Default)]
+pub(crate) struct FrameDrawnInstances(EntityHashMap<u32>);
+
+/// Counts the instances in the buffers of the hosts with a [`DrawnInstanceCount`].
+#[allow(clippy::type_complexity)]
+pub(crate) fn record_drawn_instances<T: Instance>(
+    hosts: Query<
+        (
+            Entity,
+            &InstanceBuffer<T>,
+            Option<&InstanceDataTextureBindGroup>,
+        ),
+        With<DrawnInstanceCount>,
+    >,
+    mut frame: ResMut<FrameDrawnInstances>,
+) {
+    for (host, instance_buffer, data_texture) in &hosts {
+        let drawn = data_texture.map_or(instance_buffer.length as u32, |data_texture| {
+            data_texture.instances
+        });
+        frame.0.insert(host, drawn);
+    }
+}
+
+/// Hands the counts of a whole frame to the main world at once, so it never sees half a frame.
+fn publish_drawn_instance_counts(
+    counts: Res<DrawnInstanceCounts>,
+    mut frame: ResMut<FrameDrawnInstances>,
+) {
+    *counts.0.lock().unwrap() = Some(std::mem::take(&mut frame.0));
+}
+
+fn update_drawn_instance_counts(
+    counts: Res<DrawnInstanceCounts>,
+    cull_results: Option<Res<InstanceCullResults>>,
+    mut hosts: Query<(Entity, &mut DrawnInstanceCount, Has<InstanceCullReadback>)>,
+    mut latest: Local<EntityHashMap<u32>>,
+) {
+    // kept while no frame finished since the last update
+    if let Some(frame) = counts.0.lock().unwrap().take() {
+        *latest = frame;
+    }
+
+    for (host, mut count, readback) in &mut hosts {
+        let drawn = latest.get(&host).map_or(0, |&uploaded| {
+            let culled = cull_results
+                .as_ref()
+                .filter(|_| readback)
+                .and_then(|results| results.views(host).map(|(_, result)| result.visible).max());
+            culled.map_or(uploaded, |culled| culled.min(uploaded))
+        });
+        count.set_if_neq(DrawnInstanceCount(drawn));
+    }
+}
@@ -0,0 +1,185 @@
+//! Instances built from entities instead of an [`InstanceMaterialData`] edited by hand.
+//!
+//! A host with [`EntityInstances<C>`] draws one instance per child entity with a `C` component,
+//! in the order of its [`Children`]. Spawning, despawning and moving the children spawns,
+//! despawns and moves the instances, while the host still draws all of them in one call. The
+//! child's `Transform` is relative to the host like for any other child, and the instances are
+//! only rebuilt in frames in which a child was added, removed or changed.
+//!
+//! A host with [`QueryInstances<M>`] draws one instance per entity with an `M` component instead,
+//! wherever the entity is in the hierarchy, built by the closure of its [`QueryInstancesPlugin`].
+//! The entities don't have to be children of the host, so the host is left at the origin and their
+//! `Transform` is taken as relative to it.
+
+use bevy::{ecs::entity::EntityHashSet, prelude::*, render::view::VisibilitySystems};
+use std::{marker::PhantomData, sync::Arc};
+
+use crate::{Instance, InstanceData, InstanceMaterialData};
+pub use instancing_derive::InstanceChild;
+
+/// A component of child entities that describes the instance of type `T` drawn for them.
+///
+/// `#[derive(InstanceChild)]` implements it for [`InstanceData`](crate::InstanceData).
+pub trait InstanceChild<T: Instance>: Component {
+    /// Builds the instance of a child with this component and the `Transform` of the child.
+    fn instance(&self, transform: &Transform) -> T;
+}
+
+/// Rebuilds the [`InstanceMaterialData`] of this host entity from its children with a `C`
+/// component. Requires an [`EntityInstancesPlugin`] for the instance type and `C`.
+///
+/// Spawn it alongside an [`InstancedMeshBundle`](crate::InstancedMeshBundle) without instances.
+/// Instances pushed to the host directly are replaced as soon as a child changes.
+#[derive(Component)]
+pub struct EntityInstances<C: Component>(PhantomData<C>);
+
+impl<C: Component> Default for EntityInstances<C> {
+    fn default() -> Self {
+        EntityInstances(PhantomData)
+    }
+}
+
+/// Builds the instances of type `T` of hosts with [`EntityInstances<C>`] from their children.
+pub struct EntityInstancesPlugin<T, C>(PhantomData<(T, C)>);
+
+impl<T, C> Default for EntityInstancesPlugin<T, C> {
+    fn default() -> Self {
+        EntityInstancesPlugin(PhantomData)
+    }
+}
+
+impl<T: Instance, C: InstanceChild<T>> Plugin for EntityInstancesPlugin<T, C> {
+    fn build(&self, app: &mut App) {
+        // the batch bounds are computed from the instances
+        app.add_systems(
+            PostUpdate,
+            build_entity_instances::<T, C>.before(VisibilitySystems::CalculateBounds),
+        );
+    }
+}
+
+/// Rebuilds the instances of hosts whose children were added, removed, reordered or changed.
+///
+/// Edits made after this system ran, like despawns in [`Last`], are picked up next frame.
+#[allow(clippy::type_complexity)]
+pub fn build_entity_instances<T: Instance, C: InstanceChild<T>>(
+    mut hosts: Query<
+        (Entity, &mut InstanceMaterialData<T>, Option<Ref<Children>>),
+        With<EntityInstances<C>>,
+    >,
+    children: Query<(Ref<C>, Ref<Transform>)>,
+    mut removed_children: RemovedComponents<Children>,
+    mut removed_instances: RemovedComponents<C>,
+) {
+    // hosts lose `Children` along with their last child, which leaves nothing to compare against
+    let emptied: EntityHashSet = removed_children.read().collect();
+    // a despawned child can't be traced back to its host, so every host is rebuilt
+    let instances_removed = removed_instances.read().count() > 0;
+
+    for (host, mut instances, host_children) in &mut hosts {
+        let entities = host_children
+            .as_deref()
+            .map_or(&[][..], |entities| &entities[..]);
+        // skips children that aren't instances, like the sprites of `CpuFallbackPlugin`
+        let children = entities
+            .iter()
+            .filter_map(|entity| children.get(*entity).ok());
+
+        let changed = instances_removed
+            || emptied.contains(&host)
+            || host_children
+                .as_ref()
+                .is_some_and(|entities| entities.is_changed())
+            || children
+                .clone()
+                .any(|(child, transform)| child.is_changed() || transform.is_changed());
+        if !changed {
+            continue;
+        }
+
+        *instances = children
+            .map(|(child, transform)| child.instance(&transform))
+            .collect();
+    }
+}
+
+/// Rebuilds the [`InstanceMaterialData`] of this host entity from every entity with an `M`
+/// component, see the [module docs](self). Requires a [`QueryInstancesPlugin`] for the instance
+/// type and `M`.
+#[derive(Component)]
+pub struct QueryInstances<M: Component>(PhantomData<M>);
+
+impl<M: Component> Default for QueryInstances<M> {
+    fn default() -> Self {
+        QueryInstances(PhantomData)
+    }
+}
+
+/// Builds the instance of an entity from its `Transform` and its `M` component.
+type BuildInstance<T, M> = Arc<dyn Fn(&Transform, &M) -> T + Send + Sync>;
+
+/// Builds the instances of type `T` of hosts with [`QueryInstances<M>`] from the entities with an
+/// `M` component, with the closure passed to [`new`](Self::new).
+///
+/// The default plugin for [`InstanceData`] takes the position, rotation and scale from the
+/// `Transform` alone, see [`InstanceData::from_transform`].
+pub struct QueryInstancesPlugin<T, M> {
+    build: BuildInstance<T, M>,
+}
+
+impl<T: Instance, M: Component> QueryInstancesPlugin<T, M> {
+    /// Builds the instance of every entity with `build`, e.g. to take its color from `M`.
+    pub fn new(build: impl Fn(&Transform, &M) -> T + Send + Sync + 'static) -> Self {
+        QueryInstancesPlugin {
+            build: Arc::new(build),
+        }
+    }
+}
+
+impl<M: Component> Default for QueryInstancesPlugin<InstanceData, M> {
+    fn default() -> Self {
+        Self::new(|transform, _| InstanceData::from_transform(transform))
+    }
+}
+
+impl<T: Instance, M: Component> Plugin for QueryInstancesPlugin<T, M> {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(QueryInstanceBuilder(self.build.clone()))
+            // the batch bounds are computed from the instances
+            .add_systems(
+                PostUpdate,
+                sync_instances_from_query::<T, M>.before(VisibilitySystems::CalculateBounds),
+            );
+    }
+}
+
+/// The closure of the [`QueryInstancesPlugin`] of `T` and `M`.
+#[derive(Resource)]
+pub struct QueryInstanceBuilder<T, M>(BuildInstance<T, M>);
+
+/// Rebuilds the instances of hosts with [`QueryInstances<M>`] in frames in which an entity with an
+/// `M` component was added, removed or changed, or its `Transform` changed. The instances follow
+/// the order of the query, which changes as entities are spawned and despawned.
+pub fn sync_instances_from_query<T: Instance, M: Component>(
+    mut hosts: Query<(&mut InstanceMaterialData<T>, Ref<QueryInstances<M>>)>,
+    entities: Query<(Ref<M>, Ref<Transform>)>,
+    mut removed: RemovedComponents<M>,
+    builder: Res<QueryInstanceBuilder<T, M>>,
+) {
+    let removed = removed.read().count() > 0;
+    let changed = removed
+        || entities
+            .iter()
+            .any(|(marker, transform)| marker.is_changed() || transform.is_changed());
+
+    for (mut instances, host) in &mut hosts {
+        // new hosts are filled even if nothing else changed
+        if !changed && !host.is_added() {
+            continue;
+        }
+        *instances = entities
+            .iter()
+            .map(|(marker, transform)| (builder.0)(&transform, &marker))
+            .collect();
+    }
+}
@@ -0,0 +1,122 @@
+//! Draws [`InstanceData`] without the instancing pipeline, as a reference to compare it against.
+//!
+//! Add [`CpuFallbackPlugin`] in place of
+//! [`InstancingPlugin::<InstanceData>`](crate::InstancingPlugin) and every 2D host entity gets one
+//! ordinary [`ColorMaterial`] mesh child per instance, with the position, scale, rotation, color
+//! and emissive color of the instance. Atlas cells, UV offsets and borders are not reproduced and
+//! the colors are taken as linear. Only available with the `cpu_fallback` feature.
+
+use bevy::{prelude::*, sprite::Mesh2dHandle, transform::TransformSystem};
+
+use crate::{InstanceData, InstanceMaterialData, InstanceVisibility};
+
+pub struct CpuFallbackPlugin;
+
+impl Plugin for CpuFallbackPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            sync_fallback_sprites.before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// A child entity drawing one instance of its parent.
+#[derive(Component)]
+pub struct FallbackSprite;
+
+#[allow(clippy::type_complexity)]
+fn sync_fallback_sprites(
+    mut commands: Commands,
+    hosts: Query<
+        (
+            Entity,
+            &InstanceMaterialData<InstanceData>,
+            Option<&InstanceVisibility>,
+            &Mesh2dHandle,
+            Option<&Children>,
+        ),
+        (
+            Without<FallbackSprite>,
+            Or<(
+                Changed<InstanceMaterialData<InstanceData>>,
+                Changed<InstanceVisibility>,
+                Changed<Mesh2dHandle>,
+            )>,
+        ),
+    >,
+    mut sprites: Query<
+        (
+            &mut Transform,
+            &mut Visibility,
+            &mut Mesh2dHandle,
+            &Handle<ColorMaterial>,
+        ),
+        With<FallbackSprite>,
+    >,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for (host, instances, visibility, mesh, children) in &hosts {
+        // the host may have children of its own, keep them out of it
+        let existing: Vec<Entity> = children
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(|child| sprites.contains(*child))
+            .collect();
+
+        for (index, instance) in instances.iter().enumerate() {
+            let transform = Transform {
+                translation: instance.position,
+                rotation: Quat::from_rotation_z(instance.rotation),
+                scale: Vec3::splat(instance.scale),
+            };
+            let shown =
+                visibility.is_none_or(|visibility| visibility.get(index).copied().unwrap_or(true));
+            let visible = if shown {
+                Visibility::Inherited
+            } else {
+                Visibility::Hidden
+            };
+
+            // same as the fragment shader, emissive is added to covered pixels only
+            let [r, g, b, a] = instance.color;
+            let [er, eg, eb] = instance.emissive;
+            let color = Color::rgba_linear(r + er * a, g + eg * a, b + eb * a, a);
+
+            match existing.get(index) {
+                Some(&sprite) => {
+                    let (mut sprite_transform, mut sprite_visibility, mut sprite_mesh, material) =
+                        sprites.get_mut(sprite).unwrap();
+                    *sprite_transform = transform;
+                    sprite_visibility.set_if_neq(visible);
+                    if sprite_mesh.0 != mesh.0 {
+                        sprite_mesh.0 = mesh.0.clone();
+                    }
+                    if let Some(material) = materials.get_mut(material) {
+                        material.color = color;
+                    }
+                }
+                None => {
+                    let sprite = commands
+                        .spawn((
+                            ColorMesh2dBundle {
+                                mesh: mesh.clone(),
+                                material: materials.add(color),
+                                transform,
+                                visibility: visible,
+                                ..default()
+                            },
+                            FallbackSprite,
+                        ))
+                        .id();
+                    commands.entity(host).add_child(sprite);
+                }
+            }
+        }
+
+        for &sprite in existing.iter().skip(instances.len()) {
+            commands.entity(sprite).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Outlines of the individual instances of 2D hosts, drawn with gizmos to debug their layout.
+//!
+//! Every instance is outlined by the bounds of the host's mesh, moved, scaled and rotated by the
+//! instance and the host like in the vertex shader. The outlines are read from the
+//! [`InstanceMaterialData`] on the CPU and drawn by bevy's gizmos, the instanced draw is left
+//! untouched. This needs [`Instance::BOUNDS`] and takes [`Instance::ROTATION_OFFSET`] into
+//! account if present, like [`picking`](crate::picking).
+
+use bevy::{prelude::*, sprite::Mesh2dHandle, transform::TransformSystem};
+use std::marker::PhantomData;
+
+use crate::{Instance, InstanceMaterialData, InstanceVisibility};
+
+/// Toggles and colors the outlines drawn by [`DebugInstanceGizmosPlugin`].
+#[derive(Resource, Clone, Copy)]
+pub struct DebugInstanceGizmos {
+    pub enabled: bool,
+    /// The color of the outline of the instance at an index, [`index_color`] by default.
+    pub color: fn(usize) -> Color,
+}
+
+impl Default for DebugInstanceGizmos {
+    fn default() -> Self {
+        DebugInstanceGizmos {
+            enabled: true,
+            color: index_color,
+        }
+    }
+}
+
+/// A hue per instance index, spread so that neighbouring instances differ.
+pub fn index_color(index: usize) -> Color {
+    Color::hsl((index as f32 * 137.508) % 360.0, 0.9, 0.6)
+}
+
+/// Outlines the instances of type `T` while [`DebugInstanceGizmos::enabled`] is set.
+pub struct DebugInstanceGizmosPlugin<T: Instance>(PhantomData<T>);
+
+impl<T: Instance> Default for DebugInstanceGizmosPlugin<T> {
+    fn default() -> Self {
+        DebugInstanceGizmosPlugin(PhantomData)
+    }
+}
+
+impl<T: Instance> Plugin for DebugInstanceGizmosPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DebugInstanceGizmos>().add_systems(
+            PostUpdate,
+            draw_instance_gizmos::<T>
+                .after(TransformSystem::TransformPropagate)
+                .run_if(|gizmos: Res<DebugInstanceGizmos>| gizmos.enabled),
+        );
+    }
+}
+
+/// Draws a `rect_2d` for every visible instance of every visible 2D host.
+#[allow(clippy::type_complexity)]
+pub fn draw_instance_gizmos<T: Instance>(
+    mut gizmos: Gizmos,
+    hosts: Query<(
+        &InstanceMaterialData<T>,
+        Option<&InstanceVisibility>,
+        &Mesh2dHandle,
+        &GlobalTransform,
+        &InheritedVisibility,
+    )>,
+    meshes: Res<Assets<Mesh>>,
+    settings: Res<DebugInstanceGizmos>,
+) {
+    let Some(bounds) = T::BOUNDS else {
+        return;
+    };
+
+    for (instances, visibility, mesh, transform, inherited_visibility) in &hosts {
+        if !inherited_visibility.get() {
+            continue;
+        }
+        let Some(mesh_bounds) = meshes.get(&mesh.0).and_then(Mesh::compute_aabb) else {
+            continue;
+        };
+        let center = Vec3::from(mesh_bounds.center);
+        let size = 2.0 * mesh_bounds.half_extents.truncate();
+
+        for (index, instance) in instances.iter().enumerate() {
+            if visibility.is_some_and(|visibility| !visibility.get(index).copied().unwrap_or(true))
+            {
+                continue;
+            }
+
+            let (position, _) = bounds.read(instance);
+            let rotation = T::ROTATION_OFFSET.map_or(0.0, |offset| {
+                let offset = offset as usize;
+                bytemuck::pod_read_unaligned::<f32>(
+                    &bytemuck::bytes_of(instance)[offset..offset + 4],
+                )
+            });
+            let instance_transform = Transform {
+                translation: position,
+                rotation: Quat::from_rotation_z(rotation),
+                scale: bounds.read_scale_2d(instance).extend(1.0),
+            };
+
+            // a host scaled unevenly and rotated shears its instances, which a rect can't show
+            let world = transform.mul_transform(instance_transform);
+            let (scale, rotation, _) = world.to_scale_rotation_translation();
+            let x_axis = rotation * Vec3::X;
+
+            gizmos.rect_2d(
+                world.transform_point(center).truncate(),
+                x_axis.y.atan2(x_axis.x),
+                size * scale.truncate(),
+                (settings.color)(index),
+            );
+        }
+    }
+}
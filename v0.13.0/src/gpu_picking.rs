@@ -0,0 +1,293 @@
+//! GPU side picking of 2D instances, reading back the instance id under the cursor.
+//!
+//! A 2D camera with [`GpuPicking`] writes the instance ids of its hosts into an extra target, see
+//! [`InstanceTarget::InstanceIds`]. After the hosts are drawn, the pixel under the cursor of
+//! the primary window is copied into a readback buffer, and once the GPU is done with it the id
+//! ends up in the [`PickedInstance`] resource, a frame or two after the cursor moved.
+//!
+//! Unlike [`picking`](crate::picking), this is exact for every mesh, rotation and shader, and picks
+//! the instance drawn on top where instances overlap. The picked index is the index in the instance
+//! buffer of the host drawn on top, which differs from the index in
+//! [`InstanceMaterialData`](crate::InstanceMaterialData) for hosts with sorted, culled or LOD split
+//! instances.
+
+use bevy::{
+    core_pipeline::core_2d::graph::{Core2d, Node2d},
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{
+        camera::NormalizedRenderTarget,
+        render_graph::{
+            NodeRunError, RenderGraphApp, RenderGraphContext, RenderLabel, ViewNode, ViewNodeRunner,
+        },
+        render_resource::*,
+        renderer::{RenderContext, RenderDevice},
+        Extract, ExtractSchedule, Render, RenderApp, RenderSet,
+    },
+    window::PrimaryWindow,
+};
+use std::sync::{Arc, Mutex};
+
+use crate::targets::{
+    InstanceTarget, InstanceTargets, InstanceTargetsPass, InstanceTargetsPlugin,
+    ViewInstanceTargets,
+};
+
+/// Picks the instance under the cursor of the primary window from the hosts seen by this 2D
+/// camera, see the [module docs](self). Inserts [`InstanceTargets::instance_ids`] unless the
+/// camera already has [`InstanceTargets`], which then have to include
+/// [`InstanceTarget::InstanceIds`].
+#[derive(Component, Clone, Copy, Default, Debug)]
+pub struct GpuPicking;
+
+/// The index of the instance under the cursor, kept up to date by [`GpuPickingPlugin`].
+#[derive(Resource, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct PickedInstance(pub Option<usize>);
+
+/// The last id read back, zero if no instance was hit, shared by the main and the render world.
+#[derive(Resource, Clone, Default)]
+struct PickingResults(Arc<Mutex<Option<u32>>>);
+
+pub struct GpuPickingPlugin;
+
+impl Plugin for GpuPickingPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<InstanceTargetsPlugin>() {
+            app.add_plugins(InstanceTargetsPlugin);
+        }
+
+        let results = PickingResults::default();
+        app.init_resource::<PickedInstance>()
+            .insert_resource(results.clone())
+            .add_systems(PreUpdate, update_picked_instance)
+            .add_systems(PostUpdate, insert_instance_targets);
+
+        app.sub_app_mut(RenderApp)
+            .insert_resource(results)
+            .add_systems(ExtractSchedule, extract_picking_cursors)
+            .add_systems(
+                Render,
+                (
+                    read_picking_readback.in_set(RenderSet::PrepareResources),
+                    map_picking_readback.in_set(RenderSet::Cleanup),
+                ),
+            )
+            .add_render_graph_node::<ViewNodeRunner<GpuPickingNode>>(Core2d, GpuPickingCopy)
+            .add_render_graph_edges(
+                Core2d,
+                (InstanceTargetsPass, GpuPickingCopy, Node2d::Tonemapping),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let render_app = app.sub_app_mut(RenderApp);
+        let buffer = render_app
+            .world
+            .resource::<RenderDevice>()
+            .create_buffer(&BufferDescriptor {
+                label: Some("gpu picking readback buffer"),
+                size: 4,
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+        render_app.insert_resource(PickingReadback {
+            buffer,
+            state: Arc::default(),
+        });
+    }
+}
+
+fn insert_instance_targets(
+    mut commands: Commands,
+    cameras: Query<Entity, (With<GpuPicking>, Without<InstanceTargets>)>,
+) {
+    for camera in &cameras {
+        commands
+            .entity(camera)
+            .insert(InstanceTargets::instance_ids());
+    }
+}
+
+fn update_picked_instance(results: Res<PickingResults>, mut picked: ResMut<PickedInstance>) {
+    if let Some(id) = results.0.lock().unwrap().take() {
+        // ids are offset by one, zero is left where no instance was drawn
+        picked.set_if_neq(PickedInstance(
+            id.checked_sub(1).map(|index| index as usize),
+        ));
+    }
+}
+
+/// The pixel of the view target under the cursor.
+#[derive(Component)]
+struct PickingCursor(UVec2);
+
+fn extract_picking_cursors(
+    mut commands: Commands,
+    windows: Extract<Query<(Entity, &Window), With<PrimaryWindow>>>,
+    cameras: Extract<Query<(Entity, &Camera), With<GpuPicking>>>,
+    results: Res<PickingResults>,
+) {
+    let Some((primary_window, cursor)) = windows
+        .get_single()
+        .ok()
+        .and_then(|(entity, window)| Some((entity, window.physical_cursor_position()?)))
+    else {
+        *results.0.lock().unwrap() = Some(0);
+        return;
+    };
+
+    // the top most camera under the cursor picks
+    let camera = cameras
+        .iter()
+        .filter(|(_, camera)| camera.is_active)
+        .filter(|(_, camera)| {
+            matches!(
+                camera.target.normalize(Some(primary_window)),
+                Some(NormalizedRenderTarget::Window(window)) if window.entity() == primary_window
+            )
+        })
+        .filter(|(_, camera)| {
+            camera
+                .physical_viewport_rect()
+                .is_some_and(|rect| rect.as_rect().contains(cursor))
+        })
+        .max_by_key(|(_, camera)| camera.order);
+
+    match camera {
+        Some((entity, _)) => {
+            commands
+                .get_or_spawn(entity)
+                .insert(PickingCursor(cursor.as_uvec2()));
+        }
+        None => *results.0.lock().unwrap() = Some(0),
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+enum ReadbackState {
+    #[default]
+    Idle,
+    /// The id is copied into the buffer by the commands of this frame.
+    Copied,
+    /// Waiting for the GPU to map the buffer.
+    Mapping,
+    Mapped,
+}
+
+/// The buffer the id under the cursor is copied into, read back by the CPU.
+#[derive(Resource)]
+struct PickingReadback {
+    buffer: Buffer,
+    state: Arc<Mutex<ReadbackState>>,
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct GpuPickingCopy;
+
+/// Copies the id under the cursor from the instance id target of a view into the readback buffer,
+/// unless the previous copy is still being read.
+#[derive(Default)]
+struct GpuPickingNode;
+
+impl ViewNode for GpuPickingNode {
+    type ViewQuery = (
+        &'static PickingCursor,
+        &'static InstanceTargets,
+        &'static ViewInstanceTargets,
+    );
+
+    fn run<'w>(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        (cursor, targets, view_targets): QueryItem<'w, Self::ViewQuery>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let readback = world.resource::<PickingReadback>();
+        let mut state = readback.state.lock().unwrap();
+        if *state != ReadbackState::Idle {
+            return Ok(());
+        }
+        let Some(texture) = targets
+            .position(InstanceTarget::InstanceIds)
+            .and_then(|index| view_targets.textures.get(index))
+        else {
+            return Ok(());
+        };
+
+        // the cursor is within the viewport, which lies within the target, but rounds up at its
+        // far edges
+        let size = texture.texture.size();
+        let pixel = cursor.0.min(UVec2::new(size.width, size.height) - 1);
+
+        render_context.command_encoder().copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture: &texture.texture,
+                mip_level: 0,
+                origin: Origin3d {
+                    x: pixel.x,
+                    y: pixel.y,
+                    z: 0,
+                },
+                aspect: TextureAspect::All,
+            },
+            ImageCopyBuffer {
+                buffer: &readback.buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    // a single row needs no stride
+                    bytes_per_row: None,
+                    rows_per_image: None,
+                },
+            },
+            Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        *state = ReadbackState::Copied;
+        Ok(())
+    }
+}
+
+/// Maps the readback buffer once the copy is submitted.
+fn map_picking_readback(readback: Res<PickingReadback>, render_device: Res<RenderDevice>) {
+    let mut state = readback.state.lock().unwrap();
+    if *state != ReadbackState::Copied {
+        return;
+    }
+    *state = ReadbackState::Mapping;
+    // the callback locks the state
+    drop(state);
+
+    let mapped_state = readback.state.clone();
+    render_device.map_buffer(&readback.buffer.slice(..), MapMode::Read, move |result| {
+        // a failed mapping is retried with the next copy
+        *mapped_state.lock().unwrap() = match result {
+            Ok(()) => ReadbackState::Mapped,
+            Err(_) => ReadbackState::Idle,
+        };
+    });
+}
+
+/// Hands the id of a mapped readback buffer to the main world.
+fn read_picking_readback(
+    readback: Res<PickingReadback>,
+    results: Res<PickingResults>,
+    render_device: Res<RenderDevice>,
+) {
+    // runs the callback of the mapping if the GPU is done with it
+    render_device.poll(Maintain::Poll);
+
+    let mut state = readback.state.lock().unwrap();
+    if *state != ReadbackState::Mapped {
+        return;
+    }
+
+    let slice = readback.buffer.slice(..);
+    let id = bytemuck::pod_read_unaligned::<u32>(&slice.get_mapped_range());
+    readback.buffer.unmap();
+    *results.0.lock().unwrap() = Some(id);
+    *state = ReadbackState::Idle;
+}
@@ -0,0 +1,352 @@
+//! Instancing of 3D meshes, drawn in the [`Transparent3d`] phase, the opaque instances of hosts
+//! with [`MixedOpacity`] in the [`Opaque3d`] phase and hosts with an [`InstanceAlphaMask`] in the
+//! [`AlphaMask3d`] phase.
+
+use bevy::{
+    core_pipeline::core_3d::{AlphaMask3d, Opaque3d, Transparent3d},
+    pbr::{MeshPipeline, MeshPipelineKey, RenderMeshInstances, SetMeshViewBindGroup},
+    prelude::*,
+    render::{
+        mesh::MeshVertexBufferLayout,
+        render_asset::RenderAssets,
+        render_phase::{AddRenderCommand, DrawFunctions, RenderPhase, SetItemPipeline},
+        render_resource::*,
+        view::{ExtractedView, VisibilitySystems, VisibleEntities},
+        Render, RenderApp, RenderSet,
+    },
+    utils::HashSet,
+};
+use std::marker::PhantomData;
+
+use crate::{
+    bounds::update_batch_aabb,
+    load_shader,
+    lod::batch_lod_instances,
+    log_missing_locations, log_unsupported_mesh,
+    opacity::{batch_opacity_instances, DrawOpacityBatch, MixedOpacity, MixedOpacityPlugin},
+    prepare_instance_buffers,
+    writer::InstanceGenerator,
+    DrawMeshInstanced, HostMeshLayouts, Instance, InstanceAlphaMask, InstanceBufferMode,
+    InstanceBufferPlugin, InstanceColorSpace, InstanceMaterialData, InstancePipeline,
+    SetHostMeshBindGroup, SetInstanceStorageBindGroup, INSTANCING_3D_SHADER_HANDLE,
+    MESH_3D_ATTRIBUTES,
+};
+
+/// Draws the instances of 3D meshes (`Handle<Mesh>`) in the [`Transparent3d`] phase.
+///
+/// Instance positions are in the local space of the host, which the host's `GlobalTransform` is
+/// applied to. Can be used alongside
+/// [`InstancingPlugin`](crate::InstancingPlugin) for the same instance type.
+pub struct Instancing3dPlugin<T: Instance> {
+    pub buffer_mode: InstanceBufferMode,
+    pub color_space: InstanceColorSpace,
+    /// Replaces the shader of the instance type, [`Instance::shader_3d`], for this plugin.
+    pub shader: Option<Handle<Shader>>,
+    marker: PhantomData<T>,
+}
+
+impl<T: Instance> Instancing3dPlugin<T> {
+    pub fn with_buffer_mode(mut self, buffer_mode: InstanceBufferMode) -> Self {
+        self.buffer_mode = buffer_mode;
+        self
+    }
+
+    pub fn with_color_space(mut self, color_space: InstanceColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+
+    pub fn with_shader(mut self, shader: Handle<Shader>) -> Self {
+        self.shader = Some(shader);
+        self
+    }
+}
+
+impl<T: Instance> Default for Instancing3dPlugin<T> {
+    fn default() -> Self {
+        Self {
+            buffer_mode: InstanceBufferMode::default(),
+            color_space: InstanceColorSpace::default(),
+            shader: None,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Instance> Plugin for Instancing3dPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<InstanceBufferPlugin<T>>() {
+            app.add_plugins(InstanceBufferPlugin::<T>(PhantomData));
+        }
+
+        if !app.is_plugin_added::<MixedOpacityPlugin>() {
+            app.add_plugins(MixedOpacityPlugin);
+        }
+
+        app.add_systems(
+            PostUpdate,
+            update_batch_aabb::<T, Handle<Mesh>>
+                .after(VisibilitySystems::CalculateBounds)
+                .before(VisibilitySystems::CheckVisibility),
+        );
+
+        app.sub_app_mut(RenderApp)
+            .add_render_command::<Transparent3d, DrawCustom3d<T>>()
+            .add_render_command::<Opaque3d, DrawOpaque3d<T>>()
+            .add_render_command::<AlphaMask3d, DrawCustom3d<T>>()
+            .add_render_command::<Transparent3d, DrawTransparent3d<T>>()
+            .init_resource::<SpecializedMeshPipelines<CustomPipeline3d<T>>>()
+            .add_systems(
+                Render,
+                (
+                    queue_custom_3d::<T>.in_set(RenderSet::QueueMeshes),
+                    // reorders the instances after the LODs did, which are ignored then
+                    batch_opacity_instances::<T>
+                        .in_set(RenderSet::PrepareResources)
+                        .after(batch_lod_instances::<T>)
+                        .before(prepare_instance_buffers::<T>),
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let render_app = app.sub_app_mut(RenderApp);
+        InstancePipeline::<T>::init(&mut render_app.world, self.buffer_mode, self.color_space);
+        let custom_pipeline =
+            CustomPipeline3d::<T>::new(&mut render_app.world, self.shader.clone());
+        render_app.insert_resource(custom_pipeline);
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn queue_custom_3d<T: Instance>(
+    opaque_3d_draw_functions: Res<DrawFunctions<Opaque3d>>,
+    alpha_mask_3d_draw_functions: Res<DrawFunctions<AlphaMask3d>>,
+    transparent_3d_draw_functions: Res<DrawFunctions<Transparent3d>>,
+    custom_pipeline: Res<CustomPipeline3d<T>>,
+    msaa: Res<Msaa>,
+    mut pipelines: ResMut<SpecializedMeshPipelines<CustomPipeline3d<T>>>,
+    pipeline_cache: Res<PipelineCache>,
+    meshes: Res<RenderAssets<Mesh>>,
+    render_mesh_instances: Res<RenderMeshInstances>,
+    material_meshes: Query<(
+        Entity,
+        Option<&InstanceMaterialData<T>>,
+        Has<InstanceGenerator<T>>,
+        Has<MixedOpacity>,
+        Has<InstanceAlphaMask>,
+    )>,
+    mut views: Query<(
+        &ExtractedView,
+        &VisibleEntities,
+        &mut RenderPhase<Opaque3d>,
+        &mut RenderPhase<AlphaMask3d>,
+        &mut RenderPhase<Transparent3d>,
+    )>,
+    mut logged_errors: Local<HashSet<String>>,
+    mut warned_unloaded: Local<bool>,
+) {
+    let draw_custom = transparent_3d_draw_functions.read().id::<DrawCustom3d<T>>();
+    let draw_opaque = opaque_3d_draw_functions.read().id::<DrawOpaque3d<T>>();
+    let draw_alpha_mask = alpha_mask_3d_draw_functions.read().id::<DrawCustom3d<T>>();
+    let draw_transparent = transparent_3d_draw_functions
+        .read()
+        .id::<DrawTransparent3d<T>>();
+
+    let msaa_key = MeshPipelineKey::from_msaa_samples(msaa.samples());
+
+    for (view, visible_entities, mut opaque_phase, mut alpha_mask_phase, mut transparent_phase) in
+        &mut views
+    {
+        let view_key = msaa_key | MeshPipelineKey::from_hdr(view.hdr);
+        let rangefinder = view.rangefinder3d();
+        // frustum culled per view on the batch Aabb, see `update_batch_aabb`
+        for entity in &visible_entities.entities {
+            let Ok((entity, instances, generated, mixed_opacity, alpha_mask)) =
+                material_meshes.get(*entity)
+            else {
+                continue;
+            };
+            if !generated && instances.is_none_or(|instances| instances.is_empty()) {
+                continue;
+            }
+            // hosts without a `Handle<Mesh>` are warned about by `warn_incomplete_hosts`
+            let Some(mesh_instance) = render_mesh_instances.get(&entity) else {
+                continue;
+            };
+            let Some(mesh) = meshes.get(mesh_instance.mesh_asset_id) else {
+                // queued again every frame, so the batch appears as soon as the mesh is ready
+                if !*warned_unloaded {
+                    warn!("The mesh of instanced host {entity:?} isn't loaded yet, its instances are drawn once it is");
+                    *warned_unloaded = true;
+                }
+                continue;
+            };
+            // skinned meshes would fail to link, as the host mesh binding has no joints
+            if log_unsupported_mesh(
+                entity,
+                &mesh.layout,
+                mesh.morph_targets.is_some(),
+                &mut logged_errors,
+            ) {
+                continue;
+            }
+            if log_missing_locations(
+                entity,
+                mesh,
+                &MESH_3D_ATTRIBUTES,
+                &custom_pipeline.instance_pipeline,
+                &mut logged_errors,
+            ) {
+                continue;
+            }
+            let key = view_key | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology);
+
+            let mut specialize = |key| {
+                match pipelines.specialize(&pipeline_cache, &custom_pipeline, key, &mesh.layout) {
+                    Ok(id) => Some(id),
+                    Err(err) => {
+                        // the same error would be logged for every view on every frame
+                        let err = err.to_string();
+                        if !logged_errors.contains(&err) {
+                            error!("{}", err);
+                            logged_errors.insert(err);
+                        }
+                        None
+                    }
+                }
+            };
+
+            let distance =
+                rangefinder.distance_translation(&mesh_instance.transforms.transform.translation);
+
+            // the cutout fragments are discarded behind the `MAY_DISCARD` shader def
+            if alpha_mask {
+                let Some(pipeline) = specialize(key | MeshPipelineKey::MAY_DISCARD) else {
+                    continue;
+                };
+                alpha_mask_phase.add(AlphaMask3d {
+                    distance,
+                    pipeline,
+                    entity,
+                    draw_function: draw_alpha_mask,
+                    batch_range: 0..1,
+                    dynamic_offset: None,
+                });
+                continue;
+            }
+
+            // split into the batches of `batch_opacity_instances`
+            if mixed_opacity && instances.is_some() && T::COLOR_OFFSET.is_some() {
+                let (Some(opaque), Some(transparent)) = (
+                    specialize(key),
+                    specialize(key | MeshPipelineKey::BLEND_ALPHA),
+                ) else {
+                    continue;
+                };
+                opaque_phase.add(Opaque3d {
+                    asset_id: mesh_instance.mesh_asset_id,
+                    pipeline: opaque,
+                    entity,
+                    draw_function: draw_opaque,
+                    batch_range: 0..1,
+                    dynamic_offset: None,
+                });
+                transparent_phase.add(Transparent3d {
+                    distance,
+                    pipeline: transparent,
+                    entity,
+                    draw_function: draw_transparent,
+                    batch_range: 0..1,
+                    dynamic_offset: None,
+                });
+                continue;
+            }
+
+            let Some(pipeline) = specialize(key) else {
+                continue;
+            };
+
+            transparent_phase.add(Transparent3d {
+                distance,
+                pipeline,
+                entity,
+                draw_function: draw_custom,
+                batch_range: 0..1,
+                dynamic_offset: None,
+            });
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct CustomPipeline3d<T: Instance> {
+    shader: Handle<Shader>,
+    mesh_pipeline: MeshPipeline,
+    instance_pipeline: InstancePipeline<T>,
+    host_mesh_layout: BindGroupLayout,
+}
+
+impl<T: Instance> CustomPipeline3d<T> {
+    fn new(world: &mut World, shader: Option<Handle<Shader>>) -> Self {
+        let shader = match shader {
+            Some(shader) => shader,
+            None => load_shader(world, T::shader_3d(), INSTANCING_3D_SHADER_HANDLE),
+        };
+
+        let mesh_pipeline = world.resource::<MeshPipeline>();
+        let instance_pipeline = world.resource::<InstancePipeline<T>>();
+        let host_mesh_layout = world.resource::<HostMeshLayouts>();
+
+        CustomPipeline3d {
+            shader,
+            mesh_pipeline: mesh_pipeline.clone(),
+            instance_pipeline: instance_pipeline.clone(),
+            host_mesh_layout: host_mesh_layout.layout_3d.clone(),
+        }
+    }
+}
+
+impl<T: Instance> SpecializedMeshPipeline for CustomPipeline3d<T> {
+    type Key = MeshPipelineKey;
+
+    fn specialize(
+        &self,
+        key: Self::Key,
+        layout: &MeshVertexBufferLayout,
+    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        let mut descriptor = self.mesh_pipeline.specialize(key, layout)?;
+        descriptor.layout[1] = self.host_mesh_layout.clone();
+
+        self.instance_pipeline
+            .specialize(&mut descriptor, &self.shader);
+
+        Ok(descriptor)
+    }
+}
+
+type DrawCustom3d<T> = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetHostMeshBindGroup<1, T>,
+    SetInstanceStorageBindGroup<2, T>,
+    DrawMeshInstanced<T>,
+);
+
+/// Draws the opaque instances of a host with [`MixedOpacity`].
+type DrawOpaque3d<T> = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetHostMeshBindGroup<1, T>,
+    SetInstanceStorageBindGroup<2, T>,
+    DrawOpacityBatch<T, false>,
+);
+
+/// Draws the transparent instances of a host with [`MixedOpacity`].
+type DrawTransparent3d<T> = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetHostMeshBindGroup<1, T>,
+    SetInstanceStorageBindGroup<2, T>,
+    DrawOpacityBatch<T, true>,
+);
@@ -0,0 +1,154 @@
+//! Smooth motion for instances updated in `FixedUpdate`.
+//!
+//! The instances of a host entity with [`InterpolateInstances`] are copied into its
+//! [`PreviousInstances`] before every fixed update. When drawn, each instance is interpolated from
+//! its previous to its current position, scale and rotation by the [`InstanceOverstep`], the
+//! fraction of a fixed timestep that passed since the last fixed update. The instances then lag a
+//! fixed timestep behind, but move every frame instead of jumping once per fixed update.
+//!
+//! Instances are matched by index, so instances pushed during a fixed update appear at their
+//! current state and removing an instance in the middle makes the following ones jump once.
+//! Requires an instance type with [`Instance::BOUNDS`], the rotation is interpolated along the
+//! shorter way if it has an [`Instance::ROTATION_OFFSET`].
+
+use bevy::{
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+    },
+};
+use std::f32::consts::{PI, TAU};
+
+use crate::{Instance, InstanceMaterialData, InstanceVisibility};
+
+/// Interpolates the instances of this host entity between fixed updates, see the
+/// [module docs](self).
+#[derive(Component, Clone, Copy, Default, ExtractComponent)]
+pub struct InterpolateInstances;
+
+/// The instances of a host with [`InterpolateInstances`] before the last fixed update.
+#[derive(Component, Deref)]
+pub struct PreviousInstances<T: Instance>(Vec<T>);
+
+impl<T: Instance> Clone for PreviousInstances<T> {
+    fn clone(&self) -> Self {
+        PreviousInstances(self.0.clone())
+    }
+}
+
+impl<T: Instance> ExtractComponent for PreviousInstances<T> {
+    type QueryData = (&'static Self, Option<&'static InstanceVisibility>);
+    type QueryFilter = With<InterpolateInstances>;
+    type Out = Self;
+
+    fn extract_component((previous, visibility): QueryItem<'_, Self::QueryData>) -> Option<Self> {
+        let Some(visibility) = visibility else {
+            return Some(previous.clone());
+        };
+
+        // compacted like the extracted instances, so the indices keep matching
+        let visible = previous
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| visibility.get(*index).copied().unwrap_or(true))
+            .map(|(_, instance)| *instance)
+            .collect();
+        Some(PreviousInstances(visible))
+    }
+}
+
+/// The fraction of a fixed timestep that passed since the last fixed update, between 0 and 1.
+#[derive(Resource, Clone, Copy, Default, Debug, ExtractResource)]
+pub struct InstanceOverstep(pub f32);
+
+pub struct InterpolationPlugin;
+
+impl Plugin for InterpolationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InstanceOverstep>()
+            .add_plugins((
+                ExtractComponentPlugin::<InterpolateInstances>::default(),
+                ExtractResourcePlugin::<InstanceOverstep>::default(),
+            ))
+            .add_systems(PostUpdate, update_overstep);
+    }
+}
+
+fn update_overstep(time: Res<Time<Fixed>>, mut overstep: ResMut<InstanceOverstep>) {
+    overstep.0 = time.overstep_fraction().clamp(0.0, 1.0);
+}
+
+/// Copies the instances of hosts with [`InterpolateInstances`] into their [`PreviousInstances`],
+/// before the fixed update moves them.
+#[allow(clippy::type_complexity)]
+pub fn store_previous_instances<T: Instance>(
+    mut commands: Commands,
+    mut query: Query<
+        (
+            Entity,
+            &InstanceMaterialData<T>,
+            Option<&mut PreviousInstances<T>>,
+        ),
+        With<InterpolateInstances>,
+    >,
+) {
+    for (entity, instances, previous) in &mut query {
+        match previous {
+            Some(mut previous) => previous.0.clone_from(instances),
+            None => {
+                commands
+                    .entity(entity)
+                    .insert(PreviousInstances(instances.to_vec()));
+            }
+        }
+    }
+}
+
+/// Moves the extracted instances of hosts with [`InterpolateInstances`] from their previous to
+/// their current state by the [`InstanceOverstep`].
+pub fn interpolate_instances<T: Instance>(
+    mut query: Query<(&mut InstanceMaterialData<T>, &PreviousInstances<T>)>,
+    overstep: Res<InstanceOverstep>,
+    mut warned: Local<bool>,
+) {
+    let Some(bounds) = T::BOUNDS else {
+        if !query.is_empty() && !*warned {
+            warn!("InterpolateInstances requires an instance type with BOUNDS, drawing the current instances");
+            *warned = true;
+        }
+        return;
+    };
+
+    let t = overstep.0;
+    let position = bounds.position_offset as usize;
+    let scale = bounds.scale_offset as usize;
+    let scale_len = 4 * bounds.scale_components as usize;
+
+    for (mut instances, previous) in &mut query {
+        for (current, previous) in instances.iter_mut().zip(previous.iter()) {
+            let from = bytemuck::bytes_of(previous);
+            let to = bytemuck::bytes_of_mut(current);
+
+            let lerp = |to: &mut [u8], range: std::ops::Range<usize>| {
+                for offset in range.step_by(4) {
+                    let a: f32 = bytemuck::pod_read_unaligned(&from[offset..offset + 4]);
+                    let b: f32 = bytemuck::pod_read_unaligned(&to[offset..offset + 4]);
+                    to[offset..offset + 4].copy_from_slice(&(a + (b - a) * t).to_ne_bytes());
+                }
+            };
+            lerp(to, position..position + 12);
+            lerp(to, scale..scale + scale_len);
+
+            if let Some(rotation) = T::ROTATION_OFFSET {
+                let rotation = rotation as usize..rotation as usize + 4;
+                let a: f32 = bytemuck::pod_read_unaligned(&from[rotation.clone()]);
+                let b: f32 = bytemuck::pod_read_unaligned(&to[rotation.clone()]);
+                // the shorter way around, angles that wrap at a full turn don't spin back
+                let delta = (b - a + PI).rem_euclid(TAU) - PI;
+                to[rotation].copy_from_slice(&(a + delta * t).to_ne_bytes());
+            }
+        }
+    }
+}
@@ -0,0 +1,3591 @@
+//! Draws a mesh many times in one draw call, once per instance of [`InstanceMaterialData`].
+//!
+//! Add an [`InstancingPlugin`] (2D) or [`Instancing3dPlugin`] (3D) for each instance type and
+//! spawn host entities with an [`InstancedMeshBundle`].
+//!
+//! On downlevel backends like WebGL2, which bevy uses on the web, the instances are drawn from
+//! vertex buffers with direct draws, as there are no storage buffers, compute shaders or indirect
+//! draws. Everything else works the same, except for these, which fall back with a warning:
+//!
+//! - [`InstanceBufferMode::Storage`] draws from vertex buffers,
+//! - [`GpuCulling`](culling::GpuCulling) draws the instances unculled,
+//! - [`GpuSimulation`] draws the instances unsimulated,
+//! - [`InstanceMotionVectors`] write zero motion.
+
+// lets `#[derive(InstanceLayout)]` refer to this crate as `::instancing` from within
+extern crate self as instancing;
+
+use atlas::{
+    InstanceAtlas, InstanceAtlasLayout, InstanceAtlasPlugin, InstanceSdf, InstanceTextureArray,
+    InstanceTextureArrayLayout, SetInstanceAtlasBindGroup,
+};
+use bevy::{
+    asset::load_internal_asset,
+    core::FrameCount,
+    core_pipeline::core_2d::Transparent2d,
+    ecs::{
+        component::Tick,
+        query::QueryItem,
+        system::{lifetimeless::*, SystemParam, SystemParamItem},
+    },
+    math::{Affine3, Affine3A},
+    pbr::{MeshPipeline, MeshUniform, RenderMeshInstances},
+    prelude::*,
+    render::{
+        batching::NoAutomaticBatching,
+        camera::ExtractedCamera,
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        mesh::{GpuBufferInfo, GpuMesh, MeshVertexAttribute, MeshVertexBufferLayout},
+        render_asset::RenderAssets,
+        render_phase::{
+            AddRenderCommand, CachedRenderPipelinePhaseItem, DrawFunctionId, DrawFunctions,
+            PhaseItem, RenderCommand, RenderCommandResult, RenderPhase, SetItemPipeline,
+            TrackedRenderPass,
+        },
+        render_resource::*,
+        renderer::{RenderDevice, RenderQueue},
+        settings::WgpuFeatures,
+        view::{ExtractedView, VisibilitySystems, VisibleEntities},
+        Render, RenderApp, RenderSet,
+    },
+    sprite::{
+        Mesh2dHandle, Mesh2dPipeline, Mesh2dPipelineKey, Mesh2dUniform, RenderMesh2dInstances,
+        SetMesh2dViewBindGroup,
+    },
+    utils::{hashbrown::hash_map::Entry, FloatOrd, HashMap, HashSet},
+};
+use bounds::{update_batch_aabb, BatchCullingPlugin, InstanceFrustumCulling};
+use bytemuck::{Pod, Zeroable};
+use culling::{CulledInstanceBuffers, CullingRadius, GpuCullingPlugin};
+use data_texture::{
+    InstanceDataTexture, InstanceDataTextureBindGroup, InstanceDataTextureLayout,
+    InstanceDataTexturePlugin, SetInstanceDataTextureBindGroup,
+};
+use diagnostics::{record_drawn_instances, DrawnInstanceCountPlugin, InstanceBufferStats};
+pub use instancing_3d::{CustomPipeline3d, Instancing3dPlugin};
+pub use instancing_derive::InstanceLayout;
+use interpolation::{
+    interpolate_instances, store_previous_instances, InterpolateInstances, InterpolationPlugin,
+    PreviousInstances,
+};
+use lod::{batch_lod_instances, InstanceLodBatches, InstanceLods, LodPlugin};
+pub use material::{DefaultInstancedMaterial, InstancedMaterial};
+use material::{InstancedMaterialLayout, InstancedMaterialPlugin, SetInstancedMaterialBindGroup};
+use merging::{merge_instances, MergeInstances, MergedHosts};
+use motion_vectors::{
+    prepare_previous_instances, InstanceMotionVectors, MotionVectorsPlugin, PreviousFrameInstances,
+    PreviousInstancesLayout, SetPreviousInstancesBindGroup,
+};
+use pool::{InstanceBufferPool, InstanceBufferPoolPlugin, RemovedHostsPlugin};
+use simulation::{GpuSimulation, GpuSimulationPlugin, GpuSimulationStates};
+use std::{
+    marker::PhantomData,
+    ops::{Deref, DerefMut, Range},
+};
+use targets::{
+    draws_instance_targets, InstanceTarget, InstanceTarget2d, InstanceTargets,
+    InstanceTargetsPlugin, MAX_INSTANCE_TARGETS,
+};
+use writer::InstanceGenerator;
+
+pub mod atlas;
+pub mod batch;
+pub mod bounds;
+pub mod culling;
+pub mod data_texture;
+pub mod diagnostics;
+pub mod entity_instances;
+#[cfg(feature = "cpu_fallback")]
+pub mod fallback;
+pub mod gizmos;
+pub mod gpu_picking;
+mod instancing_3d;
+pub mod interpolation;
+pub mod lod;
+pub mod material;
+pub mod merging;
+pub mod motion_vectors;
+pub mod opacity;
+pub mod picking;
+pub mod plain;
+pub mod pool;
+pub mod simulation;
+pub mod targets;
+pub mod writer;
+
+/// Per-instance data that is uploaded to the GPU as a vertex buffer stepped once per instance.
+///
+/// The attributes returned by [`Instance::attributes`] have to match the instance inputs declared
+/// by the vertex shader. Their shader locations are only keys: the pipeline moves the instance
+/// attributes, in the order of their locations, to the locations the mesh leaves free, which
+/// depend on the attributes of the mesh. The shader has to declare the attribute returned at
+/// location `n` at the location in the `INSTANCE_LOCATION_n` shader def, and its mesh inputs
+/// behind bevy's `VERTEX_NORMALS`, `VERTEX_UVS`, ... shader defs. Prefer
+/// `#[derive(InstanceLayout)]` over implementing this by hand.
+pub trait Instance: Pod + Zeroable + Send + Sync {
+    /// Distance in bytes between two consecutive instances in the instance buffer.
+    const ARRAY_STRIDE: u64 = std::mem::size_of::<Self>() as u64;
+
+    /// Where to find the position and scale of an instance, required for
+    /// [`GpuCulling`](culling::GpuCulling).
+    const BOUNDS: Option<InstanceBounds> = None;
+
+    /// Byte offset of the `f32` rotation around the Z axis in radians, used by
+    /// [`picking`] in addition to [`Instance::BOUNDS`].
+    const ROTATION_OFFSET: Option<u32> = None;
+
+    /// Byte offset of the `[f32; 2]` point of the mesh placed at the position, see
+    /// [`InstanceData::anchor`], used by [`picking`] in addition to [`Instance::BOUNDS`].
+    const ANCHOR_OFFSET: Option<u32> = None;
+
+    /// Byte offset of the `f32` sort key, used by [`SortInstances::Key`].
+    const SORT_KEY_OFFSET: Option<u32> = None;
+
+    /// Byte offset of the RGBA `[f32; 4]` color, whose alpha splits the instances of hosts with
+    /// [`MixedOpacity`](opacity::MixedOpacity).
+    const COLOR_OFFSET: Option<u32> = None;
+
+    /// Byte offset of the `[f32; 4]` passed on to the fragment shader as is, see
+    /// [`CustomInstanceData`].
+    const CUSTOM_OFFSET: Option<u32> = None;
+
+    /// Byte offset of the `[f32; 3]` amplitude, frequency and phase the built-in 2D shader pulses
+    /// the scale with, see [`PulsingInstanceData`].
+    const PULSE_OFFSET: Option<u32> = None;
+
+    /// Byte offset of the `f32` depth the built-in 3D shader writes instead of the depth of the
+    /// position, see [`DepthInstanceData`].
+    const DRAW_Z_OFFSET: Option<u32> = None;
+
+    fn attributes() -> Vec<VertexAttribute>;
+
+    /// The shader drawing the instances of 2D meshes, [`ShaderRef::Default`] uses the built-in
+    /// [`INSTANCING_SHADER_HANDLE`].
+    fn shader() -> ShaderRef {
+        ShaderRef::Default
+    }
+
+    /// The shader drawing the instances of 3D meshes, [`ShaderRef::Default`] uses the built-in
+    /// [`INSTANCING_3D_SHADER_HANDLE`].
+    fn shader_3d() -> ShaderRef {
+        ShaderRef::Default
+    }
+}
+
+/// Fails if the `attributes` of the instance type `T` don't fit its memory layout, at compile time
+/// when called in a `const` context. The [`Instance::ARRAY_STRIDE`] has to be the size of `T`, as
+/// the instances are uploaded as they lie in memory, and the attributes have to lie within it,
+/// without overlapping or sharing a shader location.
+///
+/// `#[derive(InstanceLayout)]` checks the types it implements [`Instance`] for, check hand written
+/// implementations with `const _: () = check_instance_layout::<MyInstance>(&MY_ATTRIBUTES);`.
+pub const fn check_instance_layout<T: Instance>(attributes: &[VertexAttribute]) {
+    assert!(
+        T::ARRAY_STRIDE == std::mem::size_of::<T>() as u64,
+        "the ARRAY_STRIDE of the instance type isn't its size"
+    );
+
+    // no iterators in const fns
+    let mut index = 0;
+    while index < attributes.len() {
+        let attribute = &attributes[index];
+        let end = attribute.offset + attribute.format.size();
+        assert!(
+            end <= T::ARRAY_STRIDE,
+            "an instance attribute reaches past the end of the instance"
+        );
+
+        let mut other_index = 0;
+        while other_index < index {
+            let other = &attributes[other_index];
+            assert!(
+                attribute.shader_location != other.shader_location,
+                "two instance attributes share a shader location"
+            );
+            assert!(
+                end <= other.offset || other.offset + other.format.size() <= attribute.offset,
+                "two instance attributes overlap"
+            );
+            other_index += 1;
+        }
+        index += 1;
+    }
+}
+
+/// The built-in shader drawing [`InstanceData`] on 2D meshes.
+pub const INSTANCING_SHADER_HANDLE: Handle<Shader> =
+    Handle::weak_from_u128(0xc84f_2e6d_b9b3_4975_a4da_4b56_e845_3cfb);
+
+/// The built-in shader drawing [`InstanceData`] on 3D meshes.
+pub const INSTANCING_3D_SHADER_HANDLE: Handle<Shader> =
+    Handle::weak_from_u128(0xdeaa_0558_1670_41c0_a02c_dce6_2787_2910);
+
+/// Resolves `shader` to a handle, loading it from the asset server if it is a path.
+fn load_shader(world: &World, shader: ShaderRef, default: Handle<Shader>) -> Handle<Shader> {
+    match shader {
+        ShaderRef::Default => default,
+        ShaderRef::Handle(handle) => handle,
+        ShaderRef::Path(path) => world.resource::<AssetServer>().load(path),
+    }
+}
+
+/// The instance type drawn by the built-in shaders.
+#[derive(Clone, Copy, Debug, Pod, Zeroable, InstanceLayout)]
+#[repr(C)]
+pub struct InstanceData {
+    /// In the local space of the host, the host's `GlobalTransform` moves, rotates and scales all
+    /// instances as one.
+    ///
+    /// The 2D pass of bevy 0.13 has no depth buffer, so 2D instances aren't depth tested and the
+    /// z only decides whether they are clipped, and their order with [`SortInstances::Z`]. Sort
+    /// by [`sort_key`](Self::sort_key) to layer them independently of the z. The z has to stay
+    /// between the near and far planes of the camera: a default `Camera2dBundle` sits at z 999.9
+    /// looking down -Z with its near plane at 0 and its far plane at 1000, so world z from -0.1 to
+    /// 999.9 is drawn. Bevy's projections map the far plane to NDC z 0 and the near plane to 1.
+    /// 3D instances are depth tested on their position like any mesh, or on their own depth with
+    /// [`DepthInstanceData`].
+    #[instance(position)]
+    pub position: Vec3,
+    #[instance(scale)]
+    pub scale: f32,
+    /// Linear RGBA, or sRGB with [`InstanceColorSpace::Srgb`]. Tints the texel of the host's
+    /// [`InstanceAtlas`] or [`InstanceTextureArray`] like the color of a sprite, multiplying it
+    /// and its alpha. Straight alpha like the texture, or premultiplied like the texture with
+    /// [`InstanceBlendMode::Premultiplied`].
+    #[instance(color)]
+    pub color: [f32; 4],
+    /// Rotation around the Z axis in radians.
+    #[instance(rotation)]
+    pub rotation: f32,
+    /// Cell of the host's [`InstanceAtlas`], or layer of its [`InstanceTextureArray`], ignored
+    /// without either. The two highest bits are the [`FLIP_X`](Self::FLIP_X) and
+    /// [`FLIP_Y`](Self::FLIP_Y) flags, set them with [`set_flip_x`](Self::set_flip_x) and
+    /// [`set_flip_y`](Self::set_flip_y).
+    pub atlas_index: u32,
+    /// Offset and scale applied to the mesh UVs before the atlas cell is selected, to step through
+    /// flipbook frames.
+    pub uv_offset: Vec2,
+    pub uv_scale: Vec2,
+    /// Linear RGB added to the color, exceeding 1 makes the instance glow on HDR cameras with
+    /// bloom.
+    pub emissive: [f32; 3],
+    /// Draw order within the batch with [`SortInstances::Key`], independent of the position, like
+    /// the row of a tile on an isometric map. Only read on the CPU, so it takes no vertex
+    /// attribute.
+    #[instance(sort_key, skip)]
+    pub sort_key: f32,
+    /// Color of the border drawn inside the edges of the mesh's UV rectangle, in the color space
+    /// of [`InstanceData::color`].
+    pub border_color: [f32; 4],
+    /// Thickness of the border in the units of the position, 0 for none. A border wider than half
+    /// the instance fills it. Only drawn by the 2D pipeline.
+    pub border_width: f32,
+    /// Radius of the rounded corners of the mesh's UV rectangle in the units of the position, 0
+    /// for square corners. Limited to half the shorter side, which rounds it into a pill. Only
+    /// drawn by the 2D pipeline, with anti-aliased edges unless the host is opaque.
+    pub corner_radius: f32,
+    /// The point of the mesh placed at the position, which the instance is scaled and rotated
+    /// around, in the units of the unscaled mesh. Zero is the center, `(-0.5, -0.5)` the bottom
+    /// left corner of a unit quad, so a health bar anchored there grows to the right. Only drawn by
+    /// the 2D pipeline.
+    ///
+    /// [`picking`] takes it into account, but the bounds of the instance used by frustum culling and
+    /// [`GpuCulling`](culling::GpuCulling) stay centered on the position, give hosts with far off
+    /// anchors [`InstanceFrustumCulling(false)`](InstanceFrustumCulling) or a larger
+    /// [`CullingRadius`](culling::CullingRadius).
+    #[instance(anchor)]
+    pub anchor: Vec2,
+}
+
+impl Default for InstanceData {
+    fn default() -> Self {
+        InstanceData {
+            position: Vec3::ZERO,
+            scale: 1.0,
+            color: [1.0; 4],
+            rotation: 0.0,
+            atlas_index: 0,
+            uv_offset: Vec2::ZERO,
+            uv_scale: Vec2::ONE,
+            emissive: [0.0; 3],
+            sort_key: 0.0,
+            border_color: [0.0, 0.0, 0.0, 1.0],
+            border_width: 0.0,
+            corner_radius: 0.0,
+            anchor: Vec2::ZERO,
+        }
+    }
+}
+
+/// Implements the flip flags packed into the `atlas_index` of an instance type.
+macro_rules! impl_flip {
+    ($($instance:ty),*) => {$(
+        impl $instance {
+            /// Bit of the `atlas_index` that mirrors the UVs horizontally, within the atlas cell and
+            /// the `uv_offset`/`uv_scale` frame. Only drawn by the 2D pipeline.
+            pub const FLIP_X: u32 = 1 << 31;
+            /// Bit of the `atlas_index` that mirrors the UVs vertically, see [`Self::FLIP_X`].
+            pub const FLIP_Y: u32 = 1 << 30;
+
+            /// Mirrors the UVs of the instance horizontally, e.g. to turn a sprite around.
+            pub fn set_flip_x(&mut self, flip: bool) {
+                self.set_flag(Self::FLIP_X, flip);
+            }
+
+            /// Mirrors the UVs of the instance vertically.
+            pub fn set_flip_y(&mut self, flip: bool) {
+                self.set_flag(Self::FLIP_Y, flip);
+            }
+
+            /// Whether [`Self::FLIP_X`] is set.
+            pub fn flip_x(&self) -> bool {
+                self.atlas_index & Self::FLIP_X != 0
+            }
+
+            /// Whether [`Self::FLIP_Y`] is set.
+            pub fn flip_y(&self) -> bool {
+                self.atlas_index & Self::FLIP_Y != 0
+            }
+
+            fn set_flag(&mut self, flag: u32, set: bool) {
+                if set {
+                    self.atlas_index |= flag;
+                } else {
+                    self.atlas_index &= !flag;
+                }
+            }
+        }
+    )*};
+}
+
+impl_flip!(
+    InstanceData,
+    StretchedInstanceData,
+    CustomInstanceData,
+    PulsingInstanceData,
+    DepthInstanceData
+);
+
+impl InstanceData {
+    /// The instance at the translation of `transform`, rotated by its rotation around the Z axis
+    /// and scaled by its x scale, like the instance of an
+    /// [`InstanceChild`](entity_instances::InstanceChild).
+    pub fn from_transform(transform: &Transform) -> Self {
+        InstanceData {
+            position: transform.translation,
+            rotation: transform.rotation.to_euler(EulerRot::ZYX).0,
+            scale: transform.scale.x,
+            ..default()
+        }
+    }
+}
+
+/// Declares an instance type with the fields of [`InstanceData`], a scale of the given type and
+/// the given fields after them, defaulting to the values of [`InstanceData::default`].
+macro_rules! instance_variant {
+    (
+        $(#[$attr:meta])*
+        $name:ident {
+            scale: $scale:ty = $scale_default:expr,
+            $($(#[$field_attr:meta])* $field:ident: $field_ty:ty = $field_default:expr,)*
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, Debug, Pod, Zeroable, InstanceLayout)]
+        #[repr(C)]
+        pub struct $name {
+            /// See [`InstanceData::position`].
+            #[instance(position)]
+            pub position: Vec3,
+            #[instance(scale)]
+            pub scale: $scale,
+            /// See [`InstanceData::color`].
+            #[instance(color)]
+            pub color: [f32; 4],
+            /// See [`InstanceData::rotation`].
+            #[instance(rotation)]
+            pub rotation: f32,
+            /// See [`InstanceData::atlas_index`].
+            pub atlas_index: u32,
+            /// See [`InstanceData::uv_offset`].
+            pub uv_offset: Vec2,
+            pub uv_scale: Vec2,
+            /// See [`InstanceData::emissive`].
+            pub emissive: [f32; 3],
+            /// See [`InstanceData::sort_key`].
+            #[instance(sort_key, skip)]
+            pub sort_key: f32,
+            /// See [`InstanceData::border_color`].
+            pub border_color: [f32; 4],
+            /// See [`InstanceData::border_width`].
+            pub border_width: f32,
+            /// See [`InstanceData::corner_radius`].
+            pub corner_radius: f32,
+            /// See [`InstanceData::anchor`].
+            #[instance(anchor)]
+            pub anchor: Vec2,
+            $($(#[$field_attr])* pub $field: $field_ty,)*
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                let InstanceData {
+                    position,
+                    scale: _,
+                    color,
+                    rotation,
+                    atlas_index,
+                    uv_offset,
+                    uv_scale,
+                    emissive,
+                    sort_key,
+                    border_color,
+                    border_width,
+                    corner_radius,
+                    anchor,
+                } = InstanceData::default();
+                $name {
+                    position,
+                    scale: $scale_default,
+                    color,
+                    rotation,
+                    atlas_index,
+                    uv_offset,
+                    uv_scale,
+                    emissive,
+                    sort_key,
+                    border_color,
+                    border_width,
+                    corner_radius,
+                    anchor,
+                    $($field: $field_default,)*
+                }
+            }
+        }
+    };
+}
+
+instance_variant! {
+    /// [`InstanceData`] with a separate scale for the x and y axes, to stretch meshes into
+    /// rectangles. The rotation is applied after the scale.
+    ///
+    /// Drawn by the built-in shaders with the `INSTANCE_SCALE_2D` shader def, which is set for
+    /// every instance type whose `#[instance(scale)]` is a `Vec2`.
+    StretchedInstanceData {
+        scale: Vec2 = Vec2::ONE,
+    }
+}
+
+instance_variant! {
+    /// [`InstanceData`] with four floats the built-in shaders pass on to the fragment shader as
+    /// they are, to try out shader logic without declaring an instance type and a shader of its
+    /// own.
+    ///
+    /// The values mean whatever the fragment shader of an [`InstancedMaterial`] makes of them, the
+    /// built-in one ignores them. It reads them as `@location(12) custom: vec4<f32>` of the 2D
+    /// vertex output, set by the `INSTANCE_CUSTOM` shader def for every instance type with an
+    /// `#[instance(custom)]` field.
+    ///
+    /// Without storage buffers, this and every other variant with a field of its own take 13 of
+    /// the 16 vertex attribute locations, so the mesh may only have 3 attributes, meshes with
+    /// vertex colors need [`InstanceBufferMode::Storage`].
+    CustomInstanceData {
+        scale: f32 = 1.0,
+        /// User defined, see the [type docs](CustomInstanceData).
+        #[instance(custom)]
+        custom: [f32; 4] = [0.0; 4],
+    }
+}
+
+instance_variant! {
+    /// [`InstanceData`] whose scale pulses on the GPU, for juice like pulsing pickups or
+    /// heartbeats without writing the instances every frame.
+    ///
+    /// The built-in 2D shader multiplies the scale by
+    /// `1 + amplitude * sin(time * frequency + phase)`, with the time from bevy's `globals`
+    /// uniform, which is the elapsed seconds of [`Time`] wrapped after an hour. It does so behind
+    /// the `INSTANCE_PULSE` shader def, set for every instance type with an `#[instance(pulse)]`
+    /// field, other instance types don't pay for it. The bounds used by frustum culling,
+    /// [`GpuCulling`](culling::GpuCulling) and [`picking`] don't pulse, give hosts with large
+    /// amplitudes a larger [`CullingRadius`](culling::CullingRadius).
+    PulsingInstanceData {
+        scale: f32 = 1.0,
+        /// The amplitude, the frequency in radians per second and the phase in radians of the
+        /// pulse, zero amplitude for none. Only drawn by the 2D pipeline.
+        #[instance(pulse)]
+        pulse: [f32; 3] = [0.0; 3],
+    }
+}
+
+instance_variant! {
+    /// [`InstanceData`] with a depth of its own, for layering 3D instances by depth testing
+    /// independently of their position, like the tiles of an isometric map whose height differs
+    /// from the order they overlap in.
+    ///
+    /// The built-in 3D shader writes `draw_z` as the NDC depth of every vertex of the instance, in
+    /// place of the depth of its position. It does so behind the `INSTANCE_DRAW_Z` shader def, set
+    /// for every instance type with an `#[instance(draw_z)]` field. Bevy's 3D projections are
+    /// reversed, the near plane is at 1 and the far plane, or infinity for perspective
+    /// projections, at 0, and the depth test keeps the greater depth: instances with a higher
+    /// `draw_z` are drawn over the ones with a lower one, and depths outside of 0 to 1 are
+    /// clipped. The order of the hosts in their phase and of the instances with
+    /// [`SortInstances`] stays as it is. Only the opaque instances of hosts with
+    /// [`MixedOpacity`](opacity::MixedOpacity) and hosts with an [`InstanceAlphaMask`] write the
+    /// depth, blended instances are only tested against it. The 2D pass of bevy 0.13 has no depth
+    /// buffer, the 2D pipeline ignores it.
+    DepthInstanceData {
+        scale: f32 = 1.0,
+        /// The NDC depth of the instance, from 0 at the far plane to 1 at the near plane. Only
+        /// drawn by the 3D pipeline.
+        #[instance(draw_z)]
+        draw_z: f32 = 0.5,
+    }
+}
+
+/// Registers the built-in shaders, so they don't have to be copied into the assets of the app.
+struct InstancingShadersPlugin;
+
+impl Plugin for InstancingShadersPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            INSTANCING_SHADER_HANDLE,
+            "shaders/instancing.wgsl",
+            Shader::from_wgsl
+        );
+        load_internal_asset!(
+            app,
+            INSTANCING_3D_SHADER_HANDLE,
+            "shaders/instancing_3d.wgsl",
+            Shader::from_wgsl
+        );
+    }
+}
+
+/// Byte offsets of the `Vec3` position and the scale within an instance.
+#[derive(Clone, Copy, Debug)]
+pub struct InstanceBounds {
+    pub position_offset: u32,
+    pub scale_offset: u32,
+    /// 1 for an `f32` uniform scale, 2 for a `Vec2` scale of the x and y axes.
+    pub scale_components: u32,
+}
+
+impl InstanceBounds {
+    /// Reads the position and scale of `instance`, the largest absolute axis of a `Vec2` scale.
+    pub fn read<T: Instance>(&self, instance: &T) -> (Vec3, f32) {
+        let bytes = bytemuck::bytes_of(instance);
+        let position = self.position_offset as usize;
+        let scale = self.read_scale_2d(instance);
+        let scale = match self.scale_components {
+            1 => scale.x,
+            _ => scale.abs().max_element(),
+        };
+
+        (
+            bytemuck::pod_read_unaligned(&bytes[position..position + 12]),
+            scale,
+        )
+    }
+
+    /// Reads the scale of the x and y axes of `instance`, a uniform scale is used for both.
+    pub fn read_scale_2d<T: Instance>(&self, instance: &T) -> Vec2 {
+        let bytes = bytemuck::bytes_of(instance);
+        let scale = self.scale_offset as usize;
+
+        match self.scale_components {
+            1 => Vec2::splat(bytemuck::pod_read_unaligned(&bytes[scale..scale + 4])),
+            _ => bytemuck::pod_read_unaligned(&bytes[scale..scale + 8]),
+        }
+    }
+}
+
+/// The instances drawn for the mesh of this entity in a single draw call.
+///
+/// Derefs to the `Vec` of instances. Editing instances with [`push`](Self::push),
+/// [`remove`](Self::remove), [`set`](Self::set) or [`get_mut`](Self::get_mut) tracks the range of
+/// indices they touched, and only that range of the instance buffer is written again. Any other
+/// mutable access marks all instances as changed.
+///
+/// Hosts that are hidden, by their [`Visibility`] or a parent's, or culled in every view are not
+/// extracted, so their instances are neither copied to the render world nor uploaded.
+#[derive(Component)]
+pub struct InstanceMaterialData<T: Instance> {
+    instances: Vec<T>,
+    /// Indices changed since the instances were last extracted, may reach past the end.
+    dirty: Range<usize>,
+}
+
+/// Marks every instance as changed.
+const ALL_DIRTY: Range<usize> = 0..usize::MAX;
+
+impl<T: Instance> InstanceMaterialData<T> {
+    pub fn new(instances: Vec<T>) -> Self {
+        InstanceMaterialData {
+            instances,
+            dirty: ALL_DIRTY,
+        }
+    }
+
+    /// Appends `instance`.
+    pub fn push(&mut self, instance: T) {
+        self.mark_dirty(self.instances.len()..self.instances.len() + 1);
+        self.instances.push(instance);
+    }
+
+    /// Appends `instances` and writes only them to the instance buffer, behind the instances it
+    /// already holds.
+    ///
+    /// Meant for logs that only ever grow until they are [`reset`](Self::reset), like motion
+    /// trails, which would otherwise upload their whole history every frame. The buffer grows
+    /// geometrically, each growth writes all instances into the new buffer once.
+    pub fn append_and_upload(&mut self, instances: &[T]) {
+        let len = self.instances.len();
+        self.mark_dirty(len..len + instances.len());
+        self.instances.extend_from_slice(instances);
+    }
+
+    /// Removes all instances, so [`append_and_upload`](Self::append_and_upload) starts over at the
+    /// front of the instance buffer.
+    pub fn reset(&mut self) {
+        self.instances.clear();
+        self.dirty = ALL_DIRTY;
+    }
+
+    /// Removes and returns the instance at `index`, shifting the following ones down.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> T {
+        self.mark_dirty(index..self.instances.len());
+        self.instances.remove(index)
+    }
+
+    /// Replaces the instance at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn set(&mut self, index: usize, instance: T) {
+        self.instances[index] = instance;
+        self.mark_dirty(index..index + 1);
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index < self.instances.len() {
+            self.mark_dirty(index..index + 1);
+        }
+        self.instances.get_mut(index)
+    }
+
+    /// Grows the changed range to include `range`, scattered edits are merged into the range
+    /// enclosing them.
+    fn mark_dirty(&mut self, range: Range<usize>) {
+        if self.dirty.is_empty() {
+            self.dirty = range;
+        } else {
+            self.dirty = self.dirty.start.min(range.start)..self.dirty.end.max(range.end);
+        }
+    }
+
+    /// Indices changed since the instances were last extracted, clamped to the instances.
+    pub fn dirty_range(&self) -> Range<usize> {
+        let end = self.dirty.end.min(self.instances.len());
+        self.dirty.start.min(end)..end
+    }
+}
+
+impl InstanceMaterialData<InstanceData> {
+    /// Builds one instance per transform, see [`InstanceData::from_transform`], in the given
+    /// color, e.g. `InstanceMaterialData::from_transforms(query.iter().map(|(t, c)| (t, c.0)))`.
+    pub fn from_transforms<'a>(
+        transforms: impl IntoIterator<Item = (&'a Transform, Color)>,
+    ) -> Self {
+        transforms
+            .into_iter()
+            .map(|(transform, color)| InstanceData {
+                color: color.as_linear_rgba_f32(),
+                ..InstanceData::from_transform(transform)
+            })
+            .collect()
+    }
+}
+
+impl<T: Instance> Deref for InstanceMaterialData<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Vec<T> {
+        &self.instances
+    }
+}
+
+impl<T: Instance> DerefMut for InstanceMaterialData<T> {
+    fn deref_mut(&mut self) -> &mut Vec<T> {
+        // the edits can't be tracked
+        self.dirty = ALL_DIRTY;
+        &mut self.instances
+    }
+}
+
+impl<T: Instance> Default for InstanceMaterialData<T> {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl<T: Instance> From<Vec<T>> for InstanceMaterialData<T> {
+    fn from(instances: Vec<T>) -> Self {
+        Self::new(instances)
+    }
+}
+
+impl<T: Instance> FromIterator<T> for InstanceMaterialData<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::new(iter.into_iter().collect())
+    }
+}
+
+impl<T: Instance> Clone for InstanceMaterialData<T> {
+    fn clone(&self) -> Self {
+        InstanceMaterialData {
+            instances: self.instances.clone(),
+            dirty: self.dirty.clone(),
+        }
+    }
+}
+
+/// Clears the changed ranges of the instances extracted in the last frame, without triggering
+/// change detection.
+fn clear_dirty_instances<T: Instance>(mut query: Query<&mut InstanceMaterialData<T>>) {
+    for mut instances in &mut query {
+        if !instances.dirty.is_empty() {
+            instances.bypass_change_detection().dirty = 0..0;
+        }
+    }
+}
+
+impl<T: Instance> ExtractComponent for InstanceMaterialData<T> {
+    type QueryData = (
+        Ref<'static, InstanceMaterialData<T>>,
+        Option<Ref<'static, InstanceVisibility>>,
+    );
+    type QueryFilter = ();
+    type Out = (Self, InstanceChangeTicks);
+
+    fn extract_component(
+        (instances, visibility): QueryItem<'_, Self::QueryData>,
+    ) -> Option<Self::Out> {
+        let ticks = InstanceChangeTicks {
+            instances: instances.last_changed(),
+            visibility: visibility
+                .as_ref()
+                .map(|visibility| visibility.last_changed()),
+        };
+
+        let Some(visibility) = visibility else {
+            return Some((instances.clone(), ticks));
+        };
+
+        // compact the visible instances to the front, keeping their order
+        let visible = instances
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| visibility.get(*index).copied().unwrap_or(true))
+            .map(|(_, instance)| *instance)
+            .collect();
+        // the indices of the compacted instances don't match, so all of them are changed
+        Some((Self::new(visible), ticks))
+    }
+}
+
+/// When the extracted [`InstanceMaterialData`] and [`InstanceVisibility`] of a host last changed
+/// in the main world. The instance buffer is only written again once these differ from the ticks
+/// of the instances it holds.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct InstanceChangeTicks {
+    instances: Tick,
+    visibility: Option<Tick>,
+}
+
+/// Everything a host entity needs to draw its instances of `T` with the mesh `M`, which is a
+/// [`Mesh2dHandle`] for 2D and a `Handle<Mesh>` for 3D meshes.
+///
+/// Batches of instance types with [`Instance::BOUNDS`] are frustum culled as a whole, see
+/// [`update_batch_aabb`]. Other instance types can't be bounded, so their batches are never
+/// culled unless [`with_frustum_culling`](Self::with_frustum_culling) says otherwise.
+///
+/// Meshes with joints or morph targets aren't supported, hosts with them log an error and draw
+/// nothing.
+#[derive(Bundle)]
+pub struct InstancedMeshBundle<
+    T: Instance,
+    M: HostMesh = Mesh2dHandle,
+    Mat: InstancedMaterial = DefaultInstancedMaterial,
+> {
+    pub mesh: M,
+    pub instances: InstanceMaterialData<T>,
+    pub material: Mat,
+    pub spatial: SpatialBundle,
+    pub frustum_culling: InstanceFrustumCulling,
+}
+
+impl<T: Instance, M: HostMesh> InstancedMeshBundle<T, M> {
+    pub fn new(mesh: impl Into<M>, instances: impl IntoIterator<Item = T>) -> Self {
+        Self {
+            mesh: mesh.into(),
+            instances: instances.into_iter().collect(),
+            material: DefaultInstancedMaterial,
+            spatial: SpatialBundle::INHERITED_IDENTITY,
+            frustum_culling: InstanceFrustumCulling(T::BOUNDS.is_some()),
+        }
+    }
+}
+
+impl<T: Instance, M: HostMesh, Mat: InstancedMaterial> InstancedMeshBundle<T, M, Mat> {
+    /// Draws the instances with `material` instead, which requires a
+    /// [`InstancingPlugin`] for it.
+    pub fn with_material<N: InstancedMaterial>(self, material: N) -> InstancedMeshBundle<T, M, N> {
+        InstancedMeshBundle {
+            mesh: self.mesh,
+            instances: self.instances,
+            material,
+            spatial: self.spatial,
+            frustum_culling: self.frustum_culling,
+        }
+    }
+
+    pub fn with_frustum_culling(mut self, enabled: bool) -> Self {
+        self.frustum_culling = InstanceFrustumCulling(enabled);
+        self
+    }
+
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.spatial.transform = transform;
+        self
+    }
+}
+
+/// Per instance visibility of the [`InstanceMaterialData`] of this host entity, indexed like the
+/// instances. Instances without an entry are visible.
+///
+/// Hidden instances are skipped when the instances are extracted, so toggling instances only
+/// touches this component. The visible instances keep their order.
+#[derive(Component, Clone, Default, Deref, DerefMut)]
+pub struct InstanceVisibility(pub Vec<bool>);
+
+/// Groups of instances of this host entity that each draw their own range of the host's mesh, to
+/// select a sub-shape of one mesh per group. Instances outside of every group are not drawn.
+///
+/// All instances of a group share its range. The instance ranges index the drawn instances, so
+/// after instances hidden by [`InstanceVisibility`] were removed. Hosts with groups are drawn
+/// without their [`InstanceLods`] and GPU culling, and [`SortInstances`] moves instances between
+/// groups.
+#[derive(Component, Clone, Default, Debug, ExtractComponent)]
+pub struct InstanceMeshRanges(pub Vec<InstanceMeshRange>);
+
+#[derive(Clone, Debug)]
+pub struct InstanceMeshRange {
+    pub instances: Range<u32>,
+    /// Indices of the mesh drawn for each instance, or vertices of meshes without indices.
+    pub elements: Range<u32>,
+}
+
+/// Draws the instances of this host entity without alpha blending.
+///
+/// Bevy 0.13 has no opaque 2D phase and no depth buffer in the 2D pass, so the instances are
+/// still drawn in the [`Transparent2d`] phase in back to front order, only blending is skipped.
+#[derive(Component, Clone, Copy, Default, ExtractComponent)]
+pub struct OpaqueInstances;
+
+/// How the instances of this host entity are blended into the target. Hosts without this
+/// component use [`InstanceBlendMode::AlphaBlend`], [`OpaqueInstances`] takes precedence.
+///
+/// The mode is part of the pipeline key, so hosts with different modes get their own pipelines.
+#[derive(Component, Clone, Copy, Default, PartialEq, Eq, Hash, Debug, ExtractComponent)]
+pub enum InstanceBlendMode {
+    /// Standard alpha blending with straight alpha colors, like the `Mesh2dPipeline`.
+    #[default]
+    AlphaBlend,
+    /// For colors and textures with premultiplied alpha, avoids dark fringes around their edges.
+    /// The anti-aliased edges of rounded corners and signed distance fields fade the color along
+    /// with the alpha.
+    Premultiplied,
+    /// Adds the color weighted by its alpha to the target, for glows and particles.
+    Additive,
+    /// Multiplies the target by the color. Partially transparent colors need premultiplied
+    /// alpha.
+    Multiply,
+}
+
+impl InstanceBlendMode {
+    pub fn blend_state(self) -> BlendState {
+        match self {
+            InstanceBlendMode::AlphaBlend => BlendState::ALPHA_BLENDING,
+            InstanceBlendMode::Premultiplied => BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            InstanceBlendMode::Additive => BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::SrcAlpha,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent::OVER,
+            },
+            InstanceBlendMode::Multiply => BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::Dst,
+                    dst_factor: BlendFactor::OneMinusSrcAlpha,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent::OVER,
+            },
+        }
+    }
+}
+
+/// Draws the instances of this 2D host entity at a constant size on screen, like point sprites.
+///
+/// The scale of an instance is its size in pixels, regardless of the camera's zoom, and the mesh
+/// is expected to span 1 unit, like a `Rectangle::new(1.0, 1.0)` or a `Circle::new(0.5)`.
+/// Borders and rounded corners are in pixels as well. WebGPU can only draw points of a single
+/// pixel, so each point is a small instance of the host's mesh instead, behind the
+/// `INSTANCE_POINTS` shader def. Hosts whose mesh has the `PointList` topology draw these single
+/// pixel points without this component.
+///
+/// The batch is frustum culled with the scale in world units, so points at the edges of the view
+/// may disappear early on cameras zoomed out by more than the size of the points. Disable
+/// [`InstanceFrustumCulling`] for such hosts.
+#[derive(Component, Clone, Copy, Default, ExtractComponent)]
+pub struct PointInstances;
+
+/// Draws the instances of this 2D host entity after those of hosts with a lower layer at the same
+/// depth, the z for cameras looking down -Z, for batches that have to be drawn in a fixed order,
+/// like the background, fill and border of UI elements. Put each layer into its own child entity
+/// of a common parent.
+///
+/// Hosts without this component are on layer 0. Layers only take effect between hosts with the
+/// same sort key, see [`SortKeyStrategy`]. They move the sort key of a host by a negligible
+/// `layer` steps of the float precision, so keep them small.
+#[derive(Component, Clone, Copy, Default, PartialEq, Eq, Debug, ExtractComponent)]
+pub struct InstanceLayer(pub u32);
+
+/// What the 2D hosts of an [`InstancingPlugin`] are sorted by in the 2D phases, among each other
+/// and among sprites and 2D meshes, which are sorted by their z. An [`InstanceLayer`] still orders
+/// hosts with the same key.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum SortKeyStrategy {
+    /// By the depth of the host along the view, which is its z for cameras looking down -Z. Hosts
+    /// at the same depth are drawn in the order they are queued in, which can change between runs.
+    #[default]
+    MeshZ,
+    /// By the [`HostSortKey`] of the host, hosts without one are at 0.
+    Custom,
+    /// In the order the hosts were spawned in, by their [`HostSpawnOrder`]. The keys count up from
+    /// 0, so sprites and 2D meshes at a z above the number of hosts are drawn over all of them.
+    SpawnOrder,
+}
+
+/// The sort key of this 2D host entity with [`SortKeyStrategy::Custom`], drawn after hosts with a
+/// lower key.
+#[derive(Component, Clone, Copy, Default, PartialEq, Debug, ExtractComponent)]
+pub struct HostSortKey(pub f32);
+
+/// The number of this 2D host entity in the order the hosts were spawned in, inserted into every
+/// host of the instance type of an [`InstancingPlugin`] with [`SortKeyStrategy::SpawnOrder`], in
+/// the `PostUpdate` after it was spawned. Hosts spawned in the same frame are numbered in the order
+/// they are queried in, which follows their archetypes rather than their ids.
+#[derive(Component, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, ExtractComponent)]
+pub struct HostSpawnOrder(u64);
+
+impl HostSpawnOrder {
+    pub fn get(&self) -> u64 {
+        self.0
+    }
+}
+
+/// The next [`HostSpawnOrder`], shared by the plugins of all instance types.
+#[derive(Resource, Default)]
+struct HostSpawnCounter(u64);
+
+/// Numbers the hosts of `T`, added once per instance type by the [`InstancingPlugin`]s with
+/// [`SortKeyStrategy::SpawnOrder`].
+struct HostSpawnOrderPlugin<T: Instance>(PhantomData<T>);
+
+impl<T: Instance> Plugin for HostSpawnOrderPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PostUpdate, number_spawned_hosts::<T>);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn number_spawned_hosts<T: Instance>(
+    mut commands: Commands,
+    hosts: Query<
+        Entity,
+        (
+            Or<(With<InstanceMaterialData<T>>, With<InstanceGenerator<T>>)>,
+            Without<HostSpawnOrder>,
+        ),
+    >,
+    mut counter: ResMut<HostSpawnCounter>,
+) {
+    for host in &hosts {
+        commands.entity(host).insert(HostSpawnOrder(counter.0));
+        counter.0 += 1;
+    }
+}
+
+/// Multiplies the alpha of all instances of this host entity, to fade the whole batch in and out
+/// without writing the instances again.
+///
+/// Applied by the built-in 2D shader on top of the alpha of each instance, through a uniform of
+/// the host that is only written when this component changes. Hosts without it are fully opaque.
+/// [`OpaqueInstances`] and 3D hosts, which are drawn without blending, ignore it.
+#[derive(Component, Clone, Copy, PartialEq, Debug, ExtractComponent)]
+pub struct InstanceGroupAlpha(pub f32);
+
+impl Default for InstanceGroupAlpha {
+    fn default() -> Self {
+        InstanceGroupAlpha(1.0)
+    }
+}
+
+/// Draws the instances of this host entity as cutouts: fragments whose alpha, after the
+/// [`InstanceGroupAlpha`], is below the cutoff are discarded and the rest are drawn without
+/// blending. For foliage and other sprites with hard edges, which don't need to be sorted among
+/// each other.
+///
+/// The cutoff is part of the host's uniform, changing it doesn't specialize new pipelines. Bevy
+/// 0.13 has no alpha mask phase and no depth buffer in the 2D pass, so like [`OpaqueInstances`]
+/// 2D hosts are still drawn in the [`Transparent2d`] phase in back to front order and ignore their
+/// [`InstanceBlendMode`]. 3D hosts are drawn in the `AlphaMask3d` phase and write depth, without
+/// being split by [`MixedOpacity`](opacity::MixedOpacity).
+#[derive(Component, Clone, Copy, PartialEq, Debug, ExtractComponent)]
+pub struct InstanceAlphaMask(pub f32);
+
+impl Default for InstanceAlphaMask {
+    fn default() -> Self {
+        InstanceAlphaMask(0.5)
+    }
+}
+
+/// Uploads the instances of this host entity into a ring of buffers, a different one each frame,
+/// so writing the instances doesn't have to wait for the draws of the previous frame reading them.
+///
+/// Meant for large batches that are rewritten every frame, static batches are better off with a
+/// single buffer as they are only written once. Each buffer of the ring holds the instances of an
+/// earlier frame, so all instances are written every frame, and the ring takes that many times
+/// the memory. Holds the number of buffers, clamped to 2-3.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, ExtractComponent)]
+pub struct InstanceBufferRing(pub u32);
+
+impl InstanceBufferRing {
+    /// The number of buffers in the ring.
+    pub fn buffers(&self) -> u32 {
+        self.0.clamp(2, 3)
+    }
+}
+
+impl Default for InstanceBufferRing {
+    fn default() -> Self {
+        InstanceBufferRing(2)
+    }
+}
+
+/// Sorts the instances of this host entity before they are uploaded, so that overlapping
+/// transparent instances blend correctly. Costs a sort every frame and requires
+/// [`Instance::BOUNDS`], or [`Instance::SORT_KEY_OFFSET`] for [`SortInstances::Key`].
+/// [`GpuCulling`](culling::GpuCulling) does not preserve the order.
+#[derive(Component, Clone, Copy, Default, ExtractComponent)]
+pub enum SortInstances {
+    /// By ascending `position.z`, back to front for 2D cameras.
+    #[default]
+    Z,
+    /// By descending distance to the view, for 3D cameras. As all views share the instance
+    /// buffer, the instances are sorted for the [`PrimaryView`] only.
+    ViewDistance,
+    /// By ascending [`Instance::SORT_KEY_OFFSET`], like [`InstanceData::sort_key`], so the draw
+    /// order doesn't depend on `position.z`. Instances with equal keys keep their order.
+    Key,
+}
+
+/// Color space of the instance colors passed to the built-in shaders.
+///
+/// Shaders output linear colors, which bevy converts to sRGB when presenting. Colors that are
+/// already sRGB, like the ones returned by [`Color::as_rgba_f32`], come out washed out unless
+/// they are converted first.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum InstanceColorSpace {
+    /// Colors are linear, e.g. from [`Color::as_linear_rgba_f32`], and used as is.
+    #[default]
+    Linear,
+    /// Colors are sRGB and converted to linear in the vertex shader, behind the
+    /// `INSTANCE_SRGB_COLOR` shader def.
+    Srgb,
+}
+
+/// Where the instance data lives on the GPU.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum InstanceBufferMode {
+    /// Instance data is bound as a second vertex buffer stepped once per instance.
+    #[default]
+    Vertex,
+    /// Instance data is bound as a read-only storage buffer in bind group 2 and indexed with
+    /// `@builtin(instance_index)` in the vertex shader. The WGSL struct has to match the memory
+    /// layout of the instance type, so avoid `vec3`/`vec4` members in it, which are 16 byte
+    /// aligned in WGSL. Falls back to [`InstanceBufferMode::Vertex`] on adapters without storage
+    /// buffers, like WebGL2.
+    Storage,
+}
+
+/// Extracts and uploads the instances of `T`, shared by [`InstancingPlugin`] and
+/// [`Instancing3dPlugin`].
+///
+/// Added by either of them, the first one to be added decides the [`InstanceBufferMode`].
+struct InstanceBufferPlugin<T: Instance>(PhantomData<T>);
+
+impl<T: Instance> Plugin for InstanceBufferPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            // hosts no view sees are neither copied nor uploaded
+            ExtractComponentPlugin::<InstanceMaterialData<T>>::extract_visible(),
+            ExtractComponentPlugin::<InstanceGenerator<T>>::extract_visible(),
+            ExtractComponentPlugin::<PreviousInstances<T>>::extract_visible(),
+        ));
+
+        app.add_systems(First, clear_dirty_instances::<T>)
+            .add_systems(FixedFirst, store_previous_instances::<T>)
+            .add_systems(
+                PostUpdate,
+                (
+                    insert_host_markers::<T>.before(bounds::sync_no_frustum_culling),
+                    warn_incomplete_hosts::<T>,
+                ),
+            );
+
+        if !app.is_plugin_added::<InstancingShadersPlugin>() {
+            app.add_plugins(InstancingShadersPlugin);
+        }
+
+        if !app.is_plugin_added::<GpuCullingPlugin>() {
+            app.add_plugins(GpuCullingPlugin);
+        }
+
+        if !app.is_plugin_added::<GpuSimulationPlugin>() {
+            app.add_plugins(GpuSimulationPlugin);
+        }
+
+        if !app.is_plugin_added::<BatchCullingPlugin>() {
+            app.add_plugins(BatchCullingPlugin);
+        }
+
+        if !app.is_plugin_added::<ExtractComponentPlugin<InstanceMeshRanges>>() {
+            app.add_plugins(ExtractComponentPlugin::<InstanceMeshRanges>::default());
+        }
+
+        if !app.is_plugin_added::<ExtractComponentPlugin<SortInstances>>() {
+            app.add_plugins(ExtractComponentPlugin::<SortInstances>::default());
+        }
+
+        if !app.is_plugin_added::<ExtractComponentPlugin<InstanceBufferRing>>() {
+            app.add_plugins(ExtractComponentPlugin::<InstanceBufferRing>::default());
+        }
+
+        if !app.is_plugin_added::<ExtractComponentPlugin<InstanceGroupAlpha>>() {
+            app.add_plugins(ExtractComponentPlugin::<InstanceGroupAlpha>::default());
+        }
+
+        if !app.is_plugin_added::<ExtractComponentPlugin<InstanceAlphaMask>>() {
+            app.add_plugins(ExtractComponentPlugin::<InstanceAlphaMask>::default());
+        }
+
+        if !app.is_plugin_added::<LodPlugin>() {
+            app.add_plugins(LodPlugin);
+        }
+
+        if !app.is_plugin_added::<InterpolationPlugin>() {
+            app.add_plugins(InterpolationPlugin);
+        }
+
+        if !app.is_plugin_added::<ExtractComponentPlugin<MergeInstances>>() {
+            app.add_plugins(ExtractComponentPlugin::<MergeInstances>::default());
+        }
+
+        if !app.is_plugin_added::<DrawnInstanceCountPlugin>() {
+            app.add_plugins(DrawnInstanceCountPlugin);
+        }
+
+        if !app.is_plugin_added::<InstanceBufferPoolPlugin>() {
+            app.add_plugins(InstanceBufferPoolPlugin);
+        }
+        app.add_plugins(RemovedHostsPlugin::<T>::default());
+
+        app.sub_app_mut(RenderApp)
+            .init_resource::<InstanceBufferCache<T>>()
+            .init_resource::<culling::CullBufferCache<T>>()
+            .init_resource::<GpuSimulationStates<T>>()
+            .init_resource::<MergedHosts<T>>()
+            .add_systems(
+                Render,
+                (
+                    // the merged hosts are left empty, so they aren't queued
+                    merge_instances::<T>
+                        .in_set(RenderSet::Queue)
+                        .before(RenderSet::QueueMeshes),
+                    interpolate_instances::<T>
+                        .in_set(RenderSet::PrepareResources)
+                        .before(sort_instances::<T>),
+                    sort_instances::<T>
+                        .in_set(RenderSet::PrepareResources)
+                        .before(batch_lod_instances::<T>),
+                    batch_lod_instances::<T>
+                        .in_set(RenderSet::PrepareResources)
+                        .before(prepare_instance_buffers::<T>),
+                    prepare_instance_buffers::<T>.in_set(RenderSet::PrepareResources),
+                    // culls the simulated instances
+                    simulation::dispatch_simulation::<T>
+                        .in_set(RenderSet::PrepareBindGroups)
+                        .before(culling::dispatch_instance_culling::<T>),
+                    culling::dispatch_instance_culling::<T>.in_set(RenderSet::PrepareBindGroups),
+                    // once the instance buffers were inserted
+                    record_drawn_instances::<T>.in_set(RenderSet::PrepareBindGroups),
+                ),
+            );
+    }
+}
+
+/// Adds the markers every host entity needs to new hosts, so they can be spawned from loose
+/// components as well as from an [`InstancedMeshBundle`].
+///
+/// [`NoAutomaticBatching`] is always added: bevy merges the draws of neighbouring entities with
+/// the same mesh otherwise, and all but the first host of a merged batch would silently not be
+/// drawn, as each host draws from its own [`InstanceBuffer`]. Hosts without an
+/// [`InstanceFrustumCulling`] get the default of the bundle, which culls the batch only if its
+/// bounds are known. Insert an [`InstanceFrustumCulling`] to override it.
+#[allow(clippy::type_complexity)]
+fn insert_host_markers<T: Instance>(
+    mut commands: Commands,
+    hosts: Query<
+        (
+            Entity,
+            Has<InstanceMaterialData<T>>,
+            Has<NoAutomaticBatching>,
+            Has<InstanceFrustumCulling>,
+        ),
+        Or<(Added<InstanceMaterialData<T>>, Added<InstanceGenerator<T>>)>,
+    >,
+) {
+    for (host, has_instances, has_no_batching, has_culling) in &hosts {
+        let mut host = commands.entity(host);
+        if !has_no_batching {
+            host.insert(NoAutomaticBatching);
+        }
+        if !has_culling {
+            // generated instances are never on the CPU to be bounded
+            host.insert(InstanceFrustumCulling(has_instances && T::BOUNDS.is_some()));
+        }
+    }
+}
+
+/// Warns once per host that lacks a component bevy needs to extract its mesh, which leaves the
+/// host out of the render world's mesh instances, so it is silently never drawn. Typically the
+/// host was spawned from its components without a [`Mesh2dHandle`]. The host is queued again every
+/// frame, so inserting the missing components later draws it without respawning.
+#[allow(clippy::type_complexity)]
+fn warn_incomplete_hosts<T: Instance>(
+    hosts: Query<
+        (
+            Entity,
+            Has<Mesh2dHandle>,
+            Has<Handle<Mesh>>,
+            Has<GlobalTransform>,
+            Has<ViewVisibility>,
+        ),
+        Or<(With<InstanceMaterialData<T>>, With<InstanceGenerator<T>>)>,
+    >,
+    mut warned: Local<HashSet<Entity>>,
+) {
+    for (host, mesh_2d, mesh_3d, global_transform, view_visibility) in &hosts {
+        let missing: Vec<_> = [
+            (!mesh_2d && !mesh_3d).then_some("a Mesh2dHandle, or a Handle<Mesh> in 3D"),
+            (!global_transform).then_some("a GlobalTransform"),
+            (!view_visibility).then_some("a ViewVisibility"),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if missing.is_empty() || !warned.insert(host) {
+            continue;
+        }
+        warn!(
+            "Instanced host {host:?} has no {}, so its mesh isn't extracted and its instances aren't drawn. Spawn hosts with an InstancedMeshBundle, or insert the missing components to draw them from the next frame",
+            missing.join(" and no ")
+        );
+    }
+}
+
+/// A phase 2D hosts can be drawn in, see [`InstancingPlugin`].
+pub trait InstancePhaseItem: CachedRenderPipelinePhaseItem {
+    /// The item drawing the instances of the host `entity`, sorted by `sort_key` like the hosts in
+    /// [`Transparent2d`], see [`SortKeyStrategy`].
+    fn new(
+        entity: Entity,
+        sort_key: FloatOrd,
+        pipeline: CachedRenderPipelineId,
+        draw_function: DrawFunctionId,
+    ) -> Self;
+}
+
+impl InstancePhaseItem for Transparent2d {
+    fn new(
+        entity: Entity,
+        sort_key: FloatOrd,
+        pipeline: CachedRenderPipelineId,
+        draw_function: DrawFunctionId,
+    ) -> Self {
+        Transparent2d {
+            sort_key,
+            entity,
+            pipeline,
+            draw_function,
+            batch_range: 0..1,
+            dynamic_offset: None,
+        }
+    }
+}
+
+/// Draws the instances of 2D meshes ([`Mesh2dHandle`]) in the [`Transparent2d`] phase, or the
+/// phase `P` of a custom render graph.
+///
+/// A custom phase is queued for every view with a [`RenderPhase<P>`], which the app has to add in
+/// the render world, sort and render with 2D view bindings like bevy does for [`Transparent2d`].
+/// Each instance type and material is drawn in one phase, add a plugin per phase for another
+/// material to draw the same instance type in several.
+pub struct InstancingPlugin<
+    T: Instance,
+    M: InstancedMaterial = DefaultInstancedMaterial,
+    P: InstancePhaseItem = Transparent2d,
+> {
+    pub buffer_mode: InstanceBufferMode,
+    pub color_space: InstanceColorSpace,
+    /// Replaces the shader of the instance type, [`Instance::shader`], for this plugin.
+    pub shader: Option<Handle<Shader>>,
+    pub sort_key_strategy: SortKeyStrategy,
+    marker: PhantomData<(T, M, P)>,
+}
+
+impl<T: Instance, M: InstancedMaterial, P: InstancePhaseItem> InstancingPlugin<T, M, P> {
+    pub fn with_buffer_mode(mut self, buffer_mode: InstanceBufferMode) -> Self {
+        self.buffer_mode = buffer_mode;
+        self
+    }
+
+    pub fn with_color_space(mut self, color_space: InstanceColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+
+    pub fn with_shader(mut self, shader: Handle<Shader>) -> Self {
+        self.shader = Some(shader);
+        self
+    }
+
+    pub fn with_sort_key_strategy(mut self, sort_key_strategy: SortKeyStrategy) -> Self {
+        self.sort_key_strategy = sort_key_strategy;
+        self
+    }
+}
+
+impl<T: Instance, M: InstancedMaterial, P: InstancePhaseItem> Default
+    for InstancingPlugin<T, M, P>
+{
+    fn default() -> Self {
+        Self {
+            buffer_mode: InstanceBufferMode::default(),
+            color_space: InstanceColorSpace::default(),
+            shader: None,
+            sort_key_strategy: SortKeyStrategy::default(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Instance, M: InstancedMaterial, P: InstancePhaseItem> Plugin for InstancingPlugin<T, M, P> {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<InstanceBufferPlugin<T>>() {
+            app.add_plugins(InstanceBufferPlugin::<T>(PhantomData));
+        }
+
+        if !app.is_plugin_added::<InstanceAtlasPlugin>() {
+            app.add_plugins(InstanceAtlasPlugin);
+        }
+
+        if !app.is_plugin_added::<InstanceDataTexturePlugin>() {
+            app.add_plugins(InstanceDataTexturePlugin);
+        }
+
+        if !app.is_plugin_added::<ExtractComponentPlugin<OpaqueInstances>>() {
+            app.add_plugins(ExtractComponentPlugin::<OpaqueInstances>::default());
+        }
+
+        if !app.is_plugin_added::<ExtractComponentPlugin<InstanceLayer>>() {
+            app.add_plugins(ExtractComponentPlugin::<InstanceLayer>::default());
+        }
+
+        if !app.is_plugin_added::<ExtractComponentPlugin<InstanceBlendMode>>() {
+            app.add_plugins(ExtractComponentPlugin::<InstanceBlendMode>::default());
+        }
+
+        if !app.is_plugin_added::<ExtractComponentPlugin<PointInstances>>() {
+            app.add_plugins(ExtractComponentPlugin::<PointInstances>::default());
+        }
+
+        match self.sort_key_strategy {
+            SortKeyStrategy::MeshZ => {}
+            SortKeyStrategy::Custom => {
+                if !app.is_plugin_added::<ExtractComponentPlugin<HostSortKey>>() {
+                    app.add_plugins(ExtractComponentPlugin::<HostSortKey>::default());
+                }
+            }
+            SortKeyStrategy::SpawnOrder => {
+                if !app.is_plugin_added::<ExtractComponentPlugin<HostSpawnOrder>>() {
+                    app.init_resource::<HostSpawnCounter>()
+                        .add_plugins(ExtractComponentPlugin::<HostSpawnOrder>::default());
+                }
+                if !app.is_plugin_added::<HostSpawnOrderPlugin<T>>() {
+                    app.add_plugins(HostSpawnOrderPlugin::<T>(PhantomData));
+                }
+            }
+        }
+
+        if !app.is_plugin_added::<InstancedMaterialPlugin<M>>() {
+            app.add_plugins(InstancedMaterialPlugin::<M>::default());
+        }
+
+        if !app.is_plugin_added::<InstanceTargetsPlugin>() {
+            app.add_plugins(InstanceTargetsPlugin);
+        }
+
+        if !app.is_plugin_added::<MotionVectorsPlugin>() {
+            app.add_plugins(MotionVectorsPlugin);
+        }
+
+        app.add_systems(
+            PostUpdate,
+            update_batch_aabb::<T, Mesh2dHandle>
+                .after(VisibilitySystems::CalculateBounds)
+                .before(VisibilitySystems::CheckVisibility),
+        );
+
+        app.sub_app_mut(RenderApp)
+            // a custom phase may be set up by a plugin added later
+            .init_resource::<DrawFunctions<P>>()
+            .add_render_command::<P, DrawCustom<T, M>>()
+            .add_render_command::<InstanceTarget2d, DrawCustom<T, M>>()
+            .init_resource::<SpecializedMeshPipelines<CustomPipeline<T, M>>>()
+            .init_resource::<PreviousFrameInstances<T>>()
+            .add_systems(
+                Render,
+                (
+                    queue_custom::<T, M, P>.in_set(RenderSet::QueueMeshes),
+                    // the instances in the order they are drawn in
+                    prepare_previous_instances::<T>
+                        .in_set(RenderSet::PrepareResources)
+                        .after(prepare_instance_buffers::<T>),
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let render_app = app.sub_app_mut(RenderApp);
+        InstancePipeline::<T>::init(&mut render_app.world, self.buffer_mode, self.color_space);
+        render_app
+            .init_resource::<InstanceAtlasLayout>()
+            .init_resource::<InstanceTextureArrayLayout>()
+            .init_resource::<InstanceDataTextureLayout>()
+            .init_resource::<InstancedMaterialLayout<M>>();
+        PreviousInstancesLayout::init(&mut render_app.world);
+        let custom_pipeline = CustomPipeline::<T, M>::new(
+            &mut render_app.world,
+            self.shader.clone(),
+            self.sort_key_strategy,
+        );
+        render_app.insert_resource(custom_pipeline);
+    }
+}
+
+/// A component holding the mesh that is drawn once per instance.
+pub trait HostMesh: Component {
+    fn mesh(&self) -> &Handle<Mesh>;
+}
+
+impl HostMesh for Mesh2dHandle {
+    fn mesh(&self) -> &Handle<Mesh> {
+        &self.0
+    }
+}
+
+impl HostMesh for Handle<Mesh> {
+    fn mesh(&self) -> &Handle<Mesh> {
+        self
+    }
+}
+
+/// Looks up the extracted mesh of a host entity, which is either a 2D or a 3D mesh.
+#[derive(SystemParam)]
+pub struct HostMeshes<'w> {
+    mesh_2d: Option<Res<'w, RenderMesh2dInstances>>,
+    mesh_3d: Option<Res<'w, RenderMeshInstances>>,
+    pipeline_2d: Option<Res<'w, Mesh2dPipeline>>,
+    pipeline_3d: Option<Res<'w, MeshPipeline>>,
+    layouts: Option<Res<'w, HostMeshLayouts>>,
+}
+
+impl HostMeshes<'_> {
+    /// Returns the mesh and the mesh transform of `entity`.
+    pub fn get(&self, entity: Entity) -> Option<(AssetId<Mesh>, &Affine3)> {
+        let mesh_2d = self
+            .mesh_2d
+            .as_ref()
+            .and_then(|instances| instances.get(&entity))
+            .map(|instance| (instance.mesh_asset_id, &instance.transforms.transform));
+        let mesh_3d = || {
+            self.mesh_3d
+                .as_ref()
+                .and_then(|instances| instances.get(&entity))
+                .map(|instance| (instance.mesh_asset_id, &instance.transforms.transform))
+        };
+        mesh_2d.or_else(mesh_3d)
+    }
+
+    /// Returns the mesh uniform of `entity` encoded for bevy's mesh bind group, the layout of the
+    /// bind group replacing it and the number of uniforms it binds, `None` if it binds a storage
+    /// buffer.
+    pub fn uniform(&self, entity: Entity) -> Option<(Vec<u8>, &BindGroupLayout, Option<u32>)> {
+        let layouts = self.layouts.as_ref()?;
+        let mesh_2d = self
+            .mesh_2d
+            .as_ref()
+            .and_then(|instances| instances.get(&entity))
+            .zip(self.pipeline_2d.as_ref())
+            .map(|(instance, pipeline)| {
+                (
+                    encode_uniform(&Mesh2dUniform::from(&instance.transforms)),
+                    &layouts.layout_2d,
+                    pipeline.per_object_buffer_batch_size,
+                )
+            });
+        let mesh_3d = || {
+            self.mesh_3d
+                .as_ref()
+                .and_then(|instances| instances.get(&entity))
+                .zip(self.pipeline_3d.as_ref())
+                .map(|(instance, pipeline)| {
+                    (
+                        encode_uniform(&MeshUniform::new(&instance.transforms, None)),
+                        &layouts.layout_3d,
+                        pipeline.per_object_buffer_batch_size,
+                    )
+                })
+        };
+        mesh_2d.or_else(mesh_3d)
+    }
+}
+
+fn encode_uniform(uniform: &(impl ShaderType + encase::internal::WriteInto)) -> Vec<u8> {
+    let mut buffer = encase::StorageBuffer::new(Vec::new());
+    buffer.write(uniform).unwrap();
+    buffer.into_inner()
+}
+
+/// The view instances are sorted and LOD levels picked for.
+///
+/// All views share the instance buffer of a host, so this is the camera drawn first. Views without
+/// a camera, like the shadow views of lights, are ignored.
+#[derive(SystemParam)]
+pub struct PrimaryView<'w, 's> {
+    views: Query<'w, 's, (Entity, &'static ExtractedView, &'static ExtractedCamera)>,
+}
+
+impl PrimaryView<'_, '_> {
+    pub fn get(&self) -> Option<&ExtractedView> {
+        self.views
+            .iter()
+            .min_by_key(|(entity, _, camera)| (camera.order, *entity))
+            .map(|(_, view, _)| view)
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn queue_custom<T: Instance, M: InstancedMaterial, P: InstancePhaseItem>(
+    phase_draw_functions: Res<DrawFunctions<P>>,
+    target_draw_functions: Res<DrawFunctions<InstanceTarget2d>>,
+    custom_pipeline: Res<CustomPipeline<T, M>>,
+    msaa: Res<Msaa>,
+    mut pipelines: ResMut<SpecializedMeshPipelines<CustomPipeline<T, M>>>,
+    pipeline_cache: Res<PipelineCache>,
+    meshes: Res<RenderAssets<Mesh>>,
+    render_mesh_instances: Res<RenderMesh2dInstances>,
+    material_meshes: Query<
+        (
+            Entity,
+            Option<&InstanceMaterialData<T>>,
+            Has<InstanceGenerator<T>>,
+            Has<OpaqueInstances>,
+            Has<InstanceAlphaMask>,
+            Option<&InstanceBlendMode>,
+            Option<&InstanceLayer>,
+            Option<&InstanceAtlas>,
+            Option<&InstanceTextureArray>,
+            Has<InstanceSdf>,
+            Option<&InstanceDataTexture>,
+            Has<PointInstances>,
+            Has<InstanceMotionVectors>,
+            Option<&HostSortKey>,
+            Option<&HostSpawnOrder>,
+        ),
+        With<M>,
+    >,
+    images: Res<RenderAssets<Image>>,
+    mut views: Query<(
+        &ExtractedView,
+        &VisibleEntities,
+        &mut RenderPhase<P>,
+        Option<(&InstanceTargets, &mut RenderPhase<InstanceTarget2d>)>,
+    )>,
+    mut logged_errors: Local<HashSet<String>>,
+    mut warned_unloaded: Local<bool>,
+    mut warned_msaa: Local<bool>,
+) {
+    let draw_custom = phase_draw_functions.read().id::<DrawCustom<T, M>>();
+    let draw_custom_targets = target_draw_functions.read().id::<DrawCustom<T, M>>();
+
+    let msaa_key = Mesh2dPipelineKey::from_msaa_samples(msaa.samples());
+
+    for (view, visible_entities, mut phase, targets) in &mut views {
+        let view_key = msaa_key | Mesh2dPipelineKey::from_hdr(view.hdr);
+        let mut targets = targets.filter(|_| draws_instance_targets(&msaa, &mut warned_msaa));
+        // `MeshZ` sorts back to front by the view space z under any projection, shifted by
+        // the camera position so it is the world z for cameras looking down -Z, like the default
+        // 2D camera, and hosts keep sorting among sprites and 2D meshes by z
+        let view_back = view.transform.back();
+        // frustum culled per view on the batch Aabb, see `update_batch_aabb`
+        for entity in &visible_entities.entities {
+            let Ok((
+                entity,
+                instances,
+                generated,
+                opaque,
+                alpha_mask,
+                blend_mode,
+                layer,
+                atlas,
+                texture_array,
+                sdf,
+                data_texture,
+                points,
+                motion_vectors,
+                host_sort_key,
+                spawn_order,
+            )) = material_meshes.get(*entity)
+            else {
+                continue;
+            };
+            // an atlas takes precedence over a texture array
+            let texture_array = texture_array.filter(|_| atlas.is_none());
+            if !generated && instances.is_none_or(|instances| instances.is_empty()) {
+                continue;
+            }
+            // drawn once the atlas or texture array and the data texture are loaded
+            let image = atlas
+                .map(|atlas| &atlas.image)
+                .or(texture_array.map(|texture_array| &texture_array.image));
+            let data_image = data_texture.map(|data_texture| &data_texture.image);
+            if image
+                .into_iter()
+                .chain(data_image)
+                .any(|image| images.get(image).is_none())
+            {
+                continue;
+            }
+            // hosts without a `Mesh2dHandle` are warned about by `warn_incomplete_hosts`
+            let Some(mesh_instance) = render_mesh_instances.get(&entity) else {
+                continue;
+            };
+            let Some(mesh) = meshes.get(mesh_instance.mesh_asset_id) else {
+                // queued again every frame, so the batch appears as soon as the mesh is ready
+                if !*warned_unloaded {
+                    warn!("The mesh of instanced host {entity:?} isn't loaded yet, its instances are drawn once it is");
+                    *warned_unloaded = true;
+                }
+                continue;
+            };
+            // drawn unskinned and unmorphed by the 2D pipeline, rejected like in 3D for clarity
+            if log_unsupported_mesh(
+                entity,
+                &mesh.layout,
+                mesh.morph_targets.is_some(),
+                &mut logged_errors,
+            ) {
+                continue;
+            }
+            // meshes with many attributes leave too few locations for the instance attributes
+            if log_missing_locations(
+                entity,
+                mesh,
+                &MESH_2D_ATTRIBUTES,
+                &custom_pipeline.instance_pipeline,
+                &mut logged_errors,
+            ) {
+                continue;
+            }
+            let key = CustomPipelineKey {
+                mesh_key: view_key
+                    | Mesh2dPipelineKey::from_primitive_topology(mesh.primitive_topology),
+                opaque,
+                alpha_mask,
+                blend_mode: blend_mode.copied().unwrap_or_default(),
+                atlas: atlas.is_some(),
+                texture_array: texture_array.is_some(),
+                sdf: sdf && atlas.is_some(),
+                data_texture: data_texture.is_some(),
+                points,
+                targets: targets
+                    .as_ref()
+                    .map_or([None; MAX_INSTANCE_TARGETS], |(targets, _)| targets.key()),
+                // generated instances aren't kept, and data textures aren't read back
+                previous_instances: motion_vectors
+                    && instances.is_some()
+                    && data_texture.is_none()
+                    && custom_pipeline.previous_instances_layout.is_some()
+                    && targets.as_ref().is_some_and(|(targets, _)| {
+                        targets.position(InstanceTarget::MotionVectors).is_some()
+                    }),
+                strip_index_format: strip_index_format(mesh),
+            };
+
+            let pipeline =
+                pipelines.specialize(&pipeline_cache, &custom_pipeline, key, &mesh.layout);
+
+            let pipeline = match pipeline {
+                Ok(id) => id,
+                Err(err) => {
+                    // the same error would be logged for every view on every frame
+                    let err = err.to_string();
+                    if !logged_errors.contains(&err) {
+                        error!("{}", err);
+                        logged_errors.insert(err);
+                    }
+                    continue;
+                }
+            };
+
+            let host_key = match custom_pipeline.sort_key_strategy {
+                SortKeyStrategy::MeshZ => {
+                    view_back.dot(mesh_instance.transforms.transform.translation)
+                }
+                SortKeyStrategy::Custom => host_sort_key.map_or(0.0, |key| key.0),
+                // hosts are drawn once numbered, a frame after they were spawned
+                SortKeyStrategy::SpawnOrder => {
+                    let Some(spawn_order) = spawn_order else {
+                        continue;
+                    };
+                    spawn_order.0 as f32
+                }
+            };
+            // the smallest steps above the key of the host, so layers with the same key are
+            // drawn in order instead of in the order the hosts were queued in. layers are child
+            // hosts with their own buffers rather than ranges of one host, as bevy's Mesh2d
+            // batching overwrites the batch range and dynamic offset of every item of a Mesh2d
+            let layer = layer.map_or(0, |layer| layer.0);
+            let sort_key = step_up(host_key, layer);
+
+            if let Some((_, target_phase)) = &mut targets {
+                target_phase.add(InstanceTarget2d {
+                    sort_key: FloatOrd(sort_key),
+                    entity,
+                    pipeline,
+                    draw_function: draw_custom_targets,
+                    batch_range: 0..1,
+                    dynamic_offset: None,
+                });
+                continue;
+            }
+            phase.add(P::new(entity, FloatOrd(sort_key), pipeline, draw_custom));
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct InstanceBuffer<T: Instance> {
+    buffer: Buffer,
+    length: usize,
+    /// Number of instances `buffer` and the `overflow` buffers have room for.
+    capacity: usize,
+    /// Buffers holding the instances past the first [`InstancePipeline::max_instances_per_buffer`]
+    /// ones, that many each, for batches exceeding the limits of a single buffer. Their instances
+    /// are drawn one buffer after the other, unculled and unsimulated.
+    overflow: Vec<OverflowBuffer>,
+    /// [`InstancePipeline::max_instances_per_buffer`] when the buffers were created.
+    instances_per_buffer: usize,
+    /// Consecutive frames in which less than a quarter of `capacity` was used, or the host wasn't
+    /// drawn at all.
+    low_usage_frames: u32,
+    /// Binds `buffer` as a storage buffer, only present in [`InstanceBufferMode::Storage`].
+    storage_bind_group: Option<BindGroup>,
+    /// `DrawIndexedIndirect` or `DrawIndirect` arguments drawing all `length` instances, only
+    /// present if the adapter supports indirect draws.
+    indirect: Option<Buffer>,
+    /// Change ticks of the instances in `buffer`, `None` if they have to be written next frame.
+    uploaded: Option<InstanceChangeTicks>,
+    /// Whether instances were written from the CPU this frame, which restarts a
+    /// [`GpuSimulation`](simulation::GpuSimulation).
+    written: bool,
+    /// The mesh uniform of the host, bound by [`SetHostMeshBindGroup`]. `None` until the host's
+    /// mesh is extracted.
+    host_mesh: Option<HostMeshBinding>,
+    marker: PhantomData<T>,
+}
+
+/// A buffer holding instances of a host that don't fit into its first buffer, see
+/// [`InstanceBuffer::overflow`].
+#[derive(Clone)]
+struct OverflowBuffer {
+    buffer: Buffer,
+    storage_bind_group: Option<BindGroup>,
+}
+
+impl<T: Instance> InstanceBuffer<T> {
+    /// Whether the instances are split across several buffers, see [`InstanceBuffer::overflow`].
+    pub(crate) fn is_split(&self) -> bool {
+        !self.overflow.is_empty()
+    }
+
+    /// Returns the buffer at `index`, 0 for the first buffer, and its storage bind group.
+    fn buffer(&self, index: usize) -> (&Buffer, Option<&BindGroup>) {
+        match index {
+            0 => (&self.buffer, self.storage_bind_group.as_ref()),
+            _ => {
+                let overflow = &self.overflow[index - 1];
+                (&overflow.buffer, overflow.storage_bind_group.as_ref())
+            }
+        }
+    }
+
+    /// Returns the buffers holding the instances to `pool`, for other hosts to take over.
+    pub(crate) fn release(&self, pool: &mut InstanceBufferPool) {
+        pool.release(self.buffer.clone());
+        for overflow in &self.overflow {
+            pool.release(overflow.buffer.clone());
+        }
+    }
+
+    /// Splits `instances` at the boundaries of the buffers, returning the index of each buffer
+    /// and the instances within it.
+    fn split(&self, instances: Range<usize>) -> impl Iterator<Item = (usize, Range<usize>)> {
+        let per_buffer = self.instances_per_buffer;
+        let mut start = instances.start;
+        std::iter::from_fn(move || {
+            (start < instances.end).then(|| {
+                let index = start / per_buffer;
+                let end = instances.end.min((index + 1) * per_buffer);
+                let first = index * per_buffer;
+                let range = start - first..end - first;
+                start = end;
+                (index, range)
+            })
+        })
+    }
+
+    /// Writes the consecutive instances in `bytes` from the instance at `start` on, across as
+    /// many buffers as they span.
+    fn write(&self, render_queue: &RenderQueue, start: usize, bytes: &[u8]) {
+        let stride = T::ARRAY_STRIDE as usize;
+        let end = start + bytes.len() / stride;
+        for (index, range) in self.split(start..end) {
+            let first = index * self.instances_per_buffer + range.start - start;
+            render_queue.write_buffer(
+                self.buffer(index).0,
+                (range.start * stride) as u64,
+                &bytes[first * stride..(first + range.len()) * stride],
+            );
+        }
+    }
+}
+
+/// A buffer holding only the mesh uniform of one host in place of bevy's mesh bind group, followed
+/// by the [`HostParams`] of the host, see [`HostMeshLayouts`].
+///
+/// Bevy writes the uniforms of all meshes into one array and the shader looks a mesh up by its
+/// instance index, which the instances take over. Binding an array of one host instead lets the
+/// shader read the host's transform at index 0.
+#[derive(Clone)]
+struct HostMeshBinding {
+    buffer: Buffer,
+    params_buffer: Buffer,
+    bind_group: BindGroup,
+    /// The encoded uniform last written to `buffer`.
+    uniform: Vec<u8>,
+    /// The params last written to `params_buffer`.
+    params: HostParams,
+    /// Whether the layout binds a uniform array with a dynamic offset, on platforms without
+    /// storage buffers.
+    dynamic_offset: bool,
+}
+
+/// Mirrors `HostParams` in the built-in shaders.
+#[derive(Clone, Copy, PartialEq, Pod, Zeroable)]
+#[repr(C)]
+struct HostParams {
+    group_alpha: f32,
+    /// The cutoff of the [`InstanceAlphaMask`], only read by pipelines drawing cutouts.
+    alpha_cutoff: f32,
+    _padding: [f32; 2],
+}
+
+/// Layouts of the [`HostMeshBinding`]s of 2D and 3D hosts: the mesh uniform array like in bevy's
+/// mesh bind group at binding 0, and the [`HostParams`] at binding 1. Replace bevy's mesh layout
+/// in the pipelines drawing instances.
+#[derive(Resource)]
+struct HostMeshLayouts {
+    layout_2d: BindGroupLayout,
+    layout_3d: BindGroupLayout,
+}
+
+impl FromWorld for HostMeshLayouts {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let layout = |label, mesh| {
+            render_device.create_bind_group_layout(
+                label,
+                &BindGroupLayoutEntries::sequential(
+                    ShaderStages::VERTEX_FRAGMENT,
+                    (mesh, binding_types::uniform_buffer_sized(false, None)),
+                ),
+            )
+        };
+
+        HostMeshLayouts {
+            layout_2d: layout(
+                "instance host mesh 2d layout",
+                GpuArrayBuffer::<Mesh2dUniform>::binding_layout(render_device),
+            ),
+            layout_3d: layout(
+                "instance host mesh 3d layout",
+                GpuArrayBuffer::<MeshUniform>::binding_layout(render_device),
+            ),
+        }
+    }
+}
+
+impl<T: Instance> Clone for InstanceBuffer<T> {
+    fn clone(&self) -> Self {
+        InstanceBuffer {
+            buffer: self.buffer.clone(),
+            length: self.length,
+            capacity: self.capacity,
+            overflow: self.overflow.clone(),
+            instances_per_buffer: self.instances_per_buffer,
+            low_usage_frames: self.low_usage_frames,
+            storage_bind_group: self.storage_bind_group.clone(),
+            indirect: self.indirect.clone(),
+            uploaded: self.uploaded,
+            written: self.written,
+            host_mesh: self.host_mesh.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+/// Number of consecutive frames a host has to use less than a quarter of its instance buffer
+/// before the buffer is shrunk, or not be drawn before it is dropped, so fluctuating instance
+/// counts and visibility don't reallocate it repeatedly.
+pub(crate) const SHRINK_AFTER_FRAMES: u32 = 60;
+
+/// The [`InstanceBuffer`]s of all hosts, kept across frames as the render world entities are
+/// cleared every frame. Keyed by the host and the slot of its [`InstanceBufferRing`], 0 without
+/// one.
+#[derive(Resource)]
+pub struct InstanceBufferCache<T: Instance> {
+    buffers: HashMap<(Entity, u32), InstanceBuffer<T>>,
+}
+
+impl<T: Instance> Default for InstanceBufferCache<T> {
+    fn default() -> Self {
+        InstanceBufferCache {
+            buffers: HashMap::default(),
+        }
+    }
+}
+
+impl<T: Instance> InstanceBufferCache<T> {
+    /// Number of [`InstanceBuffer`]s kept, one per host and ring slot. Hidden hosts keep theirs
+    /// for a while, despawned hosts return theirs to the
+    /// [`InstanceBufferPool`](pool::InstanceBufferPool) right away.
+    pub fn len(&self) -> usize {
+        self.buffers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffers.is_empty()
+    }
+}
+
+fn sort_instances<T: Instance>(
+    mut query: Query<(Entity, &mut InstanceMaterialData<T>, &SortInstances)>,
+    primary_view: PrimaryView,
+    host_meshes: HostMeshes,
+    mut warned: Local<bool>,
+    mut warned_key: Local<bool>,
+) {
+    for (entity, mut instances, sort) in &mut query {
+        match (sort, T::BOUNDS, T::SORT_KEY_OFFSET) {
+            (SortInstances::Key, _, Some(offset)) => {
+                let offset = offset as usize;
+                let key = |instance: &T| {
+                    bytemuck::pod_read_unaligned::<f32>(
+                        &bytemuck::bytes_of(instance)[offset..offset + 4],
+                    )
+                };
+                instances.sort_by(|a, b| key(a).total_cmp(&key(b)));
+            }
+            (SortInstances::Key, _, None) => {
+                if !*warned_key {
+                    warn!("SortInstances::Key requires an instance type with SORT_KEY_OFFSET, drawing unsorted");
+                    *warned_key = true;
+                }
+            }
+            (_, None, _) => {
+                if !*warned {
+                    warn!("SortInstances requires an instance type with BOUNDS, drawing unsorted");
+                    *warned = true;
+                }
+            }
+            (SortInstances::Z, Some(bounds), _) => instances.sort_by(|a, b| {
+                let (a, _) = bounds.read(a);
+                let (b, _) = bounds.read(b);
+                a.z.total_cmp(&b.z)
+            }),
+            (SortInstances::ViewDistance, Some(bounds), _) => {
+                let (Some(view), Some((_, transform))) =
+                    (primary_view.get(), host_meshes.get(entity))
+                else {
+                    continue;
+                };
+                let model = Affine3A::from(transform);
+                let view_position = view.transform.translation();
+                let distance = |instance: &T| {
+                    let (position, _) = bounds.read(instance);
+                    model
+                        .transform_point3(position)
+                        .distance_squared(view_position)
+                };
+                instances.sort_by(|a, b| distance(b).total_cmp(&distance(a)));
+            }
+        }
+    }
+}
+
+/// Number of instances the buffer of a host needs room for.
+fn required_capacity<T: Instance>(
+    instances: Option<&InstanceMaterialData<T>>,
+    generator: Option<&InstanceGenerator<T>>,
+) -> usize {
+    match (instances, generator) {
+        (Some(instances), _) => instances.len(),
+        (None, Some(generator)) => generator.capacity,
+        (None, None) => 0,
+    }
+}
+
+/// `key` moved up by `steps` of [`f32::next_up`] at once, stopping at infinity.
+fn step_up(key: f32, steps: u32) -> f32 {
+    if key.is_nan() || steps == 0 {
+        return key;
+    }
+    // the bits ordered like the floats, with both zeros at 0
+    let bits = key.to_bits();
+    let ordered = match bits >> 31 {
+        0 => bits as i64,
+        _ => -((bits & 0x7FFF_FFFF) as i64),
+    };
+    let stepped = (ordered + steps as i64).min(f32::INFINITY.to_bits() as i64);
+    if stepped < 0 {
+        f32::from_bits(0x8000_0000 | (-stepped) as u32)
+    } else {
+        f32::from_bits(stepped as u32)
+    }
+}
+
+/// Frames a buffer of `capacity` has been used less than a quarter, after it held `required`
+/// instances this frame.
+fn low_usage_frames(required: usize, capacity: usize, low_usage_frames: u32) -> u32 {
+    if required < capacity / 4 {
+        low_usage_frames + 1
+    } else {
+        0
+    }
+}
+
+/// Capacity the buffer of a host is created again with to hold `required` instances, or `None` to
+/// keep its buffer of `capacity`, 0 without one. Grows geometrically so slowly growing instance
+/// counts don't reallocate every frame, and keeps some headroom when shrinking for the same reason.
+fn buffer_capacity(
+    required: usize,
+    capacity: usize,
+    low_usage_frames: u32,
+    per_buffer: usize,
+) -> Option<usize> {
+    let shrink = low_usage_frames >= SHRINK_AFTER_FRAMES;
+    if capacity >= required && !shrink {
+        return None;
+    }
+    let capacity = if shrink {
+        (required * 2).next_power_of_two()
+    } else {
+        required.next_power_of_two()
+    };
+    if capacity > per_buffer {
+        // past the limit, whole buffers are added as needed
+        return Some(required.div_ceil(per_buffer) * per_buffer);
+    }
+    Some(capacity)
+}
+
+/// The instances of a host written to its instance buffer in a frame.
+#[derive(Debug, PartialEq)]
+enum InstanceUpload {
+    /// The buffer holds them already.
+    Skip,
+    /// Only the edited ones, the rest are the instances of the last frame.
+    Range(Range<usize>),
+    All,
+}
+
+/// What to write of the `instances` that last changed at `ticks` to a buffer holding the
+/// instances of `uploaded`, `None` if it holds none that can be kept. Static instances are only
+/// written once, until they change in the main world.
+fn instance_upload<T: Instance>(
+    instances: &InstanceMaterialData<T>,
+    ticks: Option<InstanceChangeTicks>,
+    uploaded: Option<InstanceChangeTicks>,
+) -> InstanceUpload {
+    if ticks == uploaded {
+        InstanceUpload::Skip
+    } else if uploaded.is_some() && instances.dirty != ALL_DIRTY {
+        InstanceUpload::Range(instances.dirty_range())
+    } else {
+        InstanceUpload::All
+    }
+}
+
+/// Collapses the instances with a non-finite position or scale, e.g. from a division by zero in
+/// game logic, to a zero scale at the origin so they aren't drawn, and logs their indices once per
+/// host. Indices are kept, so picking and partial writes still line up. Only checked in debug
+/// builds and for instance types with [`Instance::BOUNDS`].
+#[cfg(debug_assertions)]
+fn finite_instances<'a, T: Instance>(
+    entity: Entity,
+    start: usize,
+    instances: &'a [T],
+    warned: &mut HashSet<Entity>,
+) -> std::borrow::Cow<'a, [T]> {
+    let Some(bounds) = T::BOUNDS else {
+        return instances.into();
+    };
+    let is_finite = |instance: &T| {
+        let (position, _) = bounds.read(instance);
+        position.is_finite() && bounds.read_scale_2d(instance).is_finite()
+    };
+    if instances.iter().all(is_finite) {
+        return instances.into();
+    }
+
+    let mut instances = instances.to_vec();
+    let mut non_finite = Vec::new();
+    for (index, instance) in instances.iter_mut().enumerate() {
+        if is_finite(instance) {
+            continue;
+        }
+        non_finite.push(start + index);
+        let bytes = bytemuck::bytes_of_mut(instance);
+        let position = bounds.position_offset as usize;
+        bytes[position..position + 12].fill(0);
+        let scale = bounds.scale_offset as usize;
+        bytes[scale..scale + 4 * bounds.scale_components as usize].fill(0);
+    }
+    if warned.insert(entity) {
+        warn!(
+            "host {entity:?} has instances with a non-finite position or scale at {non_finite:?}, \
+             they are not drawn"
+        );
+    }
+    instances.into()
+}
+
+#[cfg(not(debug_assertions))]
+fn finite_instances<'a, T: Instance>(
+    _entity: Entity,
+    _start: usize,
+    instances: &'a [T],
+    _warned: &mut HashSet<Entity>,
+) -> std::borrow::Cow<'a, [T]> {
+    instances.into()
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn prepare_instance_buffers<T: Instance>(
+    mut commands: Commands,
+    query: Query<
+        (
+            Entity,
+            Option<(&InstanceMaterialData<T>, &InstanceChangeTicks)>,
+            Option<&InstanceGenerator<T>>,
+            Has<CullingRadius>,
+            Has<GpuSimulation>,
+            Has<SortInstances>,
+            Has<InstanceLods>,
+            Has<InterpolateInstances>,
+            Option<&InstanceBufferRing>,
+            Option<&InstanceGroupAlpha>,
+            Option<&InstanceAlphaMask>,
+        ),
+        Or<(With<InstanceMaterialData<T>>, With<InstanceGenerator<T>>)>,
+    >,
+    rings: Query<&InstanceBufferRing>,
+    host_meshes: HostMeshes,
+    meshes: Res<RenderAssets<Mesh>>,
+    instance_pipeline: Res<InstancePipeline<T>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    frame_count: Res<FrameCount>,
+    mut cache: ResMut<InstanceBufferCache<T>>,
+    merged_hosts: Res<MergedHosts<T>>,
+    stats: Option<Res<InstanceBufferStats>>,
+    mut pool: ResMut<InstanceBufferPool>,
+    mut warned_non_finite: Local<HashSet<Entity>>,
+) {
+    let mut instance_count = 0;
+    let created = pool.created();
+
+    // hosts that weren't extracted are hidden, culled, emptied or despawned. their buffers are
+    // kept for a while in case they are shown again, but they miss the edits in the meantime
+    cache.buffers.retain(|(entity, slot), instance_buffer| {
+        if let Ok((_, instances, generator, ..)) = query.get(*entity) {
+            // looked up on its own, its position in the tuple shifts as components are added
+            let ring = rings.get(*entity).ok();
+            // slots past a shortened ring are never drawn again
+            if *slot >= ring.map_or(1, InstanceBufferRing::buffers) {
+                return false;
+            }
+            if required_capacity(instances.map(|(instances, _)| instances), generator) > 0 {
+                return true;
+            }
+        }
+        instance_buffer.uploaded = None;
+        instance_buffer.low_usage_frames += 1;
+        if instance_buffer.low_usage_frames < SHRINK_AFTER_FRAMES {
+            return true;
+        }
+        instance_buffer.release(&mut pool);
+        false
+    });
+
+    for (
+        entity,
+        instances,
+        generator,
+        culled,
+        simulated,
+        sorted,
+        lods,
+        interpolated,
+        ring,
+        group_alpha,
+        alpha_mask,
+    ) in &query
+    {
+        let slot = ring.map_or(0, |ring| frame_count.0 % ring.buffers());
+        let ticks = instances.map(|(_, ticks)| *ticks);
+        let instances = instances.map(|(instances, _)| instances);
+        // empty hosts are not queued, so they don't need a buffer
+        let required = required_capacity(instances, generator);
+        if required == 0 {
+            continue;
+        }
+
+        let mut usage = match instance_pipeline.buffer_mode {
+            InstanceBufferMode::Vertex => BufferUsages::VERTEX,
+            InstanceBufferMode::Storage => BufferUsages::STORAGE,
+        };
+        if culled || simulated {
+            // read by the culling or written by the simulation compute shader
+            usage |= BufferUsages::STORAGE;
+        }
+        usage |= BufferUsages::COPY_DST;
+
+        if let Some(instance_buffer) = cache.buffers.get_mut(&(entity, slot)) {
+            instance_buffer.low_usage_frames = low_usage_frames(
+                required,
+                instance_buffer.capacity,
+                instance_buffer.low_usage_frames,
+            );
+        }
+
+        let per_buffer = instance_pipeline.max_instances_per_buffer;
+        let resized = match cache.buffers.get(&(entity, slot)) {
+            Some(instance_buffer) => buffer_capacity(
+                required,
+                // a buffer created for another usage is replaced whatever its capacity
+                if instance_buffer.buffer.usage() == usage {
+                    instance_buffer.capacity
+                } else {
+                    0
+                },
+                instance_buffer.low_usage_frames,
+                per_buffer,
+            ),
+            None => buffer_capacity(required, 0, 0, per_buffer),
+        };
+
+        let instance_buffer = match (cache.buffers.entry((entity, slot)), resized) {
+            (Entry::Occupied(entry), None) => entry.into_mut(),
+            (Entry::Vacant(_), None) => unreachable!(),
+            (entry, Some(capacity)) => {
+                if let Entry::Occupied(entry) = &entry {
+                    entry.get().release(&mut pool);
+                }
+
+                let mut create_buffer = |instances: usize| {
+                    let buffer =
+                        pool.acquire(&render_device, instances as u64 * T::ARRAY_STRIDE, usage);
+                    let storage_bind_group =
+                        instance_pipeline.storage_layout.as_ref().map(|layout| {
+                            render_device.create_bind_group(
+                                "instance storage bind group",
+                                layout,
+                                &BindGroupEntries::single(buffer.as_entire_binding()),
+                            )
+                        });
+                    OverflowBuffer {
+                        buffer,
+                        storage_bind_group,
+                    }
+                };
+                let first = create_buffer(capacity.min(per_buffer));
+                let overflow = (1..capacity.div_ceil(per_buffer))
+                    .map(|_| create_buffer(per_buffer))
+                    .collect();
+
+                entry
+                    .insert(InstanceBuffer {
+                        buffer: first.buffer,
+                        length: 0,
+                        capacity,
+                        overflow,
+                        instances_per_buffer: per_buffer,
+                        low_usage_frames: 0,
+                        storage_bind_group: first.storage_bind_group,
+                        indirect: None,
+                        uploaded: None,
+                        written: false,
+                        host_mesh: None,
+                        marker: PhantomData,
+                    })
+                    .into_mut()
+            }
+        };
+
+        instance_buffer.written = true;
+        let upload = instances.map(|instances| {
+            (
+                instances,
+                instance_upload(instances, ticks, instance_buffer.uploaded),
+            )
+        });
+        instance_buffer.length = match (upload, generator) {
+            (Some((_, InstanceUpload::Skip)), _) => {
+                instance_buffer.written = false;
+                instance_buffer.length
+            }
+            (Some((instances, InstanceUpload::Range(dirty))), _) => {
+                let written = finite_instances(
+                    entity,
+                    dirty.start,
+                    &instances[dirty.clone()],
+                    &mut warned_non_finite,
+                );
+                instance_buffer.write(&render_queue, dirty.start, bytemuck::cast_slice(&written));
+                instance_buffer.uploaded = ticks;
+                instances.len()
+            }
+            (Some((instances, InstanceUpload::All)), _) => {
+                let written = finite_instances(entity, 0, instances, &mut warned_non_finite);
+                instance_buffer.write(&render_queue, 0, bytemuck::cast_slice(&written));
+                // reordered, interpolated or merged in the render world, or the buffer holds the
+                // instances of an earlier frame, so written again every frame
+                let merged = merged_hosts.contains(entity);
+                instance_buffer.uploaded = ticks
+                    .filter(|_| !sorted && !lods && !interpolated && !merged && ring.is_none());
+                instances.len()
+            }
+            (None, Some(generator)) if !instance_buffer.is_split() => {
+                // writes into wgpu's staging memory, which is copied to the buffer on submit
+                instance_buffer.uploaded = None;
+                let size = BufferSize::new(required as u64 * T::ARRAY_STRIDE).unwrap();
+                match render_queue.write_buffer_with(&instance_buffer.buffer, 0, size) {
+                    Some(mut view) => generator.write(&mut view),
+                    None => 0,
+                }
+            }
+            (None, Some(generator)) => {
+                // a view can't span several buffers, so the instances are generated on the heap
+                instance_buffer.uploaded = None;
+                let mut bytes = vec![0; required * T::ARRAY_STRIDE as usize];
+                let length = generator.write(&mut bytes);
+                instance_buffer.write(
+                    &render_queue,
+                    0,
+                    &bytes[..length * T::ARRAY_STRIDE as usize],
+                );
+                length
+            }
+            (None, None) => unreachable!(),
+        };
+
+        let gpu_mesh = host_meshes
+            .get(entity)
+            .and_then(|(mesh_asset_id, _)| meshes.get(mesh_asset_id));
+
+        // split batches are drawn with a direct draw per buffer
+        if let (Some(gpu_mesh), true, true) = (
+            gpu_mesh,
+            instance_pipeline.indirect_draw,
+            !instance_buffer.is_split(),
+        ) {
+            let index_or_vertex_count = match &gpu_mesh.buffer_info {
+                GpuBufferInfo::Indexed { count, .. } => *count,
+                GpuBufferInfo::NonIndexed => gpu_mesh.vertex_count,
+            };
+
+            let indirect = instance_buffer.indirect.get_or_insert_with(|| {
+                render_device.create_buffer(&BufferDescriptor {
+                    label: Some("instance indirect buffer"),
+                    size: 5 * 4,
+                    usage: BufferUsages::INDIRECT | BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                })
+            });
+
+            // the instance count is at the same position for indexed and non-indexed draws
+            render_queue.write_buffer(
+                indirect,
+                0,
+                bytemuck::cast_slice(&[
+                    index_or_vertex_count,
+                    instance_buffer.length as u32,
+                    0,
+                    0,
+                    0,
+                ]),
+            );
+        }
+
+        if let Some((uniform, layout, batch_size)) = host_meshes.uniform(entity) {
+            let params = HostParams {
+                group_alpha: group_alpha.map_or(1.0, |group_alpha| group_alpha.0),
+                alpha_cutoff: alpha_mask.map_or(0.0, |alpha_mask| alpha_mask.0),
+                _padding: [0.0; 2],
+            };
+            let host_mesh = instance_buffer.host_mesh.get_or_insert_with(|| {
+                let usage = match batch_size {
+                    Some(_) => BufferUsages::UNIFORM,
+                    None => BufferUsages::STORAGE,
+                };
+                let buffer = render_device.create_buffer(&BufferDescriptor {
+                    label: Some("instance host mesh buffer"),
+                    // a uniform array has the fixed length of a batch
+                    size: uniform.len() as u64 * batch_size.unwrap_or(1) as u64,
+                    usage: usage | BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                let params_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+                    label: Some("instance host params buffer"),
+                    contents: bytemuck::bytes_of(&params),
+                    usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+                });
+                let bind_group = render_device.create_bind_group(
+                    "instance host mesh bind group",
+                    layout,
+                    &BindGroupEntries::sequential((
+                        buffer.as_entire_binding(),
+                        params_buffer.as_entire_binding(),
+                    )),
+                );
+                HostMeshBinding {
+                    buffer,
+                    params_buffer,
+                    bind_group,
+                    uniform: Vec::new(),
+                    params,
+                    dynamic_offset: batch_size.is_some(),
+                }
+            });
+            if host_mesh.uniform != uniform {
+                render_queue.write_buffer(&host_mesh.buffer, 0, &uniform);
+                host_mesh.uniform = uniform;
+            }
+            if host_mesh.params != params {
+                render_queue.write_buffer(&host_mesh.params_buffer, 0, bytemuck::bytes_of(&params));
+                host_mesh.params = params;
+            }
+        }
+
+        instance_count += instance_buffer.length;
+        commands.entity(entity).insert(instance_buffer.clone());
+    }
+
+    if let Some(stats) = stats {
+        let bytes = cache
+            .buffers
+            .values()
+            .map(|instance_buffer| instance_buffer.capacity as u64 * T::ARRAY_STRIDE)
+            .sum();
+        stats.record::<T>(instance_count, bytes, pool.created() - created);
+    }
+}
+
+/// Whether the adapter lacks compute shaders or storage buffers, like WebGL2. Instances are still
+/// drawn from vertex buffers with direct draws, see [`InstanceBufferMode`].
+pub(crate) fn is_downlevel(render_device: &RenderDevice) -> bool {
+    let limits = render_device.limits();
+    limits.max_compute_workgroups_per_dimension == 0
+        || limits.max_storage_buffers_per_shader_stage == 0
+}
+
+/// Pipeline state of the instances of `T`, shared by [`CustomPipeline`] and
+/// [`CustomPipeline3d`].
+#[derive(Resource)]
+pub struct InstancePipeline<T: Instance> {
+    buffer_mode: InstanceBufferMode,
+    color_space: InstanceColorSpace,
+    /// Layout of bind group 2 holding the instances, only present in
+    /// [`InstanceBufferMode::Storage`].
+    storage_layout: Option<BindGroupLayout>,
+    /// Whether instances are drawn with indirect draws, falls back to direct draws on adapters
+    /// without `MULTI_DRAW_INDIRECT`.
+    indirect_draw: bool,
+    /// Number of instances fitting into a single buffer, larger batches are split into several
+    /// buffers and draws.
+    max_instances_per_buffer: usize,
+    /// Number of vertex attribute locations the mesh and the instance attributes share.
+    max_vertex_attributes: u32,
+    marker: PhantomData<T>,
+}
+
+impl<T: Instance> Clone for InstancePipeline<T> {
+    fn clone(&self) -> Self {
+        InstancePipeline {
+            buffer_mode: self.buffer_mode,
+            color_space: self.color_space,
+            storage_layout: self.storage_layout.clone(),
+            indirect_draw: self.indirect_draw,
+            max_instances_per_buffer: self.max_instances_per_buffer,
+            max_vertex_attributes: self.max_vertex_attributes,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Instance> InstancePipeline<T> {
+    /// Inserts the pipeline state unless another plugin already did.
+    fn init(world: &mut World, buffer_mode: InstanceBufferMode, color_space: InstanceColorSpace) {
+        // shared by all instance types
+        world.init_resource::<HostMeshLayouts>();
+
+        if world.contains_resource::<Self>() {
+            return;
+        }
+
+        let render_device = world.resource::<RenderDevice>();
+
+        let buffer_mode = match buffer_mode {
+            InstanceBufferMode::Storage if is_downlevel(render_device) => {
+                warn!("InstanceBufferMode::Storage requires an adapter supporting storage buffers, drawing from vertex buffers");
+                InstanceBufferMode::Vertex
+            }
+            buffer_mode => buffer_mode,
+        };
+        let storage_layout = (buffer_mode == InstanceBufferMode::Storage).then(|| {
+            render_device.create_bind_group_layout(
+                "instance storage layout",
+                &BindGroupLayoutEntries::single(
+                    ShaderStages::VERTEX,
+                    binding_types::storage_buffer_read_only_sized(false, None),
+                ),
+            )
+        });
+
+        // not exposed by downlevel backends, which draw directly
+        let indirect_draw = render_device
+            .features()
+            .contains(WgpuFeatures::MULTI_DRAW_INDIRECT);
+
+        let limits = render_device.limits();
+        let mut max_buffer_size = limits.max_buffer_size;
+        if limits.max_storage_buffer_binding_size > 0 {
+            // instance buffers are bound as storage buffers in storage mode, by the culling and
+            // by the simulation
+            max_buffer_size = max_buffer_size.min(limits.max_storage_buffer_binding_size as u64);
+        }
+        let max_instances_per_buffer = ((max_buffer_size / T::ARRAY_STRIDE) as usize).max(1);
+
+        world.insert_resource(InstancePipeline::<T> {
+            buffer_mode,
+            color_space,
+            storage_layout,
+            indirect_draw,
+            max_instances_per_buffer,
+            max_vertex_attributes: limits.max_vertex_attributes,
+            marker: PhantomData,
+        });
+    }
+
+    /// Returns how many instances a single buffer holds on this adapter. Hosts with more instances
+    /// are drawn from several buffers, without [`GpuCulling`](culling::GpuCulling) and
+    /// [`GpuSimulation`].
+    pub fn max_instances_per_buffer(&self) -> usize {
+        self.max_instances_per_buffer
+    }
+
+    /// Whether the instance attributes fit the vertex attribute locations `mesh_attributes` leave
+    /// free, always in storage mode, which reads the instances from a buffer instead.
+    fn fits_locations(&self, mesh_attributes: usize) -> bool {
+        self.storage_layout.is_some()
+            || mesh_attributes + T::attributes().len() <= self.max_vertex_attributes as usize
+    }
+
+    /// Index of the [`InstanceAtlas`] or [`InstanceTextureArray`] bind group, which follows the
+    /// instance storage bind group.
+    fn atlas_bind_group_index(&self) -> usize {
+        2 + self.storage_layout.is_some() as usize
+    }
+
+    /// Replaces the shaders of a mesh pipeline with `shader` and adds the instance inputs.
+    fn specialize(&self, descriptor: &mut RenderPipelineDescriptor, shader: &Handle<Shader>) {
+        descriptor.vertex.shader = shader.clone();
+        descriptor.fragment.as_mut().unwrap().shader = shader.clone();
+
+        match &self.storage_layout {
+            None => {
+                // the instance attributes take the locations the mesh leaves free in order,
+                // filling the gaps between the mesh attributes, as the locations are limited to 16
+                let mesh_locations: Vec<_> = descriptor
+                    .vertex
+                    .buffers
+                    .iter()
+                    .flat_map(|buffer| &buffer.attributes)
+                    .map(|attribute| attribute.shader_location)
+                    .collect();
+                let mut free_locations =
+                    (0..).filter(|location| !mesh_locations.contains(location));
+
+                let mut attributes = T::attributes();
+                attributes.sort_by_key(|attribute| attribute.shader_location);
+                for attribute in &mut attributes {
+                    let location = free_locations.next().unwrap();
+                    // the fragment stage is built from the same file, so it needs them as well
+                    let def = ShaderDefVal::UInt(
+                        format!("INSTANCE_LOCATION_{}", attribute.shader_location),
+                        location,
+                    );
+                    descriptor.vertex.shader_defs.push(def.clone());
+                    if let Some(fragment) = &mut descriptor.fragment {
+                        fragment.shader_defs.push(def);
+                    }
+                    attribute.shader_location = location;
+                }
+
+                descriptor.vertex.buffers.push(VertexBufferLayout {
+                    array_stride: T::ARRAY_STRIDE,
+                    step_mode: VertexStepMode::Instance,
+                    attributes,
+                });
+            }
+            Some(storage_layout) => {
+                descriptor.layout.push(storage_layout.clone());
+                descriptor
+                    .vertex
+                    .shader_defs
+                    .push("INSTANCE_STORAGE".into());
+                // or the fragment stage declares the instance attributes without their locations
+                if let Some(fragment) = &mut descriptor.fragment {
+                    fragment.shader_defs.push("INSTANCE_STORAGE".into());
+                }
+            }
+        }
+
+        if self.color_space == InstanceColorSpace::Srgb {
+            descriptor
+                .vertex
+                .shader_defs
+                .push("INSTANCE_SRGB_COLOR".into());
+        }
+
+        if T::BOUNDS.is_some_and(|bounds| bounds.scale_components == 2) {
+            descriptor
+                .vertex
+                .shader_defs
+                .push("INSTANCE_SCALE_2D".into());
+        }
+
+        // passed on to the fragment shader, whose vertex output has to match
+        if T::CUSTOM_OFFSET.is_some() {
+            descriptor.vertex.shader_defs.push("INSTANCE_CUSTOM".into());
+            let fragment = descriptor.fragment.as_mut().unwrap();
+            fragment.shader_defs.push("INSTANCE_CUSTOM".into());
+        }
+
+        if T::PULSE_OFFSET.is_some() {
+            descriptor.vertex.shader_defs.push("INSTANCE_PULSE".into());
+        }
+
+        if T::DRAW_Z_OFFSET.is_some() {
+            descriptor.vertex.shader_defs.push("INSTANCE_DRAW_Z".into());
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct CustomPipeline<T: Instance, M: InstancedMaterial = DefaultInstancedMaterial> {
+    vertex_shader: Handle<Shader>,
+    fragment_shader: Handle<Shader>,
+    mesh_pipeline: Mesh2dPipeline,
+    instance_pipeline: InstancePipeline<T>,
+    host_mesh_layout: BindGroupLayout,
+    atlas_layout: BindGroupLayout,
+    texture_array_layout: BindGroupLayout,
+    data_texture_layout: BindGroupLayout,
+    material_layout: Option<BindGroupLayout>,
+    /// `None` on adapters without storage buffers, which draw no motion vectors.
+    previous_instances_layout: Option<BindGroupLayout>,
+    sort_key_strategy: SortKeyStrategy,
+    marker: PhantomData<M>,
+}
+
+impl<T: Instance, M: InstancedMaterial> CustomPipeline<T, M> {
+    fn new(
+        world: &mut World,
+        shader: Option<Handle<Shader>>,
+        sort_key_strategy: SortKeyStrategy,
+    ) -> Self {
+        let shader = match shader {
+            Some(shader) => shader,
+            None => load_shader(world, T::shader(), INSTANCING_SHADER_HANDLE),
+        };
+        let vertex_shader = load_shader(world, M::vertex_shader(), shader.clone());
+        let fragment_shader = load_shader(world, M::fragment_shader(), shader);
+
+        let mesh_pipeline = world.resource::<Mesh2dPipeline>();
+        let instance_pipeline = world.resource::<InstancePipeline<T>>();
+        let host_mesh_layout = world.resource::<HostMeshLayouts>();
+        let atlas_layout = world.resource::<InstanceAtlasLayout>();
+        let texture_array_layout = world.resource::<InstanceTextureArrayLayout>();
+        let data_texture_layout = world.resource::<InstanceDataTextureLayout>();
+        let material_layout = world.resource::<InstancedMaterialLayout<M>>();
+        let previous_instances_layout = world.get_resource::<PreviousInstancesLayout>();
+
+        CustomPipeline {
+            vertex_shader,
+            fragment_shader,
+            mesh_pipeline: mesh_pipeline.clone(),
+            instance_pipeline: instance_pipeline.clone(),
+            host_mesh_layout: host_mesh_layout.layout_2d.clone(),
+            atlas_layout: (*atlas_layout).clone(),
+            texture_array_layout: (*texture_array_layout).clone(),
+            data_texture_layout: (*data_texture_layout).clone(),
+            material_layout: material_layout.layout.clone(),
+            previous_instances_layout: previous_instances_layout.map(|layout| (**layout).clone()),
+            sort_key_strategy,
+            marker: PhantomData,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CustomPipelineKey {
+    mesh_key: Mesh2dPipelineKey,
+    /// Set for hosts with [`OpaqueInstances`].
+    opaque: bool,
+    /// Set for hosts with an [`InstanceAlphaMask`].
+    alpha_mask: bool,
+    /// The [`InstanceBlendMode`] of the host, ignored if `opaque` or `alpha_mask` is set.
+    blend_mode: InstanceBlendMode,
+    /// Set for hosts with an [`InstanceAtlas`].
+    atlas: bool,
+    /// Set for hosts with an [`InstanceTextureArray`] and no [`InstanceAtlas`].
+    texture_array: bool,
+    /// Set for hosts with an [`InstanceAtlas`] and [`InstanceSdf`].
+    sdf: bool,
+    /// Set for hosts with an [`InstanceDataTexture`].
+    data_texture: bool,
+    /// Set for hosts with [`PointInstances`].
+    points: bool,
+    /// The extra color targets of the view, see [`InstanceTargets`].
+    targets: [Option<InstanceTarget>; MAX_INSTANCE_TARGETS],
+    /// Set for hosts with [`InstanceMotionVectors`] in views with a motion vector target.
+    previous_instances: bool,
+    /// See [`strip_index_format`].
+    strip_index_format: Option<IndexFormat>,
+}
+
+impl<T: Instance, M: InstancedMaterial> SpecializedMeshPipeline for CustomPipeline<T, M> {
+    type Key = CustomPipelineKey;
+
+    fn specialize(
+        &self,
+        key: Self::Key,
+        layout: &MeshVertexBufferLayout,
+    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        let mut descriptor = self.mesh_pipeline.specialize(key.mesh_key, layout)?;
+        descriptor.primitive.strip_index_format = key.strip_index_format;
+
+        // meshes typically live in bind group 2. because we are using bindgroup 1
+        // we need to add MESH_BINDGROUP_1 shader def so that the bindings are correctly
+        // linked in the shader
+        descriptor
+            .vertex
+            .shader_defs
+            .push("MESH_BINDGROUP_1".into());
+        descriptor.layout[1] = self.host_mesh_layout.clone();
+
+        self.instance_pipeline
+            .specialize(&mut descriptor, &self.vertex_shader);
+        descriptor.fragment.as_mut().unwrap().shader = self.fragment_shader.clone();
+
+        // both take the same bind group
+        let texture = if key.atlas {
+            Some(("INSTANCE_ATLAS", &self.atlas_layout))
+        } else if key.texture_array {
+            Some(("INSTANCE_TEXTURE_ARRAY", &self.texture_array_layout))
+        } else {
+            None
+        };
+        if let Some((shader_def, layout)) = texture {
+            let atlas_bind_group = ShaderDefVal::UInt(
+                "ATLAS_BIND_GROUP".into(),
+                self.instance_pipeline.atlas_bind_group_index() as u32,
+            );
+            descriptor.layout.push(layout.clone());
+            descriptor
+                .vertex
+                .shader_defs
+                .extend([shader_def.into(), atlas_bind_group.clone()]);
+            let fragment = descriptor.fragment.as_mut().unwrap();
+            fragment
+                .shader_defs
+                .extend([shader_def.into(), atlas_bind_group]);
+        }
+        if key.sdf {
+            descriptor
+                .fragment
+                .as_mut()
+                .unwrap()
+                .shader_defs
+                .push("INSTANCE_SDF".into());
+        }
+
+        if key.data_texture {
+            let data_texture_bind_group = ShaderDefVal::UInt(
+                "DATA_TEXTURE_BIND_GROUP".into(),
+                descriptor.layout.len() as u32,
+            );
+            descriptor.layout.push(self.data_texture_layout.clone());
+            descriptor
+                .vertex
+                .shader_defs
+                .extend(["INSTANCE_DATA_TEXTURE".into(), data_texture_bind_group]);
+            // a stride of 0 reads the template instance for every instance
+            for buffer in &mut descriptor.vertex.buffers {
+                if buffer.step_mode == VertexStepMode::Instance {
+                    buffer.array_stride = 0;
+                }
+            }
+        }
+
+        if key.points {
+            descriptor.vertex.shader_defs.push("INSTANCE_POINTS".into());
+        }
+
+        let blend = if key.opaque || key.alpha_mask {
+            BlendState::REPLACE
+        } else {
+            key.blend_mode.blend_state()
+        };
+        if key.alpha_mask {
+            descriptor
+                .fragment
+                .as_mut()
+                .unwrap()
+                .shader_defs
+                .push("INSTANCE_ALPHA_MASK".into());
+        }
+        if !key.opaque
+            && !key.alpha_mask
+            && matches!(
+                key.blend_mode,
+                InstanceBlendMode::Premultiplied | InstanceBlendMode::Multiply
+            )
+        {
+            // the group alpha has to scale the premultiplied color along with the alpha
+            descriptor
+                .fragment
+                .as_mut()
+                .unwrap()
+                .shader_defs
+                .push("INSTANCE_PREMULTIPLIED_ALPHA".into());
+        }
+        for target in descriptor
+            .fragment
+            .as_mut()
+            .unwrap()
+            .targets
+            .iter_mut()
+            .flatten()
+        {
+            target.blend = Some(blend);
+        }
+
+        if key.targets.iter().any(Option::is_some) {
+            let mut shader_defs = vec!["INSTANCE_TARGETS".into()];
+            // the targets written by the built-in shader, at their first location
+            for (shader_def, target) in [
+                ("INSTANCE_ID_LOCATION", InstanceTarget::InstanceIds),
+                ("MOTION_VECTOR_LOCATION", InstanceTarget::MotionVectors),
+            ] {
+                if let Some(index) = key.targets.iter().position(|&other| other == Some(target)) {
+                    shader_defs.push(ShaderDefVal::UInt(shader_def.into(), index as u32 + 1));
+                }
+            }
+            descriptor.vertex.shader_defs.extend(shader_defs.clone());
+            let fragment = descriptor.fragment.as_mut().unwrap();
+            fragment.shader_defs.extend(shader_defs);
+            fragment
+                .targets
+                .extend(key.targets.iter().flatten().map(|target| {
+                    Some(ColorTargetState {
+                        format: target.format(),
+                        // integer formats, like the one of instance ids, can't be blended
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    })
+                }));
+        }
+
+        if let Some(material_layout) = &self.material_layout {
+            let material_bind_group =
+                ShaderDefVal::UInt("MATERIAL_BIND_GROUP".into(), descriptor.layout.len() as u32);
+            descriptor.layout.push(material_layout.clone());
+            descriptor
+                .vertex
+                .shader_defs
+                .push(material_bind_group.clone());
+            let fragment = descriptor.fragment.as_mut().unwrap();
+            fragment.shader_defs.push(material_bind_group);
+        }
+
+        if let Some(previous_instances_layout) = self
+            .previous_instances_layout
+            .as_ref()
+            .filter(|_| key.previous_instances)
+        {
+            let previous_bind_group = ShaderDefVal::UInt(
+                "PREVIOUS_INSTANCES_BIND_GROUP".into(),
+                descriptor.layout.len() as u32,
+            );
+            descriptor.layout.push(previous_instances_layout.clone());
+            descriptor
+                .vertex
+                .shader_defs
+                .extend(["INSTANCE_MOTION_VECTORS".into(), previous_bind_group]);
+        }
+
+        Ok(descriptor)
+    }
+}
+
+type DrawCustom<T, M> = (
+    SetItemPipeline,
+    SetMesh2dViewBindGroup<0>,
+    SetHostMeshBindGroup<1, T>,
+    SetInstanceStorageBindGroup<2, T>,
+    SetInstanceAtlasBindGroup<T>,
+    SetInstanceDataTextureBindGroup<T>,
+    SetInstancedMaterialBindGroup<T, M>,
+    SetPreviousInstancesBindGroup<T, M>,
+    DrawMeshInstanced<T>,
+);
+
+/// Binds the host's own mesh uniform in place of bevy's mesh bind group, see [`HostMeshBinding`].
+pub struct SetHostMeshBindGroup<const I: usize, T>(PhantomData<T>);
+
+impl<P: PhaseItem, const I: usize, T: Instance> RenderCommand<P> for SetHostMeshBindGroup<I, T> {
+    type Param = ();
+    type ViewQuery = ();
+    type ItemQuery = Read<InstanceBuffer<T>>;
+
+    #[inline]
+    fn render<'w>(
+        _item: &P,
+        _view: (),
+        instance_buffer: Option<&'w InstanceBuffer<T>>,
+        _param: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(host_mesh) = instance_buffer.and_then(|buffer| buffer.host_mesh.as_ref()) else {
+            return RenderCommandResult::Failure;
+        };
+        let dynamic_offsets: &[u32] = if host_mesh.dynamic_offset { &[0] } else { &[] };
+        pass.set_bind_group(I, &host_mesh.bind_group, dynamic_offsets);
+        RenderCommandResult::Success
+    }
+}
+
+/// Binds the instance storage buffer when the plugin runs in [`InstanceBufferMode::Storage`].
+pub struct SetInstanceStorageBindGroup<const I: usize, T>(PhantomData<T>);
+
+impl<P: PhaseItem, const I: usize, T: Instance> RenderCommand<P>
+    for SetInstanceStorageBindGroup<I, T>
+{
+    type Param = ();
+    type ViewQuery = Entity;
+    type ItemQuery = (
+        Read<InstanceBuffer<T>>,
+        Option<Read<CulledInstanceBuffers<T>>>,
+    );
+
+    #[inline]
+    fn render<'w>(
+        _item: &P,
+        view: Entity,
+        buffers: Option<(&'w InstanceBuffer<T>, Option<&'w CulledInstanceBuffers<T>>)>,
+        _param: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some((instance_buffer, culled_buffers)) = buffers else {
+            return RenderCommandResult::Failure;
+        };
+
+        let bind_group = match culled_buffers.and_then(|culled| culled.get(view)) {
+            Some(culled) => culled.storage_bind_group.as_ref(),
+            None => instance_buffer.storage_bind_group.as_ref(),
+        };
+        if let Some(bind_group) = bind_group {
+            pass.set_bind_group(I, bind_group, &[]);
+        }
+        RenderCommandResult::Success
+    }
+}
+
+pub struct DrawMeshInstanced<T>(PhantomData<T>);
+
+impl<P: PhaseItem, T: Instance> RenderCommand<P> for DrawMeshInstanced<T> {
+    type Param = (SRes<RenderAssets<Mesh>>, HostMeshes<'static>);
+    type ViewQuery = Entity;
+    type ItemQuery = (
+        Read<InstanceBuffer<T>>,
+        Option<Read<CulledInstanceBuffers<T>>>,
+        Option<Read<InstanceLodBatches>>,
+        Option<Read<InstanceMeshRanges>>,
+        Option<Read<InstanceDataTextureBindGroup>>,
+    );
+
+    #[inline]
+    fn render<'w>(
+        item: &P,
+        view: Entity,
+        buffers: Option<(
+            &'w InstanceBuffer<T>,
+            Option<&'w CulledInstanceBuffers<T>>,
+            Option<&'w InstanceLodBatches>,
+            Option<&'w InstanceMeshRanges>,
+            Option<&'w InstanceDataTextureBindGroup>,
+        )>,
+        (meshes, host_meshes): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let meshes = meshes.into_inner();
+        let Some((mesh_asset_id, _)) = host_meshes.get(item.entity()) else {
+            return RenderCommandResult::Failure;
+        };
+        let gpu_mesh = match meshes.get(mesh_asset_id) {
+            Some(gpu_mesh) => gpu_mesh,
+            None => return RenderCommandResult::Failure,
+        };
+
+        let (instance_buffer, culled_buffers, lod_batches, mesh_ranges, data_texture) =
+            match buffers {
+                Some(buffers) => buffers,
+                None => return RenderCommandResult::Failure,
+            };
+
+        // every instance reads the template at the start of the buffer and its own texels
+        if let Some(data_texture) = data_texture {
+            pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+            if instance_buffer.storage_bind_group.is_none() {
+                pass.set_vertex_buffer(1, instance_buffer.buffer.slice(..));
+            }
+            draw_mesh(pass, gpu_mesh, None, None, 0..data_texture.instances);
+            return RenderCommandResult::Success;
+        }
+
+        if let Some(mesh_ranges) = mesh_ranges {
+            pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+            let length = instance_buffer.length as u32;
+            for range in &mesh_ranges.0 {
+                let instances = range.instances.start.min(length)..range.instances.end.min(length);
+                if instances.is_empty() {
+                    continue;
+                }
+                draw_instances(pass, instance_buffer, instances, |pass, instances| {
+                    draw_mesh(
+                        pass,
+                        gpu_mesh,
+                        None,
+                        Some(range.elements.clone()),
+                        instances,
+                    );
+                });
+            }
+            return RenderCommandResult::Success;
+        }
+
+        if let Some(lod_batches) = lod_batches {
+            for (lod_mesh_asset_id, instances) in &lod_batches.0 {
+                // the pipeline was specialized for the layout of the host's mesh
+                let Some(lod_mesh) = meshes.get(*lod_mesh_asset_id) else {
+                    continue;
+                };
+                if lod_mesh.layout != gpu_mesh.layout
+                    || lod_mesh.primitive_topology != gpu_mesh.primitive_topology
+                    || strip_index_format(lod_mesh) != strip_index_format(gpu_mesh)
+                {
+                    continue;
+                }
+
+                pass.set_vertex_buffer(0, lod_mesh.vertex_buffer.slice(..));
+                draw_instances(
+                    pass,
+                    instance_buffer,
+                    instances.clone(),
+                    |pass, instances| {
+                        draw_mesh(pass, lod_mesh, None, None, instances);
+                    },
+                );
+            }
+            return RenderCommandResult::Success;
+        }
+
+        pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+
+        if instance_buffer.is_split() {
+            let instances = 0..instance_buffer.length as u32;
+            draw_instances(pass, instance_buffer, instances, |pass, instances| {
+                draw_mesh(pass, gpu_mesh, None, None, instances);
+            });
+            return RenderCommandResult::Success;
+        }
+
+        let culled = culled_buffers.and_then(|culled| culled.get(view));
+        let indirect = culled
+            .map(|culled| &culled.indirect)
+            .or(instance_buffer.indirect.as_ref());
+
+        if instance_buffer.storage_bind_group.is_none() {
+            let buffer = culled.map_or(&instance_buffer.buffer, |culled| &culled.output);
+            pass.set_vertex_buffer(1, buffer.slice(..));
+        }
+
+        draw_mesh(
+            pass,
+            gpu_mesh,
+            indirect,
+            None,
+            0..instance_buffer.length as u32,
+        );
+        RenderCommandResult::Success
+    }
+}
+
+/// Binds the non-empty range `instances` of the instance buffers and calls `draw` with the
+/// instances to draw, once per buffer the range spans.
+fn draw_instances<'w, T: Instance>(
+    pass: &mut TrackedRenderPass<'w>,
+    instance_buffer: &'w InstanceBuffer<T>,
+    instances: Range<u32>,
+    mut draw: impl FnMut(&mut TrackedRenderPass<'w>, Range<u32>),
+) {
+    let instances = instances.start as usize..instances.end as usize;
+    for (index, instances) in instance_buffer.split(instances) {
+        let (buffer, storage_bind_group) = instance_buffer.buffer(index);
+        let instances = instances.start as u32..instances.end as u32;
+        match storage_bind_group {
+            // read at the instance index in the shader, the first buffer is bound by
+            // `SetInstanceStorageBindGroup`
+            Some(bind_group) => {
+                if instance_buffer.is_split() {
+                    pass.set_bind_group(2, bind_group, &[]);
+                }
+                draw(pass, instances);
+            }
+            // offset the buffer instead of the instances, as a first instance other than 0 isn't
+            // supported by all backends
+            None => {
+                let offset = instances.start as u64 * T::ARRAY_STRIDE;
+                pass.set_vertex_buffer(1, buffer.slice(offset..));
+                draw(pass, 0..instances.len() as u32);
+            }
+        }
+    }
+}
+
+/// Why the instances of a host can't be drawn on a mesh with `layout`, with or without
+/// `morph_targets`, `None` if they can. The instances take the place of bevy's skinning and
+/// morphing: the host mesh binding replaces the joint matrices and morph weights, and the instance
+/// attributes take the locations of the joints.
+fn unsupported_mesh(layout: &MeshVertexBufferLayout, morph_targets: bool) -> Option<&'static str> {
+    if layout.contains(Mesh::ATTRIBUTE_JOINT_INDEX) || layout.contains(Mesh::ATTRIBUTE_JOINT_WEIGHT)
+    {
+        Some("joints for skinning")
+    } else if morph_targets {
+        Some("morph targets")
+    } else {
+        None
+    }
+}
+
+/// The mesh attributes bevy's 2D mesh pipeline passes on to the vertex shader, each taking a
+/// vertex attribute location if the mesh has it.
+pub(crate) const MESH_2D_ATTRIBUTES: [MeshVertexAttribute; 5] = [
+    Mesh::ATTRIBUTE_POSITION,
+    Mesh::ATTRIBUTE_NORMAL,
+    Mesh::ATTRIBUTE_UV_0,
+    Mesh::ATTRIBUTE_TANGENT,
+    Mesh::ATTRIBUTE_COLOR,
+];
+
+/// The mesh attributes bevy's 3D mesh pipeline passes on to the vertex shader, without the joints
+/// of skinned meshes, see [`unsupported_mesh`].
+pub(crate) const MESH_3D_ATTRIBUTES: [MeshVertexAttribute; 6] = [
+    Mesh::ATTRIBUTE_POSITION,
+    Mesh::ATTRIBUTE_NORMAL,
+    Mesh::ATTRIBUTE_UV_0,
+    Mesh::ATTRIBUTE_UV_1,
+    Mesh::ATTRIBUTE_TANGENT,
+    Mesh::ATTRIBUTE_COLOR,
+];
+
+/// Logs once per host that the instance attributes don't fit next to the `mesh_attributes` of
+/// `gpu_mesh`, which would fail to create the pipeline. Only vertex buffers are limited, any mesh
+/// fits in storage mode.
+pub(crate) fn log_missing_locations<T: Instance>(
+    entity: Entity,
+    gpu_mesh: &GpuMesh,
+    mesh_attributes: &[MeshVertexAttribute],
+    instance_pipeline: &InstancePipeline<T>,
+    logged_errors: &mut HashSet<String>,
+) -> bool {
+    let mesh_attributes = mesh_attributes
+        .iter()
+        .filter(|attribute| gpu_mesh.layout.contains(attribute.id))
+        .count();
+    if instance_pipeline.fits_locations(mesh_attributes) {
+        return false;
+    }
+    let err = format!(
+        "The mesh of instanced host {entity:?} has {mesh_attributes} vertex attributes, which \
+         leave too few of the {} vertex attribute locations for the {} instance attributes, its \
+         instances aren't drawn. Remove attributes from the mesh or use \
+         InstanceBufferMode::Storage",
+        instance_pipeline.max_vertex_attributes,
+        T::attributes().len()
+    );
+    if !logged_errors.contains(&err) {
+        error!("{}", err);
+        logged_errors.insert(err);
+    }
+    true
+}
+
+/// Logs once per host that its mesh can't be drawn instanced, see [`unsupported_mesh`].
+pub(crate) fn log_unsupported_mesh(
+    entity: Entity,
+    layout: &MeshVertexBufferLayout,
+    morph_targets: bool,
+    logged_errors: &mut HashSet<String>,
+) -> bool {
+    let Some(reason) = unsupported_mesh(layout, morph_targets) else {
+        return false;
+    };
+    let err = format!(
+        "The mesh of instanced host {entity:?} has {reason}, which instancing doesn't support, \
+         its instances aren't drawn"
+    );
+    if !logged_errors.contains(&err) {
+        error!("{}", err);
+        logged_errors.insert(err);
+    }
+    true
+}
+
+/// The index format of an indexed mesh with a strip topology. Set on the pipeline, the largest
+/// index of the format restarts the strip on every backend, instead of only on those that always
+/// restart, so one mesh can hold several separate strips.
+fn strip_index_format(gpu_mesh: &GpuMesh) -> Option<IndexFormat> {
+    match (&gpu_mesh.buffer_info, gpu_mesh.primitive_topology) {
+        (
+            GpuBufferInfo::Indexed { index_format, .. },
+            PrimitiveTopology::LineStrip | PrimitiveTopology::TriangleStrip,
+        ) => Some(*index_format),
+        _ => None,
+    }
+}
+
+/// Draws `gpu_mesh` with the bound instances, from the `indirect` arguments if given. Draws only
+/// `elements` of the indices, or of the vertices for meshes without indices, if given, the same
+/// for every topology.
+fn draw_mesh<'w>(
+    pass: &mut TrackedRenderPass<'w>,
+    gpu_mesh: &'w GpuMesh,
+    indirect: Option<&'w Buffer>,
+    elements: Option<Range<u32>>,
+    instances: Range<u32>,
+) {
+    let clamp = |count: u32| match &elements {
+        Some(elements) => elements.start.min(count)..elements.end.min(count),
+        None => 0..count,
+    };
+
+    match &gpu_mesh.buffer_info {
+        GpuBufferInfo::Indexed {
+            buffer,
+            index_format,
+            count,
+        } => {
+            pass.set_index_buffer(buffer.slice(..), 0, *index_format);
+            match indirect {
+                Some(indirect) => pass.draw_indexed_indirect(indirect, 0),
+                None => pass.draw_indexed(clamp(*count), 0, instances),
+            }
+        }
+        GpuBufferInfo::NonIndexed => match indirect {
+            Some(indirect) => pass.draw_indirect(indirect, 0),
+            None => pass.draw(clamp(gpu_mesh.vertex_count), instances),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::render::mesh::VertexAttributeValues;
+
+    const PER_BUFFER: usize = 1 << 20;
+
+    /// Runs the sizing of [`prepare_instance_buffers`] over the instance counts of consecutive
+    /// frames, returning the number of buffers created.
+    fn created_buffers(counts: impl IntoIterator<Item = usize>) -> usize {
+        let (mut capacity, mut low_usage) = (0, 0);
+        let mut created = 0;
+        for required in counts {
+            if capacity > 0 {
+                low_usage = low_usage_frames(required, capacity, low_usage);
+            }
+            if let Some(resized) = buffer_capacity(required, capacity, low_usage, PER_BUFFER) {
+                assert!(resized >= required);
+                (capacity, low_usage) = (resized, 0);
+                created += 1;
+            }
+        }
+        created
+    }
+
+    fn ticks(tick: u32) -> Option<InstanceChangeTicks> {
+        Some(InstanceChangeTicks {
+            instances: Tick::new(tick),
+            visibility: None,
+        })
+    }
+
+    #[test]
+    fn static_instances_upload_once() {
+        let instances: InstanceMaterialData<InstanceData> =
+            (0..100).map(|_| InstanceData::default()).collect();
+        let mut uploaded = None;
+        let mut uploads = 0;
+        for _ in 0..1000 {
+            if instance_upload(&instances, ticks(1), uploaded) != InstanceUpload::Skip {
+                uploads += 1;
+                uploaded = ticks(1);
+            }
+        }
+        assert_eq!(uploads, 1);
+    }
+
+    #[test]
+    fn edited_instances_upload_range() {
+        let mut instances: InstanceMaterialData<InstanceData> =
+            (0..100).map(|_| InstanceData::default()).collect();
+        assert_eq!(
+            instance_upload(&instances, ticks(1), None),
+            InstanceUpload::All
+        );
+
+        // cleared once extracted
+        instances.dirty = 0..0;
+        instances.set(10, InstanceData::default());
+        instances.set(20, InstanceData::default());
+        assert_eq!(
+            instance_upload(&instances, ticks(2), ticks(1)),
+            InstanceUpload::Range(10..21)
+        );
+        // a buffer that doesn't hold the last frame's instances is written whole
+        assert_eq!(
+            instance_upload(&instances, ticks(2), None),
+            InstanceUpload::All
+        );
+
+        // untracked edit
+        instances[0].rotation = 1.0;
+        assert_eq!(
+            instance_upload(&instances, ticks(3), ticks(2)),
+            InstanceUpload::All
+        );
+    }
+
+    #[test]
+    fn skinned_mesh_is_unsupported() {
+        let mut mesh = Mesh::from(Rectangle::new(1.0, 1.0));
+        let rigid = mesh.get_mesh_vertex_buffer_layout();
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_JOINT_INDEX,
+            VertexAttributeValues::Uint16x4(vec![[0; 4]; 4]),
+        );
+        mesh.insert_attribute(Mesh::ATTRIBUTE_JOINT_WEIGHT, vec![[1.0, 0.0, 0.0, 0.0]; 4]);
+        let skinned = mesh.get_mesh_vertex_buffer_layout();
+
+        let host = Entity::from_raw(7);
+        let mut logged_errors = HashSet::new();
+        assert!(!log_unsupported_mesh(
+            host,
+            &rigid,
+            false,
+            &mut logged_errors
+        ));
+        assert!(logged_errors.is_empty());
+
+        assert!(log_unsupported_mesh(
+            host,
+            &skinned,
+            false,
+            &mut logged_errors
+        ));
+        assert_eq!(
+            logged_errors.iter().collect::<Vec<_>>(),
+            [&format!(
+                "The mesh of instanced host {host:?} has joints for skinning, which instancing \
+                 doesn't support, its instances aren't drawn"
+            )]
+        );
+        // logged once per host
+        assert!(log_unsupported_mesh(
+            host,
+            &skinned,
+            false,
+            &mut logged_errors
+        ));
+        assert_eq!(logged_errors.len(), 1);
+
+        assert!(log_unsupported_mesh(host, &rigid, true, &mut logged_errors));
+        assert!(logged_errors
+            .iter()
+            .any(|err| err.contains("has morph targets")));
+    }
+
+    #[test]
+    fn step_up_matches_next_up() {
+        let keys = [
+            0.0,
+            -0.0,
+            1.0,
+            -1.0,
+            f32::from_bits(1),
+            -f32::from_bits(3),
+            f32::MIN_POSITIVE,
+            -f32::MIN_POSITIVE,
+            f32::MAX,
+            f32::MIN,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            12345.678,
+        ];
+        for key in keys {
+            for steps in 0..20 {
+                let expected = (0..steps).fold(key, |key, _| key.next_up());
+                assert_eq!(step_up(key, steps), expected, "{key} up {steps}");
+            }
+        }
+        assert!(step_up(f32::NAN, 3).is_nan());
+        assert_eq!(step_up(1.0, u32::MAX), f32::INFINITY);
+    }
+
+    #[test]
+    fn buffer_capacity_doubles() {
+        assert_eq!(buffer_capacity(100, 0, 0, PER_BUFFER), Some(128));
+        assert_eq!(buffer_capacity(128, 128, 0, PER_BUFFER), None);
+        assert_eq!(buffer_capacity(129, 128, 0, PER_BUFFER), Some(256));
+        // whole buffers past the limit
+        assert_eq!(
+            buffer_capacity(PER_BUFFER + 1, PER_BUFFER, 0, PER_BUFFER),
+            Some(2 * PER_BUFFER)
+        );
+        assert_eq!(
+            buffer_capacity(2 * PER_BUFFER + 1, 2 * PER_BUFFER, 0, PER_BUFFER),
+            Some(3 * PER_BUFFER)
+        );
+    }
+
+    #[test]
+    fn low_usage_below_a_quarter() {
+        assert_eq!(low_usage_frames(31, 128, 5), 6);
+        assert_eq!(low_usage_frames(32, 128, 5), 0);
+        assert_eq!(low_usage_frames(128, 128, 5), 0);
+    }
+
+    #[test]
+    fn buffer_capacity_shrinks_after_frames() {
+        assert_eq!(
+            buffer_capacity(10, 1024, SHRINK_AFTER_FRAMES - 1, PER_BUFFER),
+            None
+        );
+        // keeps headroom
+        assert_eq!(
+            buffer_capacity(10, 1024, SHRINK_AFTER_FRAMES, PER_BUFFER),
+            Some(32)
+        );
+    }
+
+    #[test]
+    fn fluctuating_counts_create_few_buffers() {
+        // a few frames of low usage never reach the shrink
+        let alternating = (0..1000).map(|frame| if frame % 2 == 0 { 1000 } else { 10 });
+        assert_eq!(created_buffers(alternating), 1);
+        let bursts = (0..1000).map(|frame| if frame % 50 < 40 { 10 } else { 1000 });
+        assert_eq!(created_buffers(bursts), 2);
+
+        // growing and shrinking one instance per frame reallocates logarithmically
+        let ramp = (1..=10_000).chain((1..10_000).rev());
+        assert!(created_buffers(ramp.clone()) <= 2 * 15);
+        assert!(created_buffers(ramp.clone().chain(ramp)) <= 4 * 15);
+    }
+}
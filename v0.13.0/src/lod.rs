@@ -0,0 +1,113 @@
+//! Per instance level of detail.
+//!
+//! Every instance of a host entity with [`InstanceLods`] picks a mesh by its distance to the
+//! camera, see [`LodConfig`]. The instances are grouped by level, keeping their order within a
+//! level, and each level is drawn with one draw call from its slice of the instance buffer.
+//! The meshes of all levels have to share the vertex layout and topology of the host's mesh,
+//! levels with a different mesh layout are skipped. Hosts with LODs are never GPU culled.
+
+use bevy::{
+    math::Affine3A,
+    prelude::*,
+    render::{
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+    },
+};
+use std::ops::Range;
+
+use crate::{HostMeshes, Instance, InstanceMaterialData, PrimaryView};
+
+/// The meshes of the LOD levels after the first one, which is the host's own mesh.
+#[derive(Component, Clone, Default, ExtractComponent)]
+pub struct InstanceLods(pub Vec<Handle<Mesh>>);
+
+/// Distances at which instances switch to the next LOD level.
+///
+/// An instance uses level `i` once its distance to the camera reaches `thresholds[i - 1]`,
+/// limited to the levels its host has. The thresholds have to be ascending.
+#[derive(Resource, Clone, Default, Debug, ExtractResource)]
+pub struct LodConfig {
+    pub thresholds: Vec<f32>,
+}
+
+pub struct LodPlugin;
+
+impl Plugin for LodPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LodConfig>().add_plugins((
+            ExtractComponentPlugin::<InstanceLods>::default(),
+            ExtractResourcePlugin::<LodConfig>::default(),
+        ));
+    }
+}
+
+/// The instance range drawn with each mesh, in level order.
+#[derive(Component)]
+pub struct InstanceLodBatches(pub Vec<(AssetId<Mesh>, Range<u32>)>);
+
+/// Groups the instances of hosts with [`InstanceLods`] by level and inserts their
+/// [`InstanceLodBatches`].
+///
+/// All views share the instance buffer, so the levels are picked by the distance to the
+/// [`PrimaryView`].
+pub fn batch_lod_instances<T: Instance>(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut InstanceMaterialData<T>, &InstanceLods)>,
+    primary_view: PrimaryView,
+    host_meshes: HostMeshes,
+    lod_config: Res<LodConfig>,
+    mut warned: Local<bool>,
+) {
+    if query.is_empty() {
+        return;
+    }
+
+    let Some(bounds) = T::BOUNDS else {
+        if !*warned {
+            warn!("InstanceLods requires an instance type with BOUNDS, drawing the first level");
+            *warned = true;
+        }
+        return;
+    };
+
+    let Some(view) = primary_view.get() else {
+        return;
+    };
+    let view_position = view.transform.translation();
+
+    for (entity, mut instances, lods) in &mut query {
+        let Some((mesh_asset_id, transform)) = host_meshes.get(entity) else {
+            continue;
+        };
+
+        let model = Affine3A::from(transform);
+        let max_level = lods.0.len();
+        let level = |instance: &T| {
+            let (position, _) = bounds.read(instance);
+            let distance = model.transform_point3(position).distance(view_position);
+            lod_config
+                .thresholds
+                .partition_point(|threshold| *threshold <= distance)
+                .min(max_level)
+        };
+
+        // stable, so sorted instances stay sorted within a level
+        instances.sort_by_cached_key(level);
+
+        let mut batches = Vec::new();
+        let mut start = 0;
+        while start < instances.len() {
+            let current = level(&instances[start]);
+            let end = start + instances[start..].partition_point(|i| level(i) == current);
+            let mesh = match current {
+                0 => mesh_asset_id,
+                _ => lods.0[current - 1].id(),
+            };
+            batches.push((mesh, start as u32..end as u32));
+            start = end;
+        }
+
+        commands.entity(entity).insert(InstanceLodBatches(batches));
+    }
+}
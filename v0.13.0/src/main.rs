@@ -1,335 +1,327 @@
-//! A shader that renders a mesh multiple times in one draw call.
+//! Demo of the instancing plugins: a grid of instances built from child entities, with an
+//! atlas, picking and bloom, plus a row of affine instances. Run with `--3d` for the 3D scene.
+//! Press G to outline the instances.
 
 use bevy::{
-    asset::AssetMetaCheck, core_pipeline::core_2d::Transparent2d, ecs::{
-        query::QueryItem,
-        system::{lifetimeless::*, SystemParamItem},
-    }, prelude::*, render::{
-        batching::NoAutomaticBatching,
-        extract_component::{ExtractComponent, ExtractComponentPlugin},
-        mesh::{GpuBufferInfo, MeshVertexBufferLayout},
-        render_asset::RenderAssets,
-        render_phase::{
-            AddRenderCommand, DrawFunctions, PhaseItem, RenderCommand, RenderCommandResult,
-            RenderPhase, SetItemPipeline, TrackedRenderPass,
-        },
-        render_resource::*,
-        renderer::RenderDevice,
-        view::{ExtractedView, NoFrustumCulling},
-        Render, RenderApp, RenderSet,
-    }, sprite::{
-        MaterialMesh2dBundle, Mesh2d, Mesh2dHandle, Mesh2dPipeline, Mesh2dPipelineKey,
-        RenderMesh2dInstances, SetMesh2dBindGroup, SetMesh2dViewBindGroup,
-    }, utils::FloatOrd
+    asset::AssetMetaCheck,
+    core_pipeline::bloom::BloomSettings,
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
 };
 use bytemuck::{Pod, Zeroable};
+use instancing::{
+    atlas::InstanceAtlas,
+    entity_instances::{EntityInstances, EntityInstancesPlugin, InstanceChild},
+    gizmos::{DebugInstanceGizmos, DebugInstanceGizmosPlugin},
+    picking::{Hovered, InstancedPickingPlugin},
+    InstanceData, InstanceLayout, InstancedMeshBundle, Instancing3dPlugin, InstancingPlugin,
+    OpaqueInstances,
+};
 
 fn main() {
     App::new()
         .insert_resource(AssetMetaCheck::Never)
-        .add_plugins((DefaultPlugins, CustomMaterialPlugin))
-        .add_systems(Startup, setup)
+        .add_plugins((
+            DefaultPlugins,
+            InstancingPlugin::<InstanceData>::default(),
+            InstancingPlugin::<AffineInstanceData>::default(),
+            Instancing3dPlugin::<InstanceData>::default(),
+            EntityInstancesPlugin::<InstanceData, InstancedMaterialChild>::default(),
+            EntityInstancesPlugin::<AffineInstanceData, InstancedMaterialChild>::default(),
+        ))
+        .add_systems(
+            Startup,
+            (setup.run_if(not(scene_3d)), setup_3d.run_if(scene_3d)),
+        )
+        .add_plugins((
+            InstancedPickingPlugin::<InstanceData>::default(),
+            DebugInstanceGizmosPlugin::<InstanceData>::default(),
+        ))
+        .insert_resource(DebugInstanceGizmos {
+            enabled: false,
+            ..default()
+        })
+        .add_systems(
+            Update,
+            (spin_instances, spin_hovered, animate_frames, toggle_gizmos),
+        )
         .run();
 }
 
-fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+) {
     commands
         .spawn((
-            Mesh2dHandle(meshes.add(Rectangle::new(1.0, 1.0))),
-            SpatialBundle::INHERITED_IDENTITY,
-            InstancedMaterialHost::default(),
-            // NOTE: Frustum culling is done based on the Aabb of the Mesh and the GlobalTransform.
-            // As the cube is at the origin, if its Aabb moves outside the view frustum, all the
-            // instanced cubes will be culled.
-            // The InstanceMaterialData contains the 'GlobalTransform' information for this custom
-            // instancing, and that is not taken into account with the built-in frustum culling.
-            // We must disable the built-in frustum culling by adding the `NoFrustumCulling` marker
-            // component to avoid incorrect culling.
-            NoFrustumCulling,
+            InstancedMeshBundle::<InstanceData>::new(meshes.add(Rectangle::new(1.0, 1.0)), []),
+            EntityInstances::<InstancedMaterialChild>::default(),
+            Hovered::default(),
+            InstanceAtlas {
+                image: images.add(shapes_atlas()),
+                columns: 2,
+                rows: 2,
+            },
+            // NOTE: Frustum culling is done based on the Aabb and the GlobalTransform. The Aabb of
+            // the mesh alone would cull all instances as soon as the quad at the origin leaves the
+            // view frustum, so the plugin replaces it with an Aabb enclosing all instances (see
+            // `update_batch_aabb`). Use `InstancedMeshBundle::with_frustum_culling` to opt out.
+        ))
+        .with_children(|parent| spawn_grid(parent, 1.0));
+
+    commands
+        .spawn((
+            // AffineInstanceData has no position and scale fields to compute the bounds from, so
+            // the bundle disables frustum culling
+            InstancedMeshBundle::<AffineInstanceData>::new(
+                meshes.add(Rectangle::new(1.0, 1.0)),
+                [],
+            ),
+            EntityInstances::<InstancedMaterialChild>::default(),
+            // all instances are fully opaque, so blending can be skipped
+            OpaqueInstances,
         ))
         .with_children(|parent| {
-            (1..=10)
-                .flat_map(|x| (1..=10).map(move |y| (x as f32 / 10.0, y as f32 / 10.0)))
-                .for_each(|(x, y)| {
-                    parent.spawn((
-                        InstancedMaterialChild {
-                            color: Color::hsla(x * 360., y, 0.5, 1.0).as_rgba_f32(),
-                            scale: 1.0,
-                        },
-                        TransformBundle::from_transform(Transform {
-                            translation: Vec3::new(x * 10.0 - 5.0, y * 10.0 - 5.0, 0.0),
-                            ..Default::default()
-                        }),
-                    ));
-                });
+            (1..=10).map(|x| x as f32 / 10.0).for_each(|x| {
+                parent.spawn((
+                    InstancedMaterialChild {
+                        color: Color::hsla(x * 360., 0.8, 0.5, 1.0).as_linear_rgba_f32(),
+                        scale: 1.0,
+                        atlas_index: 0,
+                        frame: 0,
+                        emissive: [0.0; 3],
+                    },
+                    TransformBundle::from_transform(
+                        Transform::from_xyz(x * 10.0 - 5.0, -7.0, 0.0).with_scale(Vec3::new(
+                            0.4 + x,
+                            1.4 - x,
+                            1.0,
+                        )),
+                    ),
+                ));
+            });
         });
 
-    // camera
-    commands.spawn(Camera2dBundle {
-        transform: Transform::from_xyz(0.0, 0.0, 15.0),
-        projection: OrthographicProjection {
-            far: 1000.,
-            near: -1000.,
-            scale: 0.08,
-            ..Default::default()
+    // camera, HDR with bloom so the emissive instances glow
+    commands.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                hdr: true,
+                ..default()
+            },
+            transform: Transform::from_xyz(0.0, 0.0, 15.0),
+            projection: OrthographicProjection {
+                far: 1000.,
+                near: -1000.,
+                scale: 0.08,
+                ..Default::default()
+            },
+            ..default()
         },
-        ..default()
-    });
+        BloomSettings::default(),
+    ));
 }
 
-#[derive(Component, Default, ExtractComponent, Clone)]
-struct InstancedMaterialHost {
-    pub buffer: Vec<InstanceData>,
+/// Whether to show the 3D scene, selected by running with `--3d`.
+fn scene_3d() -> bool {
+    std::env::args().any(|arg| arg == "--3d")
 }
 
-#[derive(Component, Clone)]
-struct InstancedMaterialChild {
-    pub color: [f32; 4],
-    pub scale: f32,
-}
+fn setup_3d(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    commands
+        .spawn((
+            InstancedMeshBundle::<InstanceData, Handle<Mesh>>::new(
+                meshes.add(Cuboid::new(1.0, 1.0, 1.0)),
+                [],
+            ),
+            EntityInstances::<InstancedMaterialChild>::default(),
+        ))
+        .with_children(|parent| spawn_grid(parent, 0.6));
 
-pub struct CustomMaterialPlugin;
+    // camera
+    commands.spawn((
+        Camera3dBundle {
+            camera: Camera {
+                hdr: true,
+                ..default()
+            },
+            transform: Transform::from_xyz(0.5, -9.0, 12.0)
+                .looking_at(Vec3::new(0.5, 0.5, 0.0), Vec3::Y),
+            ..default()
+        },
+        BloomSettings::default(),
+    ));
+}
 
-impl Plugin for CustomMaterialPlugin {
-    fn build(&self, app: &mut App) {
-        app.add_plugins(ExtractComponentPlugin::<InstancedMaterialHost>::default());
-        app.add_systems(Last, prepare_buffer);
+/// Spawns a 10x10 grid of instances in the XY plane.
+fn spawn_grid(parent: &mut ChildBuilder, scale: f32) {
+    (1..=10)
+        .flat_map(|x| (1..=10).map(move |y| (x, y)))
+        .for_each(|(column, row)| {
+            let (x, y) = (column as f32 / 10.0, row as f32 / 10.0);
+            let color = Color::hsla(x * 360., y, 0.5, 1.0).as_linear_rgba_f32();
+            // the diagonal glows, bloom picks up the colors exceeding 1
+            let emissive = match column == row {
+                true => [color[0] * 4.0, color[1] * 4.0, color[2] * 4.0],
+                false => [0.0; 3],
+            };
+            parent.spawn((
+                InstancedMaterialChild {
+                    color,
+                    scale,
+                    atlas_index: (column + row) % 4,
+                    frame: 0,
+                    emissive,
+                },
+                TransformBundle::from_transform(Transform {
+                    translation: Vec3::new(x * 10.0 - 5.0, y * 10.0 - 5.0, 0.0),
+                    ..Default::default()
+                }),
+            ));
+        });
+}
 
-        app.sub_app_mut(RenderApp)
-            .add_render_command::<Transparent2d, DrawCustom>()
-            .init_resource::<SpecializedMeshPipelines<CustomPipeline>>()
-            .add_systems(
-                Render,
-                (
-                    queue_custom.in_set(RenderSet::QueueMeshes),
-                    prepare_instance_buffers.in_set(RenderSet::PrepareResources),
-                ),
-            );
+/// Number of frames in each cell of the [`shapes_atlas`], laid out horizontally.
+const FLIPBOOK_FRAMES: u32 = 4;
+
+/// Generates a 2x2 atlas of white shapes: a square, a circle, a ring and a diamond. Each cell is
+/// a flipbook of [`FLIPBOOK_FRAMES`] frames of its shape growing.
+fn shapes_atlas() -> Image {
+    const FRAME: u32 = 32;
+    const CELL: u32 = FRAME * FLIPBOOK_FRAMES;
+
+    let mut data = Vec::with_capacity((4 * CELL * FRAME * 4) as usize);
+    for y in 0..2 * FRAME {
+        for x in 0..2 * CELL {
+            // position within the frame, from -1 to 1, shrunk for the earlier frames
+            let frame = (x % CELL) / FRAME;
+            let size = 0.55 + 0.15 * frame as f32;
+            let p = ((Vec2::new((x % FRAME) as f32, (y % FRAME) as f32) + 0.5) / FRAME as f32
+                * 2.0
+                - 1.0)
+                / size;
+            let inside = match (y / FRAME) * 2 + x / CELL {
+                0 => p.abs().max_element() < 0.8,
+                1 => p.length() < 0.9,
+                2 => (0.5..0.9).contains(&p.length()),
+                _ => p.x.abs() + p.y.abs() < 0.9,
+            };
+            data.extend([255, 255, 255, if inside { 255 } else { 0 }]);
+        }
     }
 
-    fn finish(&self, app: &mut App) {
-        app.sub_app_mut(RenderApp).init_resource::<CustomPipeline>();
-    }
+    Image::new(
+        Extent3d {
+            width: 2 * CELL,
+            height: 2 * FRAME,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
 }
 
-fn prepare_buffer(
-    mut instanced_materials: Query<(&mut InstancedMaterialHost, &Children)>,
-    instanced_material_children: Query<(&InstancedMaterialChild, &Transform)>,
-) {
-    for (mut instanced_material, children) in &mut instanced_materials {
-        let children = children
-            .iter()
-            .map(|entity| instanced_material_children.get(*entity).unwrap());
-
-        instanced_material.buffer.clear();
-
-        for (child, child_transform) in children {
-            instanced_material.buffer.push(InstanceData {
-                position: child_transform.translation,
-                scale: child.scale,
-                color: child.color,
-            });
-        }
+/// Steps through the flipbook frames, offset per atlas cell.
+fn animate_frames(time: Res<Time>, mut children: Query<&mut InstancedMaterialChild>) {
+    let step = (time.elapsed_seconds() * 6.0) as u32;
+    for mut child in &mut children {
+        child.frame = (step + child.atlas_index) % FLIPBOOK_FRAMES;
     }
 }
 
-#[derive(Clone, Copy, Pod, Zeroable)]
-#[repr(C)]
-struct InstanceData {
-    position: Vec3,
-    scale: f32,
-    color: [f32; 4],
+/// Spins every instance at a speed derived from its position, so that rotation can be seen to
+/// be applied per instance rather than to the batch as a whole.
+fn spin_instances(
+    time: Res<Time>,
+    mut children: Query<&mut Transform, With<InstancedMaterialChild>>,
+) {
+    for mut transform in &mut children {
+        let speed = transform.translation.x - transform.translation.y;
+        transform.rotate_z(speed * 0.2 * time.delta_seconds());
+    }
 }
 
-#[allow(clippy::too_many_arguments)]
-fn queue_custom(
-    transparent_2d_draw_functions: Res<DrawFunctions<Transparent2d>>,
-    custom_pipeline: Res<CustomPipeline>,
-    msaa: Res<Msaa>,
-    mut pipelines: ResMut<SpecializedMeshPipelines<CustomPipeline>>,
-    pipeline_cache: Res<PipelineCache>,
-    meshes: Res<RenderAssets<Mesh>>,
-    render_mesh_instances: Res<RenderMesh2dInstances>,
-    material_meshes: Query<Entity, With<InstancedMaterialHost>>,
-    mut views: Query<(&ExtractedView, &mut RenderPhase<Transparent2d>)>,
+/// Spins the instance under the cursor faster. Instances are built from the children in order.
+fn spin_hovered(
+    time: Res<Time>,
+    hosts: Query<(&Hovered, &Children)>,
+    mut children: Query<&mut Transform, With<InstancedMaterialChild>>,
 ) {
-    let draw_custom = transparent_2d_draw_functions.read().id::<DrawCustom>();
-
-    let msaa_key = Mesh2dPipelineKey::from_msaa_samples(msaa.samples());
-
-    for (view, mut transparent_phase) in &mut views {
-        let view_key = msaa_key | Mesh2dPipelineKey::from_hdr(view.hdr);
-        for entity in &material_meshes {
-            let Some(mesh_instance) = render_mesh_instances.get(&entity) else {
-                continue;
-            };
-            let Some(mesh) = meshes.get(mesh_instance.mesh_asset_id) else {
-                continue;
-            };
-            let key =
-                view_key | Mesh2dPipelineKey::from_primitive_topology(mesh.primitive_topology);
-
-            let pipeline =
-                pipelines.specialize(&pipeline_cache, &custom_pipeline, key, &mesh.layout);
-
-            let pipeline = match pipeline {
-                Ok(id) => id,
-                Err(err) => {
-                    error!("{}", err);
-                    continue;
-                }
-            };
-
-            let mesh_z = mesh_instance.transforms.transform.translation.z;
-
-            transparent_phase.add(Transparent2d {
-                sort_key: FloatOrd(mesh_z),
-                entity: entity,
-                pipeline,
-                draw_function: draw_custom,
-                batch_range: 0..1,
-                dynamic_offset: None,
-            });
+    for (hovered, host_children) in &hosts {
+        let Some(child) = hovered.0.and_then(|index| host_children.get(index)) else {
+            continue;
+        };
+        if let Ok(mut transform) = children.get_mut(*child) {
+            transform.rotate_z(4.0 * time.delta_seconds());
         }
     }
 }
 
-#[derive(Component)]
-pub struct InstanceBuffer {
-    buffer: Buffer,
-    length: usize,
-}
-
-fn prepare_instance_buffers(
-    mut commands: Commands,
-    query: Query<(Entity, &InstancedMaterialHost)>,
-    render_device: Res<RenderDevice>,
-) {
-    for (entity, instances) in &query {
-        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
-            label: Some("instance data buffer"),
-            contents: bytemuck::cast_slice(instances.buffer.as_slice()),
-            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
-        });
-
-        commands.entity(entity).insert(InstanceBuffer {
-            buffer,
-            length: instances.buffer.len(),
-        });
+fn toggle_gizmos(keys: Res<ButtonInput<KeyCode>>, mut gizmos: ResMut<DebugInstanceGizmos>) {
+    if keys.just_pressed(KeyCode::KeyG) {
+        gizmos.enabled = !gizmos.enabled;
     }
 }
 
-#[derive(Resource)]
-pub struct CustomPipeline {
-    shader: Handle<Shader>,
-    mesh_pipeline: Mesh2dPipeline,
+#[derive(Component, Clone)]
+struct InstancedMaterialChild {
+    /// Linear RGBA, see [`InstanceColorSpace`](instancing::InstanceColorSpace).
+    pub color: [f32; 4],
+    pub scale: f32,
+    pub atlas_index: u32,
+    /// Flipbook frame within the atlas cell.
+    pub frame: u32,
+    pub emissive: [f32; 3],
 }
 
-impl FromWorld for CustomPipeline {
-    fn from_world(world: &mut World) -> Self {
-        let asset_server = world.resource::<AssetServer>();
-        let shader = asset_server.load("shaders/instancing.wgsl");
-
-        let mesh_pipeline = world.resource::<Mesh2dPipeline>();
-
-        CustomPipeline {
-            shader,
-            mesh_pipeline: mesh_pipeline.clone(),
+impl InstanceChild<InstanceData> for InstancedMaterialChild {
+    fn instance(&self, transform: &Transform) -> InstanceData {
+        InstanceData {
+            position: transform.translation,
+            scale: self.scale,
+            color: self.color,
+            rotation: transform.rotation.to_euler(EulerRot::ZYX).0,
+            atlas_index: self.atlas_index,
+            uv_offset: Vec2::new(self.frame as f32 / FLIPBOOK_FRAMES as f32, 0.0),
+            uv_scale: Vec2::new(1.0 / FLIPBOOK_FRAMES as f32, 1.0),
+            emissive: self.emissive,
+            ..default()
         }
     }
 }
 
-impl SpecializedMeshPipeline for CustomPipeline {
-    type Key = Mesh2dPipelineKey;
-
-    fn specialize(
-        &self,
-        key: Self::Key,
-        layout: &MeshVertexBufferLayout,
-    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
-        let mut descriptor = self.mesh_pipeline.specialize(key, layout)?;
-
-        // meshes typically live in bind group 2. because we are using bindgroup 1
-        // we need to add MESH_BINDGROUP_1 shader def so that the bindings are correctly
-        // linked in the shader
-        descriptor
-            .vertex
-            .shader_defs
-            .push("MESH_BINDGROUP_1".into());
-
-        descriptor.vertex.shader = self.shader.clone();
-        descriptor.vertex.buffers.push(VertexBufferLayout {
-            array_stride: std::mem::size_of::<InstanceData>() as u64,
-            step_mode: VertexStepMode::Instance,
-            attributes: vec![
-                VertexAttribute {
-                    format: VertexFormat::Float32x4,
-                    offset: 0,
-                    shader_location: 3, // shader locations 0-2 are taken up by Position, Normal and UV attributes
-                },
-                VertexAttribute {
-                    format: VertexFormat::Float32x4,
-                    offset: VertexFormat::Float32x4.size(),
-                    shader_location: 4,
-                },
-            ],
-        });
-        descriptor.fragment.as_mut().unwrap().shader = self.shader.clone();
-        Ok(descriptor)
-    }
+/// Instance with a full 2D affine transform, allowing non-uniform scale and shear in addition to
+/// rotation. Drawn by `instancing_affine.wgsl`.
+#[derive(Clone, Copy, Pod, Zeroable, InstanceLayout)]
+#[repr(C)]
+#[instance(shader = "shaders/instancing_affine.wgsl")]
+struct AffineInstanceData {
+    /// Columns of the 2x2 linear part of the transform, the x axis followed by the y axis.
+    linear: [f32; 4],
+    translation: Vec3,
+    color: [f32; 4],
 }
 
-type DrawCustom = (
-    SetItemPipeline,
-    SetMesh2dViewBindGroup<0>,
-    SetMesh2dBindGroup<1>,
-    DrawMeshInstanced,
-);
-
-pub struct DrawMeshInstanced;
-
-impl<P: PhaseItem> RenderCommand<P> for DrawMeshInstanced {
-    type Param = (SRes<RenderAssets<Mesh>>, SRes<RenderMesh2dInstances>);
-    type ViewQuery = ();
-    type ItemQuery = Read<InstanceBuffer>;
-
-    #[inline]
-    fn render<'w>(
-        item: &P,
-        _view: (),
-        instance_buffer: Option<&'w InstanceBuffer>,
-        (meshes, render_mesh_instances): SystemParamItem<'w, '_, Self::Param>,
-        pass: &mut TrackedRenderPass<'w>,
-    ) -> RenderCommandResult {
-        let Some(mesh_instance) = render_mesh_instances.get(&item.entity()) else {
-            return RenderCommandResult::Failure;
-        };
-        let gpu_mesh = match meshes.into_inner().get(mesh_instance.mesh_asset_id) {
-            Some(gpu_mesh) => gpu_mesh,
-            None => return RenderCommandResult::Failure,
-        };
-
-        let instance_buffer = match instance_buffer {
-            Some(instance_buffer) => instance_buffer,
-            None => return RenderCommandResult::Failure,
-        };
-
-        pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
-        pass.set_vertex_buffer(1, instance_buffer.buffer.slice(..));
-
-        match &gpu_mesh.buffer_info {
-            GpuBufferInfo::Indexed {
-                buffer,
-                index_format,
-                count,
-            } => {
-                pass.set_index_buffer(buffer.slice(..), 0, *index_format);
-                pass.draw_indexed(0..*count, 0, 0..instance_buffer.length as u32);
-            }
-            GpuBufferInfo::NonIndexed => {
-                pass.draw(0..gpu_mesh.vertex_count, 0..instance_buffer.length as u32);
-            }
+impl InstanceChild<AffineInstanceData> for InstancedMaterialChild {
+    fn instance(&self, transform: &Transform) -> AffineInstanceData {
+        let matrix =
+            Mat3::from_quat(transform.rotation) * Mat3::from_diagonal(transform.scale * self.scale);
+
+        AffineInstanceData {
+            linear: [
+                matrix.x_axis.x,
+                matrix.x_axis.y,
+                matrix.y_axis.x,
+                matrix.y_axis.y,
+            ],
+            translation: transform.translation,
+            color: self.color,
         }
-        RenderCommandResult::Success
     }
 }
@@ -0,0 +1,178 @@
+//! User supplied shaders and bindings for the 2D pipeline.
+//!
+//! A [`InstancingPlugin`](crate::InstancingPlugin) draws the hosts that carry its
+//! [`InstancedMaterial`] component. The material's bind group follows the instance storage, atlas
+//! and data texture bind groups, its index is passed to the shaders as the `MATERIAL_BIND_GROUP` shader def.
+
+use bevy::{
+    ecs::system::{lifetimeless::*, SystemParamItem},
+    prelude::*,
+    render::{
+        render_asset::RenderAssets,
+        render_phase::{PhaseItem, RenderCommand, RenderCommandResult, TrackedRenderPass},
+        render_resource::{BindGroup, BindGroupLayout, ShaderRef},
+        renderer::RenderDevice,
+        Extract, ExtractSchedule, Render, RenderApp, RenderSet,
+    },
+};
+use std::marker::PhantomData;
+
+use crate::{
+    atlas::InstanceAtlasBindGroup, data_texture::InstanceDataTexture, Instance, InstancePipeline,
+};
+
+/// Shaders and bindings used to draw the instances of host entities carrying this component.
+///
+/// Every method has a default reproducing the built-in material, [`DefaultInstancedMaterial`].
+pub trait InstancedMaterial: Component + Clone {
+    /// The vertex shader, [`ShaderRef::Default`] uses [`Instance::shader`].
+    fn vertex_shader() -> ShaderRef {
+        ShaderRef::Default
+    }
+
+    /// The fragment shader, [`ShaderRef::Default`] uses [`Instance::shader`].
+    fn fragment_shader() -> ShaderRef {
+        ShaderRef::Default
+    }
+
+    /// Layout of the material's bind group, `None` if the material has no bindings.
+    fn bind_group_layout(_render_device: &RenderDevice) -> Option<BindGroupLayout> {
+        None
+    }
+
+    /// Creates the bind group of a host, returning `None` until its resources are ready.
+    fn bind_group(
+        &self,
+        _layout: &BindGroupLayout,
+        _render_device: &RenderDevice,
+        _images: &RenderAssets<Image>,
+    ) -> Option<BindGroup> {
+        None
+    }
+}
+
+/// The built-in material, drawing the instances with [`Instance::shader`] and no extra bindings.
+#[derive(Component, Clone, Copy, Default, Debug)]
+pub struct DefaultInstancedMaterial;
+
+impl InstancedMaterial for DefaultInstancedMaterial {}
+
+pub struct InstancedMaterialPlugin<M: InstancedMaterial>(PhantomData<M>);
+
+impl<M: InstancedMaterial> Default for InstancedMaterialPlugin<M> {
+    fn default() -> Self {
+        InstancedMaterialPlugin(PhantomData)
+    }
+}
+
+impl<M: InstancedMaterial> Plugin for InstancedMaterialPlugin<M> {
+    fn build(&self, app: &mut App) {
+        app.sub_app_mut(RenderApp)
+            .add_systems(ExtractSchedule, extract_materials::<M>)
+            .add_systems(
+                Render,
+                prepare_material_bind_groups::<M>.in_set(RenderSet::PrepareBindGroups),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.sub_app_mut(RenderApp)
+            .init_resource::<InstancedMaterialLayout<M>>();
+    }
+}
+
+/// Layout of the bind group of `M`, if it has one.
+#[derive(Resource)]
+pub struct InstancedMaterialLayout<M: InstancedMaterial> {
+    pub layout: Option<BindGroupLayout>,
+    marker: PhantomData<M>,
+}
+
+impl<M: InstancedMaterial> FromWorld for InstancedMaterialLayout<M> {
+    fn from_world(world: &mut World) -> Self {
+        InstancedMaterialLayout {
+            layout: M::bind_group_layout(world.resource::<RenderDevice>()),
+            marker: PhantomData,
+        }
+    }
+}
+
+fn extract_materials<M: InstancedMaterial>(
+    mut commands: Commands,
+    mut previous_len: Local<usize>,
+    query: Extract<Query<(Entity, &M)>>,
+) {
+    let mut values = Vec::with_capacity(*previous_len);
+    for (entity, material) in &query {
+        values.push((entity, material.clone()));
+    }
+    *previous_len = values.len();
+    commands.insert_or_spawn_batch(values);
+}
+
+#[derive(Component)]
+pub struct InstancedMaterialBindGroup<M: InstancedMaterial> {
+    bind_group: BindGroup,
+    marker: PhantomData<M>,
+}
+
+fn prepare_material_bind_groups<M: InstancedMaterial>(
+    mut commands: Commands,
+    query: Query<(Entity, &M)>,
+    material_layout: Res<InstancedMaterialLayout<M>>,
+    images: Res<RenderAssets<Image>>,
+    render_device: Res<RenderDevice>,
+) {
+    let Some(layout) = &material_layout.layout else {
+        return;
+    };
+
+    for (entity, material) in &query {
+        if let Some(bind_group) = material.bind_group(layout, &render_device, &images) {
+            commands
+                .entity(entity)
+                .insert(InstancedMaterialBindGroup::<M> {
+                    bind_group,
+                    marker: PhantomData,
+                });
+        }
+    }
+}
+
+/// Binds the bind group of the material `M`, after the atlas and data texture bind groups if there
+/// are any. Skips
+/// hosts whose material bind group isn't ready yet.
+pub struct SetInstancedMaterialBindGroup<T, M>(PhantomData<(T, M)>);
+
+impl<P: PhaseItem, T: Instance, M: InstancedMaterial> RenderCommand<P>
+    for SetInstancedMaterialBindGroup<T, M>
+{
+    type Param = (SRes<InstancePipeline<T>>, SRes<InstancedMaterialLayout<M>>);
+    type ViewQuery = ();
+    type ItemQuery = (
+        Has<InstanceAtlasBindGroup>,
+        Has<InstanceDataTexture>,
+        Option<Read<InstancedMaterialBindGroup<M>>>,
+    );
+
+    #[inline]
+    fn render<'w>(
+        _item: &P,
+        _view: (),
+        bind_groups: Option<(bool, bool, Option<&'w InstancedMaterialBindGroup<M>>)>,
+        (instance_pipeline, material_layout): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        if material_layout.layout.is_none() {
+            return RenderCommandResult::Success;
+        }
+
+        let Some((atlas, data_texture, Some(bind_group))) = bind_groups else {
+            return RenderCommandResult::Failure;
+        };
+        let index =
+            instance_pipeline.atlas_bind_group_index() + atlas as usize + data_texture as usize;
+        pass.set_bind_group(index, &bind_group.bind_group, &[]);
+        RenderCommandResult::Success
+    }
+}
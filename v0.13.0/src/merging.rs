@@ -0,0 +1,164 @@
+//! Draws many small hosts that share a mesh in a single draw call.
+//!
+//! Before the hosts are queued, the instances of all extracted host entities with
+//! [`MergeInstances`] that share a mesh are moved into the one with the lowest entity id, the
+//! leader, and the others are left empty and not drawn. On the way, the instances are moved from
+//! the local space of their host into the one of the leader: their position, their scale and their
+//! rotation around the Z axis, found at the [`Instance::BOUNDS`] and
+//! [`Instance::ROTATION_OFFSET`] of the instance type, so hosts rotated around other axes or
+//! scaled unevenly are distorted.
+//!
+//! The batch is drawn with everything else of the leader, its material, atlas, blend mode and
+//! layer, and frustum culled with its bounds, so only merge hosts that are drawn alike, and
+//! disable [`InstanceFrustumCulling`] on them unless they are close together. [`MergedHosts`] maps
+//! the instances of a batch back to their hosts. Hosts with
+//! [`InterpolateInstances`](crate::interpolation::InterpolateInstances) aren't merged, as their
+//! previous instances are matched by index.
+//!
+//! [`InstanceFrustumCulling`]: crate::bounds::InstanceFrustumCulling
+
+use bevy::{
+    math::Affine3A, prelude::*, render::extract_component::ExtractComponent, utils::HashMap,
+};
+use std::{marker::PhantomData, ops::Range};
+
+use crate::{
+    interpolation::PreviousInstances, HostMeshes, Instance, InstanceBounds, InstanceMaterialData,
+};
+
+/// Merges the instances of this host entity with those of the other hosts with this component
+/// that share its mesh, see the [module docs](self).
+#[derive(Component, Clone, Copy, Default, ExtractComponent)]
+pub struct MergeInstances;
+
+/// The hosts merged into each batch of instances of `T` this frame, in the render world.
+#[derive(Resource)]
+pub struct MergedHosts<T: Instance> {
+    /// The hosts of each batch by its leader, with the range of their instances in the batch.
+    batches: HashMap<Entity, Vec<(Entity, Range<usize>)>>,
+    marker: PhantomData<T>,
+}
+
+impl<T: Instance> Default for MergedHosts<T> {
+    fn default() -> Self {
+        MergedHosts {
+            batches: HashMap::default(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Instance> MergedHosts<T> {
+    /// The hosts merged into the batch drawn by `leader`, including itself, with the range of
+    /// their instances in the batch. `None` if `leader` draws no merged batch.
+    pub fn batch(&self, leader: Entity) -> Option<&[(Entity, Range<usize>)]> {
+        self.batches.get(&leader).map(Vec::as_slice)
+    }
+
+    /// The host the instance at `index` in the batch drawn by `leader` came from, and its index
+    /// among the extracted instances of that host.
+    pub fn host_of(&self, leader: Entity, index: usize) -> Option<(Entity, usize)> {
+        self.batch(leader)?
+            .iter()
+            .find(|(_, range)| range.contains(&index))
+            .map(|(host, range)| (*host, index - range.start))
+    }
+
+    pub(crate) fn contains(&self, leader: Entity) -> bool {
+        self.batches.contains_key(&leader)
+    }
+}
+
+/// Moves the instances of hosts with [`MergeInstances`] into the leader of their mesh.
+#[allow(clippy::type_complexity)]
+pub fn merge_instances<T: Instance>(
+    mut hosts: Query<
+        (Entity, &mut InstanceMaterialData<T>),
+        (With<MergeInstances>, Without<PreviousInstances<T>>),
+    >,
+    host_meshes: HostMeshes,
+    mut merged: ResMut<MergedHosts<T>>,
+    mut warned: Local<bool>,
+) {
+    merged.batches.clear();
+
+    let Some(bounds) = T::BOUNDS else {
+        if !hosts.is_empty() && !*warned {
+            warn!("MergeInstances requires an instance type with BOUNDS, drawing the hosts one by one");
+            *warned = true;
+        }
+        return;
+    };
+
+    let mut groups: HashMap<AssetId<Mesh>, Vec<Entity>> = HashMap::default();
+    for (entity, instances) in &hosts {
+        if instances.is_empty() {
+            continue;
+        }
+        if let Some((mesh_asset_id, _)) = host_meshes.get(entity) {
+            groups.entry(mesh_asset_id).or_default().push(entity);
+        }
+    }
+
+    for mut group in groups.into_values().filter(|group| group.len() > 1) {
+        // the same leader every frame, so its buffer is reused
+        group.sort_unstable();
+        let leader = group[0];
+        let Some((_, leader_transform)) = host_meshes.get(leader) else {
+            continue;
+        };
+        let to_leader = Affine3A::from(leader_transform).inverse();
+
+        let mut batch = std::mem::take(&mut **hosts.get_mut(leader).unwrap().1);
+        let mut ranges = vec![(leader, 0..batch.len())];
+        for &host in &group[1..] {
+            let Some((_, transform)) = host_meshes.get(host) else {
+                continue;
+            };
+            let offset = to_leader * Affine3A::from(transform);
+            let (_, mut instances) = hosts.get_mut(host).unwrap();
+
+            let start = batch.len();
+            batch.extend(
+                instances
+                    .iter()
+                    .map(|instance| move_instance(instance, &offset, bounds)),
+            );
+            instances.clear();
+            ranges.push((host, start..batch.len()));
+        }
+
+        **hosts.get_mut(leader).unwrap().1 = batch;
+        merged.batches.insert(leader, ranges);
+    }
+}
+
+/// Applies the translation, the scale of the x and y axes and the rotation around the Z axis of
+/// `offset` to the position, scale and rotation of `instance`.
+fn move_instance<T: Instance>(instance: &T, offset: &Affine3A, bounds: InstanceBounds) -> T {
+    let mut instance = *instance;
+    let (position, _) = bounds.read(&instance);
+    let axis_scale = Vec2::new(
+        offset.matrix3.x_axis.length(),
+        offset.matrix3.y_axis.length(),
+    );
+    let scale = bounds.read_scale_2d(&instance) * axis_scale;
+
+    let bytes = bytemuck::bytes_of_mut(&mut instance);
+    let position_offset = bounds.position_offset as usize;
+    bytes[position_offset..position_offset + 12]
+        .copy_from_slice(bytemuck::bytes_of(&offset.transform_point3(position)));
+    let scale_offset = bounds.scale_offset as usize;
+    match bounds.scale_components {
+        1 => bytes[scale_offset..scale_offset + 4].copy_from_slice(bytemuck::bytes_of(&scale.x)),
+        _ => bytes[scale_offset..scale_offset + 8].copy_from_slice(bytemuck::bytes_of(&scale)),
+    }
+
+    if let Some(rotation) = T::ROTATION_OFFSET {
+        let rotation = rotation as usize..rotation as usize + 4;
+        let angle: f32 = bytemuck::pod_read_unaligned(&bytes[rotation.clone()]);
+        let offset_angle = offset.matrix3.x_axis.y.atan2(offset.matrix3.x_axis.x);
+        bytes[rotation].copy_from_slice(&(angle + offset_angle).to_ne_bytes());
+    }
+    instance
+}
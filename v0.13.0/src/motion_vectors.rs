@@ -0,0 +1,221 @@
+//! Per-instance motion vectors for temporal effects like motion blur.
+//!
+//! A 2D camera with an [`InstanceTarget::MotionVectors`] target writes how far every instance
+//! moved on screen since the last frame. The render world keeps the instances each host entity
+//! with [`InstanceMotionVectors`] drew in the last frame, and uploads them into a second buffer the
+//! vertex shader reads the previous position, scale and rotation of every instance from. Hosts
+//! without it write zero motion.
+//!
+//! Only the motion of the instances is captured, moving the camera or the host itself adds no
+//! motion. Instances are matched by their index in the instance buffer, so hosts with sorted,
+//! culled, LOD split or generated instances, or instances drawn from several buffers, get wrong
+//! vectors. Needs the built-in shader or a shader reading the previous instances the same way.
+//!
+//! [`InstanceTarget::MotionVectors`]: crate::targets::InstanceTarget::MotionVectors
+
+use bevy::{
+    ecs::system::{lifetimeless::*, SystemParamItem},
+    prelude::*,
+    render::{
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        render_phase::{PhaseItem, RenderCommand, RenderCommandResult, TrackedRenderPass},
+        render_resource::{binding_types::*, *},
+        renderer::{RenderDevice, RenderQueue},
+        RenderApp,
+    },
+    utils::HashMap,
+};
+use std::marker::PhantomData;
+
+use crate::{
+    atlas::InstanceAtlasBindGroup,
+    data_texture::InstanceDataTexture,
+    is_downlevel,
+    material::InstancedMaterialLayout,
+    targets::{InstanceTarget, InstanceTargets, ViewInstanceTargets},
+    Instance, InstanceMaterialData, InstancePipeline, InstancedMaterial,
+};
+
+/// Keeps the instances of this 2D host entity of the last frame, so their motion is written into
+/// motion vector targets, see the [module docs](self).
+#[derive(Component, Clone, Copy, Default, ExtractComponent)]
+pub struct InstanceMotionVectors;
+
+pub struct MotionVectorsPlugin;
+
+impl Plugin for MotionVectorsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ExtractComponentPlugin::<InstanceMotionVectors>::default());
+    }
+
+    fn finish(&self, app: &mut App) {
+        PreviousInstancesLayout::init(&mut app.sub_app_mut(RenderApp).world);
+    }
+}
+
+/// Layout of the bind group holding the instances of the last frame.
+#[derive(Resource, Clone, Deref)]
+pub struct PreviousInstancesLayout(BindGroupLayout);
+
+impl PreviousInstancesLayout {
+    /// Inserts the layout unless the adapter lacks storage buffers, like WebGL2, in which case
+    /// hosts with [`InstanceMotionVectors`] write zero motion.
+    pub(crate) fn init(world: &mut World) {
+        let render_device = world.resource::<RenderDevice>();
+        if world.contains_resource::<Self>() || is_downlevel(render_device) {
+            return;
+        }
+        let layout = render_device.create_bind_group_layout(
+            "previous instances layout",
+            &BindGroupLayoutEntries::single(
+                ShaderStages::VERTEX,
+                storage_buffer_read_only_sized(false, None),
+            ),
+        );
+        world.insert_resource(PreviousInstancesLayout(layout));
+    }
+}
+
+struct PreviousInstances<T: Instance> {
+    /// The instances drawn in the last frame.
+    instances: Vec<T>,
+    buffer: Option<Buffer>,
+    bind_group: Option<BindGroup>,
+}
+
+/// The instances of the last frame of every host with [`InstanceMotionVectors`].
+#[derive(Resource)]
+pub struct PreviousFrameInstances<T: Instance> {
+    hosts: HashMap<Entity, PreviousInstances<T>>,
+}
+
+impl<T: Instance> Default for PreviousFrameInstances<T> {
+    fn default() -> Self {
+        PreviousFrameInstances {
+            hosts: HashMap::default(),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct PreviousInstancesBindGroup(BindGroup);
+
+/// Uploads the instances of the last frame of every host with [`InstanceMotionVectors`] and
+/// keeps the instances of this frame for the next one.
+pub fn prepare_previous_instances<T: Instance>(
+    mut commands: Commands,
+    query: Query<(Entity, &InstanceMaterialData<T>), With<InstanceMotionVectors>>,
+    layout: Option<Res<PreviousInstancesLayout>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    mut previous_frame: ResMut<PreviousFrameInstances<T>>,
+    mut warned: Local<bool>,
+) {
+    let Some(layout) = layout else {
+        if !query.is_empty() && !*warned {
+            warn!("InstanceMotionVectors requires an adapter supporting storage buffers, writing zero motion");
+            *warned = true;
+        }
+        return;
+    };
+
+    // hosts that weren't extracted lose their history, they are drawn without motion once shown
+    previous_frame
+        .hosts
+        .retain(|entity, _| query.contains(*entity));
+
+    for (entity, instances) in &query {
+        if instances.is_empty() {
+            continue;
+        }
+        let previous = previous_frame
+            .hosts
+            .entry(entity)
+            .or_insert_with(|| PreviousInstances {
+                instances: instances.to_vec(),
+                buffer: None,
+                bind_group: None,
+            });
+
+        // instances added since the last frame haven't moved yet
+        let mut uploaded = std::mem::take(&mut previous.instances);
+        uploaded.truncate(instances.len());
+        uploaded.extend_from_slice(&instances[uploaded.len()..]);
+        let bytes: &[u8] = bytemuck::cast_slice(&uploaded);
+
+        if previous
+            .buffer
+            .as_ref()
+            .is_none_or(|buffer| buffer.size() < bytes.len() as u64)
+        {
+            let buffer = render_device.create_buffer(&BufferDescriptor {
+                label: Some("previous instance buffer"),
+                size: (bytes.len() as u64).next_power_of_two(),
+                usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            previous.bind_group = Some(render_device.create_bind_group(
+                "previous instances bind group",
+                &layout,
+                &BindGroupEntries::single(buffer.as_entire_binding()),
+            ));
+            previous.buffer = Some(buffer);
+        }
+        render_queue.write_buffer(previous.buffer.as_ref().unwrap(), 0, bytes);
+
+        previous.instances.clone_from(instances);
+        commands.entity(entity).insert(PreviousInstancesBindGroup(
+            previous.bind_group.clone().unwrap(),
+        ));
+    }
+}
+
+/// Binds the instances of the last frame after the material bind group, in views with a motion
+/// vector target. Skips hosts with [`InstanceMotionVectors`] whose bind group isn't ready yet,
+/// and binds nothing on adapters without storage buffers.
+pub struct SetPreviousInstancesBindGroup<T, M>(PhantomData<(T, M)>);
+
+impl<P: PhaseItem, T: Instance, M: InstancedMaterial> RenderCommand<P>
+    for SetPreviousInstancesBindGroup<T, M>
+{
+    type Param = (
+        SRes<InstancePipeline<T>>,
+        SRes<InstancedMaterialLayout<M>>,
+        Option<SRes<PreviousInstancesLayout>>,
+    );
+    type ViewQuery = (Option<Read<InstanceTargets>>, Has<ViewInstanceTargets>);
+    type ItemQuery = (
+        Has<InstanceMotionVectors>,
+        Has<InstanceDataTexture>,
+        Has<InstanceAtlasBindGroup>,
+        Option<Read<PreviousInstancesBindGroup>>,
+    );
+
+    #[inline]
+    fn render<'w>(
+        _item: &P,
+        (targets, has_targets): (Option<&'w InstanceTargets>, bool),
+        bind_groups: Option<(bool, bool, bool, Option<&'w PreviousInstancesBindGroup>)>,
+        (instance_pipeline, material_layout, previous_layout): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        // without MSAA, views draw into their targets
+        let motion_vectors = has_targets
+            && previous_layout.is_some()
+            && targets
+                .is_some_and(|targets| targets.position(InstanceTarget::MotionVectors).is_some());
+        // hosts with a data texture write zero motion
+        let Some((true, false, atlas, bind_group)) = bind_groups.filter(|_| motion_vectors) else {
+            return RenderCommandResult::Success;
+        };
+        let Some(bind_group) = bind_group else {
+            return RenderCommandResult::Failure;
+        };
+
+        let index = instance_pipeline.atlas_bind_group_index()
+            + atlas as usize
+            + material_layout.layout.is_some() as usize;
+        pass.set_bind_group(index, &bind_group.0, &[]);
+        RenderCommandResult::Success
+    }
+}
@@ -0,0 +1,147 @@
+//! Mixed opaque and transparent instances within one 3D host.
+//!
+//! The instances of a host entity with [`MixedOpacity`] are split by the alpha of their
+//! [`Instance::COLOR_OFFSET`] color: fully opaque instances are moved to the front of the instance
+//! buffer and drawn in the [`Opaque3d`] phase with depth writes and without blending, the others
+//! follow them and are drawn in the [`Transparent3d`] phase with alpha blending and without depth
+//! writes. Transparent instances then blend over everything opaque, regardless of the order the
+//! instances were pushed in. The split keeps the order of the instances within either batch, so
+//! [`SortInstances::ViewDistance`](crate::SortInstances::ViewDistance) still orders the
+//! transparent instances among themselves.
+//!
+//! Only 3D hosts are split, bevy 0.13 draws 2D meshes without a depth buffer. Split hosts are drawn
+//! without their [`InstanceLods`](crate::lod::InstanceLods) and
+//! [`InstanceMeshRanges`](crate::InstanceMeshRanges), and are never GPU culled.
+//!
+//! [`Opaque3d`]: bevy::core_pipeline::core_3d::Opaque3d
+//! [`Transparent3d`]: bevy::core_pipeline::core_3d::Transparent3d
+
+use bevy::{
+    ecs::system::{lifetimeless::*, SystemParamItem},
+    pbr::RenderMeshInstances,
+    prelude::*,
+    render::{
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        render_asset::RenderAssets,
+        render_phase::{PhaseItem, RenderCommand, RenderCommandResult, TrackedRenderPass},
+    },
+};
+use std::{marker::PhantomData, ops::Range};
+
+use crate::{
+    draw_instances, draw_mesh, HostMeshes, Instance, InstanceBuffer, InstanceMaterialData,
+};
+
+/// Draws the opaque instances of this 3D host entity with depth writes and the transparent ones
+/// blended on top, see the [module docs](self). Requires an instance type with
+/// [`Instance::COLOR_OFFSET`], and is ignored by hosts with generated instances.
+#[derive(Component, Clone, Copy, Default, ExtractComponent)]
+pub struct MixedOpacity;
+
+pub struct MixedOpacityPlugin;
+
+impl Plugin for MixedOpacityPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ExtractComponentPlugin::<MixedOpacity>::default());
+    }
+}
+
+/// The instances drawn in the opaque and in the transparent phase.
+#[derive(Component)]
+pub struct InstanceOpacityBatches {
+    pub opaque: Range<u32>,
+    pub transparent: Range<u32>,
+}
+
+/// Moves the opaque instances of hosts with [`MixedOpacity`] in front of the transparent ones and
+/// inserts their [`InstanceOpacityBatches`].
+pub fn batch_opacity_instances<T: Instance>(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut InstanceMaterialData<T>), With<MixedOpacity>>,
+    render_mesh_instances: Res<RenderMeshInstances>,
+    mut warned: Local<bool>,
+) {
+    if query.is_empty() {
+        return;
+    }
+
+    let Some(offset) = T::COLOR_OFFSET else {
+        if !*warned {
+            warn!("MixedOpacity requires an instance type with COLOR_OFFSET, drawing unsplit");
+            *warned = true;
+        }
+        return;
+    };
+
+    let alpha_offset = offset as usize + 12;
+    let transparent = |instance: &T| {
+        let bytes = &bytemuck::bytes_of(instance)[alpha_offset..alpha_offset + 4];
+        bytemuck::pod_read_unaligned::<f32>(bytes) < 1.0
+    };
+
+    for (entity, mut instances) in &mut query {
+        // 2D hosts aren't split
+        if !render_mesh_instances.contains_key(&entity) {
+            continue;
+        }
+
+        // stable, so sorted instances stay sorted within either batch
+        if !instances.is_sorted_by_key(transparent) {
+            instances.sort_by_key(transparent);
+        }
+
+        let opaque = instances.partition_point(|instance| !transparent(instance)) as u32;
+        commands.entity(entity).insert(InstanceOpacityBatches {
+            opaque: 0..opaque,
+            transparent: opaque..instances.len() as u32,
+        });
+    }
+}
+
+/// Draws the opaque, or with `TRANSPARENT` the transparent, instances of a host with
+/// [`InstanceOpacityBatches`].
+pub struct DrawOpacityBatch<T, const TRANSPARENT: bool>(PhantomData<T>);
+
+impl<P: PhaseItem, T: Instance, const TRANSPARENT: bool> RenderCommand<P>
+    for DrawOpacityBatch<T, TRANSPARENT>
+{
+    type Param = (SRes<RenderAssets<Mesh>>, HostMeshes<'static>);
+    type ViewQuery = ();
+    type ItemQuery = (Read<InstanceBuffer<T>>, Read<InstanceOpacityBatches>);
+
+    #[inline]
+    fn render<'w>(
+        item: &P,
+        _view: (),
+        buffers: Option<(&'w InstanceBuffer<T>, &'w InstanceOpacityBatches)>,
+        (meshes, host_meshes): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let meshes = meshes.into_inner();
+        let Some((mesh_asset_id, _)) = host_meshes.get(item.entity()) else {
+            return RenderCommandResult::Failure;
+        };
+        let Some(gpu_mesh) = meshes.get(mesh_asset_id) else {
+            return RenderCommandResult::Failure;
+        };
+        let Some((instance_buffer, batches)) = buffers else {
+            return RenderCommandResult::Failure;
+        };
+
+        let instances = match TRANSPARENT {
+            false => &batches.opaque,
+            true => &batches.transparent,
+        };
+        let length = instance_buffer.length as u32;
+        let instances = instances.start.min(length)..instances.end.min(length);
+        if instances.is_empty() {
+            return RenderCommandResult::Success;
+        }
+
+        pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+        draw_instances(pass, instance_buffer, instances, |pass, instances| {
+            draw_mesh(pass, gpu_mesh, None, None, instances);
+        });
+        RenderCommandResult::Success
+    }
+}
@@ -0,0 +1,168 @@
+//! CPU side picking of 2D instances.
+//!
+//! An instance is hit when the point lies within the 2D bounds of the host's mesh, moved, scaled
+//! and rotated by the instance. This needs [`Instance::BOUNDS`] and takes
+//! [`Instance::ROTATION_OFFSET`] and [`Instance::ANCHOR_OFFSET`] into account if present, so
+//! nothing has to be read back from the GPU.
+
+use bevy::{
+    prelude::*, render::primitives::Aabb, sprite::Mesh2dHandle, transform::TransformSystem,
+    window::PrimaryWindow,
+};
+use std::marker::PhantomData;
+
+use crate::{Instance, InstanceMaterialData, InstanceVisibility};
+
+/// The index of the instance of this host entity under the cursor of the primary window, kept up
+/// to date by [`InstancedPickingPlugin`].
+#[derive(Component, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct Hovered(pub Option<usize>);
+
+pub struct InstancedPickingPlugin<T: Instance>(PhantomData<T>);
+
+impl<T: Instance> Default for InstancedPickingPlugin<T> {
+    fn default() -> Self {
+        InstancedPickingPlugin(PhantomData)
+    }
+}
+
+impl<T: Instance> Plugin for InstancedPickingPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            update_hovered::<T>.after(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// Returns the index of the topmost instance containing `point`, given in world space.
+///
+/// `mesh_bounds` are the bounds of the host's mesh and `transform` the transform of the host.
+/// Instances with the highest `position.z` are on top, followed by the ones drawn last. Instances
+/// hidden by `visibility` are ignored.
+pub fn pick_instance<T: Instance>(
+    instances: &[T],
+    visibility: Option<&InstanceVisibility>,
+    mesh_bounds: &Aabb,
+    transform: &GlobalTransform,
+    point: Vec2,
+) -> Option<usize> {
+    let bounds = T::BOUNDS?;
+
+    let local = transform
+        .affine()
+        .inverse()
+        .transform_point3(point.extend(0.0))
+        .truncate();
+    let center = mesh_bounds.center.truncate();
+    let half_extents = mesh_bounds.half_extents.truncate();
+
+    let mut topmost = None;
+
+    for (index, instance) in instances.iter().enumerate() {
+        if visibility.is_some_and(|visibility| !visibility.get(index).copied().unwrap_or(true)) {
+            continue;
+        }
+
+        let (position, _) = bounds.read(instance);
+        let scale = bounds.read_scale_2d(instance);
+        if scale.cmpeq(Vec2::ZERO).any() {
+            continue;
+        }
+
+        let rotation = T::ROTATION_OFFSET.map_or(0.0, |offset| {
+            let offset = offset as usize;
+            bytemuck::pod_read_unaligned::<f32>(&bytemuck::bytes_of(instance)[offset..offset + 4])
+        });
+
+        let anchor = T::ANCHOR_OFFSET.map_or(Vec2::ZERO, |offset| {
+            let offset = offset as usize;
+            Vec2::from_array(bytemuck::pod_read_unaligned(
+                &bytemuck::bytes_of(instance)[offset..offset + 8],
+            ))
+        });
+
+        // undo the instance transform of the vertex shader: rotate, then scale, then move away
+        // from the anchor
+        let mesh_point =
+            Vec2::from_angle(-rotation).rotate(local - position.truncate()) / scale + anchor;
+        if (mesh_point - center).abs().cmpgt(half_extents).any() {
+            continue;
+        }
+
+        if topmost.is_none_or(|(_, z)| position.z >= z) {
+            topmost = Some((index, position.z));
+        }
+    }
+
+    topmost.map(|(index, _)| index)
+}
+
+#[allow(clippy::type_complexity)]
+fn update_hovered<T: Instance>(
+    mut hosts: Query<(
+        &mut Hovered,
+        &InstanceMaterialData<T>,
+        Option<&InstanceVisibility>,
+        &Mesh2dHandle,
+        &GlobalTransform,
+    )>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    let cursor = windows.get_single().ok().and_then(Window::cursor_position);
+    let point = cursor.and_then(|cursor| {
+        cameras.iter().find_map(|(camera, camera_transform)| {
+            camera.viewport_to_world_2d(camera_transform, cursor)
+        })
+    });
+
+    for (mut hovered, instances, visibility, mesh, transform) in &mut hosts {
+        let mesh_bounds = meshes.get(&mesh.0).and_then(Mesh::compute_aabb);
+
+        let index = match (point, mesh_bounds) {
+            (Some(point), Some(mesh_bounds)) => {
+                pick_instance(instances, visibility, &mesh_bounds, transform, point)
+            }
+            _ => None,
+        };
+        hovered.set_if_neq(Hovered(index));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InstanceData;
+
+    #[test]
+    fn picking_follows_the_anchor() {
+        let unit_quad = Aabb::from_min_max(Vec3::splat(-0.5), Vec3::splat(0.5));
+        let pick = |instance: InstanceData, point: Vec2| {
+            pick_instance(
+                &[instance],
+                None,
+                &unit_quad,
+                &GlobalTransform::IDENTITY,
+                point,
+            )
+        };
+        // anchored at the bottom left corner, the quad covers (0, 0) to (10, 10)
+        let instance = InstanceData {
+            scale: 10.0,
+            anchor: Vec2::new(-0.5, -0.5),
+            ..default()
+        };
+        assert_eq!(pick(instance, Vec2::new(8.0, 8.0)), Some(0));
+        assert_eq!(pick(instance, Vec2::new(-2.0, -2.0)), None);
+
+        // rotated a quarter turn around the anchor, it covers (-10, 0) to (0, 10)
+        let instance = InstanceData {
+            rotation: std::f32::consts::FRAC_PI_2,
+            ..instance
+        };
+        assert_eq!(pick(instance, Vec2::new(-8.0, 8.0)), Some(0));
+        assert_eq!(pick(instance, Vec2::new(8.0, 8.0)), None);
+    }
+}
@@ -0,0 +1,69 @@
+//! The instance types as plain data, shared with code that doesn't use bevy.
+//!
+//! The `instancing_data` crate, re-exported here, defines [`InstanceData`],
+//! [`StretchedInstanceData`], [`CustomInstanceData`], [`PulsingInstanceData`] and
+//! [`DepthInstanceData`] with the layouts of the instance types of this crate, built from glam
+//! types and without bevy. A headless server can fill them and ship their bytes, which a client
+//! casts into instances with `bytemuck::cast_slice`, or convert them one by one with `From`. The
+//! layouts are checked to match at compile time, so a field added to one side only fails the
+//! build instead of garbling the instances.
+
+pub use instancing_data::{
+    glam, CustomInstanceData, DepthInstanceData, InstanceData, PulsingInstanceData,
+    StretchedInstanceData,
+};
+
+/// Converts between an instance type and its plain counterpart, field by field, and checks that
+/// their fields lie at the same offsets. Takes the fields after the ones of [`InstanceData`].
+macro_rules! impl_plain {
+    ($instance:ident { $($field:ident),* $(,)? }) => {
+        impl_plain!(@fields $instance {
+            position,
+            scale,
+            color,
+            rotation,
+            atlas_index,
+            uv_offset,
+            uv_scale,
+            emissive,
+            sort_key,
+            border_color,
+            border_width,
+            corner_radius,
+            anchor,
+            $($field,)*
+        });
+    };
+    (@fields $instance:ident { $($field:ident,)* }) => {
+        const _: () = {
+            assert!(
+                std::mem::size_of::<crate::$instance>() == std::mem::size_of::<$instance>(),
+                concat!("the plain ", stringify!($instance), " has another size")
+            );
+            $(assert!(
+                std::mem::offset_of!(crate::$instance, $field)
+                    == std::mem::offset_of!($instance, $field),
+                concat!("the ", stringify!($field), " of the plain ", stringify!($instance),
+                    " lies at another offset")
+            );)*
+        };
+
+        impl From<$instance> for crate::$instance {
+            fn from(plain: $instance) -> Self {
+                crate::$instance { $($field: plain.$field),* }
+            }
+        }
+
+        impl From<crate::$instance> for $instance {
+            fn from(instance: crate::$instance) -> Self {
+                $instance { $($field: instance.$field),* }
+            }
+        }
+    };
+}
+
+impl_plain!(InstanceData {});
+impl_plain!(StretchedInstanceData {});
+impl_plain!(CustomInstanceData { custom });
+impl_plain!(PulsingInstanceData { pulse });
+impl_plain!(DepthInstanceData { draw_z });
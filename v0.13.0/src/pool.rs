@@ -0,0 +1,202 @@
+//! Recycling of instance buffers across hosts.
+//!
+//! The instance buffers of despawned hosts, and the buffers that hosts outgrew or shrank far below,
+//! are returned to the [`InstanceBufferPool`] instead of being dropped, and new buffers are taken
+//! from it when one of the same size and usages is free. Capacities are rounded up to powers of
+//! two, so bursts of short-lived hosts, like one per explosion, mostly reuse the buffers of the
+//! ones before them instead of allocating and freeing their own. Buffers left unused for a while
+//! are dropped.
+
+use bevy::{
+    prelude::*,
+    render::{
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_resource::{Buffer, BufferAddress, BufferDescriptor, BufferUsages},
+        renderer::RenderDevice,
+        Render, RenderApp, RenderSet,
+    },
+    utils::HashMap,
+};
+use std::marker::PhantomData;
+
+use crate::{
+    diagnostics::InstanceBufferStats, writer::InstanceGenerator, Instance, InstanceBufferCache,
+    InstanceMaterialData, SHRINK_AFTER_FRAMES,
+};
+
+pub struct InstanceBufferPoolPlugin;
+
+impl Plugin for InstanceBufferPoolPlugin {
+    fn build(&self, app: &mut App) {
+        app.sub_app_mut(RenderApp)
+            .init_resource::<InstanceBufferPool>()
+            .add_systems(Render, trim_instance_buffer_pool.in_set(RenderSet::Cleanup));
+    }
+}
+
+/// The free instance buffers of all instance types, by size and usages.
+#[derive(Resource, Default)]
+pub struct InstanceBufferPool {
+    /// The free buffers and the frames since they were released.
+    free: HashMap<(BufferAddress, BufferUsages), Vec<(Buffer, u32)>>,
+    /// Buffers created since the start, because none of the size was free.
+    created: u64,
+}
+
+impl InstanceBufferPool {
+    /// Takes a free buffer of `size` bytes and `usage`, or creates one.
+    pub fn acquire(
+        &mut self,
+        render_device: &RenderDevice,
+        size: BufferAddress,
+        usage: BufferUsages,
+    ) -> Buffer {
+        if let Some((buffer, _)) = self
+            .free
+            .get_mut(&(size, usage))
+            .and_then(|buffers| buffers.pop())
+        {
+            return buffer;
+        }
+        self.created += 1;
+        render_device.create_buffer(&BufferDescriptor {
+            label: Some("instance data buffer"),
+            size,
+            usage,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Returns `buffer` to the pool, its contents are left as they are.
+    pub fn release(&mut self, buffer: Buffer) {
+        self.free
+            .entry((buffer.size(), buffer.usage()))
+            .or_default()
+            .push((buffer, 0));
+    }
+
+    /// Bytes held by the free buffers.
+    pub fn free_bytes(&self) -> u64 {
+        self.free
+            .iter()
+            .map(|(&(size, _), buffers)| size * buffers.len() as u64)
+            .sum()
+    }
+
+    /// Buffers created since the start, because none of the size was free.
+    pub fn created(&self) -> u64 {
+        self.created
+    }
+}
+
+/// Drops the buffers that stayed free for a while, so a burst doesn't hold on to its memory.
+fn trim_instance_buffer_pool(
+    mut pool: ResMut<InstanceBufferPool>,
+    stats: Option<Res<InstanceBufferStats>>,
+) {
+    for buffers in pool.free.values_mut() {
+        buffers.retain_mut(|(_, frames)| {
+            *frames += 1;
+            *frames < SHRINK_AFTER_FRAMES
+        });
+    }
+    pool.free.retain(|_, buffers| !buffers.is_empty());
+
+    if let Some(stats) = stats {
+        stats.record::<InstanceBufferPool>(0, pool.free_bytes(), 0);
+    }
+}
+
+/// The hosts of `T` that were despawned or lost their instances in the main world this frame,
+/// whose buffers are returned to the pool right away instead of being kept for a while like those
+/// of hidden hosts.
+#[derive(Resource)]
+pub(crate) struct RemovedHosts<T: Instance> {
+    hosts: Vec<Entity>,
+    marker: PhantomData<T>,
+}
+
+impl<T: Instance> Default for RemovedHosts<T> {
+    fn default() -> Self {
+        RemovedHosts {
+            hosts: Vec::new(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Instance> Clone for RemovedHosts<T> {
+    fn clone(&self) -> Self {
+        RemovedHosts {
+            hosts: self.hosts.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Instance> ExtractResource for RemovedHosts<T> {
+    type Source = RemovedHosts<T>;
+
+    fn extract_resource(source: &Self::Source) -> Self {
+        source.clone()
+    }
+}
+
+/// Tracks the removed hosts of `T` and recycles their buffers.
+pub(crate) struct RemovedHostsPlugin<T: Instance>(PhantomData<T>);
+
+impl<T: Instance> Default for RemovedHostsPlugin<T> {
+    fn default() -> Self {
+        RemovedHostsPlugin(PhantomData)
+    }
+}
+
+impl<T: Instance> Plugin for RemovedHostsPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RemovedHosts<T>>()
+            .add_plugins(ExtractResourcePlugin::<RemovedHosts<T>>::default())
+            .add_systems(Last, collect_removed_hosts::<T>);
+
+        app.sub_app_mut(RenderApp).add_systems(
+            Render,
+            release_removed_hosts::<T>
+                .in_set(RenderSet::PrepareResources)
+                .before(crate::prepare_instance_buffers::<T>),
+        );
+    }
+}
+
+/// Collects the hosts of this frame, after the components were removed in `Update` or
+/// `PostUpdate`.
+fn collect_removed_hosts<T: Instance>(
+    mut removed_hosts: ResMut<RemovedHosts<T>>,
+    mut removed_instances: RemovedComponents<InstanceMaterialData<T>>,
+    mut removed_generators: RemovedComponents<InstanceGenerator<T>>,
+) {
+    removed_hosts.hosts.clear();
+    removed_hosts
+        .hosts
+        .extend(removed_instances.read().chain(removed_generators.read()));
+}
+
+fn release_removed_hosts<T: Instance>(
+    removed_hosts: Res<RemovedHosts<T>>,
+    mut cache: ResMut<InstanceBufferCache<T>>,
+    mut pool: ResMut<InstanceBufferPool>,
+) {
+    if removed_hosts.hosts.is_empty() {
+        return;
+    }
+    // every slot of an `InstanceBufferRing`
+    let removed: Vec<_> = cache
+        .buffers
+        .keys()
+        .filter(|(entity, _)| removed_hosts.hosts.contains(entity))
+        .copied()
+        .collect();
+    for key in removed {
+        if let Some(instance_buffer) = cache.buffers.remove(&key) {
+            instance_buffer.release(&mut pool);
+        }
+    }
+}
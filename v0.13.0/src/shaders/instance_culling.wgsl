@@ -0,0 +1,74 @@
+// Frustum culls instances and compacts the visible ones, see `culling.rs`.
+
+struct CullParams {
+    // inside facing planes of the view frustum in world space, as (normal, d)
+    planes: array<vec4<f32>, 6>,
+    // transform of the host mesh
+    model: mat4x4<f32>,
+    count: u32,
+    // the following are in units of 4 bytes
+    stride: u32,
+    position_offset: u32,
+    scale_offset: u32,
+    // bounding sphere radius of the mesh, including the scale of `model`
+    radius: f32,
+    // 1 for a uniform scale, 2 for a scale of the x and y axes
+    scale_components: u32,
+    // 1 to write the indices of the visible instances into `visible`
+    write_indices: u32,
+};
+
+// `DrawIndexedIndirect`, the instance count is at the same position in `DrawIndirect`
+struct DrawArgs {
+    index_or_vertex_count: u32,
+    instance_count: atomic<u32>,
+    first_index_or_vertex: u32,
+    base_vertex_or_first_instance: u32,
+    first_instance: u32,
+};
+
+@group(0) @binding(0) var<uniform> params: CullParams;
+@group(0) @binding(1) var<storage, read> instances: array<u32>;
+@group(0) @binding(2) var<storage, read_write> culled: array<u32>;
+@group(0) @binding(3) var<storage, read_write> draw_args: DrawArgs;
+@group(0) @binding(4) var<storage, read_write> visible: array<u32>;
+
+@compute @workgroup_size(64)
+fn cull(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if index >= params.count {
+        return;
+    }
+
+    let base = index * params.stride;
+    let position = base + params.position_offset;
+    let local = vec3<f32>(
+        bitcast<f32>(instances[position]),
+        bitcast<f32>(instances[position + 1u]),
+        bitcast<f32>(instances[position + 2u]),
+    );
+    let scale_index = base + params.scale_offset;
+    var scale = abs(bitcast<f32>(instances[scale_index]));
+    if params.scale_components == 2u {
+        scale = max(scale, abs(bitcast<f32>(instances[scale_index + 1u])));
+    }
+
+    let center = vec4<f32>((params.model * vec4<f32>(local, 1.0)).xyz, 1.0);
+    let radius = params.radius * scale;
+
+    for (var i = 0u; i < 6u; i += 1u) {
+        if dot(params.planes[i], center) + radius <= 0.0 {
+            return;
+        }
+    }
+
+    let visible_index = atomicAdd(&draw_args.instance_count, 1u);
+    if params.write_indices == 1u {
+        visible[visible_index] = index;
+    }
+
+    let slot = visible_index * params.stride;
+    for (var word = 0u; word < params.stride; word += 1u) {
+        culled[slot + word] = instances[base + word];
+    }
+}
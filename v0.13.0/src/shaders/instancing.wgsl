@@ -0,0 +1,490 @@
+#import bevy_sprite::{mesh2d_functions as mesh_functions, mesh2d_view_bindings::{view, globals}}
+
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    @location(0) position: vec3<f32>,
+#ifdef VERTEX_NORMALS
+    @location(1) normal: vec3<f32>,
+#endif
+#ifdef VERTEX_UVS
+    @location(2) uv: vec2<f32>,
+#endif
+#ifdef VERTEX_COLORS
+    // set by bevy's mesh pipeline for meshes with `Mesh::ATTRIBUTE_COLOR`
+    @location(4) color: vec4<f32>,
+#endif
+
+#ifndef INSTANCE_STORAGE
+    @location(#{INSTANCE_LOCATION_3}) i_position: vec3<f32>,
+#ifdef INSTANCE_SCALE_2D
+    @location(#{INSTANCE_LOCATION_4}) i_scale: vec2<f32>,
+#else
+    @location(#{INSTANCE_LOCATION_4}) i_scale: f32,
+#endif
+    @location(#{INSTANCE_LOCATION_5}) i_color: vec4<f32>,
+    @location(#{INSTANCE_LOCATION_6}) i_rotation: f32,
+    @location(#{INSTANCE_LOCATION_7}) i_atlas_index: u32,
+    @location(#{INSTANCE_LOCATION_8}) i_uv_offset: vec2<f32>,
+    @location(#{INSTANCE_LOCATION_9}) i_uv_scale: vec2<f32>,
+    @location(#{INSTANCE_LOCATION_10}) i_emissive: vec3<f32>,
+    @location(#{INSTANCE_LOCATION_12}) i_border_color: vec4<f32>,
+    @location(#{INSTANCE_LOCATION_13}) i_border_width: f32,
+    @location(#{INSTANCE_LOCATION_14}) i_corner_radius: f32,
+    @location(#{INSTANCE_LOCATION_15}) i_anchor: vec2<f32>,
+#ifdef INSTANCE_CUSTOM
+    @location(#{INSTANCE_LOCATION_16}) i_custom: vec4<f32>,
+#endif
+#ifdef INSTANCE_PULSE
+    @location(#{INSTANCE_LOCATION_16}) i_pulse: vec3<f32>,
+#endif
+#endif
+};
+
+struct Instance {
+    position: vec3<f32>,
+    // z is only scaled by a uniform scale
+    scale: vec3<f32>,
+    color: vec4<f32>,
+    rotation: f32,
+    atlas_index: u32,
+    uv_offset: vec2<f32>,
+    uv_scale: vec2<f32>,
+    emissive: vec3<f32>,
+    border_color: vec4<f32>,
+    border_width: f32,
+    corner_radius: f32,
+    anchor: vec2<f32>,
+#ifdef INSTANCE_CUSTOM
+    custom: vec4<f32>,
+#endif
+#ifdef INSTANCE_PULSE
+    // amplitude, frequency and phase
+    pulse: vec3<f32>,
+#endif
+};
+
+// Mirrors `HostParams` in `lib.rs`, bound along with the mesh of the host.
+struct HostParams {
+    // multiplies the alpha of all instances, see `InstanceGroupAlpha`
+    group_alpha: f32,
+    // fragments below it are discarded with INSTANCE_ALPHA_MASK, see `InstanceAlphaMask`
+    alpha_cutoff: f32,
+};
+
+@group(1) @binding(1) var<uniform> host: HostParams;
+
+// Mirrors the memory layout of `InstanceData`, or `StretchedInstanceData` with INSTANCE_SCALE_2D,
+// `CustomInstanceData` with INSTANCE_CUSTOM, `PulsingInstanceData` with INSTANCE_PULSE and
+// `DepthInstanceData` with INSTANCE_DRAW_Z.
+// Arrays are used instead of vectors because `vec3`/`vec4` members would be 16 byte aligned.
+struct InstanceData {
+    position: array<f32, 3>,
+#ifdef INSTANCE_SCALE_2D
+    scale: array<f32, 2>,
+#else
+    scale: f32,
+#endif
+    color: array<f32, 4>,
+    rotation: f32,
+    atlas_index: u32,
+    uv_offset: array<f32, 2>,
+    uv_scale: array<f32, 2>,
+    emissive: array<f32, 3>,
+    sort_key: f32,
+    border_color: array<f32, 4>,
+    border_width: f32,
+    corner_radius: f32,
+    anchor: array<f32, 2>,
+#ifdef INSTANCE_CUSTOM
+    custom: array<f32, 4>,
+#endif
+#ifdef INSTANCE_PULSE
+    pulse: array<f32, 3>,
+#endif
+#ifdef INSTANCE_DRAW_Z
+    // unused, the 2D pass has no depth buffer
+    draw_z: f32,
+#endif
+};
+
+#ifdef INSTANCE_STORAGE
+@group(2) @binding(0) var<storage, read> instances: array<InstanceData>;
+#endif
+
+#ifdef INSTANCE_MOTION_VECTORS
+// the instances of the last frame, see `InstanceMotionVectors`
+@group(#{PREVIOUS_INSTANCES_BIND_GROUP}) @binding(0) var<storage, read> previous_instances: array<InstanceData>;
+#endif
+
+#ifdef INSTANCE_ATLAS
+// Mirrors `AtlasParams` in `atlas.rs`.
+struct AtlasParams {
+    columns: u32,
+    rows: u32,
+    // see `InstanceSdf`
+    sdf_smoothing: f32,
+};
+
+@group(#{ATLAS_BIND_GROUP}) @binding(0) var atlas_texture: texture_2d<f32>;
+@group(#{ATLAS_BIND_GROUP}) @binding(1) var atlas_sampler: sampler;
+@group(#{ATLAS_BIND_GROUP}) @binding(2) var<uniform> atlas: AtlasParams;
+#endif
+
+#ifdef INSTANCE_TEXTURE_ARRAY
+@group(#{ATLAS_BIND_GROUP}) @binding(0) var array_texture: texture_2d_array<f32>;
+@group(#{ATLAS_BIND_GROUP}) @binding(1) var array_sampler: sampler;
+#endif
+
+#ifdef INSTANCE_DATA_TEXTURE
+// two texels per instance, see `InstanceDataTexture`
+@group(#{DATA_TEXTURE_BIND_GROUP}) @binding(0) var data_texture: texture_2d<f32>;
+
+fn load_data_texel(index: u32) -> vec4<f32> {
+    let width = textureDimensions(data_texture).x;
+    return textureLoad(data_texture, vec2<u32>(index % width, index / width), 0);
+}
+
+// moves, scales and tints the template instance by the texels of instance `index`
+fn read_data_texture(template: Instance, index: u32) -> Instance {
+    let transform = load_data_texel(2u * index);
+    var instance = template;
+    instance.position += transform.xyz;
+#ifdef INSTANCE_SCALE_2D
+    instance.scale *= vec3<f32>(transform.ww, 1.0);
+#else
+    instance.scale *= transform.w;
+#endif
+    instance.color *= load_data_texel(2u * index + 1u);
+    return instance;
+}
+#endif
+
+#ifdef INSTANCE_SRGB_COLOR
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let lower = color / 12.92;
+    let higher = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(higher, lower, color <= vec3<f32>(0.04045));
+}
+#endif
+
+#ifdef INSTANCE_PULSE
+// Scales `instance` by `1 + amplitude * sin(time * frequency + phase)`, see `PulsingInstanceData`.
+fn pulse(instance: Instance, time: f32) -> Instance {
+    var pulsed = instance;
+    pulsed.scale *= 1.0 + instance.pulse.x * sin(time * instance.pulse.y + instance.pulse.z);
+    return pulsed;
+}
+#endif
+
+fn read_instance(data: InstanceData) -> Instance {
+    var instance: Instance;
+    instance.position = vec3<f32>(data.position[0], data.position[1], data.position[2]);
+#ifdef INSTANCE_SCALE_2D
+    instance.scale = vec3<f32>(data.scale[0], data.scale[1], 1.0);
+#else
+    instance.scale = vec3<f32>(data.scale);
+#endif
+    instance.color = vec4<f32>(data.color[0], data.color[1], data.color[2], data.color[3]);
+    instance.rotation = data.rotation;
+    instance.atlas_index = data.atlas_index;
+    instance.uv_offset = vec2<f32>(data.uv_offset[0], data.uv_offset[1]);
+    instance.uv_scale = vec2<f32>(data.uv_scale[0], data.uv_scale[1]);
+    instance.emissive = vec3<f32>(data.emissive[0], data.emissive[1], data.emissive[2]);
+    instance.border_color = vec4<f32>(
+        data.border_color[0],
+        data.border_color[1],
+        data.border_color[2],
+        data.border_color[3]
+    );
+    instance.border_width = data.border_width;
+    instance.corner_radius = data.corner_radius;
+    instance.anchor = vec2<f32>(data.anchor[0], data.anchor[1]);
+#ifdef INSTANCE_CUSTOM
+    instance.custom = vec4<f32>(data.custom[0], data.custom[1], data.custom[2], data.custom[3]);
+#endif
+#ifdef INSTANCE_PULSE
+    instance.pulse = vec3<f32>(data.pulse[0], data.pulse[1], data.pulse[2]);
+#endif
+    return instance;
+}
+
+fn get_instance(vertex: Vertex) -> Instance {
+#ifdef INSTANCE_STORAGE
+#ifdef INSTANCE_DATA_TEXTURE
+    // the template, which the vertex attributes read with a stride of 0
+    var instance = read_instance(instances[0]);
+#else
+    var instance = read_instance(instances[vertex.instance_index]);
+#endif
+#else
+    var instance: Instance;
+    instance.position = vertex.i_position;
+#ifdef INSTANCE_SCALE_2D
+    instance.scale = vec3<f32>(vertex.i_scale, 1.0);
+#else
+    instance.scale = vec3<f32>(vertex.i_scale);
+#endif
+    instance.color = vertex.i_color;
+    instance.rotation = vertex.i_rotation;
+    instance.atlas_index = vertex.i_atlas_index;
+    instance.uv_offset = vertex.i_uv_offset;
+    instance.uv_scale = vertex.i_uv_scale;
+    instance.emissive = vertex.i_emissive;
+    instance.border_color = vertex.i_border_color;
+    instance.border_width = vertex.i_border_width;
+    instance.corner_radius = vertex.i_corner_radius;
+    instance.anchor = vertex.i_anchor;
+#ifdef INSTANCE_CUSTOM
+    instance.custom = vertex.i_custom;
+#endif
+#ifdef INSTANCE_PULSE
+    instance.pulse = vertex.i_pulse;
+#endif
+#endif
+#ifdef INSTANCE_SRGB_COLOR
+    instance.color = vec4<f32>(srgb_to_linear(instance.color.rgb), instance.color.a);
+    instance.border_color = vec4<f32>(
+        srgb_to_linear(instance.border_color.rgb),
+        instance.border_color.a
+    );
+#endif
+#ifdef INSTANCE_DATA_TEXTURE
+    // the texels are linear
+    instance = read_data_texture(instance, vertex.instance_index);
+#endif
+#ifdef INSTANCE_PULSE
+    instance = pulse(instance, globals.time);
+#endif
+    return instance;
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) emissive: vec3<f32>,
+    // the mesh UVs before the atlas and `uv_offset`/`uv_scale`, to find the edges of the quad
+    @location(3) mesh_uv: vec2<f32>,
+    // the vertex position scaled by the instance, in the units of `border_width`
+    @location(4) local_position: vec2<f32>,
+    @location(5) @interpolate(flat) border_color: vec4<f32>,
+    @location(6) @interpolate(flat) border_width: f32,
+    @location(7) @interpolate(flat) corner_radius: f32,
+#ifdef INSTANCE_TEXTURE_ARRAY
+    // the atlas index selects the layer of the texture array
+    @location(8) @interpolate(flat) layer: u32,
+#endif
+#ifdef INSTANCE_ID_LOCATION
+    @location(9) @interpolate(flat) instance_id: u32,
+#endif
+#ifdef MOTION_VECTOR_LOCATION
+    @location(10) current_clip: vec4<f32>,
+    @location(11) previous_clip: vec4<f32>,
+#endif
+#ifdef INSTANCE_CUSTOM
+    // the `custom` field of `CustomInstanceData`, user defined
+    @location(12) @interpolate(flat) custom: vec4<f32>,
+#endif
+};
+
+#ifdef INSTANCE_TARGETS
+// the extra targets of `InstanceTargets` written by the built-in shader
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+#ifdef INSTANCE_ID_LOCATION
+    // the index in the instance buffer plus one, zero is left where no instance was drawn
+    @location(#{INSTANCE_ID_LOCATION}) instance_id: u32,
+#endif
+#ifdef MOTION_VECTOR_LOCATION
+    @location(#{MOTION_VECTOR_LOCATION}) motion_vector: vec2<f32>,
+#endif
+};
+#endif
+
+// Moves, scales and rotates a vertex of the mesh by `instance` and projects it.
+fn instance_position_to_clip(instance: Instance, model: mat4x4<f32>, position: vec3<f32>) -> vec4<f32> {
+    let c = cos(instance.rotation);
+    let s = sin(instance.rotation);
+    let rotation = mat2x2<f32>(c, s, -s, c);
+    // scaled and rotated around the anchor, which ends up at the instance position
+    let scaled = (position - vec3<f32>(instance.anchor, 0.0)) * instance.scale;
+#ifdef INSTANCE_POINTS
+    // the mesh is scaled in pixels around the projected instance, see `PointInstances`
+    let center = mesh_functions::mesh2d_position_local_to_clip(
+        model,
+        vec4<f32>(instance.position, 1.0)
+    );
+    let offset = rotation * scaled.xy * 2.0 / view.viewport.zw;
+    return vec4<f32>(center.xy + offset * center.w, center.zw);
+#else
+    let moved = vec3<f32>(rotation * scaled.xy, scaled.z) + instance.position;
+    return mesh_functions::mesh2d_position_local_to_clip(model, vec4<f32>(moved, 1.0));
+#endif
+}
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let instance = get_instance(vertex);
+#ifdef VERTEX_UVS
+    let uv = vertex.uv;
+#else
+    // spans 0..1 across meshes of unit size around the origin, like the UVs of a `Rectangle`
+    let uv = vec2<f32>(0.5 + vertex.position.x, 0.5 - vertex.position.y);
+#endif
+
+    let scaled = vertex.position * instance.scale;
+    var out: VertexOutput;
+    /* OLD 3D CODE
+
+    // NOTE: Passing 0 as the instance_index to get_model_matrix() is a hack
+    // for this example as the instance_index builtin would map to the wrong
+    // index in the Mesh array. This index could be passed in via another
+    // uniform instead but it's unnecessary for the example.
+    out.clip_position = mesh_position_local_to_clip(
+        get_model_matrix(0u),
+        vec4<f32>(position, 1.0)
+    );
+    out.color = vertex.i_color;
+
+    */
+
+    // the instance_index belongs to the instances, the mesh array bound for the
+    // batch holds only the host's mesh at index 0
+    let model = mesh_functions::get_model_matrix(0u);
+    out.clip_position = instance_position_to_clip(instance, model, vertex.position);
+    out.color = instance.color;
+#ifdef VERTEX_COLORS
+    out.color *= vertex.color;
+#endif
+    out.emissive = instance.emissive;
+    out.mesh_uv = uv;
+    out.local_position = scaled.xy;
+    out.border_color = instance.border_color;
+    out.border_width = instance.border_width;
+    out.corner_radius = instance.corner_radius;
+#ifdef INSTANCE_CUSTOM
+    out.custom = instance.custom;
+#endif
+    // the two highest bits of the atlas index are `InstanceData::FLIP_X` and `FLIP_Y`. The mesh UVs
+    // are mirrored before the frame and the cell are selected, so they flip within them.
+    let atlas_index = instance.atlas_index & 0x3fffffffu;
+    let flip = vec2<bool>((instance.atlas_index & 0x80000000u) != 0u, (instance.atlas_index & 0x40000000u) != 0u);
+    // NOTE: UVs are not wrapped, keeping `uv_offset + uv_scale` within 0..1 is up to the user,
+    // otherwise neighbouring atlas cells are sampled.
+    out.uv = select(uv, 1.0 - uv, flip) * instance.uv_scale + instance.uv_offset;
+
+#ifdef INSTANCE_ATLAS
+    // cells are counted row by row, starting at the top left
+    let cell = vec2<u32>(atlas_index % atlas.columns, atlas_index / atlas.columns);
+    out.uv = (vec2<f32>(cell) + out.uv) / vec2<f32>(f32(atlas.columns), f32(atlas.rows));
+#endif
+#ifdef INSTANCE_TEXTURE_ARRAY
+    out.layer = atlas_index;
+#endif
+#ifdef INSTANCE_ID_LOCATION
+    out.instance_id = vertex.instance_index + 1u;
+#endif
+#ifdef MOTION_VECTOR_LOCATION
+    out.current_clip = out.clip_position;
+#ifdef INSTANCE_MOTION_VECTORS
+#ifdef INSTANCE_PULSE
+    // pulsed at the time of the last frame
+    let previous = pulse(
+        read_instance(previous_instances[vertex.instance_index]),
+        globals.time - globals.delta_time
+    );
+#else
+    let previous = read_instance(previous_instances[vertex.instance_index]);
+#endif
+    out.previous_clip = instance_position_to_clip(previous, model, vertex.position);
+#else
+    out.previous_clip = out.clip_position;
+#endif
+#endif
+
+    return out;
+}
+
+// Multiplies the alpha of `color` by `coverage`, along with its color if it is premultiplied.
+fn fade(color: vec4<f32>, coverage: f32) -> vec4<f32> {
+#ifdef INSTANCE_PREMULTIPLIED_ALPHA
+    return color * coverage;
+#else
+    return vec4<f32>(color.rgb, color.a * coverage);
+#endif
+}
+
+#ifdef INSTANCE_TARGETS
+@fragment
+fn fragment(in: VertexOutput) -> FragmentOutput {
+#else
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+#endif
+#ifdef INSTANCE_SDF
+    // the red channel is the distance field, 0.5 on the edge and greater inside
+    let field = textureSample(atlas_texture, atlas_sampler, in.uv).r;
+    let edge = max(fwidth(field) * atlas.sdf_smoothing, 1e-4);
+    var color = fade(in.color, smoothstep(0.5 - edge, 0.5 + edge, field));
+#else ifdef INSTANCE_ATLAS
+    // the instance color tints the texel, both straight or both premultiplied by the blend mode
+    var color = textureSample(atlas_texture, atlas_sampler, in.uv) * in.color;
+#else ifdef INSTANCE_TEXTURE_ARRAY
+    var color = textureSample(array_texture, array_sampler, in.uv, in.layer) * in.color;
+#else
+    var color = in.color;
+#endif
+
+    // signed distance to the edge of the rounded quad in local units, negative inside. The UVs
+    // run from 0 to 1 across the quad and each local axis only depends on its UV axis, so the
+    // ratio of their derivatives is the size of the quad.
+    let half_size = 0.5 * fwidth(in.local_position) / fwidth(in.mesh_uv);
+    let radius = clamp(in.corner_radius, 0.0, min(half_size.x, half_size.y));
+    let corner = abs((in.mesh_uv - 0.5) * 2.0 * half_size) - half_size + radius;
+    let distance = length(max(corner, vec2<f32>(0.0))) + min(max(corner.x, corner.y), 0.0) - radius;
+    // smoothed over a pixel, the derivative is taken outside of the branches
+    let smoothing = fwidth(distance);
+
+    if in.border_width > 0.0 {
+        // a border wider than half the quad covers every fragment, filling it
+        let border = clamp((in.border_width + distance) / smoothing + 0.5, 0.0, 1.0);
+        color = mix(color, in.border_color, border);
+    }
+    if in.corner_radius > 0.0 {
+        let coverage = clamp(0.5 - distance / smoothing, 0.0, 1.0);
+        // opaque hosts ignore the alpha, drop the corners at least
+        if coverage <= 0.0 {
+            discard;
+        }
+        color = fade(color, coverage);
+    }
+    // exceeds 1 on HDR cameras to feed bloom, covered pixels only
+    let out = vec4<f32>(color.rgb + in.emissive * color.a, color.a);
+#ifdef INSTANCE_PREMULTIPLIED_ALPHA
+    let blended = out * host.group_alpha;
+#else
+    let blended = vec4<f32>(out.rgb, out.a * host.group_alpha);
+#endif
+#ifdef INSTANCE_ALPHA_MASK
+    // drawn without blending, covered or not at all
+    if blended.a < host.alpha_cutoff {
+        discard;
+    }
+#endif
+#ifdef INSTANCE_TARGETS
+    var output: FragmentOutput;
+    output.color = blended;
+#ifdef INSTANCE_ID_LOCATION
+    output.instance_id = in.instance_id;
+#endif
+#ifdef MOTION_VECTOR_LOCATION
+    // from the previous to the current position in UV units, whose y axis points down
+    let current = in.current_clip.xy / in.current_clip.w;
+    let previous = in.previous_clip.xy / in.previous_clip.w;
+    output.motion_vector = (current - previous) * vec2<f32>(0.5, -0.5);
+#endif
+    return output;
+#else
+    return blended;
+#endif
+}
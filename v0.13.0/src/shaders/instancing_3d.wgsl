@@ -0,0 +1,193 @@
+#import bevy_pbr::mesh_functions
+
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    @location(0) position: vec3<f32>,
+#ifdef VERTEX_NORMALS
+    @location(1) normal: vec3<f32>,
+#endif
+#ifdef VERTEX_UVS
+    @location(2) uv: vec2<f32>,
+#endif
+#ifdef VERTEX_COLORS
+    // set by bevy's mesh pipeline for meshes with `Mesh::ATTRIBUTE_COLOR`
+    @location(5) color: vec4<f32>,
+#endif
+
+#ifndef INSTANCE_STORAGE
+    @location(#{INSTANCE_LOCATION_3}) i_position: vec3<f32>,
+#ifdef INSTANCE_SCALE_2D
+    @location(#{INSTANCE_LOCATION_4}) i_scale: vec2<f32>,
+#else
+    @location(#{INSTANCE_LOCATION_4}) i_scale: f32,
+#endif
+    @location(#{INSTANCE_LOCATION_5}) i_color: vec4<f32>,
+    @location(#{INSTANCE_LOCATION_6}) i_rotation: f32,
+    @location(#{INSTANCE_LOCATION_10}) i_emissive: vec3<f32>,
+#ifdef INSTANCE_DRAW_Z
+    @location(#{INSTANCE_LOCATION_16}) i_draw_z: f32,
+#endif
+#endif
+};
+
+struct Instance {
+    position: vec3<f32>,
+    // z is only scaled by a uniform scale
+    scale: vec3<f32>,
+    color: vec4<f32>,
+    rotation: f32,
+    emissive: vec3<f32>,
+#ifdef INSTANCE_DRAW_Z
+    draw_z: f32,
+#endif
+};
+
+// Mirrors `HostParams` in `lib.rs`, bound along with the mesh of the host.
+struct HostParams {
+    // unused, 3D hosts are drawn without group alpha
+    group_alpha: f32,
+    // fragments below it are discarded with MAY_DISCARD, see `InstanceAlphaMask`
+    alpha_cutoff: f32,
+};
+
+@group(1) @binding(1) var<uniform> host: HostParams;
+
+#ifdef INSTANCE_STORAGE
+// Mirrors the memory layout of `InstanceData`, or `StretchedInstanceData` with INSTANCE_SCALE_2D,
+// `CustomInstanceData` with INSTANCE_CUSTOM and `DepthInstanceData` with INSTANCE_DRAW_Z.
+// Arrays are used instead of vectors because `vec3`/`vec4` members would be 16 byte aligned.
+struct InstanceData {
+    position: array<f32, 3>,
+#ifdef INSTANCE_SCALE_2D
+    scale: array<f32, 2>,
+#else
+    scale: f32,
+#endif
+    color: array<f32, 4>,
+    rotation: f32,
+    // unused, atlases are only supported by the 2D pipeline
+    atlas_index: u32,
+    uv_offset: array<f32, 2>,
+    uv_scale: array<f32, 2>,
+    emissive: array<f32, 3>,
+    sort_key: f32,
+    // unused, borders and rounded corners are only supported by the 2D pipeline
+    border_color: array<f32, 4>,
+    border_width: f32,
+    corner_radius: f32,
+    // unused, only supported by the 2D pipeline
+    anchor: array<f32, 2>,
+#ifdef INSTANCE_CUSTOM
+    // unused, only passed on by the 2D pipeline
+    custom: array<f32, 4>,
+#endif
+#ifdef INSTANCE_DRAW_Z
+    draw_z: f32,
+#endif
+};
+
+@group(2) @binding(0) var<storage, read> instances: array<InstanceData>;
+#endif
+
+#ifdef INSTANCE_SRGB_COLOR
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let lower = color / 12.92;
+    let higher = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(higher, lower, color <= vec3<f32>(0.04045));
+}
+#endif
+
+fn get_instance(vertex: Vertex) -> Instance {
+    var instance: Instance;
+#ifdef INSTANCE_STORAGE
+    let data = instances[vertex.instance_index];
+    instance.position = vec3<f32>(data.position[0], data.position[1], data.position[2]);
+#ifdef INSTANCE_SCALE_2D
+    instance.scale = vec3<f32>(data.scale[0], data.scale[1], 1.0);
+#else
+    instance.scale = vec3<f32>(data.scale);
+#endif
+    instance.color = vec4<f32>(data.color[0], data.color[1], data.color[2], data.color[3]);
+    instance.rotation = data.rotation;
+    instance.emissive = vec3<f32>(data.emissive[0], data.emissive[1], data.emissive[2]);
+#ifdef INSTANCE_DRAW_Z
+    instance.draw_z = data.draw_z;
+#endif
+#else
+    instance.position = vertex.i_position;
+#ifdef INSTANCE_SCALE_2D
+    instance.scale = vec3<f32>(vertex.i_scale, 1.0);
+#else
+    instance.scale = vec3<f32>(vertex.i_scale);
+#endif
+    instance.color = vertex.i_color;
+    instance.rotation = vertex.i_rotation;
+    instance.emissive = vertex.i_emissive;
+#ifdef INSTANCE_DRAW_Z
+    instance.draw_z = vertex.i_draw_z;
+#endif
+#endif
+#ifdef INSTANCE_SRGB_COLOR
+    instance.color = vec4<f32>(srgb_to_linear(instance.color.rgb), instance.color.a);
+#endif
+    return instance;
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) emissive: vec3<f32>,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let instance = get_instance(vertex);
+
+    let c = cos(instance.rotation);
+    let s = sin(instance.rotation);
+    let rotation = mat2x2<f32>(c, s, -s, c);
+
+    let scaled = vertex.position * instance.scale;
+    let position = vec3<f32>(rotation * scaled.xy, scaled.z) + instance.position;
+    // normals are scaled inversely, which keeps them perpendicular under a non-uniform scale
+#ifdef VERTEX_NORMALS
+    let scaled_normal = vertex.normal / instance.scale;
+#else
+    // meshes without normals are lit as if they faced +Z
+    let scaled_normal = vec3<f32>(0.0, 0.0, 1.0);
+#endif
+    let normal = vec3<f32>(rotation * scaled_normal.xy, scaled_normal.z);
+
+    // the instance_index belongs to the instances, the mesh array bound for the
+    // batch holds only the host's mesh at index 0
+    var out: VertexOutput;
+    out.clip_position = mesh_functions::mesh_position_local_to_clip(
+        mesh_functions::get_model_matrix(0u),
+        vec4<f32>(position, 1.0)
+    );
+#ifdef INSTANCE_DRAW_Z
+    // the NDC depth of the instance, which is the z divided by w after the vertex stage
+    out.clip_position.z = instance.draw_z * out.clip_position.w;
+#endif
+    out.color = instance.color;
+#ifdef VERTEX_COLORS
+    out.color *= vertex.color;
+#endif
+    out.emissive = instance.emissive;
+    out.world_normal = mesh_functions::mesh_normal_local_to_world(normal, 0u);
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+#ifdef MAY_DISCARD
+    if in.color.a < host.alpha_cutoff {
+        discard;
+    }
+#endif
+    // simple directional light, so the faces of the meshes can be told apart
+    let light = normalize(vec3<f32>(0.3, -0.5, 1.0));
+    let diffuse = max(dot(normalize(in.world_normal), light), 0.0);
+    return vec4<f32>(in.color.rgb * (0.3 + 0.7 * diffuse) + in.emissive, in.color.a);
+}
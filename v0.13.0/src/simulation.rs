@@ -0,0 +1,262 @@
+//! Instances moved by a compute shader, without uploading them every frame.
+//!
+//! The instances of a host with [`GpuSimulation`] are written from its [`InstanceMaterialData`]
+//! once, like any other static batch. From then on the shader of the simulation runs every frame
+//! and writes the instances in place, in the same buffer they are drawn from. It binds
+//!
+//! - `@group(0) @binding(0) var<uniform> params: SimulationParams`, with `time: f32`,
+//!   `delta: f32`, `count: u32` and `reset: u32` members,
+//! - `@group(0) @binding(1) var<storage, read_write> instances: array<YourInstance>`, laid out like
+//!   the instance type as in [`InstanceBufferMode::Storage`](crate::InstanceBufferMode::Storage),
+//! - `@group(0) @binding(2) var<storage, read_write> state: array<YourState>`, holding
+//!   [`GpuSimulation::state_size`] bytes per instance, like a velocity, that are not drawn.
+//!
+//! and has a `simulate` entry point with a workgroup size of 64 that is dispatched once per
+//! instance. Writing the instances from the CPU again, by changing the [`InstanceMaterialData`] or
+//! showing a hidden host, restarts the simulation: the state is zeroed and `reset` is 1 for one
+//! frame so the shader can initialize it.
+//!
+//! Sorted hosts, hosts with LODs and hosts with an
+//! [`InstanceBufferRing`](crate::InstanceBufferRing) are written every frame, which undoes the
+//! simulation. The batch bounds are computed from the instances on the CPU, so disable frustum
+//! culling of the batch for instances that move away, or use
+//! [`GpuCulling`](crate::culling::GpuCulling), which culls the simulated instances.
+
+use bevy::{
+    ecs::entity::EntityHashMap,
+    prelude::*,
+    render::{
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        render_resource::{binding_types::*, *},
+        renderer::{RenderDevice, RenderQueue},
+        RenderApp,
+    },
+    utils::HashMap,
+};
+use bytemuck::{Pod, Zeroable};
+use std::marker::PhantomData;
+
+use crate::{is_downlevel, Instance, InstanceBuffer};
+
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Moves the instances of this host entity with a compute shader, see the
+/// [module docs](self) for the bindings of the shader.
+#[derive(Component, Clone, ExtractComponent)]
+pub struct GpuSimulation {
+    pub shader: Handle<Shader>,
+    /// Bytes of state kept per instance, rounded up to a multiple of 4 like the stride of a WGSL
+    /// array.
+    pub state_size: u32,
+}
+
+pub struct GpuSimulationPlugin;
+
+impl Plugin for GpuSimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ExtractComponentPlugin::<GpuSimulation>::default());
+    }
+
+    fn finish(&self, app: &mut App) {
+        let render_app = app.sub_app_mut(RenderApp);
+        // downlevel backends without compute shaders (WebGL2) fail to create the layout
+        if !is_downlevel(render_app.world.resource::<RenderDevice>()) {
+            render_app.init_resource::<GpuSimulationPipeline>();
+        }
+    }
+}
+
+/// Mirrors `SimulationParams` in the simulation shaders.
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+struct SimulationParams {
+    time: f32,
+    delta: f32,
+    count: u32,
+    reset: u32,
+}
+
+#[derive(Resource)]
+pub struct GpuSimulationPipeline {
+    layout: BindGroupLayout,
+    /// The pipelines of the simulation shaders, queued when a shader is first used.
+    pipelines: HashMap<AssetId<Shader>, CachedComputePipelineId>,
+}
+
+impl FromWorld for GpuSimulationPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let layout = render_device.create_bind_group_layout(
+            "instance simulation layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    uniform_buffer_sized(false, None),
+                    storage_buffer_sized(false, None),
+                    storage_buffer_sized(false, None),
+                ),
+            ),
+        );
+
+        GpuSimulationPipeline {
+            layout,
+            pipelines: HashMap::default(),
+        }
+    }
+}
+
+impl GpuSimulationPipeline {
+    /// Returns the pipeline of `shader`, `None` in the frame it is queued as the pipeline cache
+    /// only takes in new pipelines when rendering.
+    fn pipeline(
+        &mut self,
+        shader: &Handle<Shader>,
+        pipeline_cache: &PipelineCache,
+    ) -> Option<CachedComputePipelineId> {
+        if let Some(pipeline) = self.pipelines.get(&shader.id()) {
+            return Some(*pipeline);
+        }
+        let pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: Some("instance simulation pipeline".into()),
+            layout: vec![self.layout.clone()],
+            push_constant_ranges: Vec::new(),
+            shader: shader.clone(),
+            shader_defs: Vec::new(),
+            entry_point: "simulate".into(),
+        });
+        self.pipelines.insert(shader.id(), pipeline);
+        None
+    }
+}
+
+/// The state buffers of all simulated hosts, kept across frames as the render world entities are
+/// cleared every frame.
+#[derive(Resource)]
+pub struct GpuSimulationStates<T: Instance> {
+    states: EntityHashMap<Buffer>,
+    marker: PhantomData<T>,
+}
+
+impl<T: Instance> Default for GpuSimulationStates<T> {
+    fn default() -> Self {
+        GpuSimulationStates {
+            states: EntityHashMap::default(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Instance> GpuSimulationStates<T> {
+    /// The state buffer of a simulated host, once it was dispatched.
+    pub fn get(&self, host: Entity) -> Option<&Buffer> {
+        self.states.get(&host)
+    }
+}
+
+/// Runs the simulation of every [`GpuSimulation`] host on its instance buffer.
+#[allow(clippy::too_many_arguments)]
+pub fn dispatch_simulation<T: Instance>(
+    query: Query<(Entity, &InstanceBuffer<T>, &GpuSimulation)>,
+    simulation_pipeline: Option<ResMut<GpuSimulationPipeline>>,
+    mut states: ResMut<GpuSimulationStates<T>>,
+    pipeline_cache: Res<PipelineCache>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    time: Res<Time>,
+    mut warned: Local<bool>,
+    mut warned_split: Local<bool>,
+) {
+    // hosts that weren't extracted restart their simulation when they are shown again
+    states.states.retain(|entity, _| query.contains(*entity));
+
+    if query.is_empty() {
+        return;
+    }
+
+    let Some(mut simulation_pipeline) = simulation_pipeline else {
+        if !*warned {
+            warn!("GpuSimulation requires an adapter supporting compute shaders, drawing the instances unsimulated");
+            *warned = true;
+        }
+        return;
+    };
+
+    let mut encoder = render_device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("instance simulation"),
+    });
+
+    for (entity, instance_buffer, simulation) in &query {
+        if instance_buffer.is_split() {
+            if !*warned_split {
+                warn!("GpuSimulation doesn't support hosts with more instances than a buffer holds, drawing them unsimulated");
+                *warned_split = true;
+            }
+            continue;
+        }
+
+        let Some(pipeline) = simulation_pipeline
+            .pipeline(&simulation.shader, &pipeline_cache)
+            .and_then(|pipeline| pipeline_cache.get_compute_pipeline(pipeline))
+        else {
+            continue;
+        };
+
+        // storage bindings can't be empty and their size has to be a multiple of 4
+        let state_size = simulation.state_size.next_multiple_of(4);
+        let size = (instance_buffer.length as u64 * state_size as u64).max(4);
+        let mut reset = instance_buffer.written;
+        let state = match states.states.get(&entity) {
+            Some(state) if state.size() >= size => state,
+            _ => {
+                reset = true;
+                let state = render_device.create_buffer(&BufferDescriptor {
+                    label: Some("instance simulation state buffer"),
+                    size,
+                    usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                states.states.insert(entity, state);
+                &states.states[&entity]
+            }
+        };
+        if reset {
+            encoder.clear_buffer(state, 0, None);
+        }
+
+        let params = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("instance simulation params"),
+            contents: bytemuck::bytes_of(&SimulationParams {
+                time: time.elapsed_seconds_wrapped(),
+                delta: time.delta_seconds(),
+                count: instance_buffer.length as u32,
+                reset: reset as u32,
+            }),
+            usage: BufferUsages::UNIFORM,
+        });
+
+        let bind_group = render_device.create_bind_group(
+            "instance simulation bind group",
+            &simulation_pipeline.layout,
+            &BindGroupEntries::sequential((
+                params.as_entire_binding(),
+                instance_buffer.buffer.as_entire_binding(),
+                state.as_entire_binding(),
+            )),
+        );
+
+        let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some("instance simulation pass"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.dispatch_workgroups(
+            (instance_buffer.length as u32).div_ceil(WORKGROUP_SIZE),
+            1,
+            1,
+        );
+    }
+
+    render_queue.submit([encoder.finish()]);
+}
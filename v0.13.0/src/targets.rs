@@ -0,0 +1,328 @@
+//! Extra color targets written by the instances of 2D hosts, for deferred setups.
+//!
+//! A 2D camera with [`InstanceTargets`] draws the hosts it sees in its own render pass, which runs
+//! right after bevy's main 2D pass and attaches one texture per extra format after the view
+//! target. The fragment shader writes the view target at `@location(0)` and the extra targets at
+//! the following locations. The built-in shader writes the [`InstanceTarget::InstanceIds`] and
+//! [`InstanceTarget::MotionVectors`] targets, shaders of an [`InstancedMaterial`] are free to write
+//! [`InstanceTarget::Custom`] targets with anything else.
+//!
+//! The hosts are drawn after everything in the main pass, so they cover sprites and meshes at a
+//! higher z. The targets are cleared to zero every frame and have the size of the view target,
+//! they are found in the [`ViewInstanceTargets`] of the view in the render world. Only drawn
+//! without MSAA, as integer targets can't be multisampled, cameras with MSAA draw their hosts in
+//! the main pass and write no extra targets.
+//!
+//! [`InstancedMaterial`]: crate::InstancedMaterial
+
+use bevy::{
+    core_pipeline::core_2d::graph::{Core2d, Node2d},
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{
+        camera::ExtractedCamera,
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        render_graph::{
+            NodeRunError, RenderGraphApp, RenderGraphContext, RenderLabel, ViewNode, ViewNodeRunner,
+        },
+        render_phase::{
+            sort_phase_system, CachedRenderPipelinePhaseItem, DrawFunctionId, DrawFunctions,
+            PhaseItem, RenderPhase,
+        },
+        render_resource::*,
+        renderer::{RenderContext, RenderDevice},
+        texture::{CachedTexture, TextureCache},
+        view::ViewTarget,
+        Extract, ExtractSchedule, Render, RenderApp, RenderSet,
+    },
+    utils::{nonmax::NonMaxU32, FloatOrd},
+};
+use std::ops::Range;
+
+/// Most extra targets of a camera, the view target takes up another attachment.
+pub const MAX_INSTANCE_TARGETS: usize = 3;
+
+/// The extra color targets the hosts seen by this 2D camera write, see the [module docs](self). At
+/// most [`MAX_INSTANCE_TARGETS`] are attached, the rest are ignored.
+#[derive(Component, Clone, Default, Debug, ExtractComponent)]
+pub struct InstanceTargets {
+    pub targets: Vec<InstanceTarget>,
+}
+
+impl InstanceTargets {
+    /// A single [`InstanceTarget::InstanceIds`] target.
+    pub fn instance_ids() -> Self {
+        InstanceTargets {
+            targets: vec![InstanceTarget::InstanceIds],
+        }
+    }
+
+    /// A single [`InstanceTarget::MotionVectors`] target.
+    pub fn motion_vectors() -> Self {
+        InstanceTargets {
+            targets: vec![InstanceTarget::MotionVectors],
+        }
+    }
+
+    /// The targets in the order of their locations, starting at `@location(1)`, for the pipeline
+    /// key.
+    pub(crate) fn key(&self) -> [Option<InstanceTarget>; MAX_INSTANCE_TARGETS] {
+        let mut key = [None; MAX_INSTANCE_TARGETS];
+        for (target, key) in self.targets.iter().zip(&mut key) {
+            *key = Some(*target);
+        }
+        key
+    }
+
+    /// The index of the first `target` among the attached targets.
+    pub fn position(&self, target: InstanceTarget) -> Option<usize> {
+        self.targets
+            .iter()
+            .take(MAX_INSTANCE_TARGETS)
+            .position(|&other| other == target)
+    }
+}
+
+/// What an extra target of [`InstanceTargets`] holds.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum InstanceTarget {
+    /// `R32Uint`, the index of the instance in the instance buffer plus one, zero where no instance
+    /// was drawn.
+    ///
+    /// The index matches the index in [`InstanceMaterialData`](crate::InstanceMaterialData) unless
+    /// the instances are sorted, culled, split by LOD or drawn from several buffers.
+    InstanceIds,
+    /// `Rg16Float`, how far the instance moved on screen since the last frame, in UV units from the
+    /// previous to the current position. Zero for hosts without
+    /// [`InstanceMotionVectors`](crate::motion_vectors::InstanceMotionVectors).
+    MotionVectors,
+    /// A target of the given format, only written by the shaders of an [`InstancedMaterial`].
+    ///
+    /// [`InstancedMaterial`]: crate::InstancedMaterial
+    Custom(TextureFormat),
+}
+
+impl InstanceTarget {
+    pub fn format(&self) -> TextureFormat {
+        match self {
+            InstanceTarget::InstanceIds => TextureFormat::R32Uint,
+            InstanceTarget::MotionVectors => TextureFormat::Rg16Float,
+            InstanceTarget::Custom(format) => *format,
+        }
+    }
+}
+
+/// The extra targets of a view, in the order of [`InstanceTargets::targets`].
+#[derive(Component)]
+pub struct ViewInstanceTargets {
+    pub textures: Vec<CachedTexture>,
+}
+
+/// A host drawn into the extra targets of a view, sorted like
+/// [`Transparent2d`](bevy::core_pipeline::core_2d::Transparent2d).
+pub struct InstanceTarget2d {
+    pub sort_key: FloatOrd,
+    pub entity: Entity,
+    pub pipeline: CachedRenderPipelineId,
+    pub draw_function: DrawFunctionId,
+    pub batch_range: Range<u32>,
+    pub dynamic_offset: Option<NonMaxU32>,
+}
+
+impl PhaseItem for InstanceTarget2d {
+    type SortKey = FloatOrd;
+
+    fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    fn sort_key(&self) -> Self::SortKey {
+        self.sort_key
+    }
+
+    fn draw_function(&self) -> DrawFunctionId {
+        self.draw_function
+    }
+
+    fn sort(items: &mut [Self]) {
+        // stable, so hosts at the same z keep the order they were queued in
+        items.sort_by_key(|item| item.sort_key);
+    }
+
+    fn batch_range(&self) -> &Range<u32> {
+        &self.batch_range
+    }
+
+    fn batch_range_mut(&mut self) -> &mut Range<u32> {
+        &mut self.batch_range
+    }
+
+    fn dynamic_offset(&self) -> Option<NonMaxU32> {
+        self.dynamic_offset
+    }
+
+    fn dynamic_offset_mut(&mut self) -> &mut Option<NonMaxU32> {
+        &mut self.dynamic_offset
+    }
+}
+
+impl CachedRenderPipelinePhaseItem for InstanceTarget2d {
+    fn cached_pipeline(&self) -> CachedRenderPipelineId {
+        self.pipeline
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+pub struct InstanceTargetsPass;
+
+pub struct InstanceTargetsPlugin;
+
+impl Plugin for InstanceTargetsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ExtractComponentPlugin::<InstanceTargets>::default());
+
+        app.sub_app_mut(RenderApp)
+            .init_resource::<DrawFunctions<InstanceTarget2d>>()
+            .add_systems(ExtractSchedule, extract_instance_target_phases)
+            .add_systems(
+                Render,
+                (
+                    sort_phase_system::<InstanceTarget2d>.in_set(RenderSet::PhaseSort),
+                    prepare_instance_targets.in_set(RenderSet::PrepareResources),
+                ),
+            )
+            .add_render_graph_node::<ViewNodeRunner<InstanceTargetsNode>>(
+                Core2d,
+                InstanceTargetsPass,
+            )
+            .add_render_graph_edges(
+                Core2d,
+                (Node2d::MainPass, InstanceTargetsPass, Node2d::Tonemapping),
+            );
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn extract_instance_target_phases(
+    mut commands: Commands,
+    cameras: Extract<Query<(Entity, &Camera), (With<Camera2d>, With<InstanceTargets>)>>,
+) {
+    for (entity, camera) in &cameras {
+        if camera.is_active {
+            commands
+                .get_or_spawn(entity)
+                .insert(RenderPhase::<InstanceTarget2d>::default());
+        }
+    }
+}
+
+/// Whether the hosts seen by the views are drawn into their extra targets, warning once if MSAA
+/// prevents it.
+pub(crate) fn draws_instance_targets(msaa: &Msaa, warned_msaa: &mut bool) -> bool {
+    if msaa.samples() > 1 {
+        if !*warned_msaa {
+            warn!("Instance targets are only written without MSAA, set `Msaa::Off` to write them");
+            *warned_msaa = true;
+        }
+        return false;
+    }
+    true
+}
+
+fn prepare_instance_targets(
+    mut commands: Commands,
+    mut texture_cache: ResMut<TextureCache>,
+    render_device: Res<RenderDevice>,
+    msaa: Res<Msaa>,
+    views: Query<(Entity, &ExtractedCamera, &InstanceTargets)>,
+) {
+    // the hosts are drawn in the main pass instead, see `draws_instance_targets`
+    if msaa.samples() > 1 {
+        return;
+    }
+
+    for (entity, camera, targets) in &views {
+        let Some(size) = camera.physical_target_size else {
+            continue;
+        };
+
+        let textures = targets
+            .targets
+            .iter()
+            .take(MAX_INSTANCE_TARGETS)
+            .map(|target| {
+                texture_cache.get(
+                    &render_device,
+                    TextureDescriptor {
+                        label: Some("instance_target"),
+                        size: Extent3d {
+                            width: size.x,
+                            height: size.y,
+                            depth_or_array_layers: 1,
+                        },
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: TextureDimension::D2,
+                        format: target.format(),
+                        usage: TextureUsages::RENDER_ATTACHMENT
+                            | TextureUsages::TEXTURE_BINDING
+                            | TextureUsages::COPY_SRC,
+                        view_formats: &[],
+                    },
+                )
+            })
+            .collect();
+
+        commands
+            .entity(entity)
+            .insert(ViewInstanceTargets { textures });
+    }
+}
+
+/// Draws the [`InstanceTarget2d`] phase of a view into its view target and extra targets.
+#[derive(Default)]
+pub struct InstanceTargetsNode;
+
+impl ViewNode for InstanceTargetsNode {
+    type ViewQuery = (
+        &'static ExtractedCamera,
+        &'static RenderPhase<InstanceTarget2d>,
+        &'static ViewTarget,
+        &'static ViewInstanceTargets,
+    );
+
+    fn run<'w>(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        (camera, phase, target, targets): QueryItem<'w, Self::ViewQuery>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let color_attachments: Vec<_> = std::iter::once(Some(target.get_color_attachment()))
+            .chain(targets.textures.iter().map(|texture| {
+                Some(RenderPassColorAttachment {
+                    view: &texture.default_view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Default::default()),
+                        store: StoreOp::Store,
+                    },
+                })
+            }))
+            .collect();
+
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("instance_targets_pass"),
+            color_attachments: &color_attachments,
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        if let Some(viewport) = camera.viewport.as_ref() {
+            render_pass.set_camera_viewport(viewport);
+        }
+
+        phase.render(&mut render_pass, world, graph.view_entity());
+        Ok(())
+    }
+}
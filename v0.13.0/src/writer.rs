@@ -0,0 +1,110 @@
+//! Instances written straight into GPU visible memory instead of an [`InstanceMaterialData`].
+//!
+//! An [`InstanceGenerator`] is extracted like any other component, but only carries a closure.
+//! The closure runs in the render world while the instance buffer is prepared and pushes the
+//! instances into a mapped staging buffer with an [`InstanceWriter`], so there is no `Vec` to
+//! build, extract and copy every frame. As the instances are never on the CPU, sorting, LODs,
+//! [`InstanceVisibility`](crate::InstanceVisibility), picking and the batch bounds don't apply to
+//! generated hosts. Their batches are not frustum culled, use
+//! [`GpuCulling`](crate::culling::GpuCulling) to cull their instances.
+
+use bevy::{prelude::*, render::extract_component::ExtractComponent};
+use std::{marker::PhantomData, sync::Arc};
+
+use crate::{Instance, InstanceMaterialData};
+
+/// Generates the instances of this host entity every frame, in place of an
+/// [`InstanceMaterialData`].
+///
+/// Replace the component to change what is generated, for example to capture the current time.
+#[derive(Component)]
+pub struct InstanceGenerator<T: Instance> {
+    /// Most instances written per frame, the instance buffer is sized for it. Further instances
+    /// are dropped.
+    pub capacity: usize,
+    write: Arc<WriteFn<T>>,
+}
+
+type WriteFn<T> = dyn Fn(&mut InstanceWriter<T>) + Send + Sync;
+
+impl<T: Instance> InstanceGenerator<T> {
+    pub fn new(
+        capacity: usize,
+        write: impl Fn(&mut InstanceWriter<T>) + Send + Sync + 'static,
+    ) -> Self {
+        InstanceGenerator {
+            capacity,
+            write: Arc::new(write),
+        }
+    }
+
+    /// Runs the closure on `bytes`, which has room for `capacity` instances, and returns the
+    /// number of instances written.
+    pub(crate) fn write(&self, bytes: &mut [u8]) -> usize {
+        let mut writer = InstanceWriter {
+            bytes,
+            len: 0,
+            marker: PhantomData,
+        };
+        (self.write)(&mut writer);
+        writer.len
+    }
+}
+
+impl<T: Instance> Clone for InstanceGenerator<T> {
+    fn clone(&self) -> Self {
+        InstanceGenerator {
+            capacity: self.capacity,
+            write: self.write.clone(),
+        }
+    }
+}
+
+impl<T: Instance> ExtractComponent for InstanceGenerator<T> {
+    type QueryData = &'static Self;
+    // a host with instances of its own draws those
+    type QueryFilter = Without<InstanceMaterialData<T>>;
+    type Out = Self;
+
+    fn extract_component(generator: &Self) -> Option<Self> {
+        (generator.capacity > 0).then(|| generator.clone())
+    }
+}
+
+/// Appends instances to the mapped instance buffer of a host.
+pub struct InstanceWriter<'a, T: Instance> {
+    bytes: &'a mut [u8],
+    len: usize,
+    marker: PhantomData<T>,
+}
+
+impl<T: Instance> InstanceWriter<'_, T> {
+    /// Appends `instance`, returns `false` and drops it if the buffer is full.
+    #[inline]
+    pub fn push(&mut self, instance: T) -> bool {
+        let stride = T::ARRAY_STRIDE as usize;
+        let Some(slot) = self
+            .bytes
+            .get_mut(self.len * stride..(self.len + 1) * stride)
+        else {
+            return false;
+        };
+        slot[..std::mem::size_of::<T>()].copy_from_slice(bytemuck::bytes_of(&instance));
+        self.len += 1;
+        true
+    }
+
+    /// Number of instances written so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of instances the buffer has room for.
+    pub fn capacity(&self) -> usize {
+        self.bytes.len() / T::ARRAY_STRIDE as usize
+    }
+}
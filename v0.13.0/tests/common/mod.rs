@@ -0,0 +1,296 @@
+//! Headless apps for the tests that render. They need a GPU adapter but no window, and are skipped
+//! without one. The backend can be picked with `WGPU_BACKEND`, e.g. `gl` for llvmpipe.
+
+#![allow(dead_code)]
+
+use bevy::{
+    app::PluginsState,
+    audio::AudioPlugin,
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        pipelined_rendering::PipelinedRenderingPlugin,
+        render_asset::{RenderAssetUsages, RenderAssets},
+        render_phase::{CachedRenderPipelinePhaseItem, RenderPhase},
+        render_resource::{
+            BufferDescriptor, BufferUsages, CachedPipelineState, CachedRenderPipelineId,
+            CommandEncoderDescriptor, Extent3d, ImageCopyBuffer, ImageDataLayout, Maintain,
+            MapMode, PipelineCache, TextureDimension, TextureFormat, TextureUsages,
+        },
+        renderer::{initialize_renderer, RenderDevice, RenderInstance, RenderQueue},
+        settings::{RenderCreation, WgpuSettings},
+        Render, RenderApp, RenderPlugin, RenderSet,
+    },
+    tasks::{block_on, tick_global_task_pools_on_main_thread},
+    window::ExitCondition,
+    winit::WinitPlugin,
+};
+use instancing::{InstanceData, InstancedMeshBundle, InstancingPlugin};
+use std::sync::{Arc, Mutex};
+
+/// Width and height of the images the cameras render into.
+pub const SIZE: u32 = 256;
+
+/// An app with the default plugins rendering without a window, or `None` without a GPU adapter.
+/// The render world runs on the same thread, so it has rendered the frame when `update` returns.
+pub fn headless_app() -> Option<App> {
+    // the instance is kept for the renderer, as dropping one releases the display of the GL backend
+    let settings = WgpuSettings::default();
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: settings.backends?,
+        dx12_shader_compiler: settings.dx12_shader_compiler.clone(),
+        flags: settings.instance_flags,
+        gles_minor_version: settings.gles3_minor_version,
+    });
+    let request_adapter_options = wgpu::RequestAdapterOptions {
+        power_preference: settings.power_preference,
+        ..default()
+    };
+    if block_on(instance.request_adapter(&request_adapter_options)).is_none() {
+        eprintln!("no GPU adapter, skipped");
+        return None;
+    }
+    let (device, queue, adapter_info, adapter) = block_on(initialize_renderer(
+        &instance,
+        &settings,
+        &request_adapter_options,
+    ));
+    let render_creation = RenderCreation::Manual(
+        device,
+        queue,
+        adapter_info,
+        adapter,
+        RenderInstance(Arc::new(instance)),
+    );
+
+    let mut app = App::new();
+    app.add_plugins(
+        DefaultPlugins
+            .set(WindowPlugin {
+                primary_window: None,
+                exit_condition: ExitCondition::DontExit,
+                close_when_requested: false,
+            })
+            .set(RenderPlugin {
+                render_creation,
+                // pipelines are ready in the frame they are queued, so every run draws the same
+                // frames
+                synchronous_pipeline_compilation: true,
+            })
+            .disable::<WinitPlugin>()
+            .disable::<PipelinedRenderingPlugin>()
+            .disable::<AudioPlugin>(),
+    );
+    Some(app)
+}
+
+/// Runs `frames` frames, finishing the plugins before the first one.
+pub fn update(app: &mut App, frames: u32) {
+    if app.plugins_state() != PluginsState::Cleaned {
+        while app.plugins_state() == PluginsState::Adding {
+            tick_global_task_pools_on_main_thread();
+        }
+        app.finish();
+        app.cleanup();
+    }
+    for _ in 0..frames {
+        app.update();
+    }
+}
+
+/// A 2D camera rendering into a new image of [`SIZE`], which can be copied back.
+pub fn image_camera(images: &mut Assets<Image>) -> Camera2dBundle {
+    let mut target = Image::new_fill(
+        Extent3d {
+            width: SIZE,
+            height: SIZE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0; 4],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    target.texture_descriptor.usage |= TextureUsages::COPY_SRC | TextureUsages::RENDER_ATTACHMENT;
+
+    Camera2dBundle {
+        camera: Camera {
+            target: RenderTarget::Image(images.add(target)),
+            clear_color: ClearColorConfig::Custom(Color::BLACK),
+            ..default()
+        },
+        ..default()
+    }
+}
+
+/// The image a camera renders into.
+pub fn target_of(camera: &Camera) -> Handle<Image> {
+    match &camera.target {
+        RenderTarget::Image(target) => target.clone(),
+        target => panic!("{target:?} can't be read back"),
+    }
+}
+
+/// An app drawing [`InstanceData`] with a camera.
+pub fn instancing_app() -> Option<App> {
+    let mut app = headless_app()?;
+    app.add_plugins(InstancingPlugin::<InstanceData>::default());
+    let camera = image_camera(&mut app.world.resource_mut::<Assets<Image>>());
+    app.world.spawn(camera);
+    Some(app)
+}
+
+/// Spawns a host of `count` default instances, drawn as unit quads.
+pub fn spawn_host(app: &mut App, count: usize) -> Entity {
+    let mesh = app
+        .world
+        .resource_mut::<Assets<Mesh>>()
+        .add(Rectangle::new(1.0, 1.0));
+    app.world
+        .spawn(InstancedMeshBundle::<InstanceData>::new(
+            mesh,
+            (0..count).map(|_| InstanceData::default()),
+        ))
+        .id()
+}
+
+/// An item of a [`RenderPhase`], as queued in the last frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QueuedItem {
+    /// The camera whose phase it was queued into.
+    pub view: Entity,
+    pub entity: Entity,
+    pub pipeline: CachedRenderPipelineId,
+    /// Whether the pipeline was created, `false` while it is compiled or if it failed to.
+    pub pipeline_ready: bool,
+}
+
+/// The items queued into the `RenderPhase<I>` of every view in the last frame, recorded before
+/// the render world is cleared.
+#[derive(Resource, Clone, Default)]
+pub struct QueuedItems(Arc<Mutex<Vec<QueuedItem>>>);
+
+impl QueuedItems {
+    pub fn get(&self) -> Vec<QueuedItem> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// The items of `entity`.
+    pub fn of(&self, entity: Entity) -> Vec<QueuedItem> {
+        self.get()
+            .into_iter()
+            .filter(|item| item.entity == entity)
+            .collect()
+    }
+}
+
+/// Records the items of the `RenderPhase<I>` of every view each frame.
+pub fn record_queued<I: CachedRenderPipelinePhaseItem>(app: &mut App) -> QueuedItems {
+    let queued = QueuedItems::default();
+    let recorded = queued.clone();
+    app.sub_app_mut(RenderApp).add_systems(
+        Render,
+        (move |phases: Query<(Entity, &RenderPhase<I>)>, pipeline_cache: Res<PipelineCache>| {
+            *recorded.0.lock().unwrap() = phases
+                .iter()
+                .flat_map(|(view, phase)| phase.items.iter().map(move |item| (view, item)))
+                .map(|(view, item)| QueuedItem {
+                    view,
+                    entity: item.entity(),
+                    pipeline: item.cached_pipeline(),
+                    pipeline_ready: matches!(
+                        pipeline_cache.get_render_pipeline_state(item.cached_pipeline()),
+                        CachedPipelineState::Ok(_)
+                    ),
+                })
+                .collect();
+        })
+        .after(RenderSet::Render)
+        .before(RenderSet::Cleanup),
+    );
+    queued
+}
+
+/// The render target as it was drawn in the last frame, copied back before the render world is
+/// cleared.
+#[derive(Clone, Default)]
+pub struct Readback(Arc<Mutex<Option<Image>>>);
+
+impl Readback {
+    /// The image of the last frame, `None` until the target is drawn or if it was already taken.
+    pub fn take(&self) -> Option<Image> {
+        self.0.lock().unwrap().take()
+    }
+}
+
+/// Copies `target` back each frame, at the size it has in the render world. A row has to be a
+/// multiple of 256 bytes to be copied, so the width a multiple of 64 pixels.
+pub fn read_back(app: &mut App, target: Handle<Image>) -> Readback {
+    let readback = Readback::default();
+    let recorded = readback.clone();
+    let target = target.id();
+    app.sub_app_mut(RenderApp).add_systems(
+        Render,
+        (move |images: Res<RenderAssets<Image>>,
+               render_device: Res<RenderDevice>,
+               render_queue: Res<RenderQueue>| {
+            let Some(image) = images.get(target) else {
+                return;
+            };
+            let size = image.texture.size();
+
+            let buffer = render_device.create_buffer(&BufferDescriptor {
+                label: Some("test readback"),
+                size: (size.width * size.height * 4) as u64,
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+            let mut encoder =
+                render_device.create_command_encoder(&CommandEncoderDescriptor::default());
+            encoder.copy_texture_to_buffer(
+                image.texture.as_image_copy(),
+                ImageCopyBuffer {
+                    buffer: &buffer,
+                    layout: ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(size.width * 4),
+                        rows_per_image: None,
+                    },
+                },
+                size,
+            );
+            render_queue.submit([encoder.finish()]);
+
+            let slice = buffer.slice(..);
+            slice.map_async(MapMode::Read, |result| result.unwrap());
+            render_device.poll(Maintain::Wait);
+            *recorded.0.lock().unwrap() = Some(Image::new(
+                size,
+                TextureDimension::D2,
+                slice.get_mapped_range().to_vec(),
+                image.texture_format,
+                RenderAssetUsages::default(),
+            ));
+        })
+        .after(RenderSet::Render)
+        .before(RenderSet::Cleanup),
+    );
+    readback
+}
+
+/// The brightest channel of the pixel at `x`, `y` of an image read back, `None` if it is black.
+pub fn dominant(image: &Image, x: u32, y: u32) -> Option<usize> {
+    let index = ((y * image.width() + x) * 4) as usize;
+    brightest(&image.data[index..index + 3])
+}
+
+/// The channel [`dominant`] finds where `color` is drawn, without tonemapping.
+pub fn channel(color: Color) -> Option<usize> {
+    brightest(&color.as_rgba_u8()[..3])
+}
+
+fn brightest(rgb: &[u8]) -> Option<usize> {
+    (0..3)
+        .filter(|&channel| rgb[channel] > 128)
+        .max_by_key(|&channel| rgb[channel])
+}
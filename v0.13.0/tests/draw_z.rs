@@ -0,0 +1,78 @@
+//! 3D instances depth tested on their own depth.
+
+mod common;
+
+use bevy::{core_pipeline::tonemapping::Tonemapping, prelude::*};
+use common::SIZE;
+use instancing::{DepthInstanceData, InstanceAlphaMask, InstancedMeshBundle, Instancing3dPlugin};
+
+/// Frames rendered before the image is read back, so every pipeline is in use.
+const READBACK_FRAME: u32 = 10;
+
+const HIGHER: Color = Color::GREEN;
+const LOWER: Color = Color::RED;
+
+/// The instance with the higher `draw_z` is drawn over the one nearer to the camera, whichever is
+/// drawn last.
+#[test]
+fn higher_draw_z_wins_depth_test() {
+    let Some(mut app) = common::headless_app() else {
+        return;
+    };
+    app.add_plugins(Instancing3dPlugin::<DepthInstanceData>::default());
+
+    let square = app
+        .world
+        .resource_mut::<Assets<Mesh>>()
+        .add(Rectangle::new(1.0, 1.0));
+    // the lower instance is nearer and drawn last, so it would cover the higher one if the depth
+    // of the positions was tested. It is larger, to see that it is drawn at all.
+    app.world.spawn((
+        InstancedMeshBundle::<DepthInstanceData, Handle<Mesh>>::new(
+            square,
+            [
+                DepthInstanceData {
+                    color: HIGHER.as_linear_rgba_f32(),
+                    draw_z: 0.8,
+                    ..default()
+                },
+                DepthInstanceData {
+                    position: Vec3::new(0.0, 0.0, 1.0),
+                    scale: 2.0,
+                    color: LOWER.as_linear_rgba_f32(),
+                    draw_z: 0.2,
+                    ..default()
+                },
+            ],
+        ),
+        // writes the depth
+        InstanceAlphaMask::default(),
+    ));
+
+    let camera = common::image_camera(&mut app.world.resource_mut::<Assets<Image>>()).camera;
+    let readback = common::read_back(&mut app, common::target_of(&camera));
+    app.world.spawn(Camera3dBundle {
+        camera,
+        transform: Transform::from_xyz(0.0, 0.0, 10.0).looking_at(Vec3::ZERO, Vec3::Y),
+        tonemapping: Tonemapping::None,
+        ..default()
+    });
+    common::update(&mut app, READBACK_FRAME);
+    let image = readback.take().expect("nothing was read back");
+
+    // at 10 units a pixel is about 1/31 of a unit, the higher instance covers 15 pixels around the
+    // center and the lower one about 34
+    let center = SIZE / 2;
+    let expected = [
+        ("center", UVec2::splat(center), Some(HIGHER)),
+        ("lower only", UVec2::new(center + 25, center), Some(LOWER)),
+        ("corner", UVec2::splat(4), None),
+    ];
+    for (name, pixel, color) in expected {
+        assert_eq!(
+            common::dominant(&image, pixel.x, pixel.y),
+            color.and_then(common::channel),
+            "the {name} pixel {pixel} shows another color"
+        );
+    }
+}
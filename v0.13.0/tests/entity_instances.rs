@@ -0,0 +1,78 @@
+//! Instances built from the children of a host, without rendering.
+
+use bevy::prelude::*;
+use instancing::{
+    entity_instances::{EntityInstances, EntityInstancesPlugin, InstanceChild},
+    InstanceData, InstanceMaterialData,
+};
+
+#[derive(Component, InstanceChild)]
+struct Dot {
+    scale: f32,
+}
+
+/// A xorshift generator, so the despawned subsets are the same on every run.
+struct Random(u32);
+
+impl Random {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+#[test]
+fn despawned_children_are_removed() {
+    let mut app = App::new();
+    app.add_plugins(EntityInstancesPlugin::<InstanceData, Dot>::default());
+    let host = app
+        .world
+        .spawn((
+            InstanceMaterialData::<InstanceData>::default(),
+            EntityInstances::<Dot>::default(),
+        ))
+        .id();
+    // the index of a child is its x, to tell which instances survived
+    let mut children: Vec<_> = (0..100)
+        .map(|index| {
+            app.world
+                .spawn((
+                    Dot { scale: 1.0 },
+                    Transform::from_xyz(index as f32, 0.0, 0.0),
+                ))
+                .set_parent(host)
+                .id()
+        })
+        .collect();
+    // not an instance, so it is skipped
+    app.world.spawn(TransformBundle::default()).set_parent(host);
+    app.update();
+    assert_eq!(instance_positions(&app, host).len(), 100);
+
+    let mut random = Random(0x2545_f491);
+    while !children.is_empty() {
+        let despawned = (random.next() as usize % children.len()).max(1);
+        for _ in 0..despawned {
+            let child = children.remove(random.next() as usize % children.len());
+            app.world.entity_mut(child).despawn_recursive();
+        }
+        app.update();
+
+        let survivors: Vec<_> = children
+            .iter()
+            .map(|child| app.world.get::<Transform>(*child).unwrap().translation.x)
+            .collect();
+        assert_eq!(instance_positions(&app, host), survivors);
+    }
+}
+
+fn instance_positions(app: &App, host: Entity) -> Vec<f32> {
+    app.world
+        .get::<InstanceMaterialData<InstanceData>>(host)
+        .unwrap()
+        .iter()
+        .map(|instance| instance.position.x)
+        .collect()
+}
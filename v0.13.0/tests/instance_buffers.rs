@@ -0,0 +1,141 @@
+//! The instance buffers kept in the render world across frames.
+
+mod common;
+
+use bevy::{
+    prelude::*,
+    render::{view::ExtractedView, Render, RenderApp, RenderSet},
+    utils::HashSet,
+};
+use instancing::{
+    culling::{CullBufferCache, CulledInstanceBuffers, GpuCulling},
+    pool::InstanceBufferPool,
+    InstanceBufferCache, InstanceData, InstanceMaterialData,
+};
+use std::sync::{Arc, Mutex};
+
+/// Instance buffers created since the start.
+fn created_buffers(app: &App) -> u64 {
+    app.sub_app(RenderApp)
+        .world
+        .resource::<InstanceBufferPool>()
+        .created()
+}
+
+#[test]
+fn constant_count_keeps_buffer() {
+    let Some(mut app) = common::instancing_app() else {
+        return;
+    };
+    let host = common::spawn_host(&mut app, 100);
+    common::update(&mut app, 1);
+    assert_eq!(created_buffers(&app), 1);
+
+    for frame in 0..100 {
+        // every instance is written again, but their count stays the same
+        let mut instances = app
+            .world
+            .get_mut::<InstanceMaterialData<InstanceData>>(host)
+            .unwrap();
+        for instance in instances.iter_mut() {
+            instance.rotation = frame as f32;
+        }
+        common::update(&mut app, 1);
+    }
+    assert_eq!(created_buffers(&app), 1);
+}
+
+#[test]
+fn despawned_hosts_release_buffers() {
+    let Some(mut app) = common::instancing_app() else {
+        return;
+    };
+    let cached_buffers = |app: &App| {
+        app.sub_app(RenderApp)
+            .world
+            .resource::<InstanceBufferCache<InstanceData>>()
+            .len()
+    };
+    common::update(&mut app, 1);
+    assert_eq!(cached_buffers(&app), 0);
+
+    let mut created = None;
+    for _ in 0..10 {
+        let hosts: Vec<_> = (1..=20)
+            .map(|count| common::spawn_host(&mut app, count))
+            .collect();
+        common::update(&mut app, 1);
+        assert_eq!(cached_buffers(&app), 20);
+
+        for host in hosts {
+            app.world.entity_mut(host).despawn();
+        }
+        common::update(&mut app, 1);
+        assert_eq!(cached_buffers(&app), 0);
+        // the next hosts reuse the released buffers
+        assert_eq!(
+            *created.get_or_insert(created_buffers(&app)),
+            created_buffers(&app)
+        );
+    }
+
+    // and the pool drops them once they stay unused
+    common::update(&mut app, 100);
+    let pool = app
+        .sub_app(RenderApp)
+        .world
+        .resource::<InstanceBufferPool>();
+    assert_eq!(pool.free_bytes(), 0);
+}
+
+#[test]
+fn culled_host_keeps_culling_buffers() {
+    let Some(mut app) = common::instancing_app() else {
+        return;
+    };
+    let host = common::spawn_host(&mut app, 100);
+    app.world.entity_mut(host).insert(GpuCulling);
+    let outputs = Arc::new(Mutex::new(HashSet::new()));
+    let recorded = outputs.clone();
+    app.sub_app_mut(RenderApp).add_systems(
+        Render,
+        (move |culled: Query<&CulledInstanceBuffers<InstanceData>>,
+               views: Query<Entity, With<ExtractedView>>| {
+            let mut outputs = recorded.lock().unwrap();
+            for culled in &culled {
+                outputs.extend(
+                    views
+                        .iter()
+                        .filter_map(|view| Some(culled.get(view)?.output.id())),
+                );
+            }
+        })
+        .in_set(RenderSet::Render),
+    );
+    common::update(&mut app, 10);
+    let cull_buffers = app
+        .sub_app(RenderApp)
+        .world
+        .resource::<CullBufferCache<InstanceData>>()
+        .len();
+    if cull_buffers == 0 {
+        eprintln!("no compute shaders, skipped");
+        return;
+    }
+    // one image camera
+    assert_eq!(cull_buffers, 1);
+    assert_eq!(outputs.lock().unwrap().len(), 1);
+
+    // more instances than the output holds resize it, fewer keep it
+    app.world
+        .get_mut::<InstanceMaterialData<InstanceData>>(host)
+        .unwrap()
+        .extend((0..1000).map(|_| InstanceData::default()));
+    common::update(&mut app, 1);
+    app.world
+        .get_mut::<InstanceMaterialData<InstanceData>>(host)
+        .unwrap()
+        .truncate(500);
+    common::update(&mut app, 10);
+    assert_eq!(outputs.lock().unwrap().len(), 2);
+}
@@ -0,0 +1,93 @@
+//! 2D hosts seen through a perspective camera.
+
+mod common;
+
+use bevy::{core_pipeline::tonemapping::Tonemapping, prelude::*};
+use common::SIZE;
+use instancing::{InstanceData, InstancedMeshBundle, InstancingPlugin};
+use std::f32::consts::FRAC_PI_2;
+
+/// Frames rendered before the image is read back, so every pipeline is in use.
+const READBACK_FRAME: u32 = 10;
+
+const NEAR: Color = Color::GREEN;
+const FAR: Color = Color::RED;
+const BEHIND: Color = Color::BLUE;
+
+/// Hosts are drawn back to front by their depth along the view instead of by their world z, and
+/// instances behind the camera aren't drawn.
+#[test]
+fn hosts_sort_by_view_depth() {
+    let Some(mut app) = common::headless_app() else {
+        return;
+    };
+    app.add_plugins(InstancingPlugin::<InstanceData>::default());
+
+    let square = app
+        .world
+        .resource_mut::<Assets<Mesh>>()
+        .add(Rectangle::new(1.0, 1.0));
+    // faces +X, the local z of the instances points towards the camera
+    let facing_camera = Quat::from_rotation_y(FRAC_PI_2);
+
+    // nearer to the camera but at a lower world z, sorting by z would draw the far host over it
+    app.world
+        .spawn(InstancedMeshBundle::<InstanceData>::new(
+            square.clone(),
+            [
+                InstanceData {
+                    color: NEAR.as_linear_rgba_f32(),
+                    ..default()
+                },
+                InstanceData {
+                    // at x = 12, behind the camera
+                    position: Vec3::new(0.0, 0.0, 10.0),
+                    scale: 50.0,
+                    color: BEHIND.as_linear_rgba_f32(),
+                    ..default()
+                },
+            ],
+        ))
+        .insert(Transform::from_xyz(2.0, 0.0, -1.0).with_rotation(facing_camera));
+    app.world
+        .spawn(InstancedMeshBundle::<InstanceData>::new(
+            square,
+            [InstanceData {
+                scale: 4.0,
+                color: FAR.as_linear_rgba_f32(),
+                ..default()
+            }],
+        ))
+        .insert(Transform::from_xyz(-2.0, 0.0, 1.0).with_rotation(facing_camera));
+
+    // a 2D camera with a perspective projection, looking along -X at the hosts
+    let camera = Camera2dBundle {
+        transform: Transform::from_xyz(10.0, 0.0, 0.0).looking_at(Vec3::ZERO, Vec3::Y),
+        tonemapping: Tonemapping::None,
+        ..common::image_camera(&mut app.world.resource_mut::<Assets<Image>>())
+    };
+    let readback = common::read_back(&mut app, common::target_of(&camera.camera));
+    app.world
+        .spawn(camera)
+        .remove::<OrthographicProjection>()
+        .insert(PerspectiveProjection::default());
+    common::update(&mut app, READBACK_FRAME);
+    let image = readback.take().expect("nothing was read back");
+
+    let center = SIZE / 2;
+    // world -z is to the right, so the near host is right of the center and overlaps the far one
+    let expected = [
+        // the near host is drawn over the far one, though it is at a lower world z
+        ("overlap", UVec2::new(center + 32, center), Some(NEAR)),
+        ("far host", UVec2::new(center - 32, center), Some(FAR)),
+        // the instance behind the camera would cover everything if it was drawn
+        ("corner", UVec2::splat(4), None),
+    ];
+    for (name, pixel, color) in expected {
+        assert_eq!(
+            common::dominant(&image, pixel.x, pixel.y),
+            color.and_then(common::channel),
+            "the {name} pixel {pixel} shows another color"
+        );
+    }
+}
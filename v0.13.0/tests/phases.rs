@@ -0,0 +1,236 @@
+//! The items the hosts queue into the render phases of the cameras.
+
+mod common;
+
+use bevy::{
+    core_pipeline::{core_2d::Transparent2d, core_3d::Transparent3d},
+    prelude::*,
+    render::{render_asset::RenderAssets, Render, RenderApp, RenderSet},
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+};
+use instancing::{
+    DepthInstanceData, HostMeshes, HostSpawnOrder, Instance, InstanceBufferMode, InstanceData,
+    InstanceMaterialData, InstancedMeshBundle, Instancing3dPlugin, InstancingPlugin,
+    SortKeyStrategy,
+};
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn empty_host_is_not_queued() {
+    let Some(mut app) = common::instancing_app() else {
+        return;
+    };
+    let queued = common::record_queued::<Transparent2d>(&mut app);
+    let empty = common::spawn_host(&mut app, 0);
+    let host = common::spawn_host(&mut app, 10);
+    common::update(&mut app, 2);
+    assert!(queued.of(empty).is_empty());
+    assert_eq!(queued.of(host).len(), 1);
+    assert!(queued.of(host)[0].pipeline_ready);
+
+    // emptied at runtime
+    app.world
+        .get_mut::<InstanceMaterialData<InstanceData>>(host)
+        .unwrap()
+        .clear();
+    common::update(&mut app, 1);
+    assert!(queued.of(host).is_empty());
+}
+
+#[test]
+fn swapped_mesh_is_drawn() {
+    let Some(mut app) = common::instancing_app() else {
+        return;
+    };
+    let queued = common::record_queued::<Transparent2d>(&mut app);
+    let host = common::spawn_host(&mut app, 10);
+    let drawn_vertices = Arc::new(Mutex::new(None));
+    let recorded = drawn_vertices.clone();
+    app.sub_app_mut(RenderApp).add_systems(
+        Render,
+        (move |host_meshes: HostMeshes, meshes: Res<RenderAssets<Mesh>>| {
+            *recorded.lock().unwrap() = host_meshes
+                .get(host)
+                .and_then(|(mesh, _)| meshes.get(mesh))
+                .map(|mesh| mesh.vertex_count);
+        })
+        .in_set(RenderSet::Render),
+    );
+    common::update(&mut app, 2);
+    assert_eq!(*drawn_vertices.lock().unwrap(), Some(4));
+
+    let circle = Mesh::from(Circle::new(0.5));
+    let circle_vertices = circle.count_vertices() as u32;
+    let circle = app.world.resource_mut::<Assets<Mesh>>().add(circle);
+    app.world.entity_mut(host).insert(Mesh2dHandle(circle));
+    common::update(&mut app, 1);
+    assert_eq!(*drawn_vertices.lock().unwrap(), Some(circle_vertices));
+    assert_eq!(queued.of(host).len(), 1);
+    assert!(queued.of(host)[0].pipeline_ready);
+}
+
+#[test]
+fn every_camera_queues_host() {
+    let Some(mut app) = common::instancing_app() else {
+        return;
+    };
+    let queued = common::record_queued::<Transparent2d>(&mut app);
+    let camera = common::image_camera(&mut app.world.resource_mut::<Assets<Image>>());
+    app.world.spawn(camera);
+    let host = common::spawn_host(&mut app, 10);
+    common::update(&mut app, 2);
+
+    let items = queued.of(host);
+    assert_eq!(items.len(), 2);
+    assert_ne!(items[0].view, items[1].view);
+}
+
+#[test]
+fn host_is_queued_once_mesh_loads() {
+    let Some(mut app) = common::instancing_app() else {
+        return;
+    };
+    let queued = common::record_queued::<Transparent2d>(&mut app);
+    // like a handle of a mesh still loading from a file
+    let mesh = app.world.resource::<Assets<Mesh>>().reserve_handle();
+    let host = app
+        .world
+        .spawn(InstancedMeshBundle::<InstanceData>::new(
+            mesh.clone(),
+            [InstanceData::default()],
+        ))
+        .id();
+    common::update(&mut app, 3);
+    assert!(queued.of(host).is_empty());
+
+    app.world
+        .resource_mut::<Assets<Mesh>>()
+        .insert(&mesh, Rectangle::new(1.0, 1.0).into());
+    common::update(&mut app, 2);
+    assert_eq!(queued.of(host).len(), 1);
+    assert!(queued.of(host)[0].pipeline_ready);
+}
+
+#[test]
+fn host_under_hidden_parent_is_not_queued() {
+    let Some(mut app) = common::instancing_app() else {
+        return;
+    };
+    let queued = common::record_queued::<Transparent2d>(&mut app);
+    let host = common::spawn_host(&mut app, 10);
+    let parent = app
+        .world
+        .spawn(SpatialBundle {
+            visibility: Visibility::Hidden,
+            ..default()
+        })
+        .add_child(host)
+        .id();
+    common::update(&mut app, 2);
+    assert!(queued.of(host).is_empty());
+
+    *app.world.get_mut::<Visibility>(parent).unwrap() = Visibility::Inherited;
+    common::update(&mut app, 1);
+    assert_eq!(queued.of(host).len(), 1);
+}
+
+#[test]
+fn msaa_change_specializes_pipeline() {
+    let Some(mut app) = common::instancing_app() else {
+        return;
+    };
+    let queued = common::record_queued::<Transparent2d>(&mut app);
+    let host = common::spawn_host(&mut app, 10);
+    let pipeline_with = |app: &mut App, msaa: Msaa| {
+        app.insert_resource(msaa);
+        common::update(app, 2);
+        let items = queued.of(host);
+        assert_eq!(items.len(), 1);
+        assert!(items[0].pipeline_ready, "no pipeline with {msaa:?}");
+        items[0].pipeline
+    };
+
+    let off = pipeline_with(&mut app, Msaa::Off);
+    let sample_4 = pipeline_with(&mut app, Msaa::Sample4);
+    assert_ne!(off, sample_4);
+    assert_eq!(pipeline_with(&mut app, Msaa::Off), off);
+}
+
+#[test]
+fn only_hosts_are_numbered_in_spawn_order() {
+    let Some(mut app) = common::headless_app() else {
+        return;
+    };
+    app.add_plugins(
+        InstancingPlugin::<InstanceData>::default()
+            .with_sort_key_strategy(SortKeyStrategy::SpawnOrder),
+    );
+    let first = common::spawn_host(&mut app, 1);
+    let mesh = app
+        .world
+        .resource_mut::<Assets<Mesh>>()
+        .add(Rectangle::new(1.0, 1.0));
+    let plain_mesh = app
+        .world
+        .spawn(MaterialMesh2dBundle::<ColorMaterial> {
+            mesh: mesh.into(),
+            ..default()
+        })
+        .id();
+    common::update(&mut app, 1);
+    let second = common::spawn_host(&mut app, 1);
+    common::update(&mut app, 1);
+
+    let order = |entity| {
+        app.world
+            .get::<HostSpawnOrder>(entity)
+            .map(|order| order.get())
+    };
+    assert_eq!(order(first), Some(0));
+    assert_eq!(order(second), Some(1));
+    assert_eq!(order(plain_mesh), None);
+}
+
+/// Whether a 3D host of default `T`s queues with a ready pipeline, `None` without a GPU adapter.
+fn specializes_in_3d<T: Instance + Default>(buffer_mode: InstanceBufferMode) -> Option<bool> {
+    let mut app = common::headless_app()?;
+    app.add_plugins(Instancing3dPlugin::<T>::default().with_buffer_mode(buffer_mode));
+    let queued = common::record_queued::<Transparent3d>(&mut app);
+    let camera = common::image_camera(&mut app.world.resource_mut::<Assets<Image>>()).camera;
+    app.world.spawn(Camera3dBundle {
+        camera,
+        transform: Transform::from_xyz(0.0, 0.0, 10.0).looking_at(Vec3::ZERO, Vec3::Y),
+        ..default()
+    });
+    let mesh = app
+        .world
+        .resource_mut::<Assets<Mesh>>()
+        .add(Cuboid::new(1.0, 1.0, 1.0));
+    let host = app
+        .world
+        .spawn(InstancedMeshBundle::<T, Handle<Mesh>>::new(
+            mesh,
+            [T::default()],
+        ))
+        .id();
+    common::update(&mut app, 2);
+    Some(queued.of(host).iter().any(|item| item.pipeline_ready))
+}
+
+#[test]
+fn instances_specialize_in_3d() {
+    for buffer_mode in [InstanceBufferMode::Vertex, InstanceBufferMode::Storage] {
+        if let Some(ready) = specializes_in_3d::<InstanceData>(buffer_mode) {
+            assert!(ready, "{buffer_mode:?}");
+        }
+    }
+}
+
+#[test]
+fn depth_instances_specialize_in_3d() {
+    for buffer_mode in [InstanceBufferMode::Vertex, InstanceBufferMode::Storage] {
+        if let Some(ready) = specializes_in_3d::<DepthInstanceData>(buffer_mode) {
+            assert!(ready, "{buffer_mode:?}");
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Renders the 10x10 grid of the demo off-screen and compares the pixels with a reference image,
+//! to catch shader and layout breakage, e.g. across bevy upgrades.
+//!
+//! Fails if more than [`MAX_MISMATCHED`] of the pixels differ from
+//! `assets/reference/instanced_grid.png` by more than [`TOLERANCE`] in any channel, and saves what
+//! it rendered next to the reference. Run with `BLESS=1` to replace the reference after an
+//! intended change.
+
+mod common;
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        texture::{CompressedImageFormats, ImageSampler, ImageType},
+    },
+};
+use common::SIZE;
+use instancing::{InstanceData, InstancedMeshBundle, InstancingPlugin};
+
+/// Frames rendered before the image is read back, so every pipeline is in use.
+const READBACK_FRAME: u32 = 10;
+/// Largest difference of a channel, out of 255, for a pixel to match.
+const TOLERANCE: u8 = 8;
+/// Share of the pixels that may not match, for rasterization differences along the edges.
+const MAX_MISMATCHED: f32 = 0.01;
+
+const REFERENCE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/assets/reference/instanced_grid.png"
+);
+const ACTUAL: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/assets/reference/instanced_grid.actual.png"
+);
+
+#[test]
+fn instanced_grid_matches_reference() {
+    let Some(mut app) = common::headless_app() else {
+        return;
+    };
+    app.add_plugins(InstancingPlugin::<InstanceData>::default())
+        .add_systems(Startup, setup);
+    let camera = Camera2dBundle {
+        transform: Transform::from_xyz(0.5, 0.5, 0.0),
+        projection: OrthographicProjection {
+            scale: 0.045,
+            ..default()
+        },
+        ..common::image_camera(&mut app.world.resource_mut::<Assets<Image>>())
+    };
+    let readback = common::read_back(&mut app, common::target_of(&camera.camera));
+    app.world.spawn(camera);
+    common::update(&mut app, READBACK_FRAME);
+
+    let actual = readback.take().expect("nothing was read back");
+
+    if std::env::var_os("BLESS").is_some() {
+        save(actual, REFERENCE);
+        println!("saved the reference to {REFERENCE}");
+        return;
+    }
+
+    let reference = Image::from_buffer(
+        &std::fs::read(REFERENCE).expect("no reference image, run with BLESS=1 to create it"),
+        ImageType::Extension("png"),
+        CompressedImageFormats::NONE,
+        true,
+        ImageSampler::Default,
+        RenderAssetUsages::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        reference.size(),
+        UVec2::splat(SIZE),
+        "reference of another size"
+    );
+
+    let mismatched = actual
+        .data
+        .chunks_exact(4)
+        .zip(reference.data.chunks_exact(4))
+        .filter(|(actual, reference)| {
+            actual
+                .iter()
+                .zip(reference.iter())
+                .any(|(actual, reference)| actual.abs_diff(*reference) > TOLERANCE)
+        })
+        .count();
+    let share = mismatched as f32 / (SIZE * SIZE) as f32;
+
+    if share > MAX_MISMATCHED {
+        save(actual, ACTUAL);
+        panic!(
+            "{mismatched} pixels ({:.2}%) differ from the reference, saved the rendered image to \
+             {ACTUAL}",
+            share * 100.0
+        );
+    }
+}
+
+/// The grid of the demo, without the atlas and the bloom, which depend more on the GPU.
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let instances = (1..=10)
+        .flat_map(|column| (1..=10).map(move |row| (column, row)))
+        .map(|(column, row)| {
+            let (x, y) = (column as f32 / 10.0, row as f32 / 10.0);
+            InstanceData {
+                position: Vec3::new(x * 10.0 - 5.0, y * 10.0 - 5.0, 0.0),
+                scale: 0.8,
+                color: Color::hsla(x * 360., y, 0.5, 1.0).as_linear_rgba_f32(),
+                rotation: (column + row) as f32 * 0.1,
+                ..default()
+            }
+        });
+
+    commands.spawn(InstancedMeshBundle::<InstanceData>::new(
+        meshes.add(Rectangle::new(1.0, 1.0)),
+        instances,
+    ));
+}
+
+fn save(image: Image, path: &str) {
+    image
+        .try_into_dynamic()
+        .unwrap()
+        .save(path)
+        .unwrap_or_else(|err| panic!("failed to save {path}: {err}"));
+}
@@ -0,0 +1,37 @@
+//! Hosts moved by a compute shader.
+
+mod common;
+
+use bevy::{prelude::*, render::RenderApp};
+use instancing::{
+    simulation::{GpuSimulation, GpuSimulationStates},
+    InstanceData,
+};
+
+#[test]
+fn state_size_is_rounded_up() {
+    let Some(mut app) = common::instancing_app() else {
+        return;
+    };
+    let host = common::spawn_host(&mut app, 3);
+    let shader = app
+        .world
+        .resource::<AssetServer>()
+        .load("shaders/particle_simulation.wgsl");
+    // a state of 6 bytes can't be bound, a WGSL array takes 8 per element anyway
+    app.world.entity_mut(host).insert(GpuSimulation {
+        shader,
+        state_size: 6,
+    });
+    common::update(&mut app, 10);
+
+    let states = app
+        .sub_app(RenderApp)
+        .world
+        .resource::<GpuSimulationStates<InstanceData>>();
+    let Some(state) = states.get(host) else {
+        eprintln!("no compute shaders, skipped");
+        return;
+    };
+    assert_eq!(state.size(), 3 * 8);
+}
@@ -0,0 +1,115 @@
+//! Renders 2D hosts into a target that is resized every few frames, like a window being dragged,
+//! and checks every frame that the hosts are drawn where the new size puts them. Catches batch
+//! bounds, GPU culling or instance targets that keep using the frustum or the size of an earlier
+//! frame.
+
+mod common;
+
+use bevy::{core_pipeline::tonemapping::Tonemapping, prelude::*, render::render_resource::Extent3d};
+use common::SIZE;
+use instancing::{
+    culling::GpuCulling, targets::InstanceTargets, InstanceData, InstancedMeshBundle,
+    InstancingPlugin,
+};
+
+/// The widths the target is resized to in turn, starting at the width of the image camera.
+const WIDTHS: [u32; 4] = [SIZE, 128, 512, SIZE];
+/// Frames checked at each width before the next resize.
+const FRAMES_PER_WIDTH: u32 = 5;
+/// Frames skipped after a resize. The projection of an image target follows the asset events of
+/// the image, which may reach the camera a frame after the resized texture reaches the renderer.
+const SETTLE_FRAMES: u32 = 1;
+
+/// At the origin, on screen at every width.
+const CENTER: Color = Color::GREEN;
+/// At x = 100, culled on the GPU while the target is narrower than 256 pixels.
+const CULLED_ON_GPU: Color = Color::BLUE;
+/// At x = 200, its batch is culled while the target is narrower than 512 pixels.
+const CULLED_AS_BATCH: Color = Color::RED;
+
+#[test]
+fn hosts_follow_target_resize() {
+    let Some(mut app) = common::headless_app() else {
+        return;
+    };
+    app.add_plugins(InstancingPlugin::<InstanceData>::default())
+        // the instance targets are only drawn without MSAA
+        .insert_resource(Msaa::Off);
+
+    let square = app
+        .world
+        .resource_mut::<Assets<Mesh>>()
+        .add(Rectangle::new(1.0, 1.0));
+    let host = |position: Vec3, color: Color| {
+        InstancedMeshBundle::<InstanceData>::new(
+            square.clone(),
+            [InstanceData {
+                position,
+                scale: 30.0,
+                color: color.as_linear_rgba_f32(),
+                ..default()
+            }],
+        )
+    };
+    app.world.spawn(host(Vec3::ZERO, CENTER));
+    app.world
+        .spawn((host(Vec3::new(100.0, 80.0, 0.0), CULLED_ON_GPU), GpuCulling));
+    app.world
+        .spawn(host(Vec3::new(200.0, -60.0, 0.0), CULLED_AS_BATCH));
+
+    // one pixel per unit, so a wider target shows more of the world. The instance targets are
+    // sized along with the view target.
+    let camera = Camera2dBundle {
+        tonemapping: Tonemapping::None,
+        ..common::image_camera(&mut app.world.resource_mut::<Assets<Image>>())
+    };
+    let target = common::target_of(&camera.camera);
+    let readback = common::read_back(&mut app, target.clone());
+    app.world.spawn((camera, InstanceTargets::instance_ids()));
+
+    for (index, &width) in WIDTHS.iter().enumerate() {
+        if index > 0 {
+            let mut images = app.world.resource_mut::<Assets<Image>>();
+            images.get_mut(&target).unwrap().resize(Extent3d {
+                width,
+                height: SIZE,
+                depth_or_array_layers: 1,
+            });
+            common::update(&mut app, SETTLE_FRAMES);
+        }
+
+        for frame in 1..=FRAMES_PER_WIDTH {
+            common::update(&mut app, 1);
+            let image = readback.take().expect("nothing was read back");
+            assert_eq!(image.width(), width, "frame {frame} read back another size");
+
+            let center = UVec2::new(width / 2, SIZE / 2);
+            let mut expected = vec![("center", center, Some(CENTER))];
+            if width >= 256 {
+                expected.push((
+                    "GPU culled",
+                    UVec2::new(center.x + 100, center.y - 80),
+                    Some(CULLED_ON_GPU),
+                ));
+            }
+            if width >= 512 {
+                expected.push((
+                    "batch culled",
+                    UVec2::new(center.x + 200, center.y + 60),
+                    Some(CULLED_AS_BATCH),
+                ));
+            }
+            // nothing is drawn at the edges, left over from another size
+            expected.push(("left edge", UVec2::new(0, center.y), None));
+            expected.push(("right edge", UVec2::new(width - 1, center.y), None));
+
+            for (name, pixel, color) in expected {
+                assert_eq!(
+                    common::dominant(&image, pixel.x, pixel.y),
+                    color.and_then(common::channel),
+                    "frame {frame} at width {width}: the {name} pixel {pixel} shows another color"
+                );
+            }
+        }
+    }
+}